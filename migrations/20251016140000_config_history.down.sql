@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS config_history_update;
+DROP TRIGGER IF EXISTS config_history_insert;
+DROP TABLE IF EXISTS config_history;
@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS config_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    modified DATETIME NOT NULL,
+    modified_by_user_id INTEGER
+);
+CREATE INDEX IF NOT EXISTS config_history_key ON config_history (key);
+CREATE TRIGGER IF NOT EXISTS config_history_insert AFTER INSERT ON config
+BEGIN
+    INSERT INTO config_history (key, value, modified) VALUES (NEW.key, NEW.value, NEW.modified);
+    DELETE FROM config_history WHERE key = NEW.key AND id NOT IN (
+        SELECT id FROM config_history WHERE key = NEW.key ORDER BY id DESC LIMIT 20
+    );
+END;
+CREATE TRIGGER IF NOT EXISTS config_history_update AFTER UPDATE OF value ON config
+BEGIN
+    INSERT INTO config_history (key, value, modified) VALUES (NEW.key, NEW.value, NEW.modified);
+    DELETE FROM config_history WHERE key = NEW.key AND id NOT IN (
+        SELECT id FROM config_history WHERE key = NEW.key ORDER BY id DESC LIMIT 20
+    );
+END;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_farmer_rewards_launcher_timestamp;
+DROP TABLE IF EXISTS farmer_rewards;
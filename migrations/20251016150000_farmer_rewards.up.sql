@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS farmer_rewards (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    launcher_id BLOB NOT NULL,
+    amount REAL NOT NULL,
+    coin_name TEXT NOT NULL UNIQUE,
+    block_height INTEGER NOT NULL,
+    timestamp DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_farmer_rewards_launcher_timestamp
+    ON farmer_rewards (launcher_id, timestamp);
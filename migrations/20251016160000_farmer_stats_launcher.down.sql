@@ -0,0 +1,32 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_farmer_stats_launcher_gathered;
+
+CREATE TABLE farmer_stats_old (
+    challenge_hash BLOB NOT NULL,
+    sp_hash BLOB NOT NULL,
+    running BOOLEAN NOT NULL,
+    og_passed_filter INTEGER NOT NULL,
+    og_plot_count INTEGER NOT NULL,
+    nft_passed_filter INTEGER NOT NULL,
+    nft_plot_count INTEGER NOT NULL,
+    compressed_passed_filter INTEGER NOT NULL,
+    compressed_plot_count INTEGER NOT NULL,
+    invalid_plot_count INTEGER NOT NULL,
+    proofs_found INTEGER NOT NULL,
+    total_plot_space INTEGER NOT NULL,
+    full_node_height INTEGER NOT NULL,
+    full_node_difficulty INTEGER NOT NULL,
+    full_node_synced BOOLEAN NOT NULL,
+    gathered DATETIME NOT NULL,
+    UNIQUE (challenge_hash, sp_hash)
+);
+
+INSERT OR IGNORE INTO farmer_stats_old
+SELECT challenge_hash, sp_hash, running, og_passed_filter, og_plot_count,
+    nft_passed_filter, nft_plot_count, compressed_passed_filter,
+    compressed_plot_count, invalid_plot_count, proofs_found, total_plot_space,
+    full_node_height, full_node_difficulty, full_node_synced, gathered
+FROM farmer_stats;
+
+DROP TABLE farmer_stats;
+ALTER TABLE farmer_stats_old RENAME TO farmer_stats;
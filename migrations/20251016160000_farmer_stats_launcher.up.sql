@@ -0,0 +1,39 @@
+-- Add up migration script here
+CREATE TABLE farmer_stats_new (
+    launcher_id BLOB NOT NULL DEFAULT (zeroblob(32)),
+    challenge_hash BLOB NOT NULL,
+    sp_hash BLOB NOT NULL,
+    running BOOLEAN NOT NULL,
+    og_passed_filter INTEGER NOT NULL,
+    og_plot_count INTEGER NOT NULL,
+    nft_passed_filter INTEGER NOT NULL,
+    nft_plot_count INTEGER NOT NULL,
+    compressed_passed_filter INTEGER NOT NULL,
+    compressed_plot_count INTEGER NOT NULL,
+    invalid_plot_count INTEGER NOT NULL,
+    proofs_found INTEGER NOT NULL,
+    total_plot_space INTEGER NOT NULL,
+    full_node_height INTEGER NOT NULL,
+    full_node_difficulty INTEGER NOT NULL,
+    full_node_synced BOOLEAN NOT NULL,
+    gathered DATETIME NOT NULL,
+    UNIQUE (launcher_id, challenge_hash, sp_hash)
+);
+
+INSERT INTO farmer_stats_new (
+    challenge_hash, sp_hash, running, og_passed_filter, og_plot_count,
+    nft_passed_filter, nft_plot_count, compressed_passed_filter,
+    compressed_plot_count, invalid_plot_count, proofs_found, total_plot_space,
+    full_node_height, full_node_difficulty, full_node_synced, gathered
+)
+SELECT challenge_hash, sp_hash, running, og_passed_filter, og_plot_count,
+    nft_passed_filter, nft_plot_count, compressed_passed_filter,
+    compressed_plot_count, invalid_plot_count, proofs_found, total_plot_space,
+    full_node_height, full_node_difficulty, full_node_synced, gathered
+FROM farmer_stats;
+
+DROP TABLE farmer_stats;
+ALTER TABLE farmer_stats_new RENAME TO farmer_stats;
+
+CREATE INDEX IF NOT EXISTS idx_farmer_stats_launcher_gathered
+    ON farmer_stats (launcher_id, gathered);
@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS wizard_state (
+    step TEXT PRIMARY KEY NOT NULL,
+    completed BOOLEAN NOT NULL DEFAULT 0,
+    completed_at TIMESTAMP
+);
+
+INSERT OR IGNORE INTO wizard_state (step, completed) VALUES
+    ('Network', 0),
+    ('FarmerKey', 0),
+    ('PlotDirectories', 0),
+    ('FarmerStart', 0);
+
+-- Existing installs already have a saved farmer config, skip the wizard for them
+UPDATE wizard_state
+SET completed = 1, completed_at = CURRENT_TIMESTAMP
+WHERE EXISTS (SELECT 1 FROM config WHERE key = 'farmer_config');
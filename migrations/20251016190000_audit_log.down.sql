@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS audit_log_timestamp;
+DROP TABLE IF EXISTS audit_log;
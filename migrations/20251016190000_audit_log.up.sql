@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp DATETIME NOT NULL,
+    user_id INTEGER,
+    username TEXT,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    request_body_hash TEXT,
+    response_status INTEGER NOT NULL,
+    ip_address TEXT
+);
+CREATE INDEX IF NOT EXISTS audit_log_timestamp ON audit_log (timestamp);
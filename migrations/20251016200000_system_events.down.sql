@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS system_events_timestamp;
+DROP TABLE IF EXISTS system_events;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE plugins DROP COLUMN download_count;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE plugins ADD COLUMN download_count INTEGER NOT NULL DEFAULT 0;
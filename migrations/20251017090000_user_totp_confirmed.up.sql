@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN totp_confirmed BOOLEAN NOT NULL DEFAULT 0;
+UPDATE users SET totp_confirmed = 1 WHERE totp_secret IS NOT NULL;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- History rows are written by create_config_entry so they can record the author
+DROP TRIGGER IF EXISTS config_history_update;
+DROP TRIGGER IF EXISTS config_history_insert;
@@ -1,39 +1,366 @@
 use crate::database::config::{
-    create_config_entry, delete_config_entry, get_config, get_config_key,
+    create_config_entries_batch, create_config_entry, delete_config_entry, get_config,
+    get_config_key,
 };
 use crate::models::config::{AddConfigEntry, ConfigEntry};
+use crate::plugins::system_monitor::{
+    validate_fan_curve, validate_fan_curve_fans, FAN_CURVE_CONFIG_KEY, FAN_CURVE_FANS_CONFIG_KEY,
+    FAN_CURVE_MIN_PWM,
+};
+use crate::plugins::{validate_plugin_store_url, PLUGIN_STORE_URL_CONFIG_KEY};
+use crate::tls::{
+    validate_tls_hostname, TLS_CERT_PATH_CONFIG_KEY, TLS_HOSTNAME_CONFIG_KEY,
+    TLS_KEY_PATH_CONFIG_KEY,
+};
+use crate::web::auth::{
+    MAX_SESSION_TIMEOUT_SECONDS, MIN_SESSION_TIMEOUT_SECONDS, SESSION_TIMEOUT_CONFIG_KEY,
+};
+use crate::web::notifications::{EMAIL_NOTIFIER_CONFIG_KEY, WEBHOOKS_CONFIG_KEY};
+use log::{debug, warn};
+use portfu::prelude::serde_json;
+use portfu::prelude::serde_json::Value;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
 use time::OffsetDateTime;
+use tokio::sync::broadcast;
 
 pub static DEFAULT_FULLNODE_WS_HOST: &str = "druid.garden";
 pub static DEFAULT_FULLNODE_WS_PORT: u16 = 443;
 pub static DEFAULT_FULLNODE_RPC_HOST: &str = "druid.garden";
 pub static DEFAULT_FULLNODE_RPC_PORT: u16 = 443;
 
+//Keys only their own endpoints may write, the generic config routes reject them
+pub const PROTECTED_CONFIG_KEYS: [&str; 5] = [
+    EMAIL_NOTIFIER_CONFIG_KEY,
+    WEBHOOKS_CONFIG_KEY,
+    PLUGIN_STORE_URL_CONFIG_KEY,
+    TLS_CERT_PATH_CONFIG_KEY,
+    TLS_KEY_PATH_CONFIG_KEY,
+];
+//Protected keys holding secrets, the generic config routes redact them
+const SECRET_CONFIG_KEYS: [&str; 2] = [EMAIL_NOTIFIER_CONFIG_KEY, WEBHOOKS_CONFIG_KEY];
+const REDACTED_FIELDS: [&str; 2] = ["password", "secret"];
+
+pub fn is_protected_key(key: &str) -> bool {
+    PROTECTED_CONFIG_KEYS.contains(&key)
+}
+
+pub fn ensure_writable(key: &str) -> Result<(), Error> {
+    if is_protected_key(key) {
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{key} can only be changed through its own endpoint"),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn redact_fields(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (field, value) in map.iter_mut() {
+                if REDACTED_FIELDS.contains(&field.as_str()) {
+                    *value = Value::String(String::new());
+                } else {
+                    redact_fields(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_fields),
+        _ => {}
+    }
+}
+
+/// Blanks the secrets in a secret value, values that don't parse are hidden entirely
+pub fn redact_value(key: &str, value: &str) -> String {
+    if !SECRET_CONFIG_KEYS.contains(&key) || value.is_empty() {
+        return value.to_string();
+    }
+    match serde_json::from_str::<Value>(value) {
+        Ok(mut value) => {
+            redact_fields(&mut value);
+            value.to_string()
+        }
+        Err(_) => String::new(),
+    }
+}
+
+pub fn redact_entry(mut entry: ConfigEntry) -> ConfigEntry {
+    entry.value = redact_value(&entry.key, &entry.value);
+    entry.last_value = redact_value(&entry.key, &entry.last_value);
+    entry
+}
+
+type ConfigValidator = Box<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+pub struct ConfigSchema {
+    pub key: &'static str,
+    pub rule: String,
+    pub validator: ConfigValidator,
+}
+impl ConfigSchema {
+    pub fn new(key: &'static str, rule: String, validator: ConfigValidator) -> Self {
+        Self {
+            key,
+            rule,
+            validator,
+        }
+    }
+    pub fn u64(key: &'static str) -> Self {
+        Self::new(
+            key,
+            "u64".to_string(),
+            Box::new(|value| {
+                value
+                    .parse::<u64>()
+                    .map(|_| ())
+                    .map_err(|e| format!("{value} is not a valid u64: {e}"))
+            }),
+        )
+    }
+    pub fn u64_range(key: &'static str, min: u64, max: u64) -> Self {
+        Self::new(
+            key,
+            format!("u64 in range {min}-{max}"),
+            Box::new(move |value| match value.parse::<u64>() {
+                Ok(v) if (min..=max).contains(&v) => Ok(()),
+                Ok(v) => Err(format!("{v} is not in range {min}-{max}")),
+                Err(e) => Err(format!("{value} is not a valid u64: {e}")),
+            }),
+        )
+    }
+    pub fn f32_range(key: &'static str, min: f32, max: f32) -> Self {
+        Self::new(
+            key,
+            format!("f32 in range {min}-{max}"),
+            Box::new(move |value| match value.parse::<f32>() {
+                Ok(v) if (min..=max).contains(&v) => Ok(()),
+                Ok(v) => Err(format!("{v} is not in range {min}-{max}")),
+                Err(e) => Err(format!("{value} is not a valid f32: {e}")),
+            }),
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigSchemaInfo {
+    pub key: String,
+    pub rule: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigValidationError {
+    pub key: String,
+    pub error: String,
+}
+
+fn default_schemas() -> Vec<ConfigSchema> {
+    vec![
+        ConfigSchema::u64_range("stats_days_saved", 1, 3650),
+        ConfigSchema::u64_range("stats_max_rows", 1000, 10_000_000),
+        ConfigSchema::u64_range("audit_log_days", 1, 3650),
+        ConfigSchema::u64("trash_max_size_bytes"),
+        ConfigSchema::f32_range("plugin_max_cpu_percent", 0.0, 100.0),
+        ConfigSchema::u64("plugin_max_memory_bytes"),
+        ConfigSchema::u64("led_idle_timeout_secs"),
+        ConfigSchema::u64_range("led_pwm_period_us", 1000, 100_000),
+        ConfigSchema::f32_range("cpu_temp_alert_celsius", 50.0, 100.0),
+        ConfigSchema::new(
+            FAN_CURVE_CONFIG_KEY,
+            format!("JSON list of [celsius, pwm] pairs, pwm at least {FAN_CURVE_MIN_PWM}"),
+            Box::new(validate_fan_curve),
+        ),
+        ConfigSchema::new(
+            FAN_CURVE_FANS_CONFIG_KEY,
+            "JSON list of fan ids".to_string(),
+            Box::new(validate_fan_curve_fans),
+        ),
+        ConfigSchema::u64_range("disk_temp_alert_celsius", 30, 100),
+        ConfigSchema::u64_range("disk_full_alert_percent", 50, 100),
+        ConfigSchema::u64_range("memory_alert_percent", 1, 100),
+        ConfigSchema::u64_range("swap_alert_percent", 1, 100),
+        ConfigSchema::u64_range(
+            SESSION_TIMEOUT_CONFIG_KEY,
+            MIN_SESSION_TIMEOUT_SECONDS as u64,
+            MAX_SESSION_TIMEOUT_SECONDS as u64,
+        ),
+        ConfigSchema::u64_range("system_disk_refresh_secs", 1, 3600),
+        ConfigSchema::u64_range("system_net_refresh_secs", 1, 3600),
+        ConfigSchema::u64_range("system_gpu_refresh_secs", 1, 3600),
+        ConfigSchema::u64_range("graceful_shutdown_timeout_secs", 1, 300),
+        ConfigSchema::new(
+            PLUGIN_STORE_URL_CONFIG_KEY,
+            "http, https or file URL".to_string(),
+            Box::new(|value| validate_plugin_store_url(value).map_err(|e| e.to_string())),
+        ),
+        ConfigSchema::new(
+            TLS_HOSTNAME_CONFIG_KEY,
+            "DNS hostname".to_string(),
+            Box::new(|value| validate_tls_hostname(value).map_err(|e| e.to_string())),
+        ),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigEventType {
+    Updated,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigEvent {
+    pub event: ConfigEventType,
+    pub key: String,
+    pub value: String,
+    pub category: String,
+}
+
 pub struct ConfigManager {
     entries: HashMap<String, ConfigEntry>,
+    schemas: HashMap<String, ConfigSchema>,
+    subscribers: broadcast::Sender<ConfigEvent>,
 }
 impl ConfigManager {
     pub async fn new(db: &SqlitePool) -> Result<ConfigManager, Error> {
         let entries = get_config(db).await?;
-        Ok(Self {
+        let mut manager = Self {
             entries: entries.into_iter().map(|e| (e.key.clone(), e)).collect(),
-        })
+            schemas: HashMap::new(),
+            subscribers: broadcast::channel(64).0,
+        };
+        for schema in default_schemas() {
+            manager.register_schema(schema);
+        }
+        Ok(manager)
+    }
+    pub fn subscribe(&self) -> broadcast::Receiver<ConfigEvent> {
+        self.subscribers.subscribe()
+    }
+    fn notify(&self, event: ConfigEventType, key: &str, value: &str, category: &str) {
+        //Send only fails when there are no subscribers
+        let _ = self.subscribers.send(ConfigEvent {
+            event,
+            key: key.to_string(),
+            value: value.to_string(),
+            category: category.to_string(),
+        });
+    }
+    pub fn register_schema(&mut self, schema: ConfigSchema) {
+        self.schemas.insert(schema.key.to_string(), schema);
+    }
+    pub fn schemas(&self) -> Vec<ConfigSchemaInfo> {
+        let mut schemas: Vec<ConfigSchemaInfo> = self
+            .schemas
+            .values()
+            .map(|s| ConfigSchemaInfo {
+                key: s.key.to_string(),
+                rule: s.rule.clone(),
+            })
+            .collect();
+        schemas.sort_by(|a, b| a.key.cmp(&b.key));
+        schemas
+    }
+    pub fn validate(&self, key: &str, value: &str) -> Result<(), Error> {
+        match self.schemas.get(key) {
+            Some(schema) => (schema.validator)(value).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid value for {key}: {e}"),
+                )
+            }),
+            None => Ok(()),
+        }
+    }
+    pub fn validate_all(&self, entries: &[AddConfigEntry]) -> Vec<ConfigValidationError> {
+        entries
+            .iter()
+            .filter_map(|entry| {
+                self.validate(&entry.key, &entry.value)
+                    .err()
+                    .map(|e| ConfigValidationError {
+                        key: entry.key.clone(),
+                        error: e.to_string(),
+                    })
+            })
+            .collect()
     }
     pub async fn get(&self, key: &str) -> Option<ConfigEntry> {
         self.entries.get(key).cloned()
     }
+    /// Parses the value stored at `key` as JSON, None if it is missing or does not parse
+    pub async fn get_as<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let entry = self.entries.get(key)?;
+        match serde_json::from_str(&entry.value) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!("Failed to parse Config Entry {key}: {e:?}");
+                None
+            }
+        }
+    }
+    pub async fn get_or_default<T: DeserializeOwned + Default>(&self, key: &str) -> T {
+        self.get_as(key).await.unwrap_or_default()
+    }
+    pub async fn set_as<T: Serialize>(
+        &mut self,
+        key: &str,
+        value: &T,
+        category: &str,
+        db: Option<&SqlitePool>,
+    ) -> Result<(), Error> {
+        let value = serde_json::to_string(value)?;
+        let (last_value, system) = self
+            .entries
+            .get(key)
+            .map(|e| (e.value.clone(), e.system))
+            .unwrap_or_default();
+        self.set(
+            key,
+            AddConfigEntry {
+                key: key.to_string(),
+                value,
+                last_value,
+                category: category.to_string(),
+                system,
+            },
+            db,
+        )
+        .await?;
+        Ok(())
+    }
     pub async fn set(
         &mut self,
         key: &str,
         entry: AddConfigEntry,
         db: Option<&SqlitePool>,
     ) -> Result<Option<ConfigEntry>, Error> {
+        self.set_by(key, entry, db, None).await
+    }
+    /// Same as set, recording `user_id` as the author in the config history
+    pub async fn set_by(
+        &mut self,
+        key: &str,
+        entry: AddConfigEntry,
+        db: Option<&SqlitePool>,
+        user_id: Option<i64>,
+    ) -> Result<Option<ConfigEntry>, Error> {
+        //The entry is what gets persisted, so it must be the key that was validated
+        if entry.key != key {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Config key {} does not match {key}", entry.key),
+            ));
+        }
+        self.validate(key, &entry.value)?;
         if let Some(db) = db {
-            create_config_entry(db, &entry).await?;
+            create_config_entry(db, &entry, user_id).await?;
         }
+        self.notify(ConfigEventType::Updated, key, &entry.value, &entry.category);
         Ok(self.entries.insert(
             key.to_string(),
             ConfigEntry {
@@ -47,6 +374,35 @@ impl ConfigManager {
             },
         ))
     }
+    pub async fn set_batch(
+        &mut self,
+        entries: Vec<AddConfigEntry>,
+        db: &SqlitePool,
+        user_id: Option<i64>,
+    ) -> Result<Vec<ConfigEntry>, Vec<ConfigValidationError>> {
+        let errors = self.validate_all(&entries);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        match create_config_entries_batch(db, &entries, user_id).await {
+            Ok(saved) => {
+                for entry in &saved {
+                    self.notify(
+                        ConfigEventType::Updated,
+                        &entry.key,
+                        &entry.value,
+                        &entry.category,
+                    );
+                    self.entries.insert(entry.key.clone(), entry.clone());
+                }
+                Ok(saved)
+            }
+            Err(e) => Err(vec![ConfigValidationError {
+                key: String::new(),
+                error: e.to_string(),
+            }]),
+        }
+    }
     pub async fn reload_key(&mut self, key: &str, db: &SqlitePool) -> Result<(), Error> {
         match get_config_key(db, key).await? {
             Some(entry) => {
@@ -70,13 +426,30 @@ impl ConfigManager {
             )),
         }
     }
-    pub async fn reload(&mut self, db: &SqlitePool) -> Result<(), Error> {
-        self.entries = get_config(db)
+    pub async fn reload(&mut self, db: &SqlitePool) -> Result<usize, Error> {
+        let entries: HashMap<String, ConfigEntry> = get_config(db)
             .await?
             .into_iter()
             .map(|e| (e.key.clone(), e))
             .collect();
-        Ok(())
+        //Diff against the cache so out of band changes reach subscribers
+        let mut changed = 0;
+        for (key, entry) in &entries {
+            if self.entries.get(key).is_none_or(|e| e.value != entry.value) {
+                debug!("Config {key} changed in the database");
+                self.notify(ConfigEventType::Updated, key, &entry.value, &entry.category);
+                changed += 1;
+            }
+        }
+        for (key, entry) in &self.entries {
+            if !entries.contains_key(key) {
+                debug!("Config {key} removed from the database");
+                self.notify(ConfigEventType::Deleted, key, &entry.value, &entry.category);
+                changed += 1;
+            }
+        }
+        self.entries = entries;
+        Ok(changed)
     }
     pub async fn save(&mut self, db: &SqlitePool) -> Result<(), Error> {
         for entry in self.entries.values() {
@@ -89,14 +462,105 @@ impl ConfigManager {
                     category: entry.category.clone(),
                     system: entry.system,
                 },
+                None,
             )
             .await?;
         }
         Ok(())
     }
     pub async fn delete(&mut self, key: &str, db: &SqlitePool) -> Result<(), Error> {
-        self.entries.remove(key);
+        let removed = self.entries.remove(key);
         delete_config_entry(db, key).await?;
+        if let Some(removed) = removed {
+            self.notify(
+                ConfigEventType::Deleted,
+                key,
+                &removed.value,
+                &removed.category,
+            );
+        }
         Ok(())
     }
 }
+
+#[tokio::test]
+async fn test_config_typed_round_trip() {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    crate::utils::run_migrations(&pool).await.unwrap();
+    let mut manager = ConfigManager::new(&pool).await.unwrap();
+    manager
+        .set_as("test_u64", &42u64, "test", Some(&pool))
+        .await
+        .unwrap();
+    manager
+        .set_as("test_string", &"value".to_string(), "test", None)
+        .await
+        .unwrap();
+    manager
+        .set_as("test_list", &vec![1u8, 2, 3], "test", None)
+        .await
+        .unwrap();
+    manager
+        .set_as(
+            "test_map",
+            &HashMap::from([("a".to_string(), true)]),
+            "test",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(manager.get_as::<u64>("test_u64").await, Some(42));
+    assert_eq!(
+        manager.get_as::<String>("test_string").await,
+        Some("value".to_string())
+    );
+    assert_eq!(
+        manager.get_as::<Vec<u8>>("test_list").await,
+        Some(vec![1, 2, 3])
+    );
+    assert_eq!(
+        manager.get_as::<HashMap<String, bool>>("test_map").await,
+        Some(HashMap::from([("a".to_string(), true)]))
+    );
+    //Wrong types and missing keys fall back instead of erroring
+    assert_eq!(manager.get_as::<u64>("test_string").await, None);
+    assert_eq!(manager.get_or_default::<u64>("missing").await, 0);
+    manager.reload(&pool).await.unwrap();
+    assert_eq!(manager.get_as::<u64>("test_u64").await, Some(42));
+    let mismatched = AddConfigEntry {
+        key: "stats_max_rows".to_string(),
+        value: "1".to_string(),
+        last_value: String::new(),
+        category: "test".to_string(),
+        system: 0,
+    };
+    assert!(manager
+        .set("test_u64", mismatched, Some(&pool))
+        .await
+        .is_err());
+    assert_eq!(manager.get_as::<u64>("stats_max_rows").await, None);
+}
+
+#[test]
+fn test_redact_value() {
+    let email = r#"{"smtp_host":"smtp.local","password":"hunter2","to_addresses":["a@b.c"]}"#;
+    let redacted: Value = serde_json::from_str(&redact_value("email_notifier", email)).unwrap();
+    assert_eq!(redacted["password"], "");
+    assert_eq!(redacted["smtp_host"], "smtp.local");
+    let webhooks = r#"[{"url":"https://hook.local","secret":"abc","events":[]}]"#;
+    assert!(!redact_value("webhooks", webhooks).contains("abc"));
+    assert_eq!(redact_value("email_notifier", "not json"), "");
+    assert_eq!(redact_value("stats_max_rows", "5000"), "5000");
+    assert!(ensure_writable("email_notifier").is_err());
+    assert!(ensure_writable("stats_max_rows").is_ok());
+    let store_url = "https://plugins.druid.garden/plugins.yaml";
+    assert!(ensure_writable(PLUGIN_STORE_URL_CONFIG_KEY).is_err());
+    assert_eq!(
+        redact_value(PLUGIN_STORE_URL_CONFIG_KEY, store_url),
+        store_url
+    );
+}
@@ -0,0 +1,128 @@
+use crate::database::map_sqlx_error;
+use crate::models::pagination::{ListQuery, Paginated, SortOrder};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+use std::io::Error;
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    pub timestamp: OffsetDateTime,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub request_body_hash: Option<String>,
+    pub response_status: i64,
+    pub ip_address: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AddAuditLogEntry {
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub request_body_hash: Option<String>,
+    pub response_status: u16,
+    pub ip_address: Option<String>,
+}
+
+pub async fn insert_audit_log(pool: &SqlitePool, entry: &AddAuditLogEntry) -> Result<(), Error> {
+    let now = OffsetDateTime::now_utc();
+    let response_status = entry.response_status as i64;
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (timestamp, user_id, username, method, path, request_body_hash, response_status, ip_address)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        now,
+        entry.user_id,
+        entry.username,
+        entry.method,
+        entry.path,
+        entry.request_body_hash,
+        response_status,
+        entry.ip_address
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+    .map_err(map_sqlx_error)
+}
+
+const AUDIT_LOG_COLUMNS: &str = "id, timestamp, user_id, username, method, path, request_body_hash, response_status, ip_address";
+pub const AUDIT_LOG_SORT_FIELDS: [&str; 4] = ["timestamp", "user_id", "path", "response_status"];
+
+#[derive(Debug, Default, Clone)]
+pub struct AuditLogFilter {
+    pub start: Option<OffsetDateTime>,
+    pub end: Option<OffsetDateTime>,
+    pub user_id: Option<i64>,
+    pub path: Option<String>,
+}
+
+fn push_audit_log_filter<'a>(builder: &mut QueryBuilder<'a, Sqlite>, filter: &'a AuditLogFilter) {
+    builder.push(" WHERE 1 = 1");
+    if let Some(start) = filter.start {
+        builder.push(" AND timestamp >= ").push_bind(start);
+    }
+    if let Some(end) = filter.end {
+        builder.push(" AND timestamp <= ").push_bind(end);
+    }
+    if let Some(user_id) = filter.user_id {
+        builder.push(" AND user_id = ").push_bind(user_id);
+    }
+    //Paths are matched as a prefix so a whole route group can be audited at once
+    if let Some(path) = &filter.path {
+        builder
+            .push(" AND path LIKE ")
+            .push_bind(format!("{path}%"));
+    }
+}
+
+pub async fn get_audit_log_page(
+    pool: &SqlitePool,
+    filter: &AuditLogFilter,
+    query: &ListQuery,
+) -> Result<Paginated<AuditLogEntry>, Error> {
+    let sort = query.sort_field(&AUDIT_LOG_SORT_FIELDS, "timestamp")?;
+    let order = query.order_or(SortOrder::Desc);
+    let mut count_builder = QueryBuilder::new("SELECT COUNT(*) FROM audit_log");
+    push_audit_log_filter(&mut count_builder, filter);
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await
+        .map_err(map_sqlx_error)?;
+    let mut builder = QueryBuilder::new(format!("SELECT {AUDIT_LOG_COLUMNS} FROM audit_log"));
+    push_audit_log_filter(&mut builder, filter);
+    //The sort field is validated against AUDIT_LOG_SORT_FIELDS so it is safe to push
+    builder
+        .push(format!(" ORDER BY {sort} {}", order.as_sql()))
+        .push(" LIMIT ")
+        .push_bind(query.per_page as i64)
+        .push(" OFFSET ")
+        .push_bind(query.offset() as i64);
+    let items = builder
+        .build_query_as::<AuditLogEntry>()
+        .fetch_all(pool)
+        .await
+        .map_err(map_sqlx_error)?;
+    Ok(Paginated::new(items, total as usize, query))
+}
+
+pub async fn prune_audit_log(pool: &SqlitePool, older_than: OffsetDateTime) -> Result<u64, Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM audit_log
+        WHERE timestamp <= $1
+        "#,
+        older_than,
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+    .map_err(map_sqlx_error)
+}
@@ -1,9 +1,11 @@
 use crate::database::map_sqlx_error;
-use crate::models::config::{AddConfigEntry, ConfigEntry};
-use sqlx::SqlitePool;
+use crate::models::config::{AddConfigEntry, ConfigEntry, ConfigHistoryEntry};
+use sqlx::{Sqlite, SqliteConnection, SqlitePool, Transaction};
 use std::io::Error;
 use time::OffsetDateTime;
 
+pub const CONFIG_HISTORY_LIMIT: i64 = 20;
+
 pub async fn get_config(pool: &SqlitePool) -> Result<Vec<ConfigEntry>, Error> {
     sqlx::query_as!(
         ConfigEntry,
@@ -35,10 +37,48 @@ pub async fn get_config_key(pool: &SqlitePool, key: &str) -> Result<Option<Confi
         Err(e) => Err(map_sqlx_error(e)),
     }
 }
+//Records the new value and trims the key to the last CONFIG_HISTORY_LIMIT versions
+async fn record_config_history(
+    conn: &mut SqliteConnection,
+    key: &str,
+    value: &str,
+    modified: OffsetDateTime,
+    modified_by_user_id: Option<i64>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO config_history (key, value, modified, modified_by_user_id)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        key,
+        value,
+        modified,
+        modified_by_user_id,
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(map_sqlx_error)?;
+    sqlx::query!(
+        r#"
+        DELETE FROM config_history
+        WHERE key = $1 AND id NOT IN (
+            SELECT id FROM config_history WHERE key = $1 ORDER BY id DESC LIMIT $2
+        )
+        "#,
+        key,
+        CONFIG_HISTORY_LIMIT,
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(map_sqlx_error)?;
+    Ok(())
+}
 pub async fn create_config_entry(
     pool: &SqlitePool,
     entry: &AddConfigEntry,
+    modified_by_user_id: Option<i64>,
 ) -> Result<Option<ConfigEntry>, Error> {
+    let mut tx: Transaction<Sqlite> = pool.begin().await.map_err(map_sqlx_error)?;
     let now = OffsetDateTime::now_utc();
     let key = sqlx::query!(
         r#"
@@ -60,12 +100,61 @@ pub async fn create_config_entry(
         now,
         now,
     )
-    .fetch_one(pool)
+    .fetch_one(tx.as_mut())
     .await
     .map_err(map_sqlx_error)?
     .key;
+    record_config_history(tx.as_mut(), &key, &entry.value, now, modified_by_user_id).await?;
+    tx.commit().await.map_err(map_sqlx_error)?;
     get_config_key(pool, &key).await
 }
+pub async fn create_config_entries_batch(
+    pool: &SqlitePool,
+    entries: &[AddConfigEntry],
+    modified_by_user_id: Option<i64>,
+) -> Result<Vec<ConfigEntry>, Error> {
+    let mut tx: Transaction<Sqlite> = pool.begin().await.map_err(map_sqlx_error)?;
+    let now = OffsetDateTime::now_utc();
+    let mut results = Vec::with_capacity(entries.len());
+    for entry in entries {
+        //Any failure drops the transaction which rolls back all previous entries
+        let result = sqlx::query_as!(
+            ConfigEntry,
+            r#"
+            INSERT INTO config (key, value, last_value, category, system, created, modified)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (key)
+            DO UPDATE SET
+                last_value = value,
+                value = EXCLUDED.value,
+                category = EXCLUDED.category,
+                modified = EXCLUDED.modified
+            RETURNING key, value, last_value, category, system, created, modified
+            "#,
+            entry.key,
+            entry.value,
+            entry.last_value,
+            entry.category,
+            entry.system,
+            now,
+            now,
+        )
+        .fetch_one(tx.as_mut())
+        .await
+        .map_err(map_sqlx_error)?;
+        record_config_history(
+            tx.as_mut(),
+            &result.key,
+            &result.value,
+            now,
+            modified_by_user_id,
+        )
+        .await?;
+        results.push(result);
+    }
+    tx.commit().await.map_err(map_sqlx_error)?;
+    Ok(results)
+}
 pub async fn delete_config_entry(pool: &SqlitePool, key: &str) -> Result<u64, Error> {
     sqlx::query!(
         r#"
@@ -79,3 +168,76 @@ pub async fn delete_config_entry(pool: &SqlitePool, key: &str) -> Result<u64, Er
     .map(|r| r.rows_affected())
     .map_err(map_sqlx_error)
 }
+pub async fn get_config_history(
+    pool: &SqlitePool,
+    key: &str,
+) -> Result<Vec<ConfigHistoryEntry>, Error> {
+    sqlx::query_as!(
+        ConfigHistoryEntry,
+        r#"
+        SELECT id, key, value, modified, modified_by_user_id
+        FROM config_history
+        WHERE key = $1
+        ORDER BY id DESC
+        "#,
+        key
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(map_sqlx_error)
+}
+pub async fn get_config_history_entry(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<Option<ConfigHistoryEntry>, Error> {
+    let results = sqlx::query_as!(
+        ConfigHistoryEntry,
+        r#"
+        SELECT id, key, value, modified, modified_by_user_id
+        FROM config_history
+        WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .map(Some);
+    match results {
+        Ok(result) => Ok(result),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(map_sqlx_error(e)),
+    }
+}
+
+#[tokio::test]
+async fn test_config_history_author() {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    crate::utils::run_migrations(&pool).await.unwrap();
+    let entry = |value: usize| AddConfigEntry {
+        key: "test_history".to_string(),
+        value: value.to_string(),
+        last_value: String::new(),
+        category: "test".to_string(),
+        system: 0,
+    };
+    create_config_entry(&pool, &entry(0), None).await.unwrap();
+    create_config_entry(&pool, &entry(1), Some(7))
+        .await
+        .unwrap();
+    let history = get_config_history(&pool, "test_history").await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].value, "1");
+    assert_eq!(history[0].modified_by_user_id, Some(7));
+    assert_eq!(history[1].modified_by_user_id, None);
+    for value in 2..CONFIG_HISTORY_LIMIT as usize + 5 {
+        create_config_entries_batch(&pool, &[entry(value)], Some(7))
+            .await
+            .unwrap();
+    }
+    let history = get_config_history(&pool, "test_history").await.unwrap();
+    assert_eq!(history.len(), CONFIG_HISTORY_LIMIT as usize);
+}
@@ -0,0 +1,146 @@
+use crate::database::map_sqlx_error;
+use crate::models::pagination::{ListQuery, Paginated, SortOrder};
+use portfu::prelude::serde_json::Value;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventSeverity {
+    Info,
+    Warn,
+    Error,
+}
+impl EventSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventSeverity::Info => "info",
+            EventSeverity::Warn => "warn",
+            EventSeverity::Error => "error",
+        }
+    }
+}
+impl Display for EventSeverity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl FromStr for EventSeverity {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "info" => Ok(EventSeverity::Info),
+            "warn" => Ok(EventSeverity::Warn),
+            "error" => Ok(EventSeverity::Error),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid severity: {s}, expected info, warn or error"),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SystemEvent {
+    pub id: i64,
+    pub timestamp: OffsetDateTime,
+    pub severity: String,
+    pub category: String,
+    pub message: String,
+    pub metadata: Option<String>,
+}
+
+pub async fn log_event(
+    pool: &SqlitePool,
+    severity: EventSeverity,
+    category: &str,
+    message: &str,
+    metadata: Option<Value>,
+) -> Result<(), Error> {
+    let now = OffsetDateTime::now_utc();
+    let severity = severity.as_str();
+    let metadata = metadata.map(|metadata| metadata.to_string());
+    sqlx::query!(
+        r#"
+        INSERT INTO system_events (timestamp, severity, category, message, metadata)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        now,
+        severity,
+        category,
+        message,
+        metadata
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+    .map_err(map_sqlx_error)
+}
+
+const SYSTEM_EVENT_COLUMNS: &str = "id, timestamp, severity, category, message, metadata";
+pub const SYSTEM_EVENT_SORT_FIELDS: [&str; 3] = ["timestamp", "severity", "category"];
+
+#[derive(Debug, Default, Clone)]
+pub struct SystemEventFilter {
+    pub start: Option<OffsetDateTime>,
+    pub end: Option<OffsetDateTime>,
+    pub category: Option<String>,
+    pub severity: Option<EventSeverity>,
+}
+
+fn push_system_event_filter<'a>(
+    builder: &mut QueryBuilder<'a, Sqlite>,
+    filter: &'a SystemEventFilter,
+) {
+    builder.push(" WHERE 1 = 1");
+    if let Some(start) = filter.start {
+        builder.push(" AND timestamp >= ").push_bind(start);
+    }
+    if let Some(end) = filter.end {
+        builder.push(" AND timestamp <= ").push_bind(end);
+    }
+    if let Some(category) = &filter.category {
+        builder.push(" AND category = ").push_bind(category);
+    }
+    if let Some(severity) = filter.severity {
+        builder
+            .push(" AND severity = ")
+            .push_bind(severity.as_str());
+    }
+}
+
+pub async fn get_system_events_page(
+    pool: &SqlitePool,
+    filter: &SystemEventFilter,
+    query: &ListQuery,
+) -> Result<Paginated<SystemEvent>, Error> {
+    let sort = query.sort_field(&SYSTEM_EVENT_SORT_FIELDS, "timestamp")?;
+    let order = query.order_or(SortOrder::Desc);
+    let mut count_builder = QueryBuilder::new("SELECT COUNT(*) FROM system_events");
+    push_system_event_filter(&mut count_builder, filter);
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await
+        .map_err(map_sqlx_error)?;
+    let mut builder =
+        QueryBuilder::new(format!("SELECT {SYSTEM_EVENT_COLUMNS} FROM system_events"));
+    push_system_event_filter(&mut builder, filter);
+    //The sort field is validated against SYSTEM_EVENT_SORT_FIELDS so it is safe to push
+    builder
+        .push(format!(" ORDER BY {sort} {}", order.as_sql()))
+        .push(" LIMIT ")
+        .push_bind(query.per_page as i64)
+        .push(" OFFSET ")
+        .push_bind(query.offset() as i64);
+    let items = builder
+        .build_query_as::<SystemEvent>()
+        .fetch_all(pool)
+        .await
+        .map_err(map_sqlx_error)?;
+    Ok(Paginated::new(items, total as usize, query))
+}
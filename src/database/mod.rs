@@ -1,11 +1,114 @@
-use std::io::Error;
+use sqlx::{Row, SqlitePool};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
 
+pub mod audit;
 pub mod config;
+pub mod events;
 pub mod plugins;
 pub mod stats;
 pub mod users;
+pub mod wizard;
 
 pub fn map_sqlx_error(e: sqlx::Error) -> Error {
     println!("{e:?}");
     Error::other(e)
 }
+
+pub async fn backup(pool: &SqlitePool, destination_path: &Path) -> Result<(), Error> {
+    if destination_path.exists() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("Backup already exists: {}", destination_path.display()),
+        ));
+    }
+    //VACUUM INTO creates a consistent copy without blocking other readers
+    sqlx::query("VACUUM INTO $1")
+        .bind(destination_path.display().to_string())
+        .execute(pool)
+        .await
+        .map_err(map_sqlx_error)?;
+    Ok(())
+}
+
+pub async fn restore(pool: &SqlitePool, source_path: &Path) -> Result<(), Error> {
+    if !source_path.is_file() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("Backup not found: {}", source_path.display()),
+        ));
+    }
+    let mut conn = pool.acquire().await.map_err(map_sqlx_error)?;
+    sqlx::query("ATTACH DATABASE $1 AS backup")
+        .bind(source_path.display().to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(map_sqlx_error)?;
+    let result = restore_from_attached(&mut conn).await;
+    sqlx::query("DETACH DATABASE backup")
+        .execute(&mut *conn)
+        .await
+        .map_err(map_sqlx_error)?;
+    result
+}
+
+async fn restore_from_attached(conn: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+    let live_version: Option<i64> =
+        sqlx::query_scalar("SELECT max(version) FROM main._sqlx_migrations")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(map_sqlx_error)?;
+    let backup_version: Option<i64> =
+        sqlx::query_scalar("SELECT max(version) FROM backup._sqlx_migrations")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(map_sqlx_error)?;
+    if live_version != backup_version {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Backup schema version {backup_version:?} does not match the current version {live_version:?}"
+            ),
+        ));
+    }
+    let table_query = r#"
+        SELECT name FROM {schema}.sqlite_master
+        WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != '_sqlx_migrations'
+    "#;
+    let live_tables: Vec<String> = sqlx::query(&table_query.replace("{schema}", "main"))
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(map_sqlx_error)?
+        .into_iter()
+        .map(|row| row.get(0))
+        .collect();
+    let mut tables: Vec<String> = sqlx::query(&table_query.replace("{schema}", "backup"))
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(map_sqlx_error)?
+        .into_iter()
+        .map(|row| row.get::<String, _>(0))
+        .filter(|name| live_tables.contains(name))
+        .collect();
+    let mut tx = sqlx::Connection::begin(&mut *conn)
+        .await
+        .map_err(map_sqlx_error)?;
+    sqlx::query("PRAGMA defer_foreign_keys = ON")
+        .execute(tx.as_mut())
+        .await
+        .map_err(map_sqlx_error)?;
+    for table in &tables {
+        sqlx::query(&format!(r#"DELETE FROM main."{table}""#))
+            .execute(tx.as_mut())
+            .await
+            .map_err(map_sqlx_error)?;
+        sqlx::query(&format!(
+            r#"INSERT INTO main."{table}" SELECT * FROM backup."{table}""#
+        ))
+        .execute(tx.as_mut())
+        .await
+        .map_err(map_sqlx_error)?;
+    }
+    tx.commit().await.map_err(map_sqlx_error)?;
+    Ok(())
+}
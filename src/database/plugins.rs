@@ -7,7 +7,7 @@ pub async fn get_all_plugins(pool: &SqlitePool) -> Result<Vec<Plugin>, Error> {
     sqlx::query_as!(
         Plugin,
         r#"
-        SELECT id, label, name, enabled, plugin_type, source, run_command, repo, tag, version, added, updated
+        SELECT id, label, name, enabled, plugin_type, source, run_command, repo, tag, version, download_count, added, updated
         FROM plugins
         "#
     )
@@ -19,7 +19,7 @@ pub async fn get_plugin(pool: &SqlitePool, name: &str) -> Result<Option<Plugin>,
     let results = sqlx::query_as!(
         Plugin,
         r#"
-        SELECT id, label, name, enabled, plugin_type, source, run_command, repo, tag, version, added, updated
+        SELECT id, label, name, enabled, plugin_type, source, run_command, repo, tag, version, download_count, added, updated
         FROM plugins
         WHERE name = $1
         "#,
@@ -70,6 +70,20 @@ pub async fn create_plugin(pool: &SqlitePool, entry: &Plugin) -> Result<Option<P
     .name;
     get_plugin(pool, &name).await
 }
+pub async fn increment_plugin_download_count(pool: &SqlitePool, name: &str) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        UPDATE plugins
+        SET download_count = download_count + 1
+        WHERE name = $1
+        "#,
+        name
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+    .map_err(map_sqlx_error)
+}
 pub async fn delete_plugin(pool: &SqlitePool, name: &str) -> Result<u64, Error> {
     sqlx::query!(
         r#"
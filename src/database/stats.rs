@@ -1,46 +1,242 @@
 use crate::database::map_sqlx_error;
+use crate::models::pagination::{ListQuery, Paginated, SortOrder};
 use dg_xch_core::blockchain::sized_bytes::Bytes32;
 use dg_xch_core::protocols::farmer::FarmerStats;
-use sqlx::SqlitePool;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+use std::collections::{HashMap, HashSet};
 use std::io::Error;
 use time::OffsetDateTime;
 
+#[derive(FromRow)]
+struct FarmerStatsRangeRow {
+    launcher_id: Bytes32,
+    #[sqlx(flatten)]
+    stats: FarmerStats,
+}
+
+//Keyed by launcher as well, each launcher records its own row for the same signage point
 pub async fn get_farmer_stats_range(
     pool: &SqlitePool,
     start: OffsetDateTime,
     end: OffsetDateTime,
-) -> Result<HashMap<(Bytes32, Bytes32), FarmerStats>, Error> {
-    let mut results = HashMap::<(Bytes32, Bytes32), FarmerStats>::new();
+) -> Result<HashMap<(Bytes32, Bytes32, Bytes32), FarmerStats>, Error> {
+    let rows = sqlx::query_as::<_, FarmerStatsRangeRow>(&format!(
+        "SELECT launcher_id, {FARMER_STATS_COLUMNS} FROM farmer_stats WHERE gathered >= $1 AND gathered <= $2 ORDER BY gathered ASC"
+    ))
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await;
+    match rows {
+        Ok(rows) => Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    (row.launcher_id, row.stats.sp_hash, row.stats.challenge_hash),
+                    row.stats,
+                )
+            })
+            .collect()),
+        Err(sqlx::Error::RowNotFound) => Ok(HashMap::new()),
+        Err(e) => Err(map_sqlx_error(e)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LauncherFarmerStats {
+    #[serde(default)]
+    pub launcher_id: Option<Bytes32>,
+    #[serde(flatten)]
+    pub stats: FarmerStats,
+}
+
+#[derive(FromRow, Debug, Clone, Serialize, Deserialize)]
+pub struct FarmerStatsLauncher {
+    pub launcher_id: Bytes32,
+}
+
+pub async fn get_farmer_stats_by_launcher(
+    pool: &SqlitePool,
+    launcher_id: Bytes32,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> Result<Vec<FarmerStats>, Error> {
+    let launcher_id: &[u8] = launcher_id.as_ref();
     let rows = sqlx::query_as!(
         FarmerStats,
         r#"
         SELECT challenge_hash, sp_hash, running, og_passed_filter, og_plot_count,
-            nft_passed_filter, nft_plot_count, compressed_passed_filter, 
+            nft_passed_filter, nft_plot_count, compressed_passed_filter,
             compressed_plot_count, invalid_plot_count, proofs_found, total_plot_space,
             full_node_height, full_node_difficulty, full_node_synced, gathered
         FROM farmer_stats
-        WHERE gathered >= $1
-        AND gathered <= $2
+        WHERE launcher_id = $1
+        AND gathered >= $2
+        AND gathered <= $3
         ORDER BY gathered ASC
         "#,
+        launcher_id,
         start,
         end
     )
     .fetch_all(pool)
     .await;
     match rows {
-        Ok(rows) => {
-            for row in rows {
-                results.insert((row.sp_hash, row.challenge_hash), row);
-            }
-            Ok(results)
-        }
-        Err(sqlx::Error::RowNotFound) => Ok(results),
+        Ok(rows) => Ok(rows),
+        Err(sqlx::Error::RowNotFound) => Ok(vec![]),
+        Err(e) => Err(map_sqlx_error(e)),
+    }
+}
+
+pub async fn get_farmer_stats_launchers(pool: &SqlitePool) -> Result<Vec<Bytes32>, Error> {
+    let rows = sqlx::query_as!(
+        FarmerStatsLauncher,
+        r#"
+        SELECT DISTINCT launcher_id
+        FROM farmer_stats
+        ORDER BY launcher_id ASC
+        "#
+    )
+    .fetch_all(pool)
+    .await;
+    match rows {
+        Ok(rows) => Ok(rows.into_iter().map(|row| row.launcher_id).collect()),
+        Err(sqlx::Error::RowNotFound) => Ok(vec![]),
         Err(e) => Err(map_sqlx_error(e)),
     }
 }
 
+const FARMER_STATS_COLUMNS: &str = "challenge_hash, sp_hash, running, og_passed_filter, og_plot_count, nft_passed_filter, nft_plot_count, compressed_passed_filter, compressed_plot_count, invalid_plot_count, proofs_found, total_plot_space, full_node_height, full_node_difficulty, full_node_synced, gathered";
+pub const FARMER_STATS_SORT_FIELDS: [&str; 4] = [
+    "gathered",
+    "proofs_found",
+    "total_plot_space",
+    "full_node_height",
+];
+
+#[derive(Debug, Default, Clone)]
+pub struct FarmerStatsFilter {
+    pub launcher_id: Option<Bytes32>,
+    pub start: Option<OffsetDateTime>,
+    pub end: Option<OffsetDateTime>,
+}
+
+fn push_farmer_stats_filter<'a>(
+    builder: &mut QueryBuilder<'a, Sqlite>,
+    filter: &'a FarmerStatsFilter,
+) {
+    builder.push(" WHERE 1 = 1");
+    if let Some(launcher_id) = &filter.launcher_id {
+        let launcher_id: &[u8] = launcher_id.as_ref();
+        builder.push(" AND launcher_id = ").push_bind(launcher_id);
+    }
+    if let Some(start) = filter.start {
+        builder.push(" AND gathered >= ").push_bind(start);
+    }
+    if let Some(end) = filter.end {
+        builder.push(" AND gathered <= ").push_bind(end);
+    }
+}
+
+pub async fn get_farmer_stats_page(
+    pool: &SqlitePool,
+    filter: &FarmerStatsFilter,
+    query: &ListQuery,
+) -> Result<Paginated<FarmerStats>, Error> {
+    let sort = query.sort_field(&FARMER_STATS_SORT_FIELDS, "gathered")?;
+    let order = query.order_or(SortOrder::Desc);
+    let mut count_builder = QueryBuilder::new("SELECT COUNT(*) FROM farmer_stats");
+    push_farmer_stats_filter(&mut count_builder, filter);
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await
+        .map_err(map_sqlx_error)?;
+    let mut builder = QueryBuilder::new(format!("SELECT {FARMER_STATS_COLUMNS} FROM farmer_stats"));
+    push_farmer_stats_filter(&mut builder, filter);
+    //The sort field is validated against FARMER_STATS_SORT_FIELDS so it is safe to push
+    builder
+        .push(format!(" ORDER BY {sort} {}", order.as_sql()))
+        .push(" LIMIT ")
+        .push_bind(query.per_page as i64)
+        .push(" OFFSET ")
+        .push_bind(query.offset() as i64);
+    let items = builder
+        .build_query_as::<FarmerStats>()
+        .fetch_all(pool)
+        .await
+        .map_err(map_sqlx_error)?;
+    Ok(Paginated::new(items, total as usize, query))
+}
+
+pub const FARMER_STATS_CSV_HEADER: &str = "challenge_hash,sp_hash,running,og_passed_filter,og_plot_count,nft_passed_filter,nft_plot_count,compressed_passed_filter,compressed_plot_count,invalid_plot_count,proofs_found,total_plot_space,full_node_height,full_node_difficulty,full_node_synced,gathered";
+
+pub fn farmer_stats_to_csv(stats: &[FarmerStats]) -> String {
+    let mut csv = String::from(FARMER_STATS_CSV_HEADER);
+    csv.push('\n');
+    for row in stats {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            row.challenge_hash,
+            row.sp_hash,
+            row.running,
+            row.og_passed_filter,
+            row.og_plot_count,
+            row.nft_passed_filter,
+            row.nft_plot_count,
+            row.compressed_passed_filter,
+            row.compressed_plot_count,
+            row.invalid_plot_count,
+            row.proofs_found,
+            row.total_plot_space,
+            row.full_node_height,
+            row.full_node_difficulty,
+            row.full_node_synced,
+            row.gathered.unix_timestamp()
+        ));
+    }
+    csv
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FarmerStatsSummary {
+    pub signage_points: u64,
+    pub challenges: u64,
+    pub total_plots: u64,
+    pub total_plot_space: u64,
+    pub passed_filter: u64,
+    pub proofs_found: u64,
+    pub invalid_plots: u64,
+}
+
+pub fn summarize_farmer_stats(stats: &[FarmerStats]) -> FarmerStatsSummary {
+    let mut summary = FarmerStatsSummary {
+        signage_points: stats.len() as u64,
+        challenges: stats
+            .iter()
+            .map(|row| row.challenge_hash)
+            .collect::<HashSet<_>>()
+            .len() as u64,
+        ..Default::default()
+    };
+    for row in stats {
+        summary.passed_filter += row.og_passed_filter as u64
+            + row.nft_passed_filter as u64
+            + row.compressed_passed_filter as u64;
+        summary.proofs_found += row.proofs_found as u64;
+    }
+    //Plot counts are a snapshot, so report the most recent values in the range
+    if let Some(latest) = stats.iter().max_by_key(|row| row.gathered) {
+        summary.total_plots = latest.og_plot_count as u64
+            + latest.nft_plot_count as u64
+            + latest.compressed_plot_count as u64;
+        summary.total_plot_space = latest.total_plot_space as u64;
+        summary.invalid_plots = latest.invalid_plot_count as u64;
+    }
+    summary
+}
+
 pub async fn prune_farmer_stats(
     pool: &SqlitePool,
     older_than: OffsetDateTime,
@@ -62,20 +258,69 @@ pub async fn prune_farmer_stats(
     }
 }
 
+pub async fn prune_farmer_stats_rows(pool: &SqlitePool, max_rows: u64) -> Result<u64, Error> {
+    let row_count = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!: i64" FROM farmer_stats"#)
+        .fetch_one(pool)
+        .await
+        .map_err(map_sqlx_error)? as u64;
+    if row_count <= max_rows {
+        return Ok(0);
+    }
+    //farmer_stats has no id column so the implicit rowid is used
+    let to_remove = (row_count - max_rows) as i64;
+    sqlx::query!(
+        r#"
+        DELETE FROM farmer_stats
+        WHERE rowid IN (SELECT rowid FROM farmer_stats ORDER BY gathered ASC LIMIT $1)
+        "#,
+        to_remove,
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+    .map_err(map_sqlx_error)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FarmerStatsSize {
+    pub row_count: i64,
+    pub oldest_entry: Option<OffsetDateTime>,
+    pub newest_entry: Option<OffsetDateTime>,
+}
+
+pub async fn get_farmer_stats_size(pool: &SqlitePool) -> Result<FarmerStatsSize, Error> {
+    sqlx::query_as!(
+        FarmerStatsSize,
+        r#"
+        SELECT COUNT(*) as "row_count!: i64",
+            MIN(gathered) as "oldest_entry?: OffsetDateTime",
+            MAX(gathered) as "newest_entry?: OffsetDateTime"
+        FROM farmer_stats
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(map_sqlx_error)
+}
+
 pub async fn has_farmer_stats(
     pool: &SqlitePool,
+    launcher_id: Bytes32,
     challenge_hash: Bytes32,
     sp_hash: Bytes32,
 ) -> Result<bool, Error> {
+    let launcher_id: &[u8] = launcher_id.as_ref();
     let challenge_hash: &[u8] = challenge_hash.as_ref();
     let sp_hash: &[u8] = sp_hash.as_ref();
     let row = sqlx::query!(
         r#"
         SELECT gathered
         FROM farmer_stats
-        WHERE challenge_hash = $1
-          AND sp_hash        = $2
+        WHERE launcher_id    = $1
+          AND challenge_hash = $2
+          AND sp_hash        = $3
         "#,
+        launcher_id,
         challenge_hash,
         sp_hash
     )
@@ -90,8 +335,10 @@ pub async fn has_farmer_stats(
 
 pub async fn save_farmer_stats(
     pool: &SqlitePool,
+    launcher_id: Bytes32,
     farmer_stats: FarmerStats,
 ) -> Result<bool, Error> {
+    let launcher_id: &[u8] = launcher_id.as_ref();
     let challenge_hash: &[u8] = farmer_stats.challenge_hash.as_ref();
     let sp_hash: &[u8] = farmer_stats.sp_hash.as_ref();
     let q = sqlx::query!(
@@ -100,14 +347,16 @@ pub async fn save_farmer_stats(
             challenge_hash, sp_hash, running, og_passed_filter, og_plot_count,
             nft_passed_filter, nft_plot_count, compressed_passed_filter, 
             compressed_plot_count, invalid_plot_count, proofs_found, total_plot_space,
-            full_node_height, full_node_difficulty, full_node_synced, gathered
+            full_node_height, full_node_difficulty, full_node_synced, gathered,
+            launcher_id
         )
         VALUES ( $1, $2, $3, $4,
                  $5, $6, $7,
                  $8, $9, $10,
-                 $11, $12, $13, $14, $15, $16
+                 $11, $12, $13, $14, $15, $16,
+                 $17
         )
-        ON CONFLICT(challenge_hash, sp_hash) DO UPDATE SET
+        ON CONFLICT(launcher_id, challenge_hash, sp_hash) DO UPDATE SET
             running               = excluded.running,
             og_passed_filter      = excluded.og_passed_filter,
             og_plot_count         = excluded.og_plot_count,
@@ -138,10 +387,131 @@ pub async fn save_farmer_stats(
         farmer_stats.full_node_height,
         farmer_stats.full_node_difficulty,
         farmer_stats.full_node_synced,
-        farmer_stats.gathered
+        farmer_stats.gathered,
+        launcher_id
+    );
+    match q.execute(pool).await {
+        Ok(rows) => Ok(rows.rows_affected() != 0),
+        Err(e) => Err(map_sqlx_error(e)),
+    }
+}
+
+#[derive(FromRow, Debug, Clone, Serialize, Deserialize)]
+pub struct FarmerReward {
+    pub id: i64,
+    pub launcher_id: Bytes32,
+    pub amount: f64,
+    pub coin_name: String,
+    pub block_height: i64,
+    pub timestamp: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddFarmerReward {
+    pub launcher_id: Bytes32,
+    pub amount: f64,
+    pub coin_name: String,
+    pub block_height: i64,
+    #[serde(with = "time::serde::timestamp")]
+    pub timestamp: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FarmerRewardTotal {
+    pub total: f64,
+    pub count: i64,
+}
+
+pub async fn get_farmer_rewards(
+    pool: &SqlitePool,
+    launcher_id: Option<Bytes32>,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> Result<Vec<FarmerReward>, Error> {
+    let launcher_id: Option<&[u8]> = launcher_id.as_ref().map(|l| l.as_ref());
+    let rows = sqlx::query_as!(
+        FarmerReward,
+        r#"
+        SELECT id, launcher_id, amount, coin_name, block_height, timestamp
+        FROM farmer_rewards
+        WHERE timestamp >= $1
+        AND timestamp <= $2
+        AND ($3 IS NULL OR launcher_id = $3)
+        ORDER BY timestamp ASC
+        "#,
+        start,
+        end,
+        launcher_id
+    )
+    .fetch_all(pool)
+    .await;
+    match rows {
+        Ok(rows) => Ok(rows),
+        Err(sqlx::Error::RowNotFound) => Ok(vec![]),
+        Err(e) => Err(map_sqlx_error(e)),
+    }
+}
+
+pub async fn get_farmer_rewards_total(
+    pool: &SqlitePool,
+    launcher_id: Option<Bytes32>,
+) -> Result<FarmerRewardTotal, Error> {
+    let launcher_id: Option<&[u8]> = launcher_id.as_ref().map(|l| l.as_ref());
+    sqlx::query_as!(
+        FarmerRewardTotal,
+        r#"
+        SELECT COALESCE(SUM(amount), 0.0) as "total!: f64", COUNT(*) as "count!: i64"
+        FROM farmer_rewards
+        WHERE ($1 IS NULL OR launcher_id = $1)
+        "#,
+        launcher_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(map_sqlx_error)
+}
+
+pub async fn save_farmer_reward(
+    pool: &SqlitePool,
+    reward: &AddFarmerReward,
+) -> Result<bool, Error> {
+    let launcher_id: &[u8] = reward.launcher_id.as_ref();
+    let q = sqlx::query!(
+        r#"
+        INSERT INTO farmer_rewards (launcher_id, amount, coin_name, block_height, timestamp)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT(coin_name) DO NOTHING
+        "#,
+        launcher_id,
+        reward.amount,
+        reward.coin_name,
+        reward.block_height,
+        reward.timestamp
     );
     match q.execute(pool).await {
         Ok(rows) => Ok(rows.rows_affected() != 0),
         Err(e) => Err(map_sqlx_error(e)),
     }
 }
+
+#[tokio::test]
+async fn test_farmer_stats_page_empty() {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    crate::utils::run_migrations(&pool).await.unwrap();
+    let filter = FarmerStatsFilter::default();
+    let query = ListQuery::parse(Some("page=1&per_page=10")).unwrap();
+    let page = get_farmer_stats_page(&pool, &filter, &query).await.unwrap();
+    assert!(page.items.is_empty());
+    assert_eq!(page.total, 0);
+    assert_eq!(page.total_pages, 0);
+    let query = ListQuery::parse(Some("page=99&sort=proofs_found&order=asc")).unwrap();
+    let page = get_farmer_stats_page(&pool, &filter, &query).await.unwrap();
+    assert!(page.items.is_empty());
+    assert_eq!(page.page, 99);
+    let query = ListQuery::parse(Some("sort=challenge_hash; DROP TABLE farmer_stats")).unwrap();
+    assert!(get_farmer_stats_page(&pool, &filter, &query).await.is_err());
+}
@@ -9,8 +9,10 @@ use serde::{Deserialize, Serialize};
 use sha2::digest::Output;
 use sha2::{Digest, Sha256, Sha256VarCore};
 use sqlx::{FromRow, Sqlite, SqlitePool, Transaction};
+use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
 use std::sync::Arc;
+use time::OffsetDateTime;
 
 #[derive(Debug, FromRow, Deserialize, Serialize)]
 pub struct UserWithInfoWithPassword {
@@ -20,6 +22,14 @@ pub struct UserWithInfoWithPassword {
     pub role: UserRole,
 }
 
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct UserPublicInfo {
+    pub id: i64,
+    pub username: String,
+    pub role: UserRole,
+    pub created_at: Option<OffsetDateTime>,
+}
+
 #[derive(Debug, FromRow, Deserialize, Serialize)]
 pub struct UsernameWithPassword {
     pub username: String,
@@ -161,8 +171,8 @@ pub async fn register(
     let role_str = data.role.to_string();
     let user_id: i64 = sqlx::query_scalar!(
         r#"
-        INSERT INTO users (username, password, role)
-        VALUES ($1, $2, $3)
+        INSERT INTO users (username, password, role, created_at)
+        VALUES ($1, $2, $3, CURRENT_TIMESTAMP)
         RETURNING id
         "#,
         data.username,
@@ -209,3 +219,271 @@ pub async fn register(
     tx.commit().await.map_err(map_sqlx_error)?;
     Ok(Some(new_user))
 }
+
+/// Returns the TOTP secret once it has been confirmed, pending secrets don't guard logins
+pub async fn get_totp_secret(pool: &SqlitePool, user_id: i64) -> Result<Option<String>, Error> {
+    match sqlx::query_scalar!(
+        r#"
+        SELECT totp_secret
+        FROM users
+        WHERE id = $1 AND totp_confirmed = 1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(o) => Ok(o.flatten()),
+        Err(e) => Err(map_sqlx_error(e)),
+    }
+}
+
+pub async fn get_pending_totp_secret(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<Option<String>, Error> {
+    match sqlx::query_scalar!(
+        r#"
+        SELECT totp_secret
+        FROM users
+        WHERE id = $1 AND totp_confirmed = 0
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(o) => Ok(o.flatten()),
+        Err(e) => Err(map_sqlx_error(e)),
+    }
+}
+
+/// Stores a new secret as pending, it only takes effect after confirm_totp_secret
+pub async fn set_totp_secret(
+    pool: &SqlitePool,
+    user_id: i64,
+    totp_secret: Option<&str>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_secret = $1, totp_confirmed = 0 WHERE id = $2
+        "#,
+        totp_secret,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(map_sqlx_error)?;
+    Ok(())
+}
+
+pub async fn confirm_totp_secret(pool: &SqlitePool, user_id: i64) -> Result<bool, Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_confirmed = 1 WHERE id = $1 AND totp_secret IS NOT NULL
+        "#,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected() > 0)
+    .map_err(map_sqlx_error)
+}
+
+pub async fn get_all_users(pool: &SqlitePool) -> Result<Vec<UserPublicInfo>, Error> {
+    match sqlx::query_as!(
+        UserPublicInfo,
+        r#"
+        SELECT id, username, role, created_at
+        FROM users
+        ORDER BY id
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    {
+        Ok(o) => Ok(o),
+        Err(e) => Err(map_sqlx_error(e)),
+    }
+}
+
+async fn super_admin_count(tx: &mut Transaction<'_, Sqlite>) -> Result<usize, Error> {
+    let roles: Vec<String> = sqlx::query_scalar!(
+        r#"
+        SELECT role
+        FROM users
+        "#
+    )
+    .fetch_all(tx.as_mut())
+    .await
+    .map_err(map_sqlx_error)?;
+    Ok(roles
+        .into_iter()
+        .filter(|role| matches!(UserRole::from(role.clone()), UserRole::SuperAdmin))
+        .count())
+}
+
+pub async fn delete_user(
+    pool: &SqlitePool,
+    user_id: i64,
+    acting_role: &UserRole,
+) -> Result<bool, Error> {
+    let mut tx: Transaction<Sqlite> = pool.begin().await.map_err(map_sqlx_error)?;
+    let role = sqlx::query_scalar!(
+        r#"
+        SELECT role
+        FROM users
+        WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(tx.as_mut())
+    .await
+    .map_err(map_sqlx_error)?;
+    let Some(role) = role else {
+        tx.rollback().await.map_err(map_sqlx_error)?;
+        return Ok(false);
+    };
+    let role = UserRole::from(role);
+    if matches!(role, UserRole::SuperAdmin) && !matches!(acting_role, UserRole::SuperAdmin) {
+        tx.rollback().await.map_err(map_sqlx_error)?;
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Only a SuperAdmin can delete a SuperAdmin",
+        ));
+    }
+    if matches!(role, UserRole::SuperAdmin) && super_admin_count(&mut tx).await? <= 1 {
+        tx.rollback().await.map_err(map_sqlx_error)?;
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Cannot delete the last SuperAdmin",
+        ));
+    }
+    sqlx::query!(
+        r#"
+        DELETE FROM linked_oAuth WHERE user = $1
+        "#,
+        user_id
+    )
+    .execute(tx.as_mut())
+    .await
+    .map_err(map_sqlx_error)?;
+    sqlx::query!(
+        r#"
+        DELETE FROM user_preferences WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(tx.as_mut())
+    .await
+    .map_err(map_sqlx_error)?;
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM users WHERE id = $1
+        "#,
+        user_id
+    )
+    .execute(tx.as_mut())
+    .await
+    .map_err(map_sqlx_error)?;
+    tx.commit().await.map_err(map_sqlx_error)?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn update_user_role(
+    pool: &SqlitePool,
+    user_id: i64,
+    role: UserRole,
+) -> Result<bool, Error> {
+    let role_str = role.to_string();
+    let result = sqlx::query!(
+        r#"
+        UPDATE users SET role = $1 WHERE id = $2
+        "#,
+        role_str,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(map_sqlx_error)?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_user_preferences(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<HashMap<String, String>, Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT key, value
+        FROM user_preferences
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(map_sqlx_error)?;
+    Ok(rows.into_iter().map(|r| (r.key, r.value)).collect())
+}
+
+pub async fn get_user_preference(
+    pool: &SqlitePool,
+    user_id: i64,
+    key: &str,
+) -> Result<Option<String>, Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT value
+        FROM user_preferences
+        WHERE user_id = $1 AND key = $2
+        "#,
+        user_id,
+        key
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(map_sqlx_error)
+}
+
+pub async fn set_user_preference(
+    pool: &SqlitePool,
+    user_id: i64,
+    key: &str,
+    value: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_preferences (user_id, key, value)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id, key)
+        DO UPDATE SET value = EXCLUDED.value
+        "#,
+        user_id,
+        key,
+        value
+    )
+    .execute(pool)
+    .await
+    .map_err(map_sqlx_error)?;
+    Ok(())
+}
+
+pub async fn delete_user_preference(
+    pool: &SqlitePool,
+    user_id: i64,
+    key: &str,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM user_preferences
+        WHERE user_id = $1 AND key = $2
+        "#,
+        user_id,
+        key
+    )
+    .execute(pool)
+    .await
+    .map_err(map_sqlx_error)?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,53 @@
+use crate::database::map_sqlx_error;
+use crate::first_run::WizardStep;
+use log::warn;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::io::Error;
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+pub async fn get_wizard_state(pool: &SqlitePool) -> Result<HashMap<WizardStep, bool>, Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT step, completed
+        FROM wizard_state
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(map_sqlx_error)?;
+    //Steps missing from the table have never been completed
+    let mut state: HashMap<WizardStep, bool> =
+        HashMap::from_iter(WizardStep::ALL.into_iter().map(|step| (step, false)));
+    for row in rows {
+        match WizardStep::from_str(&row.step) {
+            Ok(step) => {
+                state.insert(step, row.completed);
+            }
+            Err(_) => warn!("Ignoring unknown wizard step: {}", row.step),
+        }
+    }
+    Ok(state)
+}
+
+pub async fn mark_step_complete(pool: &SqlitePool, step: WizardStep) -> Result<(), Error> {
+    let now = OffsetDateTime::now_utc();
+    let step = step.as_str();
+    sqlx::query!(
+        r#"
+        INSERT INTO wizard_state (step, completed, completed_at)
+        VALUES ($1, 1, $2)
+        ON CONFLICT (step)
+        DO UPDATE SET
+            completed = 1,
+            completed_at = EXCLUDED.completed_at
+        "#,
+        step,
+        now
+    )
+    .execute(pool)
+    .await
+    .map_err(map_sqlx_error)?;
+    Ok(())
+}
@@ -1,11 +1,60 @@
 use crate::database::config::{create_config_entry, get_config_key};
 use crate::database::users::{has_no_users, register, UserWithInfoWithPassword};
 use crate::models::config::AddConfigEntry;
+use crate::models::hotspot::{
+    DEFAULT_HOTSPOT_PASSWORD, DEFAULT_HOTSPOT_SSID, HOTSPOT_PASSWORD_CONFIG_KEY,
+    HOTSPOT_SSID_CONFIG_KEY,
+};
+use crate::plugins::farmer::{load_farmer_config, save_farmer_config, HarvesterConfig};
+use crate::plugins::system_monitor::{GpuType, SystemMonitorPlugin};
 use argon2::Argon2;
 use dg_sysfs::classes::net::{NetDevice, NetEnumerator};
+use log::info;
 use portfu_admin::users::UserRole;
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WizardStep {
+    Network,
+    FarmerKey,
+    PlotDirectories,
+    FarmerStart,
+}
+impl WizardStep {
+    pub const ALL: [WizardStep; 4] = [
+        WizardStep::Network,
+        WizardStep::FarmerKey,
+        WizardStep::PlotDirectories,
+        WizardStep::FarmerStart,
+    ];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WizardStep::Network => "Network",
+            WizardStep::FarmerKey => "FarmerKey",
+            WizardStep::PlotDirectories => "PlotDirectories",
+            WizardStep::FarmerStart => "FarmerStart",
+        }
+    }
+}
+impl FromStr for WizardStep {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "network" => Ok(WizardStep::Network),
+            "farmerkey" => Ok(WizardStep::FarmerKey),
+            "plotdirectories" => Ok(WizardStep::PlotDirectories),
+            "farmerstart" => Ok(WizardStep::FarmerStart),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid Wizard Step: {s}"),
+            )),
+        }
+    }
+}
 
 pub async fn check_for_default_admin_account(
     pool: &SqlitePool,
@@ -42,6 +91,7 @@ pub async fn validate_config_table(pool: &SqlitePool) -> Result<(), Error> {
                 category: "preferences".to_string(),
                 system: 1,
             },
+            None,
         )
         .await?;
     }
@@ -55,6 +105,7 @@ pub async fn validate_config_table(pool: &SqlitePool) -> Result<(), Error> {
                 category: "preferences".to_string(),
                 system: 1,
             },
+            None,
         )
         .await?;
     }
@@ -86,32 +137,41 @@ pub async fn validate_config_table(pool: &SqlitePool) -> Result<(), Error> {
                 category: "system".to_string(),
                 system: 1,
             },
+            None,
         )
         .await?;
     }
-    if get_config_key(pool, "hotspot_ssid").await?.is_none() {
+    if get_config_key(pool, HOTSPOT_SSID_CONFIG_KEY)
+        .await?
+        .is_none()
+    {
         create_config_entry(
             pool,
             &AddConfigEntry {
-                key: "hotspot_ssid".to_string(),
-                value: "DG_OS_SETUP".to_string(),
+                key: HOTSPOT_SSID_CONFIG_KEY.to_string(),
+                value: DEFAULT_HOTSPOT_SSID.to_string(),
                 last_value: "".to_string(),
                 category: "system".to_string(),
                 system: 1,
             },
+            None,
         )
         .await?;
     }
-    if get_config_key(pool, "hotspot_password").await?.is_none() {
+    if get_config_key(pool, HOTSPOT_PASSWORD_CONFIG_KEY)
+        .await?
+        .is_none()
+    {
         create_config_entry(
             pool,
             &AddConfigEntry {
-                key: "hotspot_password".to_string(),
-                value: "DG_xch1234!".to_string(),
+                key: HOTSPOT_PASSWORD_CONFIG_KEY.to_string(),
+                value: DEFAULT_HOTSPOT_PASSWORD.to_string(),
                 last_value: "".to_string(),
                 category: "system".to_string(),
                 system: 1,
             },
+            None,
         )
         .await?;
     }
@@ -125,8 +185,58 @@ pub async fn validate_config_table(pool: &SqlitePool) -> Result<(), Error> {
                 category: "farmer".to_string(),
                 system: 1,
             },
+            None,
         )
         .await?;
     }
     Ok(())
 }
+
+async fn create_detected_entry(pool: &SqlitePool, key: &str, value: usize) -> Result<(), Error> {
+    if get_config_key(pool, key).await?.is_none() {
+        create_config_entry(
+            pool,
+            &AddConfigEntry {
+                key: key.to_string(),
+                value: value.to_string(),
+                last_value: "".to_string(),
+                category: "hardware".to_string(),
+                system: 1,
+            },
+            None,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn detect_hardware_capabilities(pool: &SqlitePool) -> Result<(), Error> {
+    let system_monitor = SystemMonitorPlugin::new().await;
+    let gpus = system_monitor.get_gpu_info().await?;
+    let nvidia_gpus = gpus
+        .iter()
+        .filter(|g| matches!(g.brand, GpuType::Nvidia))
+        .count();
+    let amd_gpus = gpus
+        .iter()
+        .filter(|g| matches!(g.brand, GpuType::Amd))
+        .count();
+    let cpu_cores = system_monitor.get_cpu_info().await?.physical_count;
+    info!(
+        "Detected {nvidia_gpus} NVIDIA GPU(s), {amd_gpus} AMD GPU(s) and {cpu_cores} CPU core(s)"
+    );
+    create_detected_entry(pool, "detected_nvidia_gpus", nvidia_gpus).await?;
+    create_detected_entry(pool, "detected_amd_gpus", amd_gpus).await?;
+    create_detected_entry(pool, "detected_cpu_cores", cpu_cores).await?;
+    //Only seed the default Farmer Config, never overwrite one the user has saved
+    if nvidia_gpus > 0 && get_config_key(pool, "farmer_config").await?.is_none() {
+        let mut config = load_farmer_config(pool).await?;
+        config.harvester_configs.custom_config = Some(HarvesterConfig {
+            max_cuda_devices: nvidia_gpus as i32,
+            cuda_device_list: (0..nvidia_gpus.min(u8::MAX as usize) as u8).collect(),
+            ..Default::default()
+        });
+        save_farmer_config(pool, &config).await?;
+    }
+    Ok(())
+}
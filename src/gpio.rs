@@ -1,17 +1,26 @@
-use gpiod::{Active, Bias, Chip, Input, Lines, Options, Output};
-use libc::{sched_param, timespec, CLOCK_MONOTONIC, PR_SET_TIMERSLACK, SCHED_RR};
-use log::{error, info};
+use gpiod::{Active, Bias, Chip, Edge, EdgeDetect, Input, Lines, Options, Output};
+use libc::{
+    poll, pollfd, sched_param, timespec, CLOCK_MONOTONIC, POLLIN, PR_SET_TIMERSLACK, SCHED_RR,
+};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind};
 use std::mem::MaybeUninit;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc::Sender;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::thread::sleep;
 use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::task::JoinHandle;
 
+pub const INPUT_EDGE_BUFFER: usize = 64;
+
 pub async fn detect_gpio_chips() -> Result<Vec<Chip>, Error> {
     info!("Detecting Chips");
     let all_gpio_devices = Chip::list_devices()?;
@@ -24,15 +33,84 @@ pub async fn detect_gpio_chips() -> Result<Vec<Chip>, Error> {
     Ok(chips)
 }
 
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum EdgeType {
+    Rising,
+    Falling,
+    Both,
+}
+impl EdgeType {
+    pub fn matches(&self, value: bool) -> bool {
+        match self {
+            EdgeType::Rising => value,
+            EdgeType::Falling => !value,
+            EdgeType::Both => true,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum PinMode {
     Output,
     Input,
 }
 
-pub enum Pin {
+pub enum PinLines {
     Output(Lines<Output>),
     Input(Lines<Input>),
+    //Shares an input line owned by an InputWatcher and receives its edges
+    Watched(WatchedInput),
+}
+
+pub struct WatchedInput {
+    watcher: Arc<InputWatcher>,
+    edges: broadcast::Receiver<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinMetadata {
+    pub chip_index: usize,
+    pub chip_name: String,
+    pub line_offset: u32,
+}
+
+pub struct Pin {
+    lines: PinLines,
+    pin_metadata: PinMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpioChipInfo {
+    pub index: usize,
+    pub name: String,
+    pub label: String,
+    pub num_lines: u32,
+    pub consumers: Vec<String>,
+}
+
+impl GpioChipInfo {
+    pub fn from_chip(index: usize, chip: &Chip) -> Self {
+        let num_lines = chip.num_lines();
+        let mut consumers = vec![];
+        for line in 0..num_lines {
+            match chip.line_info(line) {
+                Ok(info) if !info.consumer.is_empty() => {
+                    if !consumers.contains(&info.consumer) {
+                        consumers.push(info.consumer);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => debug!("Failed to read line {line} info on {}: {e:?}", chip.name()),
+            }
+        }
+        Self {
+            index,
+            name: chip.name().to_string(),
+            label: chip.label().to_string(),
+            num_lines,
+            consumers,
+        }
+    }
 }
 
 impl Pin {
@@ -46,42 +124,126 @@ impl Pin {
                 cur_offset -= num_lines;
                 continue;
             }
-            match pin_mode {
+            let pin_metadata = PinMetadata {
+                chip_index: index,
+                chip_name: chip.name().to_string(),
+                line_offset: cur_offset,
+            };
+            info!(
+                "Mapped Pin {offset} to Chip {index} ({}) Line {cur_offset}",
+                pin_metadata.chip_name
+            );
+            let lines = match pin_mode {
                 PinMode::Output => {
                     let options = Options::output([cur_offset])
                         .values([false])
                         .active(Active::High)
                         .bias(Bias::Disable)
                         .consumer("pi-led");
-                    let lines = chip.request_lines(options)?;
-                    return Ok(Self::Output(lines));
+                    PinLines::Output(chip.request_lines(options)?)
                 }
                 PinMode::Input => {
-                    let options = Options::input([cur_offset]);
-                    let lines = chip.request_lines(options)?;
-                    return Ok(Self::Input(lines));
+                    let options = Options::input([cur_offset]).edge(EdgeDetect::Both);
+                    PinLines::Input(chip.request_lines(options)?)
                 }
-            }
+            };
+            return Ok(Self {
+                lines,
+                pin_metadata,
+            });
         }
         Err(Error::other(format!("No pin at Offset {offset}!")))
     }
 
+    pub fn metadata(&self) -> &PinMetadata {
+        &self.pin_metadata
+    }
+
     pub fn get(&self) -> Result<Vec<bool>, Error> {
-        match self {
-            Pin::Input(input) => input.get_values(vec![false; input.lines().len()]),
-            Pin::Output(output) => output.get_values(vec![false; output.lines().len()]),
+        match &self.lines {
+            PinLines::Input(input) => input.get_values(vec![false; input.lines().len()]),
+            PinLines::Output(output) => output.get_values(vec![false; output.lines().len()]),
+            PinLines::Watched(input) => Ok(vec![input.watcher.value()]),
+        }
+    }
+
+    /// Waits for the next edge of type `edge` on a watched input pin, returns the new value
+    pub async fn wait_for_edge(&mut self, edge: EdgeType) -> Result<bool, Error> {
+        let PinLines::Watched(input) = &mut self.lines else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Edges are only reported on watched input pins",
+            ));
+        };
+        loop {
+            let value = match input.edges.recv().await {
+                Ok(value) => value,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Pin {} watcher fell behind, skipped {skipped} edges",
+                        input.watcher.offset
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => {
+                    return Err(Error::other(format!(
+                        "Stopped reading edges for Pin {}",
+                        input.watcher.offset
+                    )))
+                }
+            };
+            if edge.matches(value) {
+                return Ok(value);
+            }
+        }
+    }
+
+    //Blocks until the kernel reports an edge and returns the new value, or None once stop is closed
+    fn poll_edge(&mut self, stop: &UnixStream) -> Result<Option<bool>, Error> {
+        let PinLines::Input(input) = &mut self.lines else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Pin does not own an input line",
+            ));
+        };
+        let mut fds = [
+            pollfd {
+                fd: input.as_raw_fd(),
+                events: POLLIN,
+                revents: 0,
+            },
+            pollfd {
+                fd: stop.as_raw_fd(),
+                events: POLLIN,
+                revents: 0,
+            },
+        ];
+        loop {
+            if unsafe { poll(fds.as_mut_ptr(), fds.len() as _, -1) } < 0 {
+                let e = Error::last_os_error();
+                if e.kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            if fds[1].revents != 0 {
+                return Ok(None);
+            }
+            if fds[0].revents != 0 {
+                return Ok(Some(matches!(input.read_event()?.edge, Edge::Rising)));
+            }
         }
     }
 
     pub fn set(&self, value: bool) -> Result<(), Error> {
-        match self {
-            Pin::Output(output) => {
+        match &self.lines {
+            PinLines::Output(output) => {
                 let new_vals = vec![value; output.lines().len()];
                 output.set_values(&new_vals)?;
                 assert_eq!(self.get()?, new_vals);
                 Ok(())
             }
-            Pin::Input(_) => Err(Error::new(
+            PinLines::Input(_) | PinLines::Watched(_) => Err(Error::new(
                 ErrorKind::InvalidInput,
                 "Cannot set value on input pin",
             )),
@@ -89,16 +251,108 @@ impl Pin {
     }
 }
 
+/// Owns an input line and reads its edge events on a dedicated thread.
+/// The line is released once the last Pin watching it is dropped
+pub struct InputWatcher {
+    offset: u32,
+    pin_metadata: PinMetadata,
+    value: Arc<AtomicBool>,
+    edges: broadcast::Receiver<bool>,
+    stop: Option<UnixStream>,
+    reader: Option<std::thread::JoinHandle<()>>,
+}
+
+impl InputWatcher {
+    pub fn new(chips: &[Chip], offset: u32) -> Result<Self, Error> {
+        let mut pin = Pin::new(chips, offset, PinMode::Input)?;
+        let pin_metadata = pin.metadata().clone();
+        let value = Arc::new(AtomicBool::new(
+            pin.get()?.first().copied().unwrap_or_default(),
+        ));
+        let (sender, edges) = broadcast::channel(INPUT_EDGE_BUFFER);
+        let (stop, stopped) = UnixStream::pair()?;
+        let thread_value = value.clone();
+        let reader = std::thread::Builder::new()
+            .name(format!("gpio-input-{offset}"))
+            .spawn(move || loop {
+                match pin.poll_edge(&stopped) {
+                    Ok(Some(value)) => {
+                        thread_value.store(value, Ordering::Relaxed);
+                        //Only fails when no Pin is subscribed, the edge is still recorded in value
+                        let _ = sender.send(value);
+                    }
+                    //The watcher was dropped, dropping the pin releases the line
+                    Ok(None) => return,
+                    Err(e) => {
+                        error!("Stopped reading edges for Pin {offset}: {e:?}");
+                        return;
+                    }
+                }
+            })?;
+        Ok(Self {
+            offset,
+            pin_metadata,
+            value,
+            edges,
+            stop: Some(stop),
+            reader: Some(reader),
+        })
+    }
+
+    pub fn value(&self) -> bool {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    /// Returns a Pin that receives every edge read from this point on
+    pub fn subscribe(self: &Arc<Self>) -> Pin {
+        Pin {
+            lines: PinLines::Watched(WatchedInput {
+                watcher: self.clone(),
+                edges: self.edges.resubscribe(),
+            }),
+            pin_metadata: self.pin_metadata.clone(),
+        }
+    }
+}
+
+impl Drop for InputWatcher {
+    fn drop(&mut self) {
+        //Closing the socket wakes the reader thread, join it so the line can be requested again
+        drop(self.stop.take());
+        if let Some(reader) = self.reader.take() {
+            if reader.join().is_err() {
+                error!("Input thread for Pin {} panicked", self.offset);
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct PinSet {
     pins: HashMap<u32, PwmSignalHandler>,
+    inputs: HashMap<u32, Weak<InputWatcher>>,
 }
 
 impl PinSet {
     pub fn new() -> Self {
         Self {
             pins: HashMap::new(),
+            inputs: HashMap::new(),
+        }
+    }
+
+    /// Returns a Pin receiving the edges of an input, the line is shared with other watchers
+    pub fn watch_input(&mut self, chips: &[Chip], offset: u32) -> Result<Pin, Error> {
+        if let Some(watcher) = self.get_input(offset) {
+            return Ok(watcher.subscribe());
         }
+        let watcher = Arc::new(InputWatcher::new(chips, offset)?);
+        self.inputs.insert(offset, Arc::downgrade(&watcher));
+        Ok(watcher.subscribe())
+    }
+
+    pub fn get_input(&self, offset: u32) -> Option<Arc<InputWatcher>> {
+        self.inputs.get(&offset).and_then(Weak::upgrade)
     }
 
     pub async fn get_or_init(
@@ -124,6 +378,10 @@ impl PinSet {
         self.pins.get(&offset)
     }
 
+    pub fn offsets(&self) -> impl Iterator<Item = u32> + '_ {
+        self.pins.keys().copied()
+    }
+
     pub async fn stop_all(self) -> Result<(), Error> {
         for pin in self.pins.into_values() {
             pin.stop();
@@ -148,6 +406,54 @@ impl PinSet {
         self.pins.insert(offset, handler)
     }
 
+    /// Sets the PWM of several pins so they all switch to the new values on the same cycle
+    pub fn set_all_pwm(&self, settings: Vec<(u32, Duration, Duration)>) -> Result<(), Error> {
+        let batch = PwmUpdateBatch::new();
+        self.queue_pwm(&batch, settings)
+    }
+
+    /// Queues updates on `batch`, they are applied once the batch is dropped.
+    /// Used to switch pins from several sets together
+    pub fn queue_pwm(
+        &self,
+        batch: &PwmUpdateBatch,
+        settings: Vec<(u32, Duration, Duration)>,
+    ) -> Result<(), Error> {
+        // Validate everything up front so a bad entry can't leave the batch half applied
+        let mut offsets = HashSet::new();
+        for (offset, period, pulse_width) in &settings {
+            if !offsets.insert(*offset) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Duplicate PWM pin Offset {offset}"),
+                ));
+            }
+            if !self.pins.contains_key(offset) {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("No PWM pin at Offset {offset}"),
+                ));
+            }
+            validate_pwm_settings(*period, *pulse_width)?;
+        }
+        let mut errors = vec![];
+        for (offset, period, pulse_width) in settings {
+            if let Err(e) = self.pins[&offset].control_channel.send(PwmSignal::Update(
+                PwmSignalSettings::batched(period, pulse_width, batch.queued.clone()),
+            )) {
+                errors.push(format!("Pin {offset}: {e:?}"));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::other(format!(
+                "Error Setting Pin PWM: {}",
+                errors.join(", ")
+            )))
+        }
+    }
+
     pub fn pins(&mut self) -> &mut HashMap<u32, PwmSignalHandler> {
         &mut self.pins
     }
@@ -158,6 +464,61 @@ pub const DEFAULT_PWN_PERIOD_US: u64 = 20000;
 pub struct PwmSignalSettings {
     period: Duration,
     pulse_width: Duration,
+    //Cleared once every update of the batch was queued, None for single pin updates
+    batch_queued: Option<Arc<AtomicBool>>,
+}
+
+impl PwmSignalSettings {
+    fn new(period: Duration, pulse_width: Duration) -> Self {
+        Self {
+            period,
+            pulse_width,
+            batch_queued: None,
+        }
+    }
+
+    fn batched(period: Duration, pulse_width: Duration, batch_queued: Arc<AtomicBool>) -> Self {
+        Self {
+            period,
+            pulse_width,
+            batch_queued: Some(batch_queued),
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        self.batch_queued
+            .as_ref()
+            .map(|queued| queued.load(Ordering::SeqCst))
+            .unwrap_or(true)
+    }
+}
+
+/// Holds every pin thread at the start of its next cycle through THREAD_WAITING until
+/// dropped, so the updates queued on it are picked up together
+pub struct PwmUpdateBatch {
+    queued: Arc<AtomicBool>,
+}
+
+impl PwmUpdateBatch {
+    pub fn new() -> Self {
+        THREAD_WAITING.fetch_add(1, Ordering::SeqCst);
+        Self {
+            queued: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Default for PwmUpdateBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PwmUpdateBatch {
+    fn drop(&mut self) {
+        self.queued.store(true, Ordering::SeqCst);
+        THREAD_WAITING.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 pub enum PwmSignal {
@@ -171,6 +532,33 @@ pub struct PwmSignalHandler {
     pub pin: Arc<Pin>,
 }
 
+pub const MIN_PWM_PERIOD: Duration = Duration::from_micros(1);
+pub const MAX_PWM_PERIOD: Duration = Duration::from_secs(1);
+
+pub fn validate_pwm_settings(period: Duration, pulse_width: Duration) -> Result<(), Error> {
+    if period.is_zero() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "PWM period must be greater than zero",
+        ));
+    }
+    if period < MIN_PWM_PERIOD || period > MAX_PWM_PERIOD {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "PWM period {period:?} must be between {MIN_PWM_PERIOD:?} and {MAX_PWM_PERIOD:?}"
+            ),
+        ));
+    }
+    if pulse_width > period {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("PWM pulse width {pulse_width:?} is longer than period {period:?}"),
+        ));
+    }
+    Ok(())
+}
+
 const SLEEP_THRESHOLD: i64 = 250_000;
 const BUSYWAIT_MAX: i64 = 200_000;
 const BUSYWAIT_REMAINDER: i64 = 100;
@@ -200,6 +588,7 @@ impl PwmSignalHandler {
                 let mut period_ns = period.as_nanos() as i64;
                 let mut pulse_width_ns = pulse_width.as_nanos() as i64;
                 let mut start_ns = get_time_ns();
+                let mut pending: Option<PwmSignalSettings> = None;
 
                 loop {
                     while THREAD_WAITING.load(Ordering::SeqCst) != 0 {
@@ -209,15 +598,8 @@ impl PwmSignalHandler {
                     while let Ok(msg) = receiver.try_recv() {
                         match msg {
                             PwmSignal::Update(settings) => {
-                                info!("Got Update signal");
-                                // Reconfigure period and pulse width
-                                pulse_width_ns = settings.pulse_width.as_nanos() as i64;
-                                period_ns = settings.period.as_nanos() as i64;
-                                if pulse_width_ns > period_ns {
-                                    info!("Adjusting Pulse Width from {pulse_width_ns} to {period_ns}");
-                                    pulse_width_ns = period_ns;
-                                }
-                                info!("Updating Settings to Period {period_ns}, Pulse Width {pulse_width_ns}");
+                                debug!("Got Update signal");
+                                pending = Some(settings);
                             }
                             PwmSignal::Stop => {
                                 info!("Got Stop signal");
@@ -225,6 +607,20 @@ impl PwmSignalHandler {
                             }
                         }
                     }
+                    // An update taken before its batch was fully queued waits for the next
+                    // cycle, when the rest of the batch is applied
+                    if let Some(settings) = pending.take_if(|settings| settings.is_ready()) {
+                        // Reconfigure period and pulse width
+                        pulse_width_ns = settings.pulse_width.as_nanos() as i64;
+                        period_ns = settings.period.as_nanos() as i64;
+                        if pulse_width_ns > period_ns {
+                            debug!("Adjusting Pulse Width from {pulse_width_ns} to {period_ns}");
+                            pulse_width_ns = period_ns;
+                        }
+                        debug!(
+                            "Updating Settings to Period {period_ns}, Pulse Width {pulse_width_ns}"
+                        );
+                    }
 
                     if pulse_width_ns > 0 {
                         thread_pin.set(true)?;
@@ -295,25 +691,13 @@ impl PwmSignalHandler {
         }
     }
 
-    pub fn set_pwm(&self, period: Duration, pulse_width: Duration) {
-        if let Err(e) = self
-            .control_channel
-            .send(PwmSignal::Update(PwmSignalSettings {
-                period,
-                pulse_width,
-            }))
-        {
-            error!("Error Setting Pin PWM: {e:?}");
-        }
-    }
-
     pub fn set_high(&self) {
         if let Err(e) = self
             .control_channel
-            .send(PwmSignal::Update(PwmSignalSettings {
-                period: Duration::from_micros(DEFAULT_PWN_PERIOD_US),
-                pulse_width: Duration::from_micros(DEFAULT_PWN_PERIOD_US),
-            }))
+            .send(PwmSignal::Update(PwmSignalSettings::new(
+                Duration::from_micros(DEFAULT_PWN_PERIOD_US),
+                Duration::from_micros(DEFAULT_PWN_PERIOD_US),
+            )))
         {
             error!("Error Setting Pin High: {e:?}");
         }
@@ -322,10 +706,10 @@ impl PwmSignalHandler {
     pub fn set_low(&self) {
         if let Err(e) = self
             .control_channel
-            .send(PwmSignal::Update(PwmSignalSettings {
-                period: Duration::from_micros(DEFAULT_PWN_PERIOD_US),
-                pulse_width: Duration::from_micros(0),
-            }))
+            .send(PwmSignal::Update(PwmSignalSettings::new(
+                Duration::from_micros(DEFAULT_PWN_PERIOD_US),
+                Duration::from_micros(0),
+            )))
         {
             error!("Error Setting Pin Low: {e:?}");
         }
@@ -352,3 +736,41 @@ fn get_time_ns() -> i64 {
     }
     (ts.tv_sec * NANOS_PER_SEC) + ts.tv_nsec
 }
+
+#[test]
+fn test_validate_pwm_settings() {
+    let period = Duration::from_micros(DEFAULT_PWN_PERIOD_US);
+    assert!(validate_pwm_settings(period, Duration::ZERO).is_ok());
+    assert!(validate_pwm_settings(period, period).is_ok());
+    assert!(validate_pwm_settings(period, period + Duration::from_nanos(1)).is_err());
+    assert!(validate_pwm_settings(Duration::ZERO, Duration::ZERO).is_err());
+    assert!(validate_pwm_settings(Duration::from_nanos(999), Duration::ZERO).is_err());
+    assert!(validate_pwm_settings(MIN_PWM_PERIOD, MIN_PWM_PERIOD).is_ok());
+    assert!(validate_pwm_settings(MAX_PWM_PERIOD, Duration::ZERO).is_ok());
+    assert!(validate_pwm_settings(Duration::from_secs(2), Duration::ZERO).is_err());
+}
+
+#[test]
+fn test_pwm_update_batch() {
+    let period = Duration::from_micros(DEFAULT_PWN_PERIOD_US);
+    let pins = PinSet::new();
+    let duplicate = pins.set_all_pwm(vec![(1, period, period), (1, period, Duration::ZERO)]);
+    assert_eq!(duplicate.unwrap_err().kind(), ErrorKind::InvalidInput);
+    let missing = pins.set_all_pwm(vec![(1, period, period)]);
+    assert_eq!(missing.unwrap_err().kind(), ErrorKind::NotFound);
+    let batch = PwmUpdateBatch::new();
+    let settings = PwmSignalSettings::batched(period, period, batch.queued.clone());
+    assert!(!settings.is_ready());
+    drop(batch);
+    assert!(settings.is_ready());
+    assert!(PwmSignalSettings::new(period, period).is_ready());
+}
+
+#[test]
+fn test_edge_type_matches() {
+    assert!(EdgeType::Rising.matches(true));
+    assert!(!EdgeType::Rising.matches(false));
+    assert!(EdgeType::Falling.matches(false));
+    assert!(!EdgeType::Falling.matches(true));
+    assert!(EdgeType::Both.matches(true) && EdgeType::Both.matches(false));
+}
@@ -15,6 +15,7 @@ pub mod legacy;
 pub mod models;
 pub mod plugins;
 pub mod service_groups;
+pub mod tls;
 pub mod utils;
 pub mod web;
 
@@ -33,16 +34,19 @@ pub struct HtmlFiles;
 
 pub type FarmerThread = RwLock<Option<JoinHandle<Result<(), Error>>>>;
 
-pub fn init_logger() -> Result<Arc<DruidGardenLogger>, Error> {
+pub fn init_logger(
+    level: Level,
+    targets: &[(String, Level)],
+) -> Result<Arc<DruidGardenLogger>, Error> {
     unsafe {
         env::set_var("ZBUS_TRACE", "0");
     }
-    DruidGardenLogger::build()
+    let mut builder = DruidGardenLogger::build()
         .use_colors(true)
-        .current_level(Level::Info)
-        .timestamp_format(TimestampFormat::Local)
-        .with_target_level("zbus", Level::Warn)
-        .with_target_level("tracing", Level::Warn)
-        .init()
-        .map_err(Error::other)
+        .current_level(level)
+        .timestamp_format(TimestampFormat::Local);
+    for (target, target_level) in targets {
+        builder = builder.with_target_level(target, *target_level);
+    }
+    builder.init().map_err(Error::other)
 }
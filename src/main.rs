@@ -6,6 +6,7 @@ mod legacy;
 mod models;
 mod plugins;
 mod service_groups;
+mod tls;
 mod utils;
 mod web;
 
@@ -14,20 +15,32 @@ use crate::models::ServerSettings;
 use crate::plugins::disk_management::{disk_auto_mounting, DiskManagerPlugin};
 use crate::plugins::farmer::{update_local_stats, FarmerManager};
 use crate::plugins::file_manager::FileManagerPlugin;
-use crate::plugins::led_manager::LedManager;
-use crate::plugins::system_monitor::{refresh_system_info, SystemMonitorPlugin};
-use crate::plugins::PluginManager;
+use crate::plugins::gpio_manager::GpioManagerPlugin;
+use crate::plugins::led_manager::{
+    led_idle_monitor, led_sequence_tick, led_status_monitor, LedManager,
+};
+use crate::plugins::system_monitor::{refresh_system_info, AddressType, SystemMonitorPlugin};
+use crate::plugins::{refresh_plugin_store, PluginManager};
 use crate::service_groups::{
     admin_group, editor_group, manager_group, none_group, super_group, user_groups, viewer_group,
 };
+use crate::tls::{run_tls_listener, TlsCertificates, TlsPaths};
 use crate::utils::{
-    connect_to_docker, create_argon, create_pool, find_index_service, perform_startup_checks,
-    run_migrations,
+    connect_to_docker, create_argon, create_pool, find_index_service, pending_migrations,
+    perform_startup_checks, run_migrations,
+};
+use crate::web::audit::prune_audit_log_task;
+use crate::web::auth::{
+    init_jwt_secret, AuditLogWrapper, BasicAuthHandle, ClientAddressWrapper, LoginAttemptTracker,
+    PasswordUpdateWrapper,
 };
-use crate::web::auth::{BasicAuthHandle, PasswordUpdateWrapper};
+use crate::web::config::refresh_config_cache;
+use crate::web::database::DatabaseBackupManager;
+use crate::web::logs::{LogBuffer, LOG_BUFFER_CAPACITY};
+use crate::web::notifications::WebhookNotifier;
 use dg_logger::DruidGardenLogger;
 use druid_garden_os::init_logger;
-use log::info;
+use log::{debug, info};
 use portfu::prelude::http::HeaderName;
 use portfu::prelude::*;
 use portfu::wrappers::cors::Cors;
@@ -36,47 +49,120 @@ use std::env::args;
 use std::io::Error;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::RwLock;
 
 pub const fn version() -> &'static str {
     druid_garden_os::version()
 }
 
+async fn migrate_db() -> Result<(), Error> {
+    let settings = ServerSettings::from_env()?;
+    init_logger(settings.log_level, &settings.log_targets)?;
+    let db = create_pool(&settings.database_path, &settings.database_pool).await?;
+    run_migrations(&db).await?;
+    info!("Database {} is up to date", settings.database_path);
+    db.close().await;
+    Ok(())
+}
+
+async fn check_migrations() -> Result<bool, Error> {
+    let settings = ServerSettings::from_env()?;
+    let db = create_pool(&settings.database_path, &settings.database_pool).await?;
+    let pending = pending_migrations(&db).await?;
+    db.close().await;
+    let up_to_date = pending.is_empty();
+    if up_to_date {
+        println!("Database {} is up to date", settings.database_path);
+    } else {
+        println!(
+            "Database {} has {} pending migration(s):",
+            settings.database_path,
+            pending.len()
+        );
+        for migration in pending {
+            println!("  {migration}");
+        }
+    }
+    Ok(up_to_date)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     rustls::crypto::aws_lc_rs::default_provider()
         .install_default()
         .unwrap_or_default();
-    let mut args = args();
-    if args.len() == 2 && args.nth(1).unwrap_or_default() == "--version" {
-        println!("{}", version());
-        return Ok(());
+    match args().nth(1).as_deref() {
+        Some("--version") => {
+            println!("{}", version());
+            return Ok(());
+        }
+        Some("--migrate") => return migrate_db().await,
+        Some("--check-migrations") => {
+            //Exit code 1 lets init containers and ExecStartPre detect an outdated database
+            if !check_migrations().await? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        _ => {}
     }
-    let logger = init_logger()?;
+    web::system::SERVER_STARTED.get_or_init(Instant::now);
     let settings = ServerSettings::from_env()?;
-    let db = create_pool(&settings.database_path).await?;
+    let logger = init_logger(settings.log_level, &settings.log_targets)?;
+    init_jwt_secret()?;
+    let log_buffer = Arc::new(LogBuffer::new(LOG_BUFFER_CAPACITY));
+    tokio::spawn(log_buffer.clone().capture(logger.clone()));
+    debug!(
+        "TLS Certificate: {}, TLS Key: {}",
+        settings.tls_cert, settings.tls_key
+    );
+    let db = create_pool(&settings.database_path, &settings.database_pool).await?;
     let argon = create_argon()?;
     run_migrations(&db).await?;
     perform_startup_checks(&db, &argon).await?;
     info!("Connecting to Docker");
     let docker = connect_to_docker()?;
     info!("Setting Up Auth");
-    let basic_auth = BasicAuthHandle::new(db.clone(), argon.clone());
+    let login_attempts = Arc::new(LoginAttemptTracker::new());
+    let basic_auth = BasicAuthHandle::new(db.clone(), argon.clone(), login_attempts.clone());
     info!("Setting Up Farmer Manager");
-    let farmer_manager = Arc::new(FarmerManager::new(db.clone()).await?);
-    info!("Setting Up Plugin Manager");
-    let plugin_manager = PluginManager::new(&db, PathBuf::from(settings.plugin_path)).await;
+    let farmer_manager = Arc::new(
+        FarmerManager::new(db.clone(), PathBuf::from(&settings.farmer_manifest_cache)).await?,
+    );
     info!("Setting Up Config Manager");
     let config_manager = Arc::new(RwLock::new(ConfigManager::new(&db).await?));
+    let tls_paths = TlsPaths::from_config(
+        &*config_manager.read().await,
+        &settings.tls_cert,
+        &settings.tls_key,
+    )
+    .await;
+    let tls_certificates = Arc::new(TlsCertificates::new(tls_paths).await);
+    info!("Setting Up Webhooks");
+    let webhook_notifier = Arc::new(WebhookNotifier::new(config_manager.clone()).await);
+    info!("Setting Up Plugin Manager");
+    let plugin_manager = PluginManager::new(
+        &db,
+        PathBuf::from(settings.plugin_path),
+        PathBuf::from(settings.plugin_store_cache),
+        webhook_notifier.clone(),
+    )
+    .await;
     info!("Setting Up System Monitor");
-    let system_manager = SystemMonitorPlugin::new().await;
+    let system_manager = Arc::new(SystemMonitorPlugin::new().await);
     info!("Loading Network Information");
     let network_info = system_manager.get_network_info().await?;
     let ip_list = network_info.into_iter().fold(vec![], |mut r, v| {
         for a in v.ip_addresses {
             let address = a.address.to_string();
-            r.push(format!("http://{address}:8080"));
-            r.push(format!("http://{address}:8443"));
+            //IPv6 hosts are bracketed in origins
+            let host = match a.address_type {
+                AddressType::IPv4 => address.clone(),
+                AddressType::IPv6 => format!("[{address}]"),
+            };
+            r.push(format!("http://{host}:8080"));
+            r.push(format!("https://{host}:8443"));
             r.push(address);
         }
         r
@@ -89,6 +175,12 @@ async fn main() -> Result<(), Error> {
     let led_manager = Arc::new(RwLock::new(
         LedManager::init(config_manager.clone(), db.clone()).await?,
     ));
+    info!("Setting Up GPIO Manager");
+    let gpio_manager = GpioManagerPlugin::new().await;
+    info!("Setting Up Database Backups");
+    let database_backups = DatabaseBackupManager::new(PathBuf::from(settings.backup_path));
+    let restore_wrapper = database_backups.wrapper();
+    let audit_wrapper = AuditLogWrapper { pool: db.clone() };
     info!("Setting Up Static HTML Files");
     let static_files: ServiceGroup = ServiceGroup::from(druid_garden_os::HtmlFiles {});
     let index_service = find_index_service(&static_files).expect("Failed to find index service");
@@ -99,22 +191,28 @@ async fn main() -> Result<(), Error> {
         .shared_state(RwLock::new(plugin_manager))
         .shared_state::<RwLock<LedManager>>(led_manager.clone())
         .shared_state::<DruidGardenLogger>(logger)
+        .shared_state::<LogBuffer>(log_buffer)
         .shared_state(argon)
         .shared_state(docker)
         .shared_state(db)
-        .shared_state(system_manager)
+        .shared_state::<SystemMonitorPlugin>(system_manager.clone())
         .shared_state::<FarmerManager>(farmer_manager.clone())
         .shared_state(file_manager)
         .shared_state(disk_manager)
+        .shared_state(gpio_manager)
         .shared_state::<RwLock<ConfigManager>>(config_manager)
+        .shared_state::<LoginAttemptTracker>(login_attempts)
+        .shared_state(database_backups)
+        .shared_state::<WebhookNotifier>(webhook_notifier)
+        .shared_state::<TlsCertificates>(tls_certificates.clone())
         .default_service(index_service)
         .wrap(Arc::new(Cors::new(
             [
                 "http://localhost",
-                "http://localhost:8443",
+                "https://localhost:8443",
                 "http://localhost:8080",
                 "http://127.0.0.1",
-                "http://127.0.0.1:8443",
+                "https://127.0.0.1:8443",
                 "http://127.0.0.1:8080",
                 "https://druid.garden",
                 "https://dev.druid.garden",
@@ -137,6 +235,11 @@ async fn main() -> Result<(), Error> {
         )))
         .register(static_files)
         .wrap(Arc::new(SessionWrapper::default()))
+        .wrap(Arc::new(restore_wrapper))
+        .wrap(Arc::new(ClientAddressWrapper {
+            trusted_proxies: settings.trusted_proxies,
+        }))
+        .wrap(Arc::new(audit_wrapper))
         .register(none_group(basic_auth))
         .register(user_groups())
         .wrap(Arc::new(PasswordUpdateWrapper {}))
@@ -147,12 +250,27 @@ async fn main() -> Result<(), Error> {
         .register(super_group())
         .task(disk_auto_mounting)
         .task(update_local_stats)
-        .task(refresh_system_info);
+        .task(refresh_system_info)
+        .task(refresh_plugin_store)
+        .task(led_sequence_tick)
+        .task(led_status_monitor)
+        .task(led_idle_monitor)
+        .task(prune_audit_log_task)
+        .task(refresh_config_cache);
+    info!("Starting TLS Listener");
+    let tls_listener = tokio::spawn(run_tls_listener(
+        settings.hostname.clone(),
+        settings.tls_port,
+        settings.port,
+        tls_certificates.clone(),
+    ));
     info!("Starting Services");
     let res = server.build().run().await;
     info!("Shutting Down");
+    tls_listener.abort();
     farmer_manager.stop_farmer().await?;
     let _ = led_manager.write().await.stop_all().await;
+    system_manager.restore_fan_control().await;
     info!("Farmer Stopped");
     res
 }
@@ -21,3 +21,12 @@ pub struct ConfigEntry {
     pub created: OffsetDateTime,
     pub modified: OffsetDateTime,
 }
+
+#[derive(FromRow, Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigHistoryEntry {
+    pub id: i64,
+    pub key: String,
+    pub value: String,
+    pub modified: OffsetDateTime,
+    pub modified_by_user_id: Option<i64>,
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+pub const HOTSPOT_SSID_CONFIG_KEY: &str = "hotspot_ssid";
+pub const HOTSPOT_PASSWORD_CONFIG_KEY: &str = "hotspot_password";
+pub const HOTSPOT_CHANNEL_CONFIG_KEY: &str = "hotspot_channel";
+pub const HOTSPOT_BAND_CONFIG_KEY: &str = "hotspot_band";
+pub const DEFAULT_HOTSPOT_SSID: &str = "DG_OS_SETUP";
+pub const DEFAULT_HOTSPOT_PASSWORD: &str = "DG_xch1234!";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HotspotBand {
+    #[default]
+    #[serde(rename = "2.4GHz")]
+    Band2_4GHz,
+    #[serde(rename = "5GHz")]
+    Band5GHz,
+}
+impl HotspotBand {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HotspotBand::Band2_4GHz => "2.4GHz",
+            HotspotBand::Band5GHz => "5GHz",
+        }
+    }
+    pub fn valid_channel(&self, channel: u32) -> bool {
+        match self {
+            HotspotBand::Band2_4GHz => (1..=14).contains(&channel),
+            HotspotBand::Band5GHz => (36..=165).contains(&channel),
+        }
+    }
+}
+impl Display for HotspotBand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl FromStr for HotspotBand {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "2.4ghz" => Ok(HotspotBand::Band2_4GHz),
+            "5ghz" => Ok(HotspotBand::Band5GHz),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid Hotspot Band: {s}, expected 2.4GHz or 5GHz"),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HotspotConfig {
+    pub ssid: String,
+    pub password: String,
+    pub channel: Option<u32>,
+    pub band: HotspotBand,
+}
+impl Default for HotspotConfig {
+    fn default() -> Self {
+        Self {
+            ssid: DEFAULT_HOTSPOT_SSID.to_string(),
+            password: DEFAULT_HOTSPOT_PASSWORD.to_string(),
+            channel: None,
+            band: HotspotBand::default(),
+        }
+    }
+}
+impl HotspotConfig {
+    pub fn validate(&self) -> Result<(), Error> {
+        validate_hotspot_ssid(&self.ssid)?;
+        validate_hotspot_password(&self.password)?;
+        if let Some(channel) = self.channel {
+            if !self.band.valid_channel(channel) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Channel {channel} is not valid for the {} band", self.band),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn validate_hotspot_ssid(ssid: &str) -> Result<(), Error> {
+    //The 32 octet limit applies to the encoded bytes, not characters
+    if ssid.is_empty() || ssid.len() > 32 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Hotspot SSID must be between 1 and 32 characters",
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_hotspot_password(password: &str) -> Result<(), Error> {
+    if !(8..=63).contains(&password.len()) || !password.is_ascii() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Hotspot Password must be between 8 and 63 ASCII characters for WPA2",
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_hotspot_config_validate() {
+    assert!(HotspotConfig::default().validate().is_ok());
+    let mut config = HotspotConfig {
+        ssid: "".to_string(),
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+    config.ssid = "a".repeat(33);
+    assert!(config.validate().is_err());
+    config.ssid = "Garden".to_string();
+    config.password = "short".to_string();
+    assert!(config.validate().is_err());
+    config.password = "a".repeat(64);
+    assert!(config.validate().is_err());
+    config.password = "long_enough".to_string();
+    config.channel = Some(36);
+    assert!(config.validate().is_err());
+    config.band = HotspotBand::Band5GHz;
+    assert!(config.validate().is_ok());
+}
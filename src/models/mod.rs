@@ -1,14 +1,144 @@
+use log::Level;
+use sqlx::sqlite::SqliteJournalMode;
 use std::env;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
 
 pub mod config;
+pub mod hotspot;
+pub mod pagination;
 pub mod plugins;
 
+pub const DEFAULT_TLS_CERT_PATH: &str = "./tls/cert.pem";
+pub const DEFAULT_TLS_KEY_PATH: &str = "./tls/key.pem";
+pub const DEFAULT_FARMER_MANIFEST_CACHE: &str =
+    "/var/cache/druid-garden/farmer_manifest_cache.yaml";
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 50;
+pub const DEFAULT_MIN_CONNECTIONS: u32 = 0;
+pub const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+pub const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(30 * 60);
+pub const DEFAULT_DB_MAX_RETRIES: u32 = 5;
+pub const DEFAULT_DB_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+pub const DEFAULT_LOG_TARGETS: [(&str, Level); 2] =
+    [("zbus", Level::Warn), ("tracing", Level::Warn)];
+
+pub struct DatabasePoolSettings {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+    pub journal_mode: SqliteJournalMode,
+    pub max_retries: u32,
+    pub retry_interval: Duration,
+}
+impl Default for DatabasePoolSettings {
+    fn default() -> Self {
+        Self {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            min_connections: DEFAULT_MIN_CONNECTIONS,
+            acquire_timeout: DEFAULT_ACQUIRE_TIMEOUT,
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+            max_lifetime: Some(DEFAULT_MAX_LIFETIME),
+            journal_mode: SqliteJournalMode::Wal,
+            max_retries: DEFAULT_DB_MAX_RETRIES,
+            retry_interval: DEFAULT_DB_RETRY_INTERVAL,
+        }
+    }
+}
+impl DatabasePoolSettings {
+    pub fn from_env() -> Result<Self, Error> {
+        let defaults = Self::default();
+        let max_connections = env_number("DG_MAX_CONNECTIONS")?.unwrap_or(defaults.max_connections);
+        if max_connections == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "DG_MAX_CONNECTIONS must be greater than 0",
+            ));
+        }
+        let min_connections = env_number("DG_DB_MIN_CONN")?.unwrap_or(defaults.min_connections);
+        if min_connections > max_connections {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("DG_DB_MIN_CONN {min_connections} is above DG_MAX_CONNECTIONS {max_connections}"),
+            ));
+        }
+        let acquire_timeout = env_number("DG_DB_ACQUIRE_TIMEOUT_MS")?
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.acquire_timeout);
+        //A value of 0 disables the idle timeout and lifetime so connections are kept open
+        let idle_timeout = match env_number::<u64>("DG_DB_IDLE_TIMEOUT_MS")? {
+            Some(0) => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => defaults.idle_timeout,
+        };
+        let max_lifetime = match env_number::<u64>("DG_DB_MAX_LIFETIME_MS")? {
+            Some(0) => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => defaults.max_lifetime,
+        };
+        let journal_mode = match env::var("DG_DB_JOURNAL_MODE") {
+            Ok(mode) => parse_journal_mode(&mode)?,
+            Err(_) => defaults.journal_mode,
+        };
+        let max_retries = env_number("DG_DB_MAX_RETRIES")?.unwrap_or(defaults.max_retries);
+        let retry_interval = env_number("DG_DB_RETRY_INTERVAL_MS")?
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.retry_interval);
+        Ok(Self {
+            max_connections,
+            min_connections,
+            acquire_timeout,
+            idle_timeout,
+            max_lifetime,
+            journal_mode,
+            max_retries,
+            retry_interval,
+        })
+    }
+}
+
+pub fn env_number<T: FromStr>(key: &str) -> Result<Option<T>, Error> {
+    match env::var(key) {
+        Ok(value) => value.trim().parse().map(Some).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid {key}: {value}, expected a number"),
+            )
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn parse_journal_mode(mode: &str) -> Result<SqliteJournalMode, Error> {
+    match mode.trim().to_ascii_lowercase().as_str() {
+        "wal" => Ok(SqliteJournalMode::Wal),
+        "persist" => Ok(SqliteJournalMode::Persist),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid DG_DB_JOURNAL_MODE: {mode}, expected wal or persist"),
+        )),
+    }
+}
+
 pub struct ServerSettings {
     pub hostname: String,
     pub port: u16,
+    pub tls_port: u16,
     pub database_path: String,
     pub plugin_path: String,
+    pub backup_path: String,
+    pub plugin_store_cache: String,
+    pub farmer_manifest_cache: String,
+    pub tls_cert: String,
+    pub tls_key: String,
+    pub log_level: Level,
+    pub log_targets: Vec<(String, Level)>,
+    pub database_pool: DatabasePoolSettings,
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl ServerSettings {
@@ -17,14 +147,139 @@ impl ServerSettings {
         let port = env::var("DG_PORT")
             .map(|s| s.parse().unwrap())
             .unwrap_or(8080u16);
+        let tls_port = env::var("DG_TLS_PORT")
+            .map(|s| s.parse().unwrap())
+            .unwrap_or(8443u16);
         let database_path =
             env::var("DATABASE_FILE").unwrap_or(String::from("druid_garden.sqlite"));
         let plugin_path = env::var("DG_BIN_PATH").unwrap_or(String::from("./plugins"));
+        let backup_path = env::var("DG_BACKUP_PATH").unwrap_or(String::from("./backups"));
+        let plugin_store_cache =
+            env::var("DG_PLUGIN_STORE_CACHE").unwrap_or(String::from("./plugin_store_cache.yaml"));
+        let farmer_manifest_cache = env::var("DG_FARMER_MANIFEST_CACHE")
+            .unwrap_or(String::from(DEFAULT_FARMER_MANIFEST_CACHE));
+        let log_level = match env::var("DG_LOG_LEVEL") {
+            Ok(level) => parse_log_level(&level)?,
+            Err(_) => Level::Info,
+        };
+        let log_targets = parse_log_targets(&env::var("DG_LOG_TARGETS").unwrap_or_default())?;
+        let database_pool = DatabasePoolSettings::from_env()?;
+        let trusted_proxies =
+            parse_trusted_proxies(&env::var("DG_TRUSTED_PROXIES").unwrap_or_default())?;
         Ok(ServerSettings {
             hostname,
             port,
+            tls_port,
             database_path,
             plugin_path,
+            backup_path,
+            plugin_store_cache,
+            farmer_manifest_cache,
+            tls_cert: tls_cert_path(),
+            tls_key: tls_key_path(),
+            log_level,
+            log_targets,
+            database_pool,
+            trusted_proxies,
+        })
+    }
+}
+
+pub fn tls_cert_path() -> String {
+    env::var("DG_TLS_CERT").unwrap_or(String::from(DEFAULT_TLS_CERT_PATH))
+}
+
+pub fn tls_key_path() -> String {
+    env::var("DG_TLS_KEY").unwrap_or(String::from(DEFAULT_TLS_KEY_PATH))
+}
+
+/// Parses a comma separated list of proxy addresses allowed to set the client address headers
+pub fn parse_trusted_proxies(proxies: &str) -> Result<Vec<IpAddr>, Error> {
+    proxies
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|proxy| {
+            IpAddr::from_str(proxy).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid DG_TRUSTED_PROXIES address {proxy}: {e}"),
+                )
+            })
         })
+        .collect()
+}
+
+fn parse_log_level(level: &str) -> Result<Level, Error> {
+    Level::from_str(level.trim()).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid Log Level: {level}"),
+        )
+    })
+}
+
+/// Parses `target=level` pairs separated by commas, entries override the defaults for the same target
+pub fn parse_log_targets(targets: &str) -> Result<Vec<(String, Level)>, Error> {
+    let mut log_targets: Vec<(String, Level)> = DEFAULT_LOG_TARGETS
+        .iter()
+        .map(|(target, level)| (target.to_string(), *level))
+        .collect();
+    for pair in targets.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((target, level)) = pair.split_once('=') else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid Log Target: {pair}, expected target=level"),
+            ));
+        };
+        let target = target.trim().to_string();
+        let level = parse_log_level(level)?;
+        match log_targets
+            .iter_mut()
+            .find(|(existing, _)| *existing == target)
+        {
+            Some(existing) => existing.1 = level,
+            None => log_targets.push((target, level)),
+        }
     }
+    Ok(log_targets)
+}
+
+#[test]
+fn test_parse_log_targets() {
+    let targets = parse_log_targets("sqlx=warn, bollard=info,zbus=error").unwrap();
+    assert_eq!(
+        targets,
+        vec![
+            ("zbus".to_string(), Level::Error),
+            ("tracing".to_string(), Level::Warn),
+            ("sqlx".to_string(), Level::Warn),
+            ("bollard".to_string(), Level::Info),
+        ]
+    );
+    assert_eq!(parse_log_targets("").unwrap().len(), 2);
+    assert!(parse_log_targets("sqlx").is_err());
+    assert!(parse_log_targets("sqlx=loud").is_err());
+}
+
+#[test]
+fn test_parse_journal_mode() {
+    assert!(matches!(
+        parse_journal_mode("WAL").unwrap(),
+        SqliteJournalMode::Wal
+    ));
+    assert!(matches!(
+        parse_journal_mode("persist").unwrap(),
+        SqliteJournalMode::Persist
+    ));
+    assert!(parse_journal_mode("memory").is_err());
+}
+
+#[test]
+fn test_parse_trusted_proxies() {
+    assert!(parse_trusted_proxies("").unwrap().is_empty());
+    let proxies = parse_trusted_proxies("127.0.0.1, ::1").unwrap();
+    assert_eq!(proxies.len(), 2);
+    assert!(proxies[1].is_loopback());
+    assert!(parse_trusted_proxies("proxy.local").is_err());
 }
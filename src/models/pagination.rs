@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+pub const DEFAULT_PER_PAGE: usize = 25;
+pub const MAX_PER_PAGE: usize = 500;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+impl SortOrder {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListQuery {
+    pub page: usize,
+    pub per_page: usize,
+    pub sort: Option<String>,
+    pub order: Option<SortOrder>,
+    pub filters: HashMap<String, String>,
+    //Set when page or per_page was passed, list endpoints keep their plain array otherwise
+    pub paginated: bool,
+}
+impl Default for ListQuery {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            per_page: DEFAULT_PER_PAGE,
+            sort: None,
+            order: None,
+            filters: HashMap::new(),
+            paginated: false,
+        }
+    }
+}
+impl ListQuery {
+    /// Parses `page`, `per_page`, `sort` and `order` from a query string,
+    /// every other parameter is kept as a filter for the endpoint to interpret
+    pub fn parse(query: Option<&str>) -> Result<Self, Error> {
+        let mut list_query = Self::default();
+        for (key, value) in query
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .filter(|(_, value)| !value.is_empty())
+        {
+            match key {
+                "page" => {
+                    list_query.paginated = true;
+                    list_query.page = value.parse().map_err(|e| {
+                        Error::new(ErrorKind::InvalidInput, format!("Invalid page: {e}"))
+                    })?;
+                    if list_query.page == 0 {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "Invalid page: pages start at 1",
+                        ));
+                    }
+                }
+                "per_page" => {
+                    list_query.paginated = true;
+                    let per_page: usize = value.parse().map_err(|e| {
+                        Error::new(ErrorKind::InvalidInput, format!("Invalid per_page: {e}"))
+                    })?;
+                    list_query.per_page = per_page.clamp(1, MAX_PER_PAGE);
+                }
+                "sort" => list_query.sort = Some(value.to_string()),
+                "order" => {
+                    list_query.order = Some(match value.to_ascii_lowercase().as_str() {
+                        "asc" => SortOrder::Asc,
+                        "desc" => SortOrder::Desc,
+                        _ => {
+                            return Err(Error::new(
+                                ErrorKind::InvalidInput,
+                                format!("Invalid order: {value}, expected asc or desc"),
+                            ))
+                        }
+                    })
+                }
+                _ => {
+                    list_query
+                        .filters
+                        .insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+        Ok(list_query)
+    }
+    pub fn filter(&self, key: &str) -> Option<&str> {
+        self.filters.get(key).map(String::as_str)
+    }
+    pub fn bool_filter(&self, key: &str) -> Result<Option<bool>, Error> {
+        self.filter(key)
+            .map(|value| {
+                value.parse().map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Invalid {key}: {value}, expected true or false"),
+                    )
+                })
+            })
+            .transpose()
+    }
+    /// Returns the requested sort field, or `default` when none was given.
+    /// Fields not listed in `allowed` are rejected
+    pub fn sort_field<'a>(&self, allowed: &[&'a str], default: &'a str) -> Result<&'a str, Error> {
+        match &self.sort {
+            None => Ok(default),
+            Some(sort) => allowed
+                .iter()
+                .find(|field| **field == sort.as_str())
+                .copied()
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "Invalid sort: {sort}, expected one of {}",
+                            allowed.join("|")
+                        ),
+                    )
+                }),
+        }
+    }
+    pub fn order_or(&self, default: SortOrder) -> SortOrder {
+        self.order.unwrap_or(default)
+    }
+    pub fn offset(&self) -> usize {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    pub total_pages: usize,
+}
+impl<T> Paginated<T> {
+    /// Wraps a single page of items that was already limited by the caller
+    pub fn new(items: Vec<T>, total: usize, query: &ListQuery) -> Self {
+        Self {
+            items,
+            total,
+            page: query.page,
+            per_page: query.per_page,
+            total_pages: total.div_ceil(query.per_page),
+        }
+    }
+    /// Slices the requested page out of an already filtered and sorted list
+    pub fn from_vec(items: Vec<T>, query: &ListQuery) -> Self {
+        let total = items.len();
+        let items = items
+            .into_iter()
+            .skip(query.offset())
+            .take(query.per_page)
+            .collect();
+        Self::new(items, total, query)
+    }
+}
+
+/// A plain array for callers that didn't ask for a page, so existing clients keep working
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ListResponse<T> {
+    All(Vec<T>),
+    Page(Paginated<T>),
+}
+impl<T> ListResponse<T> {
+    pub fn from_vec(items: Vec<T>, query: &ListQuery) -> Self {
+        if query.paginated {
+            Self::Page(Paginated::from_vec(items, query))
+        } else {
+            Self::All(items)
+        }
+    }
+}
+
+#[test]
+fn test_paginate_empty_results() {
+    let query = ListQuery::parse(Some("page=1&per_page=10")).unwrap();
+    let page = Paginated::<u32>::from_vec(vec![], &query);
+    assert!(page.items.is_empty());
+    assert_eq!(page.total, 0);
+    assert_eq!(page.total_pages, 0);
+    assert_eq!(page.page, 1);
+}
+
+#[test]
+fn test_paginate_out_of_range_page() {
+    let query = ListQuery::parse(Some("page=5&per_page=10")).unwrap();
+    let page = Paginated::from_vec((0..25).collect::<Vec<u32>>(), &query);
+    assert!(page.items.is_empty());
+    assert_eq!(page.total, 25);
+    assert_eq!(page.total_pages, 3);
+    let query = ListQuery::parse(Some("page=3&per_page=10")).unwrap();
+    let page = Paginated::from_vec((0..25).collect::<Vec<u32>>(), &query);
+    assert_eq!(page.items, vec![20, 21, 22, 23, 24]);
+}
+
+#[test]
+fn test_list_query_parse() {
+    let query = ListQuery::parse(Some("per_page=10000&sort=name&order=DESC&type=Docker")).unwrap();
+    assert_eq!(query.page, 1);
+    assert_eq!(query.per_page, MAX_PER_PAGE);
+    assert_eq!(query.order, Some(SortOrder::Desc));
+    assert_eq!(query.filter("type"), Some("Docker"));
+    assert_eq!(
+        query.sort_field(&["name", "added"], "added").unwrap(),
+        "name"
+    );
+    assert!(query.sort_field(&["added"], "added").is_err());
+    assert!(ListQuery::parse(Some("page=0")).is_err());
+    assert!(ListQuery::parse(Some("order=sideways")).is_err());
+    assert_eq!(ListQuery::parse(None).unwrap(), ListQuery::default());
+}
+
+#[test]
+fn test_list_response_shape() {
+    let query = ListQuery::parse(Some("sort=name")).unwrap();
+    assert!(!query.paginated);
+    let response = ListResponse::from_vec(vec![1u32, 2, 3], &query);
+    assert_eq!(
+        portfu::prelude::serde_json::to_string(&response).unwrap(),
+        "[1,2,3]"
+    );
+    let query = ListQuery::parse(Some("per_page=2")).unwrap();
+    assert!(query.paginated);
+    let response = ListResponse::from_vec(vec![1u32, 2, 3], &query);
+    assert_eq!(
+        portfu::prelude::serde_json::to_string(&response).unwrap(),
+        r#"{"items":[1,2],"total":3,"page":1,"per_page":2,"total_pages":2}"#
+    );
+}
@@ -6,6 +6,7 @@ use time::OffsetDateTime;
 pub enum PluginType {
     BuiltIn,
     Docker,
+    DockerCompose,
     File,
     RustProject,
     Invalid,
@@ -15,6 +16,7 @@ impl From<String> for PluginType {
         match s.to_ascii_lowercase().as_str() {
             "builtin" => Self::Docker,
             "docker" => Self::Docker,
+            "dockercompose" | "docker-compose" => Self::DockerCompose,
             "rustproject" => Self::RustProject,
             "file" => Self::File,
             _ => Self::Invalid,
@@ -47,6 +49,7 @@ impl From<AddPlugin> for Plugin {
             source: val.source,
             run_command: val.run_command,
             version: val.version,
+            download_count: 0,
             added: OffsetDateTime::now_utc(),
             updated: OffsetDateTime::now_utc(),
         }
@@ -65,6 +68,8 @@ pub struct Plugin {
     pub source: String,
     pub run_command: Option<String>,
     pub version: String,
+    #[serde(default)]
+    pub download_count: i64,
     pub added: OffsetDateTime,
     pub updated: OffsetDateTime,
 }
@@ -1,28 +1,187 @@
 use crate::config::ConfigManager;
+use crate::database::config::get_config;
 use crate::models::config::AddConfigEntry;
 use crate::plugins::system_monitor::{DiskInfo, SystemMonitorPlugin};
+use crate::plugins::PluginManager;
+use crate::web::sse::{EventStream, SSE_PING_INTERVAL};
 use dg_sysfs::classes::block::disk::FileSystem;
-use log::{info, warn};
+use log::{debug, info, warn};
 use portfu::prelude::State;
-use portfu_core::Json;
-use portfu_macros::{interval, post};
-use serde::Deserialize;
+use portfu_core::{Json, ServiceData};
+use portfu_macros::{get, interval, post};
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::io::{Error, ErrorKind};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use time::OffsetDateTime;
 use tokio::fs::create_dir_all;
 use tokio::process::Command;
-use tokio::sync::RwLock;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, RwLock};
 
-#[derive(Debug, Default)]
-pub struct DiskManagerPlugin {}
+const MAX_MOUNT_ATTEMPTS: u32 = 5;
+const MOUNT_BACKOFF_BASE: Duration = Duration::from_secs(30);
+const MAX_MOUNT_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MountFailureRecord {
+    failures: u32,
+    last_failure: u64,
+}
+
+fn mount_failure_key(uuid: &str) -> String {
+    //Must not start with auto-mount- or it would be read as a mount entry
+    format!("mount-failures-{uuid}")
+}
+
+/// Time to wait before retrying a mount that has failed `failures` times,
+/// doubling for every failure past MAX_MOUNT_ATTEMPTS
+fn mount_backoff(failures: u32) -> Duration {
+    if failures <= MAX_MOUNT_ATTEMPTS {
+        return Duration::ZERO;
+    }
+    let exponent = (failures - MAX_MOUNT_ATTEMPTS - 1).min(16);
+    MOUNT_BACKOFF_BASE
+        .saturating_mul(2u32.pow(exponent))
+        .min(MAX_MOUNT_BACKOFF)
+}
+
+const DISK_EVENT_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskEventType {
+    Mounted,
+    Unmounted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskEvent {
+    pub event: DiskEventType,
+    pub device: String,
+    pub mount_point: String,
+    pub uuid: Option<String>,
+    pub timestamp: OffsetDateTime,
+}
+
+#[derive(Debug)]
+pub struct DiskManagerPlugin {
+    mount_failures: RwLock<HashMap<String, (u32, Instant)>>,
+    events: broadcast::Sender<DiskEvent>,
+}
+impl Default for DiskManagerPlugin {
+    fn default() -> Self {
+        Self {
+            mount_failures: Default::default(),
+            events: broadcast::channel(DISK_EVENT_CAPACITY).0,
+        }
+    }
+}
 impl DiskManagerPlugin {
     pub fn new() -> DiskManagerPlugin {
         DiskManagerPlugin::default()
     }
+    pub fn subscribe(&self) -> broadcast::Receiver<DiskEvent> {
+        self.events.subscribe()
+    }
+    async fn send_event(&self, event: DiskEventType, device: &Path, mount_point: &str) {
+        let event = DiskEvent {
+            event,
+            device: device.display().to_string(),
+            mount_point: mount_point.to_string(),
+            uuid: device_uuid(device).await,
+            timestamp: OffsetDateTime::now_utc(),
+        };
+        //Sending only fails when nobody is listening
+        let _ = self.events.send(event);
+    }
+    async fn load_mount_failures(&self, uuid: &str, config: &ConfigManager) -> (u32, Instant) {
+        if let Some(failure) = self.mount_failures.read().await.get(uuid) {
+            return *failure;
+        }
+        //Fall back to the history saved before the last restart
+        let failure = match config
+            .get_as::<MountFailureRecord>(&mount_failure_key(uuid))
+            .await
+        {
+            Some(record) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default();
+                let elapsed = Duration::from_secs(now.saturating_sub(record.last_failure));
+                (
+                    record.failures,
+                    Instant::now()
+                        .checked_sub(elapsed)
+                        .unwrap_or(Instant::now()),
+                )
+            }
+            None => (0, Instant::now()),
+        };
+        self.mount_failures
+            .write()
+            .await
+            .insert(uuid.to_string(), failure);
+        failure
+    }
+    pub async fn should_retry_mount(&self, uuid: &str, config: &ConfigManager) -> bool {
+        let (failures, last_failure) = self.load_mount_failures(uuid, config).await;
+        last_failure.elapsed() >= mount_backoff(failures)
+    }
+    pub async fn record_mount_failure(
+        &self,
+        uuid: &str,
+        config: &mut ConfigManager,
+        pool: &SqlitePool,
+    ) -> Result<u32, Error> {
+        let (failures, _) = self.load_mount_failures(uuid, config).await;
+        let failures = failures.saturating_add(1);
+        self.mount_failures
+            .write()
+            .await
+            .insert(uuid.to_string(), (failures, Instant::now()));
+        let record = MountFailureRecord {
+            failures,
+            last_failure: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        };
+        config
+            .set_as(&mount_failure_key(uuid), &record, "system", Some(pool))
+            .await?;
+        Ok(failures)
+    }
+    pub async fn clear_mount_failures(
+        &self,
+        uuid: &str,
+        config: &mut ConfigManager,
+        pool: &SqlitePool,
+    ) -> Result<(), Error> {
+        self.mount_failures.write().await.remove(uuid);
+        let key = mount_failure_key(uuid);
+        if config.get(&key).await.is_some() {
+            config.delete(&key, pool).await?;
+        }
+        Ok(())
+    }
     pub async fn unmount<M: AsRef<OsStr>>(&self, mount_point: M) -> Result<(), Error> {
         let mount_point = mount_point.as_ref();
+        let mount_point_str = mount_point.to_string_lossy();
+        //Look up the device first, it is gone from /proc/mounts once unmounted
+        let device = tokio::fs::read_to_string(PROC_MOUNTS)
+            .await
+            .map(|contents| parse_proc_mounts(&contents))
+            .unwrap_or_default()
+            .into_iter()
+            .find(|(_, existing)| {
+                existing.trim_end_matches('/') == mount_point_str.trim_end_matches('/')
+            })
+            .map(|(device, _)| PathBuf::from(device));
         let output = Command::new("sudo")
             .arg("umount")
             .arg(mount_point)
@@ -33,6 +192,10 @@ impl DiskManagerPlugin {
                 String::from_utf8_lossy(&output.stderr).to_string(),
             ));
         }
+        if let Some(device) = device {
+            self.send_event(DiskEventType::Unmounted, &device, &mount_point_str)
+                .await;
+        }
         Ok(())
     }
     pub async fn mount<D: AsRef<OsStr>, M: AsRef<OsStr>>(
@@ -42,6 +205,30 @@ impl DiskManagerPlugin {
     ) -> Result<(), Error> {
         let device_path = device_path.as_ref();
         let mount_point = mount_path.as_ref();
+        let mount_point_str = mount_point.to_string_lossy();
+        let mount_point_str = match mount_point_str.trim_end_matches('/') {
+            "" => "/",
+            trimmed => trimmed,
+        };
+        let device = canonical_device(Path::new(device_path))
+            .await
+            .unwrap_or(PathBuf::from(device_path));
+        let mounts = parse_proc_mounts(&tokio::fs::read_to_string(PROC_MOUNTS).await?);
+        for (existing, existing_mount_point) in mounts {
+            if existing_mount_point != mount_point_str {
+                continue;
+            }
+            let existing_device = canonical_device(Path::new(&existing))
+                .await
+                .unwrap_or(PathBuf::from(&existing));
+            if existing_device == device {
+                return Ok(());
+            }
+            return Err(Error::new(
+                ErrorKind::AddrInUse,
+                format!("mount point already in use by {existing}"),
+            ));
+        }
         create_dir_all(mount_point).await?;
         let output = Command::new("sudo")
             .arg("mount")
@@ -54,16 +241,119 @@ impl DiskManagerPlugin {
                 String::from_utf8_lossy(&output.stderr).to_string(),
             ));
         }
+        self.send_event(DiskEventType::Mounted, &device, mount_point_str)
+            .await;
         Ok(())
     }
 }
 
+const PROC_MOUNTS: &str = "/proc/mounts";
+const DISK_BY_UUID: &str = "/dev/disk/by-uuid";
+
+/// Returns (device, mount point) pairs, undoing the octal escapes /proc/mounts uses for whitespace
+fn parse_proc_mounts(contents: &str) -> Vec<(String, String)> {
+    let unescape = |v: &str| {
+        v.replace("\\040", " ")
+            .replace("\\011", "\t")
+            .replace("\\012", "\n")
+            .replace("\\134", "\\")
+    };
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((unescape(fields.next()?), unescape(fields.next()?)))
+        })
+        .collect()
+}
+
+async fn canonical_device(path: &Path) -> Option<PathBuf> {
+    tokio::fs::canonicalize(path).await.ok()
+}
+
+/// Finds the filesystem UUID for a device by matching it against the by-uuid links
+async fn device_uuid(device: &Path) -> Option<String> {
+    let device = canonical_device(device).await?;
+    let mut entries = tokio::fs::read_dir(DISK_BY_UUID).await.ok()?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if canonical_device(&entry.path()).await.as_ref() == Some(&device) {
+            return Some(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    None
+}
+
+/// Repairs auto mounts left in a bad state by a crash, remounting any
+/// mount point that holds the wrong device or is missing its device
+pub async fn recover_auto_mounts(pool: &SqlitePool) -> Result<(), Error> {
+    let entries: Vec<(String, String)> = get_config(pool)
+        .await?
+        .into_iter()
+        .filter_map(|entry| {
+            entry
+                .key
+                .strip_prefix("auto-mount-")
+                .map(|uuid| (uuid.to_string(), entry.value))
+        })
+        .collect();
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mounts = parse_proc_mounts(&tokio::fs::read_to_string(PROC_MOUNTS).await?);
+    let disk_manager = DiskManagerPlugin::new();
+    for (uuid, mount_path) in entries {
+        let Some(device) = canonical_device(&Path::new(DISK_BY_UUID).join(&uuid)).await else {
+            info!("Auto Mount device {uuid} not present, skipping recovery");
+            continue;
+        };
+        let mut mounted_devices = vec![];
+        for (mounted, mount_point) in &mounts {
+            if *mount_point == mount_path {
+                mounted_devices.push(
+                    canonical_device(Path::new(mounted))
+                        .await
+                        .unwrap_or(PathBuf::from(mounted)),
+                );
+            }
+        }
+        if mounted_devices.contains(&device) {
+            continue;
+        }
+        if !mounted_devices.is_empty() {
+            info!(
+                "Recovering {mount_path}: found {mounted_devices:?} mounted instead of {}, unmounting",
+                device.display()
+            );
+            if let Err(e) = disk_manager.unmount(&mount_path).await {
+                warn!("Failed to unmount stale mount at {mount_path}: {e:?}");
+                continue;
+            }
+        }
+        info!("Recovering {mount_path}: mounting {}", device.display());
+        if let Err(e) = disk_manager.mount(&device, &mount_path).await {
+            warn!("Failed to recover mount {mount_path}: {e:?}");
+        }
+    }
+    Ok(())
+}
+
 #[interval(10_000)]
 pub async fn disk_auto_mounting(
     disk_manager: State<DiskManagerPlugin>,
     config: State<RwLock<ConfigManager>>,
     system_manager: State<SystemMonitorPlugin>,
+    plugin_manager: State<RwLock<PluginManager>>,
+    pool: State<SqlitePool>,
 ) -> Result<(), Error> {
+    if !plugin_manager
+        .0
+        .read()
+        .await
+        .is_enabled("disk_manager")
+        .await
+    {
+        return Ok(());
+    }
     system_manager.0.reload_disks().await?;
     //Load All Known Disks
     let known_disks = system_manager.0.get_disk_info().await?;
@@ -92,15 +382,46 @@ pub async fn disk_auto_mounting(
             if let Some(uuid) = uuid {
                 let key = format!("auto-mount-{}", uuid);
                 info!("Looking Automount Entry - {key}");
-                if let Some(entry) = config.read().await.get(&key).await {
+                let entry = config.read().await.get(&key).await;
+                if let Some(entry) = entry {
                     info!("Found Automount Entry");
                     if partition.mount_path.is_none() {
                         info!("Found Unmounted Disk");
                         let mount_path = entry.value;
-                        disk_manager
+                        if !disk_manager
                             .0
-                            .mount(&partition.device, &mount_path)
-                            .await?;
+                            .should_retry_mount(&uuid, &*config.read().await)
+                            .await
+                        {
+                            debug!("Waiting to retry failed Automount of {uuid}");
+                            continue;
+                        }
+                        match disk_manager.0.mount(&partition.device, &mount_path).await {
+                            Ok(()) => {
+                                disk_manager
+                                    .0
+                                    .clear_mount_failures(
+                                        &uuid,
+                                        &mut *config.write().await,
+                                        pool.as_ref(),
+                                    )
+                                    .await?;
+                            }
+                            Err(e) => {
+                                let failures = disk_manager
+                                    .0
+                                    .record_mount_failure(
+                                        &uuid,
+                                        &mut *config.write().await,
+                                        pool.as_ref(),
+                                    )
+                                    .await?;
+                                warn!(
+                                    "Failed to Automount {:?} to {mount_path} (attempt {failures}): {e}",
+                                    partition.device
+                                );
+                            }
+                        }
                     }
                 }
             }
@@ -211,3 +532,67 @@ pub async fn unmount(
         )),
     }
 }
+
+#[get("/api/disks/events", output = "none", eoutput = "bytes")]
+pub async fn disk_events(
+    data: &mut ServiceData,
+    state: State<DiskManagerPlugin>,
+) -> Result<(), Error> {
+    let mut receiver = state.0.subscribe();
+    let stream = EventStream::start(data);
+    tokio::spawn(async move {
+        let mut ping = tokio::time::interval(SSE_PING_INTERVAL);
+        loop {
+            tokio::select! {
+                result = receiver.recv() => {
+                    match result {
+                        Ok(event) => {
+                            if !stream.send_json(&event).await {
+                                break;
+                            }
+                        }
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("Disk event stream skipped {skipped} events");
+                        }
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+                _ = ping.tick() => {
+                    if !stream.ping().await {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[test]
+fn test_parse_proc_mounts() {
+    let mounts = parse_proc_mounts(
+        "/dev/sda1 / ext4 rw,relatime 0 0\n/dev/sdb1 /mnt/plot\\040drive xfs rw,noatime 0 0\n",
+    );
+    assert_eq!(
+        mounts,
+        vec![
+            ("/dev/sda1".to_string(), "/".to_string()),
+            ("/dev/sdb1".to_string(), "/mnt/plot drive".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_mount_backoff() {
+    assert_eq!(mount_backoff(0), Duration::ZERO);
+    assert_eq!(mount_backoff(MAX_MOUNT_ATTEMPTS), Duration::ZERO);
+    assert_eq!(
+        mount_backoff(MAX_MOUNT_ATTEMPTS + 1),
+        Duration::from_secs(30)
+    );
+    assert_eq!(
+        mount_backoff(MAX_MOUNT_ATTEMPTS + 3),
+        Duration::from_secs(120)
+    );
+    assert_eq!(mount_backoff(u32::MAX), MAX_MOUNT_BACKOFF);
+}
@@ -1,6 +1,9 @@
+use crate::config::ConfigManager;
 use crate::database::config::{create_config_entry, get_config_key};
+use crate::database::events::{log_event, EventSeverity};
 use crate::database::stats::{
-    get_farmer_stats_range, has_farmer_stats, prune_farmer_stats, save_farmer_stats,
+    get_farmer_stats_range, has_farmer_stats, prune_farmer_stats, prune_farmer_stats_rows,
+    save_farmer_reward, save_farmer_stats, AddFarmerReward, LauncherFarmerStats,
 };
 use crate::models::config::{AddConfigEntry, ConfigEntry};
 use dg_fast_farmer::farmer::config::{Config, MetricsConfig};
@@ -16,20 +19,29 @@ use reqwest::{Client, Url};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::io::{Error, ErrorKind};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use time::OffsetDateTime;
 use tokio::fs::{copy, metadata, remove_file, rename, set_permissions, File};
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::{Mutex, RwLock};
 
+pub const XCH_PRICE_URL_CONFIG_KEY: &str = "xch_price_url";
+pub const XCH_PRICE_POINTER_CONFIG_KEY: &str = "xch_price_json_pointer";
+const DEFAULT_XCH_PRICE_POINTER: &str = "/price";
+pub const FARMER_CAPTURE_STDERR_CONFIG_KEY: &str = "farmer_capture_stderr";
+const STDERR_LOG_LINES: usize = 1000;
+pub const GRACEFUL_SHUTDOWN_TIMEOUT_CONFIG_KEY: &str = "graceful_shutdown_timeout_secs";
+const DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT_SECS: u64 = 10;
+
 const fn default_parallel_read() -> bool {
     true
 }
@@ -49,10 +61,58 @@ const fn default_recompute() -> u16 {
     0
 }
 
+const fn default_directory_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectoryConfig {
+    pub path: String,
+    #[serde(default)]
+    pub max_cpu_cores: Option<i32>,
+    #[serde(default)]
+    pub max_cuda_devices: Option<i32>,
+    #[serde(default = "default_directory_enabled")]
+    pub enabled: bool,
+}
+impl From<String> for DirectoryConfig {
+    fn from(path: String) -> Self {
+        Self {
+            path,
+            max_cpu_cores: None,
+            max_cuda_devices: None,
+            enabled: true,
+        }
+    }
+}
+
+//Older configs saved plot_directories as plain paths, accept either form per entry
+fn deserialize_plot_directories<'de, D>(deserializer: D) -> Result<Vec<DirectoryConfig>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PlotDirectoryEntry {
+        Path(String),
+        Config(DirectoryConfig),
+    }
+    Ok(Vec::<PlotDirectoryEntry>::deserialize(deserializer)?
+        .into_iter()
+        .map(|entry| match entry {
+            PlotDirectoryEntry::Path(path) => path.into(),
+            PlotDirectoryEntry::Config(config) => config,
+        })
+        .collect())
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HarvesterConfig {
-    #[serde(default = "Vec::new")]
-    pub plot_directories: Vec<String>,
+    #[serde(
+        default = "Vec::new",
+        deserialize_with = "deserialize_plot_directories"
+    )]
+    pub plot_directories: Vec<DirectoryConfig>,
     #[serde(default = "default_parallel_read")]
     pub parallel_read: bool,
     #[serde(default = "default_plot_search_depth")]
@@ -75,7 +135,7 @@ pub struct HarvesterConfig {
 impl Default for HarvesterConfig {
     fn default() -> Self {
         Self {
-            plot_directories: vec!["/mnt".to_string()],
+            plot_directories: vec![DirectoryConfig::from("/mnt".to_string())],
             parallel_read: true,
             plot_search_depth: 2,
             max_cpu_cores: -1,
@@ -125,6 +185,30 @@ pub async fn load_farmer_config(pool: &SqlitePool) -> Result<Config<HarvesterCon
     }
 }
 
+//The farmer binary only understands a flat list of paths, so disabled directories are dropped
+pub fn farmer_binary_config(config: &Config<HarvesterConfig>) -> Result<serde_yaml::Value, Error> {
+    let mut value = serde_yaml::to_value(config).map_err(Error::other)?;
+    if let Some(harvester_config) = &config.harvester_configs.custom_config {
+        let paths: Vec<serde_yaml::Value> = harvester_config
+            .plot_directories
+            .iter()
+            .filter(|directory| directory.enabled)
+            .map(|directory| serde_yaml::Value::String(directory.path.clone()))
+            .collect();
+        if let Some(custom_config) = value
+            .get_mut("harvester_configs")
+            .and_then(|v| v.get_mut("custom_config"))
+            .and_then(|v| v.as_mapping_mut())
+        {
+            custom_config.insert(
+                serde_yaml::Value::String("plot_directories".to_string()),
+                serde_yaml::Value::Sequence(paths),
+            );
+        }
+    }
+    Ok(value)
+}
+
 pub async fn save_farmer_config(
     pool: &SqlitePool,
     config: &Config<HarvesterConfig>,
@@ -138,6 +222,7 @@ pub async fn save_farmer_config(
             category: "farmer".to_string(),
             system: 1,
         },
+        None,
     )
     .await
 }
@@ -159,6 +244,25 @@ pub struct FastFarmerManifest {
     pub beta_version: Option<Version>,
     pub date: Option<String>,
     pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architectures: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    pub peer_host: String,
+    pub peer_port: u16,
+    pub connection_type: String,
+    pub height: u32,
+    pub synced: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallProgress {
+    pub phase: String,
+    pub bytes_downloaded: u64,
+    pub total_bytes: u64,
+    pub started_at: OffsetDateTime,
 }
 
 pub struct FarmerManager {
@@ -166,21 +270,62 @@ pub struct FarmerManager {
     install_mutex: Mutex<()>,
     database: SqlitePool,
     client: Client,
+    latest_stats: RwLock<Option<FarmerStats>>,
+    capture_logs: AtomicBool,
+    stderr_log: Arc<RwLock<VecDeque<String>>>,
+    manifest_cache_path: PathBuf,
+    baseline_manifest: Option<FastFarmerManifest>,
+    install_progress: Arc<RwLock<Option<InstallProgress>>>,
 }
+fn arch_url_segment(
+    arch: &str,
+    architectures: Option<&HashMap<String, String>>,
+) -> Result<String, Error> {
+    match architectures {
+        Some(architectures) => architectures.get(arch).cloned().ok_or_else(|| {
+            let mut supported: Vec<&str> = architectures.keys().map(String::as_str).collect();
+            supported.sort();
+            Error::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "Unsupported Platform for Auto Updates: {arch}, supported: {}",
+                    supported.join(", ")
+                ),
+            )
+        }),
+        None => match arch {
+            "x86_64" => Ok("amd64".to_string()),
+            "aarch64" | "arm64" => Ok("arm64".to_string()),
+            //32-bit Raspberry Pi OS reports arm
+            "arm" | "armv7" => Ok("armv7".to_string()),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "Unsupported Platform for Auto Updates: {arch}, supported: x86_64, aarch64, armv7"
+                ),
+            )),
+        },
+    }
+}
+
 impl FarmerManager {
-    pub async fn new(database: SqlitePool) -> Result<FarmerManager, Error> {
+    pub async fn new(
+        database: SqlitePool,
+        manifest_cache_path: PathBuf,
+    ) -> Result<FarmerManager, Error> {
         let client = Client::new();
         let bin_path = Path::new(BIN_PATH);
         let current_version = Self::get_binary_version(bin_path).await;
-        match current_version {
+        match &current_version {
             Some(current_version) => {
-                info!("Found Binary Version: {}", &current_version);
+                info!("Found Binary Version: {current_version}");
             }
             None => {
                 info!("No Binary Version Installed");
             }
         }
-        match Self::fetch_manifest(&client).await {
+        let mut baseline_manifest = None;
+        match Self::fetch_manifest(&client, &manifest_cache_path).await {
             Ok(manifest) => {
                 info!("Found Farmer Manifest");
                 info!("Remote Version: {}", manifest.current_version);
@@ -199,18 +344,80 @@ impl FarmerManager {
             }
             Err(e) => {
                 error!("Failed to fetch Farmer Manifest: {e:?}");
+                //Fall back to the installed binary so updates have something to compare against
+                baseline_manifest = current_version.map(|current_version| {
+                    info!("Using Installed Version {current_version} as Baseline Manifest");
+                    FastFarmerManifest {
+                        current_version,
+                        beta_version: None,
+                        date: None,
+                        author: None,
+                        architectures: None,
+                    }
+                });
             }
         }
+        let capture_logs = AtomicBool::new(Self::capture_stderr_enabled(&database).await);
         Ok(Self {
             client,
             instance: Arc::new(RwLock::new(None)),
             install_mutex: Mutex::new(()),
             database,
+            latest_stats: RwLock::new(None),
+            capture_logs,
+            stderr_log: Arc::new(RwLock::new(VecDeque::with_capacity(STDERR_LOG_LINES))),
+            manifest_cache_path,
+            baseline_manifest,
+            install_progress: Arc::new(RwLock::new(None)),
         })
     }
+    async fn capture_stderr_enabled(database: &SqlitePool) -> bool {
+        match get_config_key(database, FARMER_CAPTURE_STDERR_CONFIG_KEY).await {
+            Ok(Some(entry)) => bool::from_str(entry.value.trim()).unwrap_or_else(|_| {
+                warn!(
+                    "Invalid {FARMER_CAPTURE_STDERR_CONFIG_KEY}: {}",
+                    entry.value
+                );
+                false
+            }),
+            _ => false,
+        }
+    }
+    async fn graceful_shutdown_timeout(&self) -> u64 {
+        match get_config_key(&self.database, GRACEFUL_SHUTDOWN_TIMEOUT_CONFIG_KEY).await {
+            Ok(Some(entry)) => u64::from_str(entry.value.trim()).unwrap_or_else(|_| {
+                warn!(
+                    "Invalid {GRACEFUL_SHUTDOWN_TIMEOUT_CONFIG_KEY}: {}",
+                    entry.value
+                );
+                DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT_SECS
+            }),
+            _ => DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT_SECS,
+        }
+    }
+    pub fn is_capturing_logs(&self) -> bool {
+        self.capture_logs.load(Ordering::Relaxed)
+    }
+    pub async fn stderr_log(&self) -> Vec<String> {
+        self.stderr_log.read().await.iter().cloned().collect()
+    }
     pub async fn is_running(&self) -> bool {
         self.instance.read().await.is_some()
     }
+    pub fn is_updating(&self) -> bool {
+        self.install_mutex.try_lock().is_err()
+    }
+    pub async fn install_progress(&self) -> Option<InstallProgress> {
+        self.install_progress.read().await.clone()
+    }
+    async fn set_install_phase(&self, phase: &str) {
+        if let Some(progress) = self.install_progress.write().await.as_mut() {
+            progress.phase = phase.to_string();
+        }
+    }
+    pub async fn latest_stats(&self) -> Option<FarmerStats> {
+        self.latest_stats.read().await.clone()
+    }
     pub async fn ensure_installed(&self) -> Result<(), Error> {
         let bin_path = Path::new(BIN_PATH);
         if !bin_path.exists() {
@@ -224,7 +431,17 @@ impl FarmerManager {
         let install_mutex = self.install_mutex.lock().await;
         let bin_path = Path::new(BIN_PATH);
         info!("Fetching Remote Manifest");
-        let current_manifest = Self::fetch_manifest(&self.client).await?;
+        let current_manifest =
+            match Self::fetch_manifest(&self.client, &self.manifest_cache_path).await {
+                Ok(manifest) => manifest,
+                Err(e) => match &self.baseline_manifest {
+                    Some(baseline) => {
+                        warn!("Failed to fetch Farmer Manifest, using Installed Version: {e:?}");
+                        baseline.clone()
+                    }
+                    None => return Err(e),
+                },
+            };
         let channel = match get_config_key(&self.database, "farmer_update_channel").await {
             Ok(Some(channel_entry)) => match channel_entry.value.to_ascii_lowercase().as_str() {
                 "beta" => UpdateChannel::Beta,
@@ -262,23 +479,50 @@ impl FarmerManager {
                     .beta_version
                     .unwrap_or(current_manifest.current_version),
             };
-            let download_url = Self::get_download_url(&version.to_string())?;
-            Self::download_file(&self.client, TMP_PATH, &download_url).await?;
-            Self::set_executable_bit(TMP_PATH).await?;
-
-            // Verify downloaded binary
-            let downloaded_version = Self::get_binary_version(TMP_PATH)
-                .await
-                .ok_or(Error::other("Failed to read downloaded binary version"))?;
-            if downloaded_version != version {
-                return Err(Error::other("Downloaded binary version mismatch"));
-            }
-            Self::swap_binaries().await?;
+            *self.install_progress.write().await = Some(InstallProgress {
+                phase: "starting".to_string(),
+                bytes_downloaded: 0,
+                total_bytes: 0,
+                started_at: OffsetDateTime::now_utc(),
+            });
+            let result = self
+                .install_farmer(&version, current_manifest.architectures.as_ref())
+                .await;
+            //Progress is only reported while an install is running
+            *self.install_progress.write().await = None;
+            result?;
         }
         drop(install_mutex);
         Ok(())
     }
 
+    async fn install_farmer(
+        &self,
+        version: &Version,
+        architectures: Option<&HashMap<String, String>>,
+    ) -> Result<(), Error> {
+        let download_url = Self::get_download_url(&version.to_string(), architectures)?;
+        self.set_install_phase("downloading").await;
+        Self::download_file(
+            &self.client,
+            TMP_PATH,
+            &download_url,
+            &self.install_progress,
+        )
+        .await?;
+        Self::set_executable_bit(TMP_PATH).await?;
+
+        // Verify downloaded binary
+        self.set_install_phase("verifying").await;
+        let downloaded_version = Self::get_binary_version(TMP_PATH)
+            .await
+            .ok_or(Error::other("Failed to read downloaded binary version"))?;
+        if downloaded_version != *version {
+            return Err(Error::other("Downloaded binary version mismatch"));
+        }
+        self.set_install_phase("installing").await;
+        Self::swap_binaries().await
+    }
     async fn get_binary_version<P: AsRef<Path>>(path: P) -> Option<Version> {
         let path = path.as_ref();
         let output = Command::new(path).arg("--version").output().await.ok()?;
@@ -299,7 +543,36 @@ impl FarmerManager {
             None
         }
     }
-    async fn fetch_manifest(client: &Client) -> Result<FastFarmerManifest, Error> {
+    async fn fetch_manifest(
+        client: &Client,
+        cache_path: &Path,
+    ) -> Result<FastFarmerManifest, Error> {
+        match Self::fetch_remote_manifest(client).await {
+            Ok(manifest) => {
+                match serde_yaml::to_string(&manifest) {
+                    Ok(yaml) => {
+                        if let Some(parent) = cache_path.parent() {
+                            let _ = tokio::fs::create_dir_all(parent).await;
+                        }
+                        if let Err(e) = tokio::fs::write(cache_path, yaml).await {
+                            warn!("Failed to cache Farmer Manifest to {cache_path:?}: {e:?}");
+                        }
+                    }
+                    Err(e) => warn!("Failed to serialize Farmer Manifest: {e:?}"),
+                }
+                Ok(manifest)
+            }
+            Err(e) => match tokio::fs::read_to_string(cache_path).await {
+                Ok(cached) => {
+                    let manifest = serde_yaml::from_str(&cached).map_err(Error::other)?;
+                    warn!("Using cached manifest from {}", cache_path.display());
+                    Ok(manifest)
+                }
+                Err(_) => Err(e),
+            },
+        }
+    }
+    async fn fetch_remote_manifest(client: &Client) -> Result<FastFarmerManifest, Error> {
         let response = client
             .get(FAST_FARMER_MANIFEST_URL)
             .send()
@@ -328,7 +601,12 @@ impl FarmerManager {
         perms.set_mode(0o755);
         set_permissions(path, perms).await
     }
-    async fn download_file(client: &Client, path: &str, download_url: &str) -> Result<(), Error> {
+    async fn download_file(
+        client: &Client,
+        path: &str,
+        download_url: &str,
+        progress: &RwLock<Option<InstallProgress>>,
+    ) -> Result<(), Error> {
         info!("Downloading from {download_url} to {path}");
         let mut resp = client
             .get(download_url)
@@ -337,27 +615,26 @@ impl FarmerManager {
             .map_err(Error::other)?
             .error_for_status()
             .map_err(Error::other)?;
+        if let Some(progress) = progress.write().await.as_mut() {
+            progress.total_bytes = resp.content_length().unwrap_or_default();
+        }
         let mut out = File::create(path).await.map_err(Error::other)?;
         while let Some(chunk) = resp.chunk().await.map_err(Error::other)? {
             out.write_all(&chunk).await?;
+            if let Some(progress) = progress.write().await.as_mut() {
+                progress.bytes_downloaded += chunk.len() as u64;
+            }
         }
         Ok(())
     }
-    fn get_download_url(version: &str) -> Result<String, Error> {
-        let arch = env::consts::ARCH;
+    fn get_download_url(
+        version: &str,
+        architectures: Option<&HashMap<String, String>>,
+    ) -> Result<String, Error> {
         Ok(format!(
             "https://builds.druid.garden/{}/{}/ff_giga",
             version,
-            if arch == "x86_64" {
-                "amd64"
-            } else if arch == "aarch64" || arch == "arm64" {
-                "arm64"
-            } else {
-                return Err(Error::new(
-                    ErrorKind::Unsupported,
-                    "Unsupported Platform for Auto Updates",
-                ));
-            }
+            arch_url_segment(env::consts::ARCH, architectures)?
         ))
     }
 
@@ -374,15 +651,22 @@ impl FarmerManager {
                 let mut tmp_file = File::create("/tmp/fast_farmer_config.yaml").await?;
                 tmp_file
                     .write_all(
-                        serde_yaml::to_string(&config)
+                        serde_yaml::to_string(&farmer_binary_config(&config)?)
                             .map_err(Error::other)?
                             .as_bytes(),
                     )
                     .await?;
-                let child = Command::new("fast_farmer_gh.app")
+                //Pick up changes to the capture setting on every start
+                let capture_logs = Self::capture_stderr_enabled(&self.database).await;
+                self.capture_logs.store(capture_logs, Ordering::Relaxed);
+                let mut child = Command::new("fast_farmer_gh.app")
                     .stdout(Stdio::null())
                     .stdin(Stdio::null())
-                    .stderr(Stdio::null())
+                    .stderr(if capture_logs {
+                        Stdio::piped()
+                    } else {
+                        Stdio::null()
+                    })
                     .arg("-c")
                     .arg("/tmp/fast_farmer_config.yaml")
                     .arg("run")
@@ -390,7 +674,41 @@ impl FarmerManager {
                     .arg("cli")
                     .kill_on_drop(true)
                     .spawn()?;
+                if let Some(stderr) = child.stderr.take() {
+                    let stderr_log = self.stderr_log.clone();
+                    tokio::spawn(async move {
+                        let mut lines = BufReader::new(stderr).lines();
+                        loop {
+                            match lines.next_line().await {
+                                Ok(Some(line)) => {
+                                    debug!("[farmer] {line}");
+                                    let mut stderr_log = stderr_log.write().await;
+                                    if stderr_log.len() >= STDERR_LOG_LINES {
+                                        stderr_log.pop_front();
+                                    }
+                                    stderr_log.push_back(line);
+                                }
+                                Ok(None) => break,
+                                Err(e) => {
+                                    warn!("Failed to read Farmer stderr: {e:?}");
+                                    break;
+                                }
+                            }
+                        }
+                    });
+                }
                 *instance = Some(child);
+                if let Err(e) = log_event(
+                    &self.database,
+                    EventSeverity::Info,
+                    "farmer",
+                    "Farmer Started",
+                    None,
+                )
+                .await
+                {
+                    warn!("Failed to record Farmer Start event: {e:?}");
+                }
                 Ok(())
             }
         }
@@ -400,8 +718,36 @@ impl FarmerManager {
         let mut instance = self.instance.write().await;
         match instance.take() {
             Some(mut handle) => {
-                let _ = handle.kill().await;
+                let timeout = Duration::from_secs(self.graceful_shutdown_timeout().await);
+                let terminated = match handle.id() {
+                    //Ask the farmer to exit so it can finish any in progress writes
+                    Some(pid) => unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) == 0 },
+                    None => false,
+                };
+                match tokio::time::timeout(timeout, handle.wait()).await {
+                    Ok(Ok(status)) if terminated => {
+                        info!("Farmer Stopped Gracefully: {status}");
+                    }
+                    Ok(result) => {
+                        info!("Farmer Exited before Shutdown Signal: {result:?}");
+                    }
+                    Err(_) => {
+                        info!("Farmer did not Exit within {timeout:?}, Forcing Shutdown");
+                        let _ = handle.kill().await;
+                    }
+                }
                 tokio::fs::remove_file("/tmp/fast_farmer_config.yaml").await?;
+                if let Err(e) = log_event(
+                    &self.database,
+                    EventSeverity::Info,
+                    "farmer",
+                    "Farmer Stopped",
+                    None,
+                )
+                .await
+                {
+                    warn!("Failed to record Farmer Stop event: {e:?}");
+                }
                 Ok(())
             }
             None => Ok(()),
@@ -481,7 +827,54 @@ impl FarmerManager {
                 )
             })
     }
+    pub async fn farmer_connections(&self) -> Result<Vec<ConnectionInfo>, Error> {
+        let mut url = Self::farmer_url(&self.database).await?;
+        url.set_path("/connections");
+        self.client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Failed to Connect to Farmer: {e}"),
+                )
+            })?
+            .json()
+            .await
+            .map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Failed to Read Response: {e}"),
+                )
+            })
+    }
+    pub async fn refresh_farmer_connections(&self) -> Result<(), Error> {
+        let mut url = Self::farmer_url(&self.database).await?;
+        url.set_path("/connections/refresh");
+        let response = self.client.post(url).send().await.map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Failed to Connect to Farmer: {e}"),
+            )
+        })?;
+        if !response.status().is_success() {
+            return Err(Error::other(format!(
+                "Farmer failed to refresh connections: {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
     pub async fn recent_farmer_stats(&self) -> Result<Vec<FarmerStats>, Error> {
+        Ok(self
+            .recent_launcher_farmer_stats()
+            .await?
+            .into_iter()
+            .map(|s| s.stats)
+            .collect())
+    }
+    pub async fn recent_launcher_farmer_stats(&self) -> Result<Vec<LauncherFarmerStats>, Error> {
         let mut url = Self::farmer_url(&self.database).await?;
         url.set_path("/stats");
         self.client
@@ -503,11 +896,62 @@ impl FarmerManager {
                 )
             })
     }
+    pub async fn recent_farmer_rewards(&self) -> Result<Vec<AddFarmerReward>, Error> {
+        let mut url = Self::farmer_url(&self.database).await?;
+        url.set_path("/rewards");
+        let response = self.client.get(url).send().await.map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Failed to Connect to Farmer: {e}"),
+            )
+        })?;
+        //Older Farmers do not expose rewards, treat it as no new rewards
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(vec![]);
+        }
+        response.json().await.map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Failed to Read Response: {e}"),
+            )
+        })
+    }
+    pub async fn xch_price(&self) -> Option<f64> {
+        let url = get_config_key(&self.database, XCH_PRICE_URL_CONFIG_KEY)
+            .await
+            .ok()
+            .flatten()
+            .map(|c| c.value)
+            .filter(|v| !v.is_empty())?;
+        let pointer = get_config_key(&self.database, XCH_PRICE_POINTER_CONFIG_KEY)
+            .await
+            .ok()
+            .flatten()
+            .map(|c| c.value)
+            .unwrap_or_else(|| DEFAULT_XCH_PRICE_POINTER.to_string());
+        let response: serde_json::Value = match self.client.get(&url).send().await {
+            Ok(response) => match response.json().await {
+                Ok(value) => value,
+                Err(e) => {
+                    warn!("Failed to parse XCH Price from {url}: {e:?}");
+                    return None;
+                }
+            },
+            Err(e) => {
+                warn!("Failed to load XCH Price from {url}: {e:?}");
+                return None;
+            }
+        };
+        let price = response.pointer(&pointer)?;
+        price
+            .as_f64()
+            .or_else(|| price.as_str().and_then(|p| f64::from_str(p).ok()))
+    }
     pub async fn farmer_stats_range(
         &self,
         start: OffsetDateTime,
         end: OffsetDateTime,
-    ) -> Result<HashMap<(Bytes32, Bytes32), FarmerStats>, Error> {
+    ) -> Result<HashMap<(Bytes32, Bytes32, Bytes32), FarmerStats>, Error> {
         get_farmer_stats_range(&self.database, start, end).await
     }
     pub async fn farmer_log_stream(
@@ -577,29 +1021,140 @@ impl Drop for FarmerManager {
     }
 }
 
+pub const STATS_DAYS_SAVED_CONFIG_KEY: &str = "stats_days_saved";
+pub const STATS_MAX_ROWS_CONFIG_KEY: &str = "stats_max_rows";
+pub const DEFAULT_STATS_DAYS_SAVED: u64 = 30;
+pub const DEFAULT_STATS_MAX_ROWS: u64 = 100_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsPolicy {
+    pub days_saved: u64,
+    pub max_rows: u64,
+}
+
+pub async fn load_stats_policy(config: &ConfigManager) -> StatsPolicy {
+    StatsPolicy {
+        days_saved: config
+            .get_as::<u64>(STATS_DAYS_SAVED_CONFIG_KEY)
+            .await
+            .unwrap_or(DEFAULT_STATS_DAYS_SAVED),
+        max_rows: config
+            .get_as::<u64>(STATS_MAX_ROWS_CONFIG_KEY)
+            .await
+            .unwrap_or(DEFAULT_STATS_MAX_ROWS),
+    }
+}
+
+//None when the retention is too long to subtract from now
+pub fn stats_retention_cutoff(now: OffsetDateTime, days_saved: u64) -> Option<OffsetDateTime> {
+    let seconds = i64::try_from(days_saved).ok()?.checked_mul(24 * 60 * 60)?;
+    now.checked_sub(time::Duration::seconds(seconds))
+}
+
 #[interval(10_000)]
 pub async fn update_local_stats(
     database: State<SqlitePool>,
     farmer_manager: State<FarmerManager>,
+    config: State<RwLock<ConfigManager>>,
 ) -> Result<(), Error> {
     if farmer_manager.0.is_running().await {
         let mut url = FarmerManager::farmer_url(&database).await?;
         url.set_path("/stats");
-        let stats = farmer_manager.0.recent_farmer_stats().await?;
+        let stats = farmer_manager.0.recent_launcher_farmer_stats().await?;
+        if let Some(latest) = stats.last() {
+            *farmer_manager.0.latest_stats.write().await = Some(latest.stats.clone());
+        }
         for farmer_stats in stats {
-            if !has_farmer_stats(&database, farmer_stats.challenge_hash, farmer_stats.sp_hash)
-                .await?
+            //Farmers that do not report a launcher share the zeroed launcher id of older rows
+            let launcher_id = farmer_stats.launcher_id.unwrap_or_default();
+            let farmer_stats = farmer_stats.stats;
+            if !has_farmer_stats(
+                &database,
+                launcher_id,
+                farmer_stats.challenge_hash,
+                farmer_stats.sp_hash,
+            )
+            .await?
             {
-                save_farmer_stats(&database, farmer_stats).await?;
+                save_farmer_stats(&database, launcher_id, farmer_stats).await?;
             }
         }
-        let mut older_than_timestamp = OffsetDateTime::now_utc();
-        let stat_days_to_keep = get_config_key(&database, "stats_days_saved")
-            .await?
-            .map(|c| u64::from_str(&c.value).unwrap_or(30))
-            .unwrap_or(30);
-        older_than_timestamp -= Duration::new(stat_days_to_keep * 24 * 60 * 60, 0);
-        prune_farmer_stats(&database, older_than_timestamp).await?;
+        match farmer_manager.0.recent_farmer_rewards().await {
+            Ok(rewards) => {
+                for reward in rewards {
+                    if save_farmer_reward(&database, &reward).await? {
+                        info!(
+                            "Recorded Farmer Reward of {} XCH at height {}",
+                            reward.amount, reward.block_height
+                        );
+                    }
+                }
+            }
+            Err(e) => debug!("Failed to load Farmer Rewards: {e:?}"),
+        }
+        let policy = load_stats_policy(&*config.0.read().await).await;
+        match stats_retention_cutoff(OffsetDateTime::now_utc(), policy.days_saved) {
+            Some(older_than_timestamp) => {
+                prune_farmer_stats(&database, older_than_timestamp).await?;
+            }
+            None => warn!(
+                "Skipping Farmer Stats age pruning, {} days is out of range",
+                policy.days_saved
+            ),
+        }
+        let pruned = prune_farmer_stats_rows(&database, policy.max_rows).await?;
+        if pruned > 0 {
+            debug!(
+                "Pruned {pruned} Farmer Stats rows over the {} row cap",
+                policy.max_rows
+            );
+        }
     }
     Ok(())
 }
+
+#[test]
+fn test_stats_retention_cutoff() {
+    let now = OffsetDateTime::now_utc();
+    assert_eq!(
+        stats_retention_cutoff(now, 30),
+        Some(now - time::Duration::days(30))
+    );
+    assert_eq!(stats_retention_cutoff(now, u64::MAX), None);
+    assert_eq!(stats_retention_cutoff(now, i64::MAX as u64 / 1000), None);
+}
+
+#[test]
+fn test_arch_url_segment() {
+    assert_eq!(arch_url_segment("x86_64", None).unwrap(), "amd64");
+    assert_eq!(arch_url_segment("aarch64", None).unwrap(), "arm64");
+    assert_eq!(arch_url_segment("arm", None).unwrap(), "armv7");
+    assert!(arch_url_segment("riscv64", None).is_err());
+    let architectures = HashMap::from([("riscv64".to_string(), "riscv".to_string())]);
+    assert_eq!(
+        arch_url_segment("riscv64", Some(&architectures)).unwrap(),
+        "riscv"
+    );
+    let err = arch_url_segment("x86_64", Some(&architectures)).unwrap_err();
+    assert!(err.to_string().contains("riscv64"));
+}
+
+#[test]
+fn test_plot_directories_backwards_compat() {
+    let config: HarvesterConfig = serde_json::from_str(
+        r#"{"plot_directories": ["/mnt/a", {"path": "/mnt/b", "max_cpu_cores": 2, "enabled": false}]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.plot_directories,
+        vec![
+            DirectoryConfig::from("/mnt/a".to_string()),
+            DirectoryConfig {
+                path: "/mnt/b".to_string(),
+                max_cpu_cores: Some(2),
+                max_cuda_devices: None,
+                enabled: false,
+            },
+        ]
+    );
+}
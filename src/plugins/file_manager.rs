@@ -1,13 +1,25 @@
-use log::debug;
-use portfu::prelude::State;
+use crate::config::ConfigManager;
+use log::{debug, error, info, warn};
+use notify::event::ModifyKind;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use portfu::prelude::tokio_tungstenite::tungstenite::Message;
+use portfu::prelude::{serde_json, State, WebSocket};
 use portfu_core::Json;
-use portfu_macros::{delete, post, put};
+use portfu_macros::{delete, get, post, put, websocket};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::fs::Permissions;
 use std::io::{Error, ErrorKind};
-use std::path::{Path, PathBuf};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 use tempfile::NamedTempFile;
+use time::OffsetDateTime;
 use tokio::fs::{read_link, File};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::{Receiver, Sender};
 use tokio::sync::RwLock;
 
 #[derive(Debug, Serialize)]
@@ -31,14 +43,457 @@ pub struct FileContents {
     pub mime_type: String,
 }
 
-#[derive(Debug, Default)]
+#[derive(Serialize)]
+pub struct FileMetadata {
+    pub path: String,
+    pub size: u64,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub modified: Option<OffsetDateTime>,
+    pub created: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    pub trash_id: String,
+    pub original_path: String,
+    //Where the item was moved, items trashed before per filesystem trash folders live in trash_folder
+    #[serde(default)]
+    pub trash_path: Option<String>,
+    pub size: u64,
+    pub trashed: OffsetDateTime,
+}
+
+pub const TRASH_MAX_SIZE_CONFIG_KEY: &str = "trash_max_size_bytes";
+pub const DEFAULT_TRASH_MAX_SIZE_BYTES: u64 = 1024 * 1024 * 1024;
+
+pub fn trash_folder() -> Result<PathBuf, Error> {
+    home::home_dir()
+        .map(|home_dir| home_dir.join(".local/share/druid-garden/trash"))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "Failed to find Home Directory"))
+}
+
+//Items are trashed on their own filesystem so large files are renamed rather than copied
+async fn trash_folder_for(path: &Path) -> Result<PathBuf, Error> {
+    let home_trash = trash_folder()?;
+    tokio::fs::create_dir_all(&home_trash).await?;
+    let dev = tokio::fs::symlink_metadata(path).await?.dev();
+    if tokio::fs::metadata(&home_trash).await?.dev() == dev {
+        return Ok(home_trash);
+    }
+    let mut mount_root = path;
+    while let Some(parent) = mount_root.parent() {
+        if tokio::fs::metadata(parent).await?.dev() != dev {
+            break;
+        }
+        mount_root = parent;
+    }
+    if mount_root == path {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Cannot Trash a Mount Point",
+        ));
+    }
+    let uid = unsafe { libc::getuid() };
+    let trash_dir = mount_root.join(format!(".Trash-{uid}"));
+    tokio::fs::create_dir_all(&trash_dir).await?;
+    tokio::fs::set_permissions(&trash_dir, Permissions::from_mode(0o700)).await?;
+    Ok(trash_dir)
+}
+
+//Trash ids are single file names, anything else could escape the trash folder
+fn validate_trash_id(trash_id: &str) -> Result<(), Error> {
+    let mut components = Path::new(trash_id).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) if name == trash_id => Ok(()),
+        _ => Err(Error::new(ErrorKind::InvalidInput, "Invalid Trash Id")),
+    }
+}
+
+fn trashed_item_path(metadata_dir: &Path, item: &TrashItem) -> Result<PathBuf, Error> {
+    validate_trash_id(&item.trash_id)?;
+    match &item.trash_path {
+        Some(trash_path) => {
+            let trash_path = PathBuf::from(trash_path);
+            let in_trash = trash_path.file_name() == Some(item.trash_id.as_ref())
+                && trash_path
+                    .parent()
+                    .and_then(Path::file_name)
+                    .is_some_and(|dir| dir.to_string_lossy().starts_with(".Trash-"));
+            if !in_trash {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid Trash Path: {trash_path:?}"),
+                ));
+            }
+            Ok(trash_path)
+        }
+        None => Ok(metadata_dir.join(&item.trash_id)),
+    }
+}
+
+//Lexical parent of an absolute path, used to detect symlinks in the canonical parent
+fn parent_without_dots(path: &Path) -> PathBuf {
+    let mut parent = PathBuf::new();
+    for component in path.parent().into_iter().flat_map(Path::components) {
+        match component {
+            Component::ParentDir => {
+                parent.pop();
+            }
+            Component::CurDir => {}
+            component => parent.push(component),
+        }
+    }
+    parent
+}
+
+async fn path_size(path: PathBuf) -> Result<u64, Error> {
+    fn size_of(path: &Path) -> Result<u64, Error> {
+        let meta_data = std::fs::symlink_metadata(path)?;
+        if meta_data.is_dir() {
+            let mut total = 0;
+            for entry in std::fs::read_dir(path)? {
+                total += size_of(&entry?.path())?;
+            }
+            Ok(total)
+        } else {
+            Ok(meta_data.len())
+        }
+    }
+    tokio::task::spawn_blocking(move || size_of(&path)).await?
+}
+
+fn archive_entry_name(path: &Path) -> Result<PathBuf, Error> {
+    path.file_name().map(PathBuf::from).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Cannot Archive Path: {path:?}"),
+        )
+    })
+}
+
+fn compress_tar<W: std::io::Write>(
+    writer: W,
+    paths: &[PathBuf],
+    progress: &mut ArchiveProgressSender,
+) -> Result<W, Error> {
+    let mut builder = tar::Builder::new(writer);
+    for path in paths {
+        let name = archive_entry_name(path)?;
+        if path.is_dir() {
+            builder.append_dir_all(&name, path)?;
+        } else {
+            builder.append_path_with_name(path, &name)?;
+        }
+        progress.entry(path);
+    }
+    builder.into_inner()
+}
+
+fn compress_zip(
+    file: std::fs::File,
+    paths: &[PathBuf],
+    progress: &mut ArchiveProgressSender,
+) -> Result<(), Error> {
+    fn add_path(
+        writer: &mut zip::ZipWriter<std::fs::File>,
+        path: &Path,
+        name: &Path,
+        progress: &mut ArchiveProgressSender,
+    ) -> Result<(), Error> {
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .large_file(true);
+        let zip_name = name.to_string_lossy().to_string();
+        if path.is_dir() {
+            writer
+                .add_directory(zip_name, options)
+                .map_err(|e| Error::other(format!("Failed to add directory: {e}")))?;
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                add_path(
+                    writer,
+                    &entry.path(),
+                    &name.join(entry.file_name()),
+                    progress,
+                )?;
+            }
+        } else {
+            writer
+                .start_file(zip_name, options)
+                .map_err(|e| Error::other(format!("Failed to add file: {e}")))?;
+            std::io::copy(&mut std::fs::File::open(path)?, writer)?;
+            progress.entry(path);
+        }
+        Ok(())
+    }
+    let mut writer = zip::ZipWriter::new(file);
+    for path in paths {
+        add_path(&mut writer, path, &archive_entry_name(path)?, progress)?;
+    }
+    writer
+        .finish()
+        .map_err(|e| Error::other(format!("Failed to finish archive: {e}")))?;
+    Ok(())
+}
+
+fn extract_tar<R: std::io::Read>(
+    reader: R,
+    destination: &Path,
+    progress: &mut ArchiveProgressSender,
+) -> Result<(), Error> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        //unpack_in refuses entries that would land outside of the destination
+        if entry.unpack_in(destination)? {
+            progress.entry(&path);
+        } else {
+            warn!("Skipped unsafe archive entry: {path:?}");
+        }
+    }
+    Ok(())
+}
+
+fn extract_zip(
+    file: std::fs::File,
+    destination: &Path,
+    progress: &mut ArchiveProgressSender,
+) -> Result<(), Error> {
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| Error::other(format!("Failed to read archive: {e}")))?;
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| Error::other(format!("Failed to read archive entry: {e}")))?;
+        //enclosed_name is None for entries that would land outside of the destination
+        let Some(name) = entry.enclosed_name() else {
+            warn!("Skipped unsafe archive entry: {}", entry.name());
+            continue;
+        };
+        let out_path = destination.join(&name);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path)?;
+        } else {
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::io::copy(&mut entry, &mut std::fs::File::create(&out_path)?)?;
+            //setuid, setgid and sticky bits from an untrusted archive are dropped
+            if let Some(mode) = entry.unix_mode() {
+                std::fs::set_permissions(&out_path, Permissions::from_mode(mode & 0o777))?;
+            }
+        }
+        progress.entry(&name);
+    }
+    Ok(())
+}
+
+async fn move_path(from: &Path, to: &Path) -> Result<(), Error> {
+    match tokio::fs::rename(from, to).await {
+        Ok(()) => Ok(()),
+        //Renames fail across filesystems, fall back to a copy for plain files
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) && from.is_file() => {
+            if let Err(e) = tokio::fs::copy(from, to).await {
+                //Dont leave a partial copy behind
+                let _ = tokio::fs::remove_file(to).await;
+                return Err(e);
+            }
+            tokio::fs::remove_file(from).await
+        }
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => Err(Error::new(
+            ErrorKind::Unsupported,
+            format!("Cannot move {from:?} to another filesystem"),
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum WatchEvent {
+    Created { path: String },
+    Modified { path: String },
+    Deleted { path: String },
+    Renamed { path: String },
+    Error { message: String },
+}
+
+pub struct NotifyHandle {
+    _watcher: RecommendedWatcher,
+    sender: Sender<WatchEvent>,
+}
+impl Debug for NotifyHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotifyHandle")
+            .field("subscribers", &self.sender.receiver_count())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "tar.bz2")]
+    TarBz2,
+}
+impl ArchiveFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar.bz2") || name.ends_with(".tbz2") {
+            Some(ArchiveFormat::TarBz2)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveOperation {
+    Compress,
+    Extract,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveProgress {
+    pub operation: ArchiveOperation,
+    pub archive: String,
+    pub entry: Option<String>,
+    pub entries_processed: u64,
+    pub done: bool,
+}
+
+struct ArchiveProgressSender {
+    sender: Sender<ArchiveProgress>,
+    operation: ArchiveOperation,
+    archive: String,
+    entries_processed: u64,
+}
+impl ArchiveProgressSender {
+    fn entry(&mut self, entry: &Path) {
+        self.entries_processed += 1;
+        self.send(Some(entry.to_string_lossy().to_string()), false);
+    }
+    fn done(&self) {
+        self.send(None, true);
+    }
+    fn send(&self, entry: Option<String>, done: bool) {
+        //No listeners is not an error, progress is best effort
+        let _ = self.sender.send(ArchiveProgress {
+            operation: self.operation,
+            archive: self.archive.clone(),
+            entry,
+            entries_processed: self.entries_processed,
+            done,
+        });
+    }
+}
+
+const ARCHIVE_PROGRESS_CAPACITY: usize = 256;
+
+#[derive(Debug)]
 pub struct FileManagerPlugin {
     problematic_paths: RwLock<Vec<PathBuf>>,
+    watches: RwLock<HashMap<String, NotifyHandle>>,
+    archive_progress: Sender<ArchiveProgress>,
+}
+impl Default for FileManagerPlugin {
+    fn default() -> Self {
+        Self {
+            problematic_paths: Default::default(),
+            watches: Default::default(),
+            archive_progress: tokio::sync::broadcast::channel(ARCHIVE_PROGRESS_CAPACITY).0,
+        }
+    }
 }
 impl FileManagerPlugin {
     pub fn new() -> FileManagerPlugin {
         FileManagerPlugin::default()
     }
+    pub fn subscribe_archive_progress(&self) -> Receiver<ArchiveProgress> {
+        self.archive_progress.subscribe()
+    }
+    pub async fn compress(
+        &self,
+        paths: Vec<PathBuf>,
+        destination: &Path,
+        format: ArchiveFormat,
+    ) -> Result<(), Error> {
+        if paths.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "No Paths Specified"));
+        }
+        let destination = destination.to_path_buf();
+        let mut progress = ArchiveProgressSender {
+            sender: self.archive_progress.clone(),
+            operation: ArchiveOperation::Compress,
+            archive: destination.to_string_lossy().to_string(),
+            entries_processed: 0,
+        };
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::create_new(&destination)?;
+            match format {
+                ArchiveFormat::Zip => compress_zip(file, &paths, &mut progress)?,
+                ArchiveFormat::TarGz => {
+                    let encoder =
+                        flate2::write::GzEncoder::new(file, flate2::Compression::default());
+                    compress_tar(encoder, &paths, &mut progress)?.finish()?;
+                }
+                ArchiveFormat::TarBz2 => {
+                    let encoder = bzip2::write::BzEncoder::new(file, bzip2::Compression::default());
+                    compress_tar(encoder, &paths, &mut progress)?.finish()?;
+                }
+            }
+            progress.done();
+            Ok(())
+        })
+        .await?
+    }
+    pub async fn decompress(&self, archive: &Path, destination: &Path) -> Result<(), Error> {
+        let format = ArchiveFormat::from_path(archive).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported Archive Format: {archive:?}"),
+            )
+        })?;
+        let archive = archive.to_path_buf();
+        let destination = destination.to_path_buf();
+        let mut progress = ArchiveProgressSender {
+            sender: self.archive_progress.clone(),
+            operation: ArchiveOperation::Extract,
+            archive: archive.to_string_lossy().to_string(),
+            entries_processed: 0,
+        };
+        tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(&destination)?;
+            let file = std::fs::File::open(&archive)?;
+            match format {
+                ArchiveFormat::Zip => extract_zip(file, &destination, &mut progress)?,
+                ArchiveFormat::TarGz => extract_tar(
+                    flate2::read::GzDecoder::new(file),
+                    &destination,
+                    &mut progress,
+                )?,
+                ArchiveFormat::TarBz2 => extract_tar(
+                    bzip2::read::BzDecoder::new(file),
+                    &destination,
+                    &mut progress,
+                )?,
+            }
+            progress.done();
+            Ok(())
+        })
+        .await?
+    }
     pub async fn list(&self, path: Option<&Path>) -> Result<Vec<FileEntry>, Error> {
         let user_path = path.unwrap_or(Path::new("/")).to_path_buf();
         let path = if user_path.starts_with("~") {
@@ -155,6 +610,219 @@ impl FileManagerPlugin {
         }
         Ok(true)
     }
+    pub async fn trash(&self, path: &Path) -> Result<String, Error> {
+        let original_path = tokio::fs::canonicalize(path).await?;
+        let file_name = original_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Cannot Trash Root Directory"))?;
+        let metadata_dir = trash_folder()?;
+        let trash_dir = trash_folder_for(&original_path).await?;
+        if original_path.starts_with(&metadata_dir) || original_path.starts_with(&trash_dir) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Path is already in the Trash",
+            ));
+        }
+        let trashed = OffsetDateTime::now_utc();
+        let trash_id = format!("{}-{file_name}", trashed.unix_timestamp_nanos());
+        let trash_path = trash_dir.join(&trash_id);
+        let item = TrashItem {
+            trash_id: trash_id.clone(),
+            original_path: original_path.to_string_lossy().to_string(),
+            trash_path: Some(trash_path.to_string_lossy().to_string()),
+            size: path_size(original_path.clone()).await?,
+            trashed,
+        };
+        move_path(&original_path, &trash_path).await?;
+        //The sidecar records where the item came from so it can be restored
+        tokio::fs::write(
+            metadata_dir.join(format!("{trash_id}.json")),
+            serde_json::to_vec(&item)?,
+        )
+        .await?;
+        Ok(trash_id)
+    }
+    pub async fn list_trash(&self) -> Result<Vec<TrashItem>, Error> {
+        let trash_dir = trash_folder()?;
+        let mut items = vec![];
+        let mut dir_entry = match tokio::fs::read_dir(&trash_dir).await {
+            Ok(dir_entry) => dir_entry,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(items),
+            Err(e) => return Err(e),
+        };
+        while let Some(entry) = dir_entry.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                match serde_json::from_slice::<TrashItem>(&tokio::fs::read(&path).await?) {
+                    Ok(item) => items.push(item),
+                    Err(e) => error!("Invalid Trash Metadata at {path:?}: {e}"),
+                }
+            }
+        }
+        items.sort_by_key(|item| item.trashed);
+        Ok(items)
+    }
+    pub async fn restore_trash(&self, trash_id: &str) -> Result<bool, Error> {
+        validate_trash_id(trash_id)?;
+        let metadata_dir = trash_folder()?;
+        let sidecar = metadata_dir.join(format!("{trash_id}.json"));
+        let item: TrashItem = serde_json::from_slice(&tokio::fs::read(&sidecar).await?)?;
+        let trashed_path = trashed_item_path(&metadata_dir, &item)?;
+        let original_path = PathBuf::from(&item.original_path);
+        let file_name = original_path
+            .file_name()
+            .filter(|_| original_path.is_absolute())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Invalid Original Path"))?;
+        let parent = original_path
+            .parent()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Invalid Original Path"))?;
+        tokio::fs::create_dir_all(parent).await?;
+        //Resolve the parent so a symlink swapped in since trashing cant redirect the restore
+        let parent = tokio::fs::canonicalize(parent).await?;
+        if parent.as_path() != parent_without_dots(&original_path) {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "{} resolves outside its original folder",
+                    item.original_path
+                ),
+            ));
+        }
+        let restore_path = parent.join(file_name);
+        if tokio::fs::try_exists(&restore_path).await? {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already exists", item.original_path),
+            ));
+        }
+        move_path(&trashed_path, &restore_path).await?;
+        tokio::fs::remove_file(sidecar).await?;
+        Ok(true)
+    }
+    pub async fn empty_trash(&self) -> Result<bool, Error> {
+        for item in self.list_trash().await? {
+            self.remove_trash_item(&item).await?;
+        }
+        Ok(true)
+    }
+    pub async fn prune_trash(&self, max_size_bytes: u64, keep: &str) -> Result<usize, Error> {
+        let items = self.list_trash().await?;
+        let mut total: u64 = items.iter().map(|item| item.size).sum();
+        let mut pruned = 0;
+        //Items are sorted oldest first, the item just trashed is never pruned
+        for item in items.into_iter().filter(|item| item.trash_id != keep) {
+            if total <= max_size_bytes {
+                break;
+            }
+            self.remove_trash_item(&item).await?;
+            total = total.saturating_sub(item.size);
+            pruned += 1;
+        }
+        Ok(pruned)
+    }
+    async fn remove_trash_item(&self, item: &TrashItem) -> Result<(), Error> {
+        let metadata_dir = trash_folder()?;
+        let trashed_path = trashed_item_path(&metadata_dir, item)?;
+        match tokio::fs::symlink_metadata(&trashed_path).await {
+            Ok(meta_data) if meta_data.is_dir() => tokio::fs::remove_dir_all(&trashed_path).await?,
+            Ok(_) => tokio::fs::remove_file(&trashed_path).await?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        tokio::fs::remove_file(metadata_dir.join(format!("{}.json", item.trash_id))).await
+    }
+    pub async fn set_permissions<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<(), Error> {
+        if mode > 0o7777 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid File Mode: {mode:o}"),
+            ));
+        }
+        tokio::fs::set_permissions(path, Permissions::from_mode(mode)).await
+    }
+    pub async fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<FileMetadata, Error> {
+        let meta_data = tokio::fs::metadata(path.as_ref()).await?;
+        Ok(FileMetadata {
+            path: path.as_ref().to_string_lossy().to_string(),
+            size: meta_data.len(),
+            mode: meta_data.permissions().mode() & 0o7777,
+            uid: meta_data.uid(),
+            gid: meta_data.gid(),
+            modified: meta_data.modified().ok().map(OffsetDateTime::from),
+            created: meta_data.created().ok().map(OffsetDateTime::from),
+        })
+    }
+    pub async fn watch(&self, path: &str) -> Result<Receiver<WatchEvent>, Error> {
+        let mut watches = self.watches.write().await;
+        if let Some(handle) = watches.get(path) {
+            return Ok(handle.sender.subscribe());
+        }
+        let (sender, receiver) = tokio::sync::broadcast::channel(128);
+        let event_sender = sender.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let events: Vec<WatchEvent> = match res {
+                Ok(event) => {
+                    let paths = event.paths.iter().map(|p| p.to_string_lossy().to_string());
+                    match event.kind {
+                        EventKind::Create(_) => {
+                            paths.map(|path| WatchEvent::Created { path }).collect()
+                        }
+                        EventKind::Modify(ModifyKind::Name(_)) => {
+                            paths.map(|path| WatchEvent::Renamed { path }).collect()
+                        }
+                        EventKind::Modify(_) => {
+                            paths.map(|path| WatchEvent::Modified { path }).collect()
+                        }
+                        EventKind::Remove(_) => {
+                            paths.map(|path| WatchEvent::Deleted { path }).collect()
+                        }
+                        _ => vec![],
+                    }
+                }
+                Err(e) => vec![WatchEvent::Error {
+                    message: format!("{e}"),
+                }],
+            };
+            for event in events {
+                //Only fails when there are no subscribers left, which is cleaned up on disconnect
+                let _ = event_sender.send(event);
+            }
+        })
+        .map_err(map_notify_error)?;
+        watcher
+            .watch(Path::new(path), RecursiveMode::NonRecursive)
+            .map_err(map_notify_error)?;
+        info!("Started File Watcher on {path}");
+        watches.insert(
+            path.to_string(),
+            NotifyHandle {
+                _watcher: watcher,
+                sender,
+            },
+        );
+        Ok(receiver)
+    }
+    pub async fn release_watch(&self, path: &str) {
+        let mut watches = self.watches.write().await;
+        if let Some(handle) = watches.get(path) {
+            if handle.sender.receiver_count() == 0 {
+                info!("Stopping File Watcher on {path}");
+                watches.remove(path);
+            }
+        }
+    }
+}
+
+fn map_notify_error(e: notify::Error) -> Error {
+    match e.kind {
+        notify::ErrorKind::MaxFilesWatch => {
+            Error::other("Inotify watch limit reached, increase fs.inotify.max_user_watches")
+        }
+        notify::ErrorKind::PathNotFound => Error::new(ErrorKind::NotFound, "Path not found"),
+        notify::ErrorKind::Io(io_error) => io_error,
+        _ => Error::other(format!("{e}")),
+    }
 }
 
 #[derive(Deserialize)]
@@ -266,6 +934,285 @@ pub async fn remove(
     }
 }
 
+#[post("/api/files/trash", output = "json", eoutput = "bytes")]
+pub async fn trash_file(
+    state: State<FileManagerPlugin>,
+    config: State<RwLock<ConfigManager>>,
+    params: Json<Option<DeleteParams>>,
+) -> Result<bool, Error> {
+    match params.inner() {
+        Some(params) => {
+            let trash_id = state.0.trash(Path::new(&params.path)).await?;
+            let max_size = config
+                .0
+                .read()
+                .await
+                .get_as::<u64>(TRASH_MAX_SIZE_CONFIG_KEY)
+                .await
+                .unwrap_or(DEFAULT_TRASH_MAX_SIZE_BYTES);
+            let pruned = state.0.prune_trash(max_size, &trash_id).await?;
+            if pruned > 0 {
+                info!("Pruned {pruned} items from the Trash");
+            }
+            Ok(true)
+        }
+        None => Err(Error::new(ErrorKind::InvalidInput, "No Path Specified")),
+    }
+}
+
+#[get("/api/files/trash", output = "json", eoutput = "bytes")]
+pub async fn list_trash(state: State<FileManagerPlugin>) -> Result<Vec<TrashItem>, Error> {
+    state.0.list_trash().await
+}
+
+#[derive(Deserialize)]
+pub struct RestoreParams {
+    trash_id: String,
+}
+
+#[post("/api/files/trash/restore", output = "json", eoutput = "bytes")]
+pub async fn restore_trash(
+    state: State<FileManagerPlugin>,
+    params: Json<Option<RestoreParams>>,
+) -> Result<bool, Error> {
+    match params.inner() {
+        Some(params) => state.0.restore_trash(&params.trash_id).await,
+        None => Err(Error::new(ErrorKind::InvalidInput, "No Trash Id Specified")),
+    }
+}
+
+#[delete("/api/files/trash", output = "json", eoutput = "bytes")]
+pub async fn empty_trash(state: State<FileManagerPlugin>) -> Result<bool, Error> {
+    state.0.empty_trash().await
+}
+
+#[derive(Deserialize)]
+pub struct CompressParams {
+    paths: Vec<PathBuf>,
+    destination: PathBuf,
+    format: ArchiveFormat,
+}
+
+#[post("/api/files/compress", output = "json", eoutput = "bytes")]
+pub async fn compress_files(
+    state: State<FileManagerPlugin>,
+    params: Json<Option<CompressParams>>,
+) -> Result<bool, Error> {
+    match params.inner() {
+        Some(params) => {
+            state
+                .0
+                .compress(params.paths, &params.destination, params.format)
+                .await?;
+            Ok(true)
+        }
+        None => Err(Error::new(ErrorKind::InvalidInput, "No Paths Specified")),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExtractParams {
+    archive: PathBuf,
+    destination: PathBuf,
+}
+
+#[post("/api/files/extract", output = "json", eoutput = "bytes")]
+pub async fn extract_archive(
+    state: State<FileManagerPlugin>,
+    params: Json<Option<ExtractParams>>,
+) -> Result<bool, Error> {
+    match params.inner() {
+        Some(params) => {
+            state
+                .0
+                .decompress(&params.archive, &params.destination)
+                .await?;
+            Ok(true)
+        }
+        None => Err(Error::new(ErrorKind::InvalidInput, "No Archive Specified")),
+    }
+}
+
+#[websocket("/api/files/archive/progress")]
+pub async fn archive_progress(
+    socket: WebSocket,
+    state: State<FileManagerPlugin>,
+) -> Result<(), Error> {
+    let mut receiver = state.0.subscribe_archive_progress();
+    loop {
+        tokio::select! {
+            result = receiver.recv() => {
+                match result {
+                    Ok(progress) => {
+                        let as_json = serde_json::to_string(&progress)?;
+                        if let Err(e) = socket.send(Message::Text(as_json.into())).await {
+                            debug!("Failed to send archive progress: {e:?}");
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(count)) => {
+                        debug!("Archive Progress skipped {count} events");
+                    }
+                    Err(RecvError::Closed) => {
+                        break;
+                    }
+                }
+            }
+            result = socket.next() => {
+                match result? {
+                    Some(Message::Ping(ping_data)) => {
+                        socket.send(Message::Pong(ping_data)).await?;
+                    }
+                    Some(Message::Close(_)) => break,
+                    Some(_) => {
+                        //Ignore Client Messages
+                        continue;
+                    }
+                    None => {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct PermissionParams {
+    path: String,
+    mode: u32,
+}
+
+#[post("/api/files/permissions", output = "json", eoutput = "bytes")]
+pub async fn set_permissions(
+    state: State<FileManagerPlugin>,
+    params: Json<Option<PermissionParams>>,
+) -> Result<(), Error> {
+    match params.inner() {
+        Some(params) => state.0.set_permissions(params.path, params.mode).await,
+        None => Err(Error::new(ErrorKind::InvalidInput, "No Path Specified")),
+    }
+}
+
+#[post("/api/files/metadata", output = "json", eoutput = "bytes")]
+pub async fn file_metadata(
+    state: State<FileManagerPlugin>,
+    params: Json<Option<FileParams>>,
+) -> Result<FileMetadata, Error> {
+    match params.inner() {
+        Some(params) => state.0.metadata(params.path).await,
+        None => Err(Error::new(ErrorKind::InvalidInput, "No Path Specified")),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WatchParams {
+    path: String,
+}
+
+#[websocket("/api/files/watch")]
+pub async fn watch_files(socket: WebSocket, state: State<FileManagerPlugin>) -> Result<(), Error> {
+    //The first Text message from the client selects the directory to watch
+    let path = loop {
+        match socket.next().await? {
+            Some(Message::Text(text)) => match serde_json::from_str::<WatchParams>(&text) {
+                Ok(params) => break params.path,
+                Err(e) => {
+                    let as_json = serde_json::to_string(&WatchEvent::Error {
+                        message: format!("Invalid Watch Params: {e}"),
+                    })?;
+                    socket.send(Message::Text(as_json.into())).await?;
+                    return Ok(());
+                }
+            },
+            Some(Message::Ping(ping_data)) => {
+                socket.send(Message::Pong(ping_data)).await?;
+            }
+            Some(Message::Close(_)) => return Ok(()),
+            Some(_) => continue,
+            None => {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    };
+    let mut receiver = match state.0.watch(&path).await {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            let as_json = serde_json::to_string(&WatchEvent::Error {
+                message: format!("{e}"),
+            })?;
+            socket.send(Message::Text(as_json.into())).await?;
+            return Ok(());
+        }
+    };
+    let mut err = None;
+    loop {
+        tokio::select! {
+            result = receiver.recv() => {
+                match result {
+                    Ok(event) => {
+                        //Errors break out of the loop so the watch is always released below
+                        let as_json = match serde_json::to_string(&event) {
+                            Ok(as_json) => as_json,
+                            Err(e) => {
+                                err = Some(e.into());
+                                break;
+                            }
+                        };
+                        if let Err(e) = socket.send(Message::Text(as_json.into())).await {
+                            debug!("Failed to send watch event: {e:?}");
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(count)) => {
+                        debug!("File Watcher for {path} skipped {count} events");
+                    }
+                    Err(RecvError::Closed) => {
+                        error!("File Watcher for {path} closed");
+                        break;
+                    }
+                }
+            }
+            result = socket.next() => {
+                match result {
+                    Ok(Some(msg)) => {
+                        match msg {
+                            Message::Ping(ping_data) => {
+                                if let Err(e) = socket.send(Message::Pong(ping_data)).await {
+                                    err = Some(e);
+                                    break;
+                                }
+                            }
+                            Message::Pong(_) | Message::Frame(_) |
+                            Message::Binary(_) | Message::Text(_) => {
+                                //Ignore Client Messages
+                                continue;
+                            }
+                            Message::Close(_close_msg) => {
+                                break;
+                            }
+                        }
+                    }
+                    Ok(None) => {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    },
+                    Err(e) => {
+                        err = Some(e);
+                        break
+                    },
+                }
+            }
+        }
+    }
+    drop(receiver);
+    state.0.release_watch(&path).await;
+    match err {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
 pub async fn is_fuse_filesystem(path: &str) -> Result<bool, Error> {
     const MOUNTS_FILE: &str = "/proc/mounts";
     let file = File::open(MOUNTS_FILE).await?;
@@ -285,3 +1232,33 @@ async fn test_fuse_detect() {
     let is_fuse_path = is_fuse_filesystem("/keybase").await;
     println!("{:?}", is_fuse_path);
 }
+
+#[test]
+fn test_archive_format_from_path() {
+    assert_eq!(
+        ArchiveFormat::from_path(Path::new("/tmp/archive.zip")),
+        Some(ArchiveFormat::Zip)
+    );
+    assert_eq!(
+        ArchiveFormat::from_path(Path::new("/tmp/archive.TAR.GZ")),
+        Some(ArchiveFormat::TarGz)
+    );
+    assert_eq!(
+        ArchiveFormat::from_path(Path::new("/tmp/archive.tbz2")),
+        Some(ArchiveFormat::TarBz2)
+    );
+    assert_eq!(
+        ArchiveFormat::from_path(Path::new("/tmp/archive.rar")),
+        None
+    );
+}
+
+#[test]
+fn test_validate_trash_id() {
+    assert!(validate_trash_id("1700000000-plot.plot").is_ok());
+    assert!(validate_trash_id("..").is_err());
+    assert!(validate_trash_id(".").is_err());
+    assert!(validate_trash_id("a/b").is_err());
+    assert!(validate_trash_id("/etc").is_err());
+    assert!(validate_trash_id("").is_err());
+}
@@ -0,0 +1,216 @@
+use crate::gpio::{detect_gpio_chips, EdgeType, GpioChipInfo, Pin, PinMode, PinSet};
+use gpiod::Chip;
+use log::{debug, error};
+use portfu::prelude::tokio_tungstenite::tungstenite::Message;
+use portfu::prelude::{serde_json, Path, State, WebSocket};
+use portfu_core::ServiceData;
+use portfu_macros::{get, websocket};
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+pub const DEFAULT_INPUT_POLL_MS: u64 = 100;
+pub const MIN_INPUT_POLL_MS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PinValueEvent {
+    pub pin: u32,
+    pub value: bool,
+    pub changed: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DetectionMode {
+    //Samples the pin at most once per interval, collapsing bursts of edges
+    PollingMs(u64),
+    //Reports every edge the kernel delivers, a client more than INPUT_EDGE_BUFFER edges behind
+    //skips the oldest ones
+    EdgeInterrupt,
+}
+
+pub struct GpioManagerPlugin {
+    chips: Vec<Chip>,
+    input_pins: RwLock<PinSet>,
+}
+impl GpioManagerPlugin {
+    pub async fn new() -> Self {
+        let mut chips = detect_gpio_chips().await.unwrap_or_else(|e| {
+            error!("Error while detect gpio chips: {e:?}");
+            vec![]
+        });
+        chips.sort_by(|my, other| my.name().cmp(other.name()));
+        Self {
+            chips,
+            input_pins: RwLock::new(PinSet::new()),
+        }
+    }
+    pub async fn watch_input(&self, offset: u32) -> Result<Pin, Error> {
+        self.input_pins
+            .write()
+            .await
+            .watch_input(&self.chips, offset)
+    }
+    pub async fn read_input(&self, offset: u32) -> Result<bool, Error> {
+        let input_pins = self.input_pins.read().await;
+        if let Some(watcher) = input_pins.get_input(offset) {
+            return Ok(watcher.value());
+        }
+        //Nothing is watching the pin, request the line just long enough to read it
+        pin_value(&Pin::new(&self.chips, offset, PinMode::Input)?)
+    }
+    pub fn chip_info(&self) -> Vec<GpioChipInfo> {
+        self.chips
+            .iter()
+            .enumerate()
+            .map(|(index, chip)| GpioChipInfo::from_chip(index, chip))
+            .collect()
+    }
+}
+
+fn parse_pin(pin: &str) -> Result<u32, Error> {
+    u32::from_str(pin).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid Pin Number: {e:?}"),
+        )
+    })
+}
+
+fn parse_detection_mode(query: Option<&str>) -> Result<DetectionMode, Error> {
+    let params: Vec<(&str, &str)> = query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+    };
+    let interval = param("interval_ms")
+        .map(|value| {
+            u64::from_str(value).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid interval_ms: {e:?}"),
+                )
+            })
+        })
+        .transpose()?;
+    //Default to edge detection unless the client explicitly asked for a polling interval
+    match (param("detection_mode"), interval) {
+        (Some("edge"), _) | (None, None) => Ok(DetectionMode::EdgeInterrupt),
+        (Some("polling"), _) | (None, Some(_)) => Ok(DetectionMode::PollingMs(
+            interval
+                .unwrap_or(DEFAULT_INPUT_POLL_MS)
+                .max(MIN_INPUT_POLL_MS),
+        )),
+        (Some(other), _) => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid detection_mode: {other}, expected edge or polling"),
+        )),
+    }
+}
+
+fn pin_value(pin: &Pin) -> Result<bool, Error> {
+    Ok(pin.get()?.first().copied().unwrap_or_default())
+}
+
+async fn next_pin_value(
+    pin: &mut Pin,
+    last_value: bool,
+    detection_mode: DetectionMode,
+) -> Result<bool, Error> {
+    match detection_mode {
+        DetectionMode::EdgeInterrupt => pin.wait_for_edge(EdgeType::Both).await,
+        DetectionMode::PollingMs(interval_ms) => loop {
+            tokio::time::sleep(Duration::from_millis(interval_ms)).await;
+            let value = pin_value(pin)?;
+            if value != last_value {
+                return Ok(value);
+            }
+        },
+    }
+}
+
+async fn send_pin_value(
+    socket: &WebSocket,
+    offset: u32,
+    value: bool,
+    changed: bool,
+) -> Result<(), Error> {
+    let as_json = serde_json::to_string(&PinValueEvent {
+        pin: offset,
+        value,
+        changed,
+    })?;
+    socket
+        .send(Message::Text(as_json.into()))
+        .await
+        .map_err(|e| Error::other(format!("Failed to send pin value: {e:?}")))
+}
+
+#[get("/gpio/chips", output = "json", eoutput = "bytes")]
+pub async fn get_gpio_chips(
+    gpio_manager: State<GpioManagerPlugin>,
+) -> Result<Vec<GpioChipInfo>, Error> {
+    Ok(gpio_manager.0.chip_info())
+}
+
+#[get("/gpio/pins/{pin}/value", output = "json", eoutput = "bytes")]
+pub async fn get_input_value(
+    pin: Path,
+    gpio_manager: State<GpioManagerPlugin>,
+) -> Result<bool, Error> {
+    let pin = parse_pin(&pin.inner())?;
+    gpio_manager.0.read_input(pin).await
+}
+
+#[websocket("/gpio/pins/{pin}/watch")]
+pub async fn watch_input_pin(
+    socket: WebSocket,
+    pin: Path,
+    data: &mut ServiceData,
+    gpio_manager: State<GpioManagerPlugin>,
+) -> Result<(), Error> {
+    let offset = parse_pin(&pin.inner())?;
+    let detection_mode = parse_detection_mode(data.request.request.uri().query())?;
+    let mut pin = gpio_manager.0.watch_input(offset).await?;
+    let mut last_value = pin_value(&pin)?;
+    //Start every watch with the current value so clients don't have to wait for an edge
+    if let Err(e) = send_pin_value(&socket, offset, last_value, false).await {
+        debug!("{e:?}");
+        return Ok(());
+    }
+    loop {
+        tokio::select! {
+            result = next_pin_value(&mut pin, last_value, detection_mode) => {
+                let value = result?;
+                last_value = value;
+                if let Err(e) = send_pin_value(&socket, offset, value, true).await {
+                    debug!("{e:?}");
+                    break;
+                }
+            }
+            result = socket.next() => {
+                match result? {
+                    Some(Message::Ping(ping_data)) => {
+                        socket.send(Message::Pong(ping_data)).await?;
+                    }
+                    Some(Message::Close(_)) => break,
+                    Some(_) => {
+                        //Ignore Client Messages
+                        continue;
+                    }
+                    None => {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
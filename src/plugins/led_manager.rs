@@ -1,21 +1,31 @@
 use crate::config::ConfigManager;
-use crate::gpio::{detect_gpio_chips, PinSet};
-use crate::models::config::AddConfigEntry;
+use crate::gpio::{detect_gpio_chips, PinSet, PwmUpdateBatch};
+use crate::models::config::{AddConfigEntry, ConfigEntry};
+use crate::plugins::farmer::{FarmerManager, FarmerStatus};
+use crate::plugins::system_monitor::SystemMonitorPlugin;
+use crate::utils::has_internet_connection;
 use gpiod::Chip;
 use log::{error, info, warn};
+use portfu::prelude::{serde_json, State};
+use portfu_macros::interval;
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use std::io::{Error, ErrorKind};
 use std::mem::replace;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
 #[derive(Default, Debug)]
 pub struct LedState {
     pub brightness: u8,
     pub mode: LedColorMode,
+    pub sequence_index: usize,
+    pub frame_started: Option<Instant>,
+    pub status_mode: bool,
+    pub manual_mode: Option<LedColorMode>,
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
@@ -25,10 +35,71 @@ pub enum PinColor {
     Blue,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TransitionType {
+    Instant,
+    LinearFade,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SequenceFrame {
+    pub color: LedColor,
+    pub duration_ms: u64,
+    pub transition: TransitionType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SystemStatus {
+    FarmerRunning,
+    FarmerStopped,
+    FarmerUpdating,
+    NoInternet,
+    HighTemp,
+}
+impl SystemStatus {
+    pub const DEFAULT_PRIORITY: [SystemStatus; 5] = [
+        SystemStatus::HighTemp,
+        SystemStatus::FarmerUpdating,
+        SystemStatus::NoInternet,
+        SystemStatus::FarmerStopped,
+        SystemStatus::FarmerRunning,
+    ];
+    //Pulse period for the status, None for a solid color
+    pub fn color(&self) -> (LedColor, Option<u64>) {
+        match self {
+            SystemStatus::FarmerRunning => (LedColor::GREEN, None),
+            SystemStatus::FarmerStopped => (LedColor::RED, None),
+            SystemStatus::FarmerUpdating => (LedColor::YELLOW, Some(STATUS_PULSE_PERIOD_US)),
+            SystemStatus::NoInternet => (LedColor::ORANGE, Some(STATUS_PULSE_PERIOD_US)),
+            SystemStatus::HighTemp => (LedColor::PURPLE, Some(STATUS_FAST_PULSE_PERIOD_US)),
+        }
+    }
+}
+impl FromStr for SystemStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "farmerrunning" => Ok(SystemStatus::FarmerRunning),
+            "farmerstopped" => Ok(SystemStatus::FarmerStopped),
+            "farmerupdating" => Ok(SystemStatus::FarmerUpdating),
+            "nointernet" => Ok(SystemStatus::NoInternet),
+            "hightemp" => Ok(SystemStatus::HighTemp),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid System Status: {s}"),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum LedColorMode {
     Pulse(LedColor, u64),
     Solid(LedColor),
+    Sequence(Vec<SequenceFrame>),
+    Status(SystemStatus),
+    ColorTemperature(u16),
 }
 impl Default for LedColorMode {
     fn default() -> Self {
@@ -37,6 +108,95 @@ impl Default for LedColorMode {
 }
 
 pub const DEFAULT_PWM_PERIOD_US: u64 = 20000;
+pub const MIN_PWM_PERIOD_US: u64 = 1000;
+pub const MAX_PWM_PERIOD_US: u64 = 100_000;
+pub const LED_PWM_PERIOD_CONFIG_KEY: &str = "led_pwm_period_us";
+pub const LED_SEQUENCE_CONFIG_KEY: &str = "led_sequence";
+pub const LED_COLOR_MODE_CONFIG_KEY: &str = "led_color_mode";
+pub const LED_BRIGHTNESS_CONFIG_KEY: &str = "led_brightness";
+pub const LED_PRESETS_CONFIG_KEY: &str = "led_presets";
+pub const LED_STATUS_PRIORITY_CONFIG_KEY: &str = "led_status_priority";
+pub const LED_HIGH_TEMP_CONFIG_KEY: &str = "led_high_temp_threshold";
+pub const LED_IDLE_TIMEOUT_CONFIG_KEY: &str = "led_idle_timeout_secs";
+pub const DEFAULT_HIGH_TEMP_THRESHOLD: u32 = 80;
+pub const STATUS_PULSE_PERIOD_US: u64 = 1_000_000;
+pub const STATUS_FAST_PULSE_PERIOD_US: u64 = 250_000;
+pub const WARM_WHITE: u16 = 2700;
+pub const NEUTRAL_WHITE: u16 = 4000;
+pub const COOL_WHITE: u16 = 6500;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LedPreset {
+    pub name: String,
+    pub mode: LedColorMode,
+}
+
+pub fn builtin_presets() -> Vec<LedPreset> {
+    [
+        ("OFF", LedColor::OFF),
+        ("WHITE", LedColor::WHITE),
+        ("RED", LedColor::RED),
+        ("GREEN", LedColor::GREEN),
+        ("BLUE", LedColor::BLUE),
+        ("PURPLE", LedColor::PURPLE),
+        ("YELLOW", LedColor::YELLOW),
+        ("ORANGE", LedColor::ORANGE),
+    ]
+    .into_iter()
+    .map(|(name, color)| LedPreset {
+        name: name.to_string(),
+        mode: LedColorMode::Solid(color),
+    })
+    .collect()
+}
+
+fn parse_saved_presets(entry: Option<&ConfigEntry>) -> Result<Vec<LedPreset>, Error> {
+    match entry {
+        Some(entry) => serde_json::from_str(&entry.value).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Failed to Parse Saved Presets: {e:?}"),
+            )
+        }),
+        None => Ok(vec![]),
+    }
+}
+
+pub fn validate_preset(preset: &LedPreset) -> Result<(), Error> {
+    if preset.name.trim().is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Preset name cannot be empty",
+        ));
+    }
+    if builtin_presets()
+        .iter()
+        .any(|p| p.name.eq_ignore_ascii_case(&preset.name))
+    {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} is a built in preset", preset.name),
+        ));
+    }
+    let has_off_color = match &preset.mode {
+        LedColorMode::Pulse(color, _) | LedColorMode::Solid(color) => *color == LedColor::OFF,
+        LedColorMode::Sequence(frames) => {
+            frames.is_empty() || frames.iter().any(|f| f.color == LedColor::OFF)
+        }
+        LedColorMode::Status(_) => false,
+        LedColorMode::ColorTemperature(kelvin) => {
+            validate_color_temperature(*kelvin)?;
+            false
+        }
+    };
+    if has_off_color {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Preset colors cannot be all zero, use the OFF preset instead",
+        ));
+    }
+    Ok(())
+}
 
 pub struct LedManager {
     state: LedState,
@@ -46,6 +206,9 @@ pub struct LedManager {
     pub blue_pins: PinSet,
     config_manager: Arc<RwLock<ConfigManager>>,
     db: SqlitePool,
+    last_activity: AtomicU64,
+    powered_off: Option<[Vec<u32>; 3]>,
+    pwm_period_us: u64,
 }
 impl LedManager {
     pub async fn init(
@@ -99,10 +262,39 @@ impl LedManager {
             }
             None => Default::default(),
         };
+        let mode = match config_manager
+            .read()
+            .await
+            .get(LED_COLOR_MODE_CONFIG_KEY)
+            .await
+        {
+            Some(entry) => serde_json::from_str(&entry.value).unwrap_or_else(|e| {
+                error!("Failed to Parse Saved Led Color Mode: {e:?}");
+                LedColorMode::Solid(LedColor::OFF)
+            }),
+            None => LedColorMode::Solid(LedColor::OFF),
+        };
+        let brightness = config_manager
+            .read()
+            .await
+            .get_as::<u8>(LED_BRIGHTNESS_CONFIG_KEY)
+            .await
+            .unwrap_or(255);
+        let pwm_period_us = config_manager
+            .read()
+            .await
+            .get_as::<u64>(LED_PWM_PERIOD_CONFIG_KEY)
+            .await
+            .unwrap_or(DEFAULT_PWM_PERIOD_US)
+            .clamp(MIN_PWM_PERIOD_US, MAX_PWM_PERIOD_US);
         let mut slf = Self {
             state: LedState {
-                brightness: 255,
-                mode: LedColorMode::Solid(LedColor::OFF),
+                brightness,
+                mode,
+                sequence_index: 0,
+                frame_started: Some(Instant::now()),
+                status_mode: false,
+                manual_mode: None,
             },
             red_pins,
             green_pins,
@@ -110,29 +302,291 @@ impl LedManager {
             chips,
             config_manager,
             db,
+            last_activity: AtomicU64::new(now_secs()),
+            powered_off: None,
+            pwm_period_us,
         };
         slf.sync_state().await;
         Ok(slf)
     }
     pub async fn set_color_mode(&mut self, mode: LedColorMode) {
+        self.power_on().await;
+        match serde_json::to_string(&mode) {
+            Ok(value) => {
+                if let Err(e) = self
+                    .save_config_value(LED_COLOR_MODE_CONFIG_KEY, value)
+                    .await
+                {
+                    error!("Failed to save Led Color Mode: {e:?}");
+                }
+            }
+            Err(e) => error!("Failed to serialize Led Color Mode: {e:?}"),
+        }
+        self.apply_mode(mode).await;
+    }
+    async fn apply_mode(&mut self, mode: LedColorMode) {
         self.state.mode = mode;
+        self.state.sequence_index = 0;
+        self.state.frame_started = Some(Instant::now());
         self.sync_state().await;
     }
+    async fn save_config_value(&self, key: &str, value: String) -> Result<(), Error> {
+        let mut config_manager = self.config_manager.write().await;
+        let last_value = config_manager
+            .get(key)
+            .await
+            .map(|e| e.value)
+            .unwrap_or_default();
+        config_manager
+            .set(
+                key,
+                AddConfigEntry {
+                    key: key.to_string(),
+                    value,
+                    last_value,
+                    category: "led-settings".to_string(),
+                    system: 0,
+                },
+                Some(&self.db),
+            )
+            .await?;
+        Ok(())
+    }
+    pub async fn set_sequence(&mut self, frames: Vec<SequenceFrame>) -> Result<(), Error> {
+        if frames.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Sequence must contain at least one frame",
+            ));
+        }
+        if frames.iter().any(|f| f.duration_ms == 0) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Sequence frame durations must be greater than 0",
+            ));
+        }
+        self.save_config_value(LED_SEQUENCE_CONFIG_KEY, serde_json::to_string(&frames)?)
+            .await?;
+        self.set_color_mode(LedColorMode::Sequence(frames)).await;
+        Ok(())
+    }
+    pub async fn get_sequence(&self) -> Result<Option<Vec<SequenceFrame>>, Error> {
+        if let LedColorMode::Sequence(frames) = &self.state.mode {
+            return Ok(Some(frames.clone()));
+        }
+        match self
+            .config_manager
+            .read()
+            .await
+            .get(LED_SEQUENCE_CONFIG_KEY)
+            .await
+        {
+            Some(entry) => serde_json::from_str(&entry.value).map(Some).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Failed to Parse Saved Sequence: {e:?}"),
+                )
+            }),
+            None => Ok(None),
+        }
+    }
+    async fn saved_presets(&self) -> Result<Vec<LedPreset>, Error> {
+        let entry = self
+            .config_manager
+            .read()
+            .await
+            .get(LED_PRESETS_CONFIG_KEY)
+            .await;
+        parse_saved_presets(entry.as_ref())
+    }
+    //The config write lock is held from the read to the write so concurrent edits aren't lost
+    async fn update_presets(
+        &self,
+        update: impl FnOnce(&mut Vec<LedPreset>) -> bool,
+    ) -> Result<bool, Error> {
+        let mut config_manager = self.config_manager.write().await;
+        let entry = config_manager.get(LED_PRESETS_CONFIG_KEY).await;
+        let mut presets = parse_saved_presets(entry.as_ref())?;
+        if !update(&mut presets) {
+            return Ok(false);
+        }
+        config_manager
+            .set(
+                LED_PRESETS_CONFIG_KEY,
+                AddConfigEntry {
+                    key: LED_PRESETS_CONFIG_KEY.to_string(),
+                    value: serde_json::to_string(&presets)?,
+                    last_value: entry.map(|e| e.value).unwrap_or_default(),
+                    category: "led-settings".to_string(),
+                    system: 0,
+                },
+                Some(&self.db),
+            )
+            .await?;
+        Ok(true)
+    }
+    pub async fn get_presets(&self) -> Result<Vec<LedPreset>, Error> {
+        let mut presets = builtin_presets();
+        presets.extend(self.saved_presets().await?);
+        Ok(presets)
+    }
+    pub async fn save_preset(&self, preset: LedPreset) -> Result<(), Error> {
+        validate_preset(&preset)?;
+        self.update_presets(|presets| {
+            match presets.iter_mut().find(|p| p.name == preset.name) {
+                Some(existing) => *existing = preset,
+                None => presets.push(preset),
+            }
+            true
+        })
+        .await
+        .map(|_| ())
+    }
+    pub async fn delete_preset(&self, name: &str) -> Result<bool, Error> {
+        if builtin_presets().iter().any(|p| p.name == name) {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Built in preset {name} cannot be deleted"),
+            ));
+        }
+        self.update_presets(|presets| {
+            let count = presets.len();
+            presets.retain(|p| p.name != name);
+            presets.len() != count
+        })
+        .await
+    }
+    pub async fn apply_preset(&mut self, name: &str) -> Result<(), Error> {
+        match self
+            .get_presets()
+            .await?
+            .into_iter()
+            .find(|p| p.name == name)
+        {
+            Some(preset) => {
+                self.set_color_mode(preset.mode).await;
+                Ok(())
+            }
+            None => Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Preset {name} not found"),
+            )),
+        }
+    }
+    pub fn is_status_mode(&self) -> bool {
+        self.state.status_mode
+    }
+    pub async fn set_status_mode(&mut self, enabled: bool) {
+        self.power_on().await;
+        if enabled == self.state.status_mode {
+            return;
+        }
+        self.state.status_mode = enabled;
+        if enabled {
+            self.state.manual_mode = Some(self.state.mode.clone());
+        } else if let Some(mode) = self.state.manual_mode.take() {
+            self.apply_mode(mode).await;
+        }
+    }
+    pub async fn set_status(&mut self, status: SystemStatus) {
+        if !self.state.status_mode {
+            return;
+        }
+        if !matches!(self.state.mode, LedColorMode::Status(current) if current == status) {
+            info!("Setting LED Status to {status:?}");
+            self.apply_mode(LedColorMode::Status(status)).await;
+        }
+    }
+    pub fn has_sequence(&self) -> bool {
+        matches!(self.state.mode, LedColorMode::Sequence(_))
+    }
+    pub async fn advance_sequence(&mut self) {
+        let (frame_count, duration_ms, transition) = match &self.state.mode {
+            LedColorMode::Sequence(frames) => match frames.get(self.state.sequence_index) {
+                Some(frame) => (frames.len(), frame.duration_ms, frame.transition),
+                None => return,
+            },
+            _ => return,
+        };
+        let started = *self.state.frame_started.get_or_insert_with(Instant::now);
+        if started.elapsed() >= Duration::from_millis(duration_ms) {
+            self.state.sequence_index = (self.state.sequence_index + 1) % frame_count;
+            self.state.frame_started = Some(Instant::now());
+            self.sync_state().await;
+        } else if transition == TransitionType::LinearFade {
+            self.sync_state().await;
+        }
+    }
     pub async fn set_brightness(&mut self, brightness: u8) {
+        self.power_on().await;
+        if let Err(e) = self
+            .save_config_value(LED_BRIGHTNESS_CONFIG_KEY, brightness.to_string())
+            .await
+        {
+            error!("Failed to save Led Brightness: {e:?}");
+        }
         self.state.brightness = brightness;
         self.sync_state().await;
     }
     pub fn get_brightness(&self) -> u8 {
         self.state.brightness
     }
+    pub async fn set_pwm_period(&mut self, period_us: u64) -> u64 {
+        let period_us = period_us.clamp(MIN_PWM_PERIOD_US, MAX_PWM_PERIOD_US);
+        if let Err(e) = self
+            .save_config_value(LED_PWM_PERIOD_CONFIG_KEY, period_us.to_string())
+            .await
+        {
+            error!("Failed to save Led PWM Period: {e:?}");
+        }
+        self.pwm_period_us = period_us;
+        self.sync_state().await;
+        period_us
+    }
+    pub fn get_pwm_period(&self) -> u64 {
+        self.pwm_period_us
+    }
     async fn sync_state(&mut self) {
         let (color, period_duration) = match &self.state.mode {
-            LedColorMode::Pulse(color, period) => (color, Duration::from_micros(*period)),
-            LedColorMode::Solid(color) => (color, Duration::from_micros(DEFAULT_PWM_PERIOD_US)),
+            LedColorMode::Pulse(color, period) => (*color, Duration::from_micros(*period)),
+            LedColorMode::Solid(color) => (*color, Duration::from_micros(self.pwm_period_us)),
+            LedColorMode::ColorTemperature(kelvin) => (
+                kelvin_to_rgb(*kelvin),
+                Duration::from_micros(self.pwm_period_us),
+            ),
+            LedColorMode::Sequence(frames) => {
+                let index = self.state.sequence_index;
+                let color = match frames.get(index) {
+                    Some(frame) => match frame.transition {
+                        TransitionType::Instant => frame.color,
+                        TransitionType::LinearFade => {
+                            let previous = frames[(index + frames.len() - 1) % frames.len()].color;
+                            let progress = self
+                                .state
+                                .frame_started
+                                .map(|s| {
+                                    s.elapsed().as_millis() as f32 / frame.duration_ms.max(1) as f32
+                                })
+                                .unwrap_or_default();
+                            previous.fade_to(&frame.color, progress)
+                        }
+                    },
+                    None => LedColor::OFF,
+                };
+                (color, Duration::from_micros(self.pwm_period_us))
+            }
+            LedColorMode::Status(status) => {
+                let (color, period) = status.color();
+                (
+                    color,
+                    Duration::from_micros(period.unwrap_or(self.pwm_period_us)),
+                )
+            }
         };
-        let red_duty = Duration::from_micros(get_duty(color.r, self.state.brightness));
-        let green_duty = Duration::from_micros(get_duty(color.g, self.state.brightness));
-        let blue_duty = Duration::from_micros(get_duty(color.b, self.state.brightness));
+        let (brightness, pwm_period_us) = (self.state.brightness, self.pwm_period_us);
+        let red_duty = Duration::from_micros(get_duty(color.r, brightness, pwm_period_us));
+        let green_duty = Duration::from_micros(get_duty(color.g, brightness, pwm_period_us));
+        let blue_duty = Duration::from_micros(get_duty(color.b, brightness, pwm_period_us));
         for (pin, signal_handle) in self.red_pins.pins().iter_mut() {
             if signal_handle.signal_thread.is_finished() {
                 warn!("Signal Thread is Finished for Pin: {}", *pin);
@@ -148,7 +602,6 @@ impl LedManager {
                     }
                 }
             }
-            signal_handle.set_pwm(period_duration, red_duty);
         }
         for (pin, signal_handle) in self.green_pins.pins().iter_mut() {
             if signal_handle.signal_thread.is_finished() {
@@ -165,7 +618,6 @@ impl LedManager {
                     }
                 }
             }
-            signal_handle.set_pwm(period_duration, green_duty);
         }
         for (pin, signal_handle) in self.blue_pins.pins().iter_mut() {
             if signal_handle.signal_thread.is_finished() {
@@ -182,10 +634,25 @@ impl LedManager {
                     }
                 }
             }
-            signal_handle.set_pwm(period_duration, blue_duty);
+        }
+        //Queued as one batch so a color change doesn't show the channels switching one by one
+        let batch = PwmUpdateBatch::new();
+        for (pins, duty) in [
+            (&self.red_pins, red_duty),
+            (&self.green_pins, green_duty),
+            (&self.blue_pins, blue_duty),
+        ] {
+            let settings = pins
+                .offsets()
+                .map(|offset| (offset, period_duration, duty))
+                .collect();
+            if let Err(e) = pins.queue_pwm(&batch, settings) {
+                error!("Failed to Set PWM: {e:?}");
+            }
         }
     }
     pub async fn set_pin_mode(&mut self, pin: u32, mode: PinColor) {
+        self.power_on().await;
         let existing_value = self
             .red_pins
             .take(pin)
@@ -368,6 +835,42 @@ impl LedManager {
         }
         Ok(())
     }
+    pub fn idle_secs(&self) -> u64 {
+        now_secs().saturating_sub(self.last_activity.load(Ordering::Relaxed))
+    }
+    pub fn is_powered_off(&self) -> bool {
+        self.powered_off.is_some()
+    }
+    pub async fn power_off(&mut self) -> Result<(), Error> {
+        if self.powered_off.is_some() {
+            return Ok(());
+        }
+        self.stop_all().await?;
+        self.powered_off = Some([
+            release_pins(&mut self.red_pins).await,
+            release_pins(&mut self.green_pins).await,
+            release_pins(&mut self.blue_pins).await,
+        ]);
+        info!("LEDs Powered Off");
+        Ok(())
+    }
+    async fn power_on(&mut self) {
+        self.last_activity.store(now_secs(), Ordering::Relaxed);
+        if let Some([red, green, blue]) = self.powered_off.take() {
+            info!("LEDs Powered On");
+            for (pins, offsets) in [
+                (&mut self.red_pins, red),
+                (&mut self.green_pins, green),
+                (&mut self.blue_pins, blue),
+            ] {
+                for pin in offsets {
+                    if let Err(e) = pins.get_or_init(&self.chips, pin).await {
+                        error!("Failed to Init Pin {pin}: {e:?}")
+                    }
+                }
+            }
+        }
+    }
     pub async fn clear(&mut self) -> Result<(), Error> {
         self.stop_all().await?;
         self.red_pins.pins().clear();
@@ -405,7 +908,7 @@ impl Drop for LedManager {
     }
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub struct LedColor {
     pub r: u8,
     pub g: u8,
@@ -436,9 +939,216 @@ impl LedColor {
         g: 80,
         b: 0,
     };
+    pub fn fade_to(&self, other: &LedColor, progress: f32) -> LedColor {
+        let progress = progress.clamp(0f32, 1f32);
+        let lerp = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * progress) as u8;
+        LedColor {
+            r: lerp(self.r, other.r),
+            g: lerp(self.g, other.g),
+            b: lerp(self.b, other.b),
+        }
+    }
+}
+
+pub fn validate_color_temperature(kelvin: u16) -> Result<(), Error> {
+    if !(WARM_WHITE..=COOL_WHITE).contains(&kelvin) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Color Temperature must be between {WARM_WHITE}K and {COOL_WHITE}K"),
+        ));
+    }
+    Ok(())
+}
+
+//Tanner Helland's approximation of black body color
+pub fn kelvin_to_rgb(kelvin: u16) -> LedColor {
+    let temp = kelvin.clamp(WARM_WHITE, COOL_WHITE) as f64 / 100.0;
+    let red = if temp <= 66.0 {
+        255.0
+    } else {
+        329.698727446 * (temp - 60.0).powf(-0.1332047592)
+    };
+    let green = if temp <= 66.0 {
+        99.4708025861 * temp.ln() - 161.1195681661
+    } else {
+        288.1221695283 * (temp - 60.0).powf(-0.0755148492)
+    };
+    let blue = if temp >= 66.0 {
+        255.0
+    } else if temp <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * (temp - 10.0).ln() - 305.0447927307
+    };
+    LedColor {
+        r: red.clamp(0.0, 255.0) as u8,
+        g: green.clamp(0.0, 255.0) as u8,
+        b: blue.clamp(0.0, 255.0) as u8,
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+//Waits for the stopped pin threads to exit so the lines are left low and can be requested again
+async fn release_pins(pins: &mut PinSet) -> Vec<u32> {
+    let mut offsets = vec![];
+    for (offset, handler) in pins.pins().drain() {
+        match handler.signal_thread.await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!("Error stopping pin thread: {e:?}"),
+            Err(e) => error!("Error joining pin thread: {e:?}"),
+        }
+        if let Err(e) = handler.pin.set(false) {
+            error!("Failed to set Pin {offset} low: {e:?}");
+        }
+        offsets.push(offset);
+    }
+    offsets
+}
+
+pub fn get_duty(color_value: u8, intensity: u8, period_us: u64) -> u64 {
+    ((color_value as u64 * period_us) as f32 / 255f32 * (intensity as f32 / 255f32)) as u64
+}
+
+#[interval(50)]
+pub async fn led_sequence_tick(led_manager: State<RwLock<LedManager>>) -> Result<(), Error> {
+    if led_manager.0.read().await.has_sequence() {
+        led_manager.0.write().await.advance_sequence().await;
+    }
+    Ok(())
+}
+
+#[interval(15_000)]
+pub async fn led_idle_monitor(
+    led_manager: State<RwLock<LedManager>>,
+    config_manager: State<RwLock<ConfigManager>>,
+) -> Result<(), Error> {
+    let timeout = config_manager
+        .0
+        .read()
+        .await
+        .get_as::<u64>(LED_IDLE_TIMEOUT_CONFIG_KEY)
+        .await
+        .unwrap_or_default();
+    if timeout == 0 {
+        return Ok(());
+    }
+    let idle_secs = {
+        let led_manager = led_manager.0.read().await;
+        if led_manager.is_powered_off() {
+            return Ok(());
+        }
+        led_manager.idle_secs()
+    };
+    if idle_secs >= timeout {
+        warn!("No LED activity for {idle_secs}s, turning LEDs off");
+        led_manager.0.write().await.power_off().await?;
+    }
+    Ok(())
+}
+
+#[interval(15_000)]
+pub async fn led_status_monitor(
+    led_manager: State<RwLock<LedManager>>,
+    config_manager: State<RwLock<ConfigManager>>,
+    farmer_manager: State<FarmerManager>,
+    system_monitor: State<SystemMonitorPlugin>,
+) -> Result<(), Error> {
+    if !led_manager.0.read().await.is_status_mode() {
+        return Ok(());
+    }
+    let (priority, high_temp) = {
+        let config_manager = config_manager.0.read().await;
+        let priority = match config_manager.get(LED_STATUS_PRIORITY_CONFIG_KEY).await {
+            Some(entry) => entry
+                .value
+                .split(",")
+                .filter_map(|v| match SystemStatus::from_str(v) {
+                    Ok(status) => Some(status),
+                    Err(e) => {
+                        warn!("{e}");
+                        None
+                    }
+                })
+                .collect(),
+            None => SystemStatus::DEFAULT_PRIORITY.to_vec(),
+        };
+        let high_temp = config_manager
+            .get_as::<u32>(LED_HIGH_TEMP_CONFIG_KEY)
+            .await
+            .unwrap_or(DEFAULT_HIGH_TEMP_THRESHOLD);
+        (priority, high_temp)
+    };
+    let mut active = vec![];
+    if farmer_manager.0.is_updating() {
+        active.push(SystemStatus::FarmerUpdating);
+    }
+    match farmer_manager.0.farmer_status().await {
+        FarmerStatus::Running => active.push(SystemStatus::FarmerRunning),
+        FarmerStatus::Stopped | FarmerStatus::Exited(_) | FarmerStatus::Unknown => {
+            active.push(SystemStatus::FarmerStopped)
+        }
+    }
+    if !has_internet_connection().await {
+        active.push(SystemStatus::NoInternet);
+    }
+    match system_monitor.0.get_max_temperature().await {
+        Ok(temp) => {
+            if temp >= high_temp {
+                active.push(SystemStatus::HighTemp);
+            }
+        }
+        Err(e) => warn!("Failed to read system temperature: {e:?}"),
+    }
+    if let Some(status) = priority.into_iter().find(|s| active.contains(s)) {
+        led_manager.0.write().await.set_status(status).await;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_led_color_mode_round_trip() {
+    let modes = vec![
+        LedColorMode::Solid(LedColor::PURPLE),
+        LedColorMode::Pulse(LedColor::ORANGE, STATUS_PULSE_PERIOD_US),
+        LedColorMode::Sequence(vec![
+            SequenceFrame {
+                color: LedColor::RED,
+                duration_ms: 500,
+                transition: TransitionType::Instant,
+            },
+            SequenceFrame {
+                color: LedColor::BLUE,
+                duration_ms: 1500,
+                transition: TransitionType::LinearFade,
+            },
+        ]),
+        LedColorMode::Status(SystemStatus::HighTemp),
+        LedColorMode::ColorTemperature(NEUTRAL_WHITE),
+    ];
+    for mode in modes {
+        let as_json = serde_json::to_string(&mode).expect("Expected Mode to Serialize");
+        let parsed: LedColorMode =
+            serde_json::from_str(&as_json).expect("Expected Mode to Deserialize");
+        assert_eq!(as_json, serde_json::to_string(&parsed).unwrap());
+    }
 }
 
-pub fn get_duty(color_value: u8, intensity: u8) -> u64 {
-    ((color_value as u64 * DEFAULT_PWM_PERIOD_US) as f32 / 255f32 * (intensity as f32 / 255f32))
-        as u64
+#[test]
+fn test_kelvin_to_rgb() {
+    let warm = kelvin_to_rgb(WARM_WHITE);
+    assert_eq!(warm.r, 255);
+    assert!(warm.g > warm.b);
+    let cool = kelvin_to_rgb(COOL_WHITE);
+    assert_eq!(cool.r, 255);
+    assert!(cool.g > 240 && cool.b > 240);
+    assert_eq!(kelvin_to_rgb(1000), warm);
+    assert!(validate_color_temperature(NEUTRAL_WHITE).is_ok());
+    assert!(validate_color_temperature(2000).is_err());
+    assert!(validate_color_temperature(7000).is_err());
 }
@@ -1,33 +1,50 @@
 pub mod disk_management;
 pub mod farmer;
 pub mod file_manager;
+pub mod gpio_manager;
 pub mod led_manager;
+pub mod resources;
 pub mod system_monitor;
-use crate::database::plugins::{create_plugin, delete_plugin, get_all_plugins};
+use crate::database::config::get_config_key;
+use crate::database::events::{log_event, EventSeverity};
+use crate::database::plugins::{
+    create_plugin, delete_plugin, get_all_plugins, increment_plugin_download_count,
+};
 use crate::models::plugins::{AddPlugin, Plugin, PluginType};
+use crate::plugins::resources::{
+    apply_plugin_limits, create_plugin_cgroup, init_plugin_cgroup, read_cgroup_usage,
+    remove_plugin_cgroup, spawn_in_cgroup, PluginResourceUsage, ResourcePolicy,
+};
 use crate::version;
+use crate::web::notifications::WebhookNotifier;
 use bollard::container::{Config, CreateContainerOptions, ListContainersOptions};
 use bollard::image::CreateImageOptions;
 use bollard::service::{HostConfig, PortBinding};
 use bollard::Docker;
 use log::{error, info, warn};
 use portfu::prelude::futures_util::StreamExt;
+use portfu::prelude::serde_json::json;
+use portfu::prelude::{serde_json, State};
+use portfu_macros::interval;
 use semver::Version;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::SqlitePool;
 use std::collections::hash_map::{Entry, VacantEntry};
 use std::collections::HashMap;
+use std::env;
 use std::fs::Permissions;
 use std::io::{Error, ErrorKind};
 use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use time::OffsetDateTime;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::process::Command;
-use tokio::select;
+use tokio::process::{Child, Command};
+use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -64,18 +81,102 @@ pub struct StorePlugin {
     pub version: String,
     pub updated: String,
     pub past_versions: Vec<PastStorePlugin>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub download_count: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PluginUpdateAvailability {
+    pub has_update: bool,
+    pub current_version: String,
+    pub new_version: Option<String>,
+}
+
+const PLUGIN_AUTOSTART_ENV: &str = "DG_PLUGIN_AUTOSTART";
+const DEFAULT_PLUGIN_STORE_TTL: Duration = Duration::from_secs(60 * 60);
+pub const DEFAULT_PLUGIN_STORE_URL: &str = "https://plugins.druid.garden/plugins.yaml";
+pub const PLUGIN_STORE_URL_CONFIG_KEY: &str = "plugin_store_url";
+
+pub fn validate_plugin_store_url(url: &str) -> Result<(), Error> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid Plugin Store Url: {e}"),
+        )
+    })?;
+    match parsed.scheme() {
+        "http" | "https" | "file" => Ok(()),
+        scheme => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unsupported Plugin Store Url scheme: {scheme}"),
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginStoreInfo {
+    pub url: String,
+    pub last_update: Option<u64>,
+}
+
+fn now_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
 }
 
 pub struct RuntimeMetadata {
     pub run: Option<Arc<AtomicBool>>,
     pub join_handle: Option<JoinHandle<Result<(), Error>>>,
     pub started: Arc<OffsetDateTime>,
+    //Set when the plugin was spawned into its own cgroup under the plugin parent
+    pub cgroup: Option<PathBuf>,
 }
 
 pub enum PluginRuntime {
     BuiltIn,
     Docker(RuntimeMetadata),
-    File(RuntimeMetadata),
+    //Compose plugins keep the compose file so they can be stopped and restarted
+    DockerCompose(RuntimeMetadata, PathBuf),
+    //File plugins run in their own process group so children they fork are stopped with them
+    ProcessGroup(RuntimeMetadata, Arc<PluginProcess>),
+}
+
+//The group leader stays unreaped until try_wait sees it exit, so while it is running
+//its pid (which is also the pgid) can't be reused by another process
+pub struct PluginProcess {
+    pub pgid: u32,
+    child: Mutex<Child>,
+}
+impl PluginProcess {
+    pub fn try_wait(&self) -> Result<Option<ExitStatus>, Error> {
+        self.child
+            .lock()
+            .map_err(|_| Error::other("Plugin Process Lock Poisoned"))?
+            .try_wait()
+    }
+    pub fn is_running(&self) -> bool {
+        matches!(self.try_wait(), Ok(None))
+    }
+    //Once the leader has exited the pgid is no longer known to be ours, so nothing is signalled
+    fn signal(&self, signal: libc::c_int) -> bool {
+        if !self.is_running() {
+            return false;
+        }
+        unsafe { libc::killpg(self.pgid as libc::pid_t, signal) == 0 }
+    }
+}
+
+pub const MAX_PLUGIN_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+//How finish_restart brings back a plugin stopped by begin_restart
+pub enum RestartStage {
+    DockerContainer,
+    DockerCompose(PathBuf),
+    Process,
 }
 
 pub struct PluginManager {
@@ -84,16 +185,49 @@ pub struct PluginManager {
     plugin_runtimes: HashMap<String, PluginRuntime>,
     available_plugins: HashMap<String, StorePlugin>,
     start_time: Arc<OffsetDateTime>,
+    plugin_store_ttl: Duration,
+    plugin_store_cache: PathBuf,
+    last_store_update: AtomicU64,
+    plugin_store_url: String,
+    database: SqlitePool,
+    resource_policy: ResourcePolicy,
+    plugin_cgroup: Option<PathBuf>,
+    notifier: Arc<WebhookNotifier>,
 }
 impl PluginManager {
-    pub async fn new(db: &SqlitePool, bin_folder: PathBuf) -> Self {
+    pub async fn new(
+        db: &SqlitePool,
+        bin_folder: PathBuf,
+        plugin_store_cache: PathBuf,
+        notifier: Arc<WebhookNotifier>,
+    ) -> Self {
         let plugins = get_all_plugins(db).await.unwrap_or_default();
+        //Builtin Plugins are recreated below, keep any enabled flag that was saved for them
+        let saved_builtins: HashMap<String, i64> = plugins
+            .iter()
+            .filter(|p| matches!(p.plugin_type, PluginType::BuiltIn))
+            .map(|p| (p.name.clone(), p.enabled))
+            .collect();
+        let plugin_store_url = match get_config_key(db, PLUGIN_STORE_URL_CONFIG_KEY).await {
+            Ok(Some(entry)) if !entry.value.is_empty() => entry.value,
+            _ => DEFAULT_PLUGIN_STORE_URL.to_string(),
+        };
+        let resource_policy = ResourcePolicy::load(db).await;
+        let plugin_cgroup = init_plugin_cgroup(&resource_policy).await;
         let mut manager = Self {
             bin_folder,
             plugins: HashMap::from_iter(plugins.into_iter().map(|v| (v.name.clone(), v))),
             plugin_runtimes: Default::default(),
             available_plugins: Default::default(),
             start_time: Arc::new(OffsetDateTime::now_utc()),
+            plugin_store_ttl: DEFAULT_PLUGIN_STORE_TTL,
+            plugin_store_cache,
+            last_store_update: AtomicU64::new(0),
+            plugin_store_url,
+            database: db.clone(),
+            resource_policy,
+            plugin_cgroup,
+            notifier,
         };
         manager.update_plugin_store().await.ok().unwrap_or_default();
         //Install the builtin Plugins
@@ -110,6 +244,7 @@ impl PluginManager {
                 source: "".to_string(),
                 run_command: None,
                 version: version().to_string(),
+                download_count: 0,
                 added: OffsetDateTime::now_utc(),
                 updated: OffsetDateTime::now_utc(),
             },
@@ -130,6 +265,7 @@ impl PluginManager {
                 source: "".to_string(),
                 run_command: None,
                 version: version().to_string(),
+                download_count: 0,
                 added: OffsetDateTime::now_utc(),
                 updated: OffsetDateTime::now_utc(),
             },
@@ -151,6 +287,7 @@ impl PluginManager {
                 source: "".to_string(),
                 run_command: None,
                 version: version().to_string(),
+                download_count: 0,
                 added: OffsetDateTime::now_utc(),
                 updated: OffsetDateTime::now_utc(),
             },
@@ -172,6 +309,7 @@ impl PluginManager {
                 source: "".to_string(),
                 run_command: None,
                 version: version().to_string(),
+                download_count: 0,
                 added: OffsetDateTime::now_utc(),
                 updated: OffsetDateTime::now_utc(),
             },
@@ -179,11 +317,44 @@ impl PluginManager {
         manager
             .plugin_runtimes
             .insert("farmer_manager".to_string(), PluginRuntime::BuiltIn);
+        for plugin in manager.plugins.values_mut() {
+            if let Some(enabled) = saved_builtins.get(&plugin.name) {
+                plugin.enabled = *enabled;
+            }
+        }
+        if env::var(PLUGIN_AUTOSTART_ENV)
+            .map(|v| !v.eq_ignore_ascii_case("false"))
+            .unwrap_or(true)
+        {
+            manager.auto_start().await;
+        } else {
+            info!("Plugin Auto Start disabled by {PLUGIN_AUTOSTART_ENV}");
+        }
         manager
     }
+    async fn auto_start(&mut self) {
+        //Plugins carry no dependency metadata, so start them in the order they were installed
+        let mut to_start: Vec<Plugin> = self
+            .plugins
+            .values()
+            .filter(|p| p.enabled > 0 && !matches!(p.plugin_type, PluginType::BuiltIn))
+            .cloned()
+            .collect();
+        to_start.sort_by(|a, b| a.added.cmp(&b.added).then_with(|| a.name.cmp(&b.name)));
+        for plugin in to_start {
+            let name = plugin.name.clone();
+            info!("Auto Starting Plugin: {name}");
+            if let Err(e) = self.start(plugin).await {
+                warn!("Failed to Auto Start Plugin {name}: {e:?}");
+            }
+        }
+    }
     pub async fn available_plugins(&self) -> Vec<StorePlugin> {
         self.available_plugins.values().cloned().collect()
     }
+    pub async fn installed_plugins(&self) -> Vec<Plugin> {
+        self.plugins.values().cloned().collect()
+    }
     pub async fn add(&mut self, plugin: AddPlugin, db: &SqlitePool) -> Result<Plugin, Error> {
         //Check Loaded Plugins for Plugin with same name
         if self.plugins.contains_key(&plugin.name) {
@@ -192,6 +363,10 @@ impl PluginManager {
                 format!("Plugin {} already exists", plugin.name),
             ));
         }
+        if matches!(plugin.plugin_type, PluginType::File) && is_local_plugin_source(&plugin.source)
+        {
+            validate_local_plugin_source(&plugin.source).await?;
+        }
         let plugin = plugin.into();
         create_plugin(db, &plugin).await?;
         self.plugins.insert(plugin.name.clone(), plugin.clone());
@@ -220,25 +395,85 @@ impl PluginManager {
                 "Plugin Already Running",
             )),
             Entry::Vacant(entry) => {
-                match plugin.plugin_type {
-                    PluginType::BuiltIn => {}
-                    PluginType::Docker => {
-                        start_docker_plugin(entry, plugin).await?;
+                let name = plugin.name.clone();
+                let installed = match plugin.plugin_type {
+                    PluginType::BuiltIn => false,
+                    PluginType::Docker => start_docker_plugin(entry, plugin).await?,
+                    PluginType::DockerCompose => {
+                        start_compose_plugin(self.bin_folder.clone(), entry, plugin).await?;
+                        false
                     }
                     PluginType::RustProject => {
                         start_rust_plugin(entry, plugin).await?;
+                        false
                     }
                     PluginType::File => {
-                        start_file_plugin(self.bin_folder.clone(), entry, plugin).await?;
+                        let cgroup = self.prepare_plugin_cgroup(&name).await;
+                        start_file_plugin(
+                            self.bin_folder.clone(),
+                            entry,
+                            plugin,
+                            self.database.clone(),
+                            self.notifier.clone(),
+                            cgroup,
+                        )
+                        .await?
                     }
                     PluginType::Invalid => {
                         warn!("Tried to Start Invalid Plugin: {}", plugin.name);
+                        false
                     }
+                };
+                if installed {
+                    self.record_download(&name).await;
                 }
                 Ok(true)
             }
         }
     }
+    //Returns the cgroup a File plugin is spawned into, None runs it without limits
+    async fn prepare_plugin_cgroup(&mut self, name: &str) -> Option<PathBuf> {
+        let parent = self.plugin_cgroup.clone()?;
+        //Reload so policy changes apply the next time a plugin starts
+        self.resource_policy = ResourcePolicy::load(&self.database).await;
+        if let Err(e) = apply_plugin_limits(&parent, &self.resource_policy).await {
+            warn!("Failed to apply plugin resource limits: {e:?}");
+        }
+        match create_plugin_cgroup(&parent, name).await {
+            Ok(cgroup) => Some(cgroup),
+            Err(e) => {
+                warn!("Failed to create cgroup for plugin {name}: {e:?}");
+                None
+            }
+        }
+    }
+    pub async fn resource_usage(&self, name: &str) -> Result<PluginResourceUsage, Error> {
+        match self.plugin_runtimes.get(name) {
+            Some(
+                PluginRuntime::Docker(metadata)
+                | PluginRuntime::DockerCompose(metadata, _)
+                | PluginRuntime::ProcessGroup(metadata, _),
+            ) => match &metadata.cgroup {
+                Some(cgroup) => read_cgroup_usage(cgroup).await,
+                None => Err(Error::new(
+                    ErrorKind::NotFound,
+                    "Plugin is not running in a cgroup",
+                )),
+            },
+            Some(PluginRuntime::BuiltIn) | None => {
+                Err(Error::new(ErrorKind::NotFound, "Plugin is not running"))
+            }
+        }
+    }
+    async fn record_download(&mut self, name: &str) {
+        if let Err(e) = increment_plugin_download_count(&self.database, name).await {
+            warn!("Failed to record download for plugin {name}: {e:?}");
+            return;
+        }
+        if let Some(plugin) = self.plugins.get_mut(name) {
+            plugin.download_count += 1;
+        }
+    }
     pub async fn stop(&mut self, plugin: Plugin) -> Result<bool, Error> {
         match self.plugin_runtimes.entry(plugin.name.clone()) {
             Entry::Occupied(runtime) => match runtime.remove() {
@@ -280,21 +515,28 @@ impl PluginManager {
                         })?;
                     Ok(true)
                 }
-                PluginRuntime::File(runtime) => {
+                PluginRuntime::DockerCompose(metadata, compose_file) => {
+                    if let Some(run) = metadata.run.as_ref() {
+                        run.store(false, Ordering::Relaxed);
+                    }
+                    info!("Stopping Compose Plugin: {}", plugin.name);
+                    docker_compose(&compose_file, &["down"]).await?;
+                    Ok(true)
+                }
+                PluginRuntime::ProcessGroup(runtime, process) => {
+                    info!(
+                        "Stopping Process Group {} for Plugin: {}",
+                        process.pgid, plugin.name
+                    );
+                    //Cleared first so the exit caused by the stop isn't reported as a crash
                     if let Some(run) = runtime.run.as_ref() {
                         run.store(false, Ordering::Relaxed);
                     }
-                    if let Some(handle) = runtime.join_handle {
-                        handle.abort();
-                        match handle.await {
-                            Ok(Ok(_)) => {}
-                            Ok(Err(e)) => {
-                                error!("Error in Plugin: {e}");
-                            }
-                            Err(e) => {
-                                error!("Error Joining Plugin Thread: {e}");
-                            }
-                        }
+                    let cgroup = runtime.cgroup.clone();
+                    stop_process_group(&process).await;
+                    stop_runtime_task(runtime).await;
+                    if let Some(cgroup) = cgroup {
+                        remove_plugin_cgroup(&cgroup).await;
                     }
                     Ok(true)
                 }
@@ -306,6 +548,62 @@ impl PluginManager {
             Entry::Vacant(_) => Ok(false),
         }
     }
+    //Stops a plugin for a restart, the caller sleeps without holding the manager lock
+    //and then passes the returned stage to finish_restart
+    pub async fn begin_restart(&mut self, plugin: &Plugin) -> Result<(RestartStage, bool), Error> {
+        match self.plugin_runtimes.get(&plugin.name) {
+            Some(PluginRuntime::BuiltIn) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Built In Plugins Cant Be Restarted",
+            )),
+            //Running Docker containers are stopped in place so the runtime entry stays valid
+            Some(PluginRuntime::Docker(_)) => {
+                info!("Connecting to Docker");
+                let docker = Docker::connect_with_defaults()
+                    .map_err(|e| Error::other(format!("Failed to connect to docker: {e}")))?;
+                info!("Stopping Container");
+                docker
+                    .stop_container(&plugin.name, None)
+                    .await
+                    .map_err(|e| Error::other(format!("Failed to stop docker container: {e}")))?;
+                Ok((RestartStage::DockerContainer, true))
+            }
+            Some(PluginRuntime::DockerCompose(_, compose_file)) => {
+                let compose_file = compose_file.clone();
+                info!("Stopping Compose Plugin: {}", plugin.name);
+                docker_compose(&compose_file, &["stop"]).await?;
+                Ok((RestartStage::DockerCompose(compose_file), true))
+            }
+            Some(PluginRuntime::ProcessGroup(..)) => {
+                self.stop(plugin.clone()).await?;
+                Ok((RestartStage::Process, true))
+            }
+            None => Ok((RestartStage::Process, false)),
+        }
+    }
+    pub async fn finish_restart(
+        &mut self,
+        plugin: Plugin,
+        stage: RestartStage,
+    ) -> Result<(), Error> {
+        match stage {
+            RestartStage::DockerContainer => {
+                info!("Connecting to Docker");
+                let docker = Docker::connect_with_defaults()
+                    .map_err(|e| Error::other(format!("Failed to connect to docker: {e}")))?;
+                info!("Starting Container");
+                docker
+                    .start_container::<String>(&plugin.name, None)
+                    .await
+                    .map_err(|e| Error::other(format!("Failed to start docker container: {e}")))
+            }
+            RestartStage::DockerCompose(compose_file) => {
+                info!("Starting Compose Plugin: {}", plugin.name);
+                docker_compose(&compose_file, &["start"]).await.map(|_| ())
+            }
+            RestartStage::Process => self.start(plugin).await.map(|_| ()),
+        }
+    }
     pub async fn status(&self, plugin: Plugin) -> Result<PluginStatus, Error> {
         match self.plugin_runtimes.get(&plugin.name) {
             Some(runtime) => match runtime {
@@ -353,12 +651,22 @@ impl PluginManager {
                         ))),
                     }
                 }
-                PluginRuntime::File(runtime) => Ok(PluginStatus {
-                    running: runtime
-                        .join_handle
-                        .as_ref()
-                        .map(|h| !h.is_finished())
-                        .unwrap_or(false),
+                PluginRuntime::DockerCompose(metadata, compose_file) => {
+                    let output = docker_compose(compose_file, &["ps", "--format", "json"]).await?;
+                    let services = parse_compose_ps(&output)?;
+                    Ok(PluginStatus {
+                        running: !services.is_empty()
+                            && services.iter().all(|service| service.is_running()),
+                        should_be_running: if let Some(v) = &metadata.run {
+                            v.load(Ordering::Relaxed)
+                        } else {
+                            plugin.enabled > 0
+                        },
+                        started: Some(*metadata.started),
+                    })
+                }
+                PluginRuntime::ProcessGroup(runtime, process) => Ok(PluginStatus {
+                    running: process.is_running(),
                     should_be_running: if let Some(v) = &runtime.run {
                         v.load(Ordering::Relaxed)
                     } else {
@@ -385,30 +693,185 @@ impl PluginManager {
             }
         }
     }
+    pub async fn list_running(&self) -> Vec<(String, PluginStatus)> {
+        let mut running = vec![];
+        for name in self.plugin_runtimes.keys() {
+            let Some(plugin) = self.plugins.get(name) else {
+                continue;
+            };
+            match self.status(plugin.clone()).await {
+                Ok(status) if status.running => running.push((name.clone(), status)),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to load status for plugin {name}: {e:?}"),
+            }
+        }
+        running
+    }
+    pub async fn list_stopped(&self) -> Vec<(String, PluginStatus)> {
+        let mut stopped = vec![];
+        for (name, plugin) in &self.plugins {
+            match self.status(plugin.clone()).await {
+                Ok(status) if !status.running => stopped.push((name.clone(), status)),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to load status for plugin {name}: {e:?}"),
+            }
+        }
+        stopped
+    }
+    pub async fn installed_plugin(&self, name: &str) -> Option<Plugin> {
+        self.plugins.get(name).cloned()
+    }
+    pub async fn is_enabled(&self, name: &str) -> bool {
+        self.plugins
+            .get(name)
+            .map(|p| p.enabled > 0)
+            .unwrap_or_default()
+    }
+    async fn set_enabled(
+        &mut self,
+        plugin: &Plugin,
+        enabled: bool,
+        db: &SqlitePool,
+    ) -> Result<Plugin, Error> {
+        let Some(existing) = self.plugins.get(&plugin.name) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Plugin {} is not installed", plugin.name),
+            ));
+        };
+        let mut updated = existing.clone();
+        updated.enabled = enabled as i64;
+        updated.updated = OffsetDateTime::now_utc();
+        create_plugin(db, &updated).await?;
+        self.plugins.insert(updated.name.clone(), updated.clone());
+        Ok(updated)
+    }
+    /// Marks the Plugin as disabled and stops it, the record and binary are kept
+    pub async fn disable(&mut self, plugin: Plugin, db: &SqlitePool) -> Result<Plugin, Error> {
+        let updated = self.set_enabled(&plugin, false, db).await?;
+        if !matches!(plugin.plugin_type, PluginType::BuiltIn) {
+            self.stop(plugin).await?;
+        }
+        info!("Disabled Plugin {}", updated.name);
+        Ok(updated)
+    }
+    pub async fn enable(&mut self, plugin: Plugin, db: &SqlitePool) -> Result<Plugin, Error> {
+        let updated = self.set_enabled(&plugin, true, db).await?;
+        if !matches!(plugin.plugin_type, PluginType::BuiltIn) {
+            match self.start(updated.clone()).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        }
+        info!("Enabled Plugin {}", updated.name);
+        Ok(updated)
+    }
+    /// Seconds since the Plugin Store was last fetched successfully, None if it never was
+    pub fn store_cache_age(&self) -> Option<u64> {
+        match self.last_store_update.load(Ordering::Relaxed) {
+            0 => None,
+            last_update => Some(now_seconds().saturating_sub(last_update)),
+        }
+    }
+    pub fn store_expired(&self) -> bool {
+        self.store_cache_age()
+            .map(|age| age >= self.plugin_store_ttl.as_secs())
+            .unwrap_or(true)
+    }
+    pub fn store_info(&self) -> PluginStoreInfo {
+        PluginStoreInfo {
+            url: self.plugin_store_url.clone(),
+            last_update: match self.last_store_update.load(Ordering::Relaxed) {
+                0 => None,
+                last_update => Some(last_update),
+            },
+        }
+    }
+    pub fn set_plugin_store_url(&mut self, url: String) {
+        self.plugin_store_url = url;
+        //Force the next refresh to use the new Url
+        self.last_store_update.store(0, Ordering::Relaxed);
+    }
+    async fn fetch_configured_store(&self) -> Result<String, Error> {
+        match Self::fetch_plugin_store(&self.plugin_store_url).await {
+            Err(e)
+                if e.kind() == ErrorKind::NotFound
+                    && self.plugin_store_url != DEFAULT_PLUGIN_STORE_URL =>
+            {
+                error!(
+                    "Plugin Store not found at {}, falling back to {DEFAULT_PLUGIN_STORE_URL}: {e:?}",
+                    self.plugin_store_url
+                );
+                Self::fetch_plugin_store(DEFAULT_PLUGIN_STORE_URL).await
+            }
+            result => result,
+        }
+    }
     pub async fn update_plugin_store(&mut self) -> Result<bool, Error> {
-        let plugin_url = "https://plugins.druid.garden/plugins.yaml";
+        match self.fetch_configured_store().await {
+            Ok(plugin_yaml) => {
+                self.load_plugin_store(&plugin_yaml)?;
+                self.last_store_update
+                    .store(now_seconds(), Ordering::Relaxed);
+                if let Err(e) = tokio::fs::write(&self.plugin_store_cache, &plugin_yaml).await {
+                    warn!(
+                        "Failed to cache Plugin Store to {:?}: {e:?}",
+                        self.plugin_store_cache
+                    );
+                }
+                Ok(true)
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to refresh Plugin Store, falling back to cache at {:?}: {e:?}",
+                    self.plugin_store_cache
+                );
+                let plugin_yaml = tokio::fs::read_to_string(&self.plugin_store_cache).await?;
+                self.load_plugin_store(&plugin_yaml)?;
+                Ok(false)
+            }
+        }
+    }
+    fn load_plugin_store(&mut self, plugin_yaml: &str) -> Result<(), Error> {
+        let plugin_store: PluginStore = serde_yaml::from_str(plugin_yaml)
+            .map_err(|e| Error::other(format!("Failed parsing yaml: {e}")))?;
+        self.available_plugins = HashMap::from_iter(
+            plugin_store
+                .plugins
+                .into_iter()
+                .map(|v| (v.name.clone(), v)),
+        );
+        Ok(())
+    }
+    async fn fetch_plugin_store(plugin_url: &str) -> Result<String, Error> {
         let plugin_yaml = if plugin_url.starts_with("http") {
-            reqwest::get(plugin_url)
+            let response = reqwest::get(plugin_url)
                 .await
+                .map_err(|e| Error::other(format!("Failed fetching plugin store: {e}")))?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("Plugin Store not found at {plugin_url}"),
+                ));
+            }
+            response
+                .error_for_status()
                 .map_err(|e| Error::other(format!("Failed fetching plugin store: {e}")))?
                 .text()
                 .await
                 .map_err(|e| Error::other(format!("Failed reading body: {e}")))?
         } else {
-            let mut file = tokio::fs::File::open(&plugin_url).await?;
+            let plugin_path = plugin_url.strip_prefix("file://").unwrap_or(plugin_url);
+            let mut file = tokio::fs::File::open(plugin_path).await?;
             let mut buf = String::new();
             file.read_to_string(&mut buf).await?;
             buf.trim().to_string()
         };
-        let plugin_store: PluginStore = serde_yaml::from_str(&plugin_yaml)
+        //Validate before the result is cached so a bad response never replaces a good cache
+        serde_yaml::from_str::<PluginStore>(&plugin_yaml)
             .map_err(|e| Error::other(format!("Failed parsing yaml: {e}")))?;
-        self.available_plugins = HashMap::from_iter(
-            plugin_store
-                .plugins
-                .into_iter()
-                .map(|v| (v.name.clone(), v)),
-        );
-        Ok(true)
+        Ok(plugin_yaml)
     }
     pub async fn plugin_updates(&self) -> Result<Vec<PluginUpdates>, Error> {
         let mut updates = vec![];
@@ -430,18 +893,190 @@ impl PluginManager {
         }
         Ok(updates)
     }
-    pub async fn uninstall(&mut self, plugin: Plugin, db: &SqlitePool) -> Result<bool, Error> {
+    pub async fn update_available(&self, name: &str) -> Result<PluginUpdateAvailability, Error> {
+        let plugin = self
+            .plugins
+            .get(name)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Plugin Does Not Exist"))?;
+        let new_version = self.available_plugins.get(name).map(|v| v.version.clone());
+        let has_update = match (
+            Version::parse(&plugin.version),
+            new_version.as_deref().map(Version::parse),
+        ) {
+            (Ok(current_version), Some(Ok(available_version))) => {
+                available_version > current_version
+            }
+            _ => false,
+        };
+        Ok(PluginUpdateAvailability {
+            has_update,
+            current_version: plugin.version.clone(),
+            new_version,
+        })
+    }
+    pub async fn update_file_plugin(
+        &mut self,
+        name: &str,
+        db: &SqlitePool,
+    ) -> Result<Plugin, Error> {
+        let current = self
+            .plugins
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Plugin Does Not Exist"))?;
+        if !matches!(current.plugin_type, PluginType::File) {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Only File Plugins can be updated in place",
+            ));
+        }
+        if !self.update_available(name).await?.has_update {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("No update available for plugin {name}"),
+            ));
+        }
+        let Some(available) = self.available_plugins.get(name).cloned() else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Plugin {name} is not in the Plugin Store"),
+            ));
+        };
+        let working_directory = self.bin_folder.join(name);
+        let bin_path = working_directory.join(name);
+        let tmp_path = working_directory.join(format!("{name}.tmp"));
+        let backup_path = working_directory.join(format!("{name}.bak"));
+        tokio::fs::create_dir_all(&working_directory).await?;
+        let url = format!(
+            "{}/{}/{}",
+            &available.repo, &available.tag, &available.source
+        )
+        .replace("//", "/")
+        .replace("//", "/");
+        info!("Downloading Plugin Update From: {url}");
+        let bytes = reqwest::get(url)
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| Error::other(format!("Failed to fetch file: {e}")))?
+            .bytes()
+            .await
+            .map_err(|e| Error::other(format!("Failed to read file from response: {e}")))?;
+        match &available.sha256 {
+            Some(expected) => {
+                let actual: String = Sha256::digest(&bytes)
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect();
+                if !actual.eq_ignore_ascii_case(expected.trim()) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Plugin {name} hash mismatch, expected {expected} found {actual}"),
+                    ));
+                }
+            }
+            None => warn!("Plugin Store has no hash for {name}, skipping verification"),
+        }
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        file.write_all(bytes.as_ref()).await?;
+        file.set_permissions(Permissions::from_mode(0o755)).await?;
+        drop(file);
+        //Swap the binaries, keeping the current one so we can roll back
+        if backup_path.exists() {
+            let _ = tokio::fs::remove_file(&backup_path).await;
+        }
+        if bin_path.exists() {
+            tokio::fs::rename(&bin_path, &backup_path).await?;
+        }
+        tokio::fs::rename(&tmp_path, &bin_path).await?;
+        let was_running = self.plugin_runtimes.contains_key(name);
+        if was_running {
+            self.stop(current.clone()).await?;
+        }
+        let updated = Plugin {
+            repo: available.repo.clone(),
+            tag: available.tag.clone(),
+            source: available.source.clone(),
+            version: available.version.clone(),
+            updated: OffsetDateTime::now_utc(),
+            ..current.clone()
+        };
+        if was_running {
+            if let Err(e) = self.start(updated.clone()).await {
+                error!("Failed to start updated plugin {name}, rolling back: {e:?}");
+                let _ = self.stop(updated).await;
+                tokio::fs::rename(&backup_path, &bin_path).await?;
+                self.start(current).await?;
+                return Err(Error::other(format!(
+                    "Failed to start updated plugin {name}, restored previous version: {e}"
+                )));
+            }
+        }
+        create_plugin(db, &updated).await?;
+        self.plugins.insert(name.to_string(), updated.clone());
+        info!("Updated Plugin {name} to {}", updated.version);
+        Ok(updated)
+    }
+    pub async fn uninstall(
+        &mut self,
+        plugin: Plugin,
+        db: &SqlitePool,
+        force: bool,
+    ) -> Result<bool, Error> {
         if let Some(PluginRuntime::BuiltIn) = self.plugin_runtimes.get(&plugin.name) {
-            Err(Error::new(
+            return Err(Error::new(
                 ErrorKind::PermissionDenied,
-                "Unable to Install Builtin Plugins",
-            ))
+                "Unable to Uninstall Builtin Plugins",
+            ));
+        }
+        if force {
+            //The runtime may already be gone (e.g. container removed by hand), drop our handle to it
+            let runtime = self.plugin_runtimes.remove(&plugin.name);
+            if let Some(PluginRuntime::ProcessGroup(_, process)) = &runtime {
+                process.signal(libc::SIGKILL);
+            }
+            if let Some(
+                PluginRuntime::Docker(metadata)
+                | PluginRuntime::DockerCompose(metadata, _)
+                | PluginRuntime::ProcessGroup(metadata, _),
+            ) = runtime
+            {
+                if let Some(run) = metadata.run {
+                    run.store(false, Ordering::Relaxed);
+                }
+                if let Some(handle) = metadata.join_handle {
+                    handle.abort();
+                }
+            }
         } else {
-            let _ = self.plugins.remove(&plugin.name);
-            let _ = self.plugin_runtimes.get(&plugin.name);
-            delete_plugin(db, &plugin.name).await.map(|v| v > 0)
+            match self.stop(plugin.clone()).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => {
+                    return Err(Error::new(
+                        e.kind(),
+                        format!(
+                            "Failed to stop plugin {} before uninstall: {e}",
+                            plugin.name
+                        ),
+                    ))
+                }
+            }
+        }
+        let deleted = delete_plugin(db, &plugin.name).await.map(|v| v > 0)?;
+        let _ = self.plugins.remove(&plugin.name);
+        Ok(deleted)
+    }
+}
+
+#[interval(60_000)]
+pub async fn refresh_plugin_store(state: State<RwLock<PluginManager>>) -> Result<(), Error> {
+    if state.0.read().await.store_expired() {
+        info!("Plugin Store cache expired, refreshing");
+        if let Err(e) = state.0.write().await.update_plugin_store().await {
+            warn!("Failed to refresh Plugin Store: {e:?}");
         }
     }
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -461,16 +1096,94 @@ pub async fn start_rust_plugin(
     ))
 }
 
+async fn stop_runtime_task(runtime: RuntimeMetadata) {
+    if let Some(run) = runtime.run.as_ref() {
+        run.store(false, Ordering::Relaxed);
+    }
+    if let Some(handle) = runtime.join_handle {
+        handle.abort();
+        match handle.await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                error!("Error in Plugin: {e}");
+            }
+            Err(e) => {
+                error!("Error Joining Plugin Thread: {e}");
+            }
+        }
+    }
+}
+
+const PROCESS_GROUP_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn stop_process_group(process: &PluginProcess) {
+    if !process.signal(libc::SIGTERM) {
+        return;
+    }
+    let deadline = tokio::time::Instant::now() + PROCESS_GROUP_STOP_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        if !process.is_running() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    warn!(
+        "Process Group {} did not exit after SIGTERM, sending SIGKILL",
+        process.pgid
+    );
+    process.signal(libc::SIGKILL);
+}
+
+//File plugins with an absolute source are copied from the device instead of downloaded
+pub fn is_local_plugin_source(source: &str) -> bool {
+    source.starts_with('/')
+}
+
+pub async fn validate_local_plugin_source(source: &str) -> Result<(), Error> {
+    let metadata = tokio::fs::metadata(source).await.map_err(|e| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("Failed to read local plugin {source}: {e}"),
+        )
+    })?;
+    if !metadata.is_file() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Local plugin {source} is not a file"),
+        ));
+    }
+    if metadata.permissions().mode() & 0o111 == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Local plugin {source} is not executable"),
+        ));
+    }
+    Ok(())
+}
+
+//Returns true when the plugin binary was freshly installed rather than reused
 pub async fn start_file_plugin(
     bin_folder: PathBuf,
     entry: VacantEntry<'_, String, PluginRuntime>,
     plugin: Plugin,
-) -> Result<(), Error> {
+    database: SqlitePool,
+    notifier: Arc<WebhookNotifier>,
+    cgroup: Option<PathBuf>,
+) -> Result<bool, Error> {
     info!("Starting Plugin: {}", plugin.name);
-    let working_directory = bin_folder.join(&plugin.name).canonicalize()?;
+    let working_directory = bin_folder.join(&plugin.name);
+    //canonicalize fails on a missing directory, which a new plugin does not have yet
+    tokio::fs::create_dir_all(&working_directory).await?;
+    let working_directory = working_directory.canonicalize()?;
     let file_path = working_directory.join(&plugin.name);
-    if !file_path.exists() {
-        tokio::fs::create_dir_all(&working_directory).await?;
+    let installed = !file_path.exists();
+    if installed && is_local_plugin_source(&plugin.source) {
+        validate_local_plugin_source(&plugin.source).await?;
+        info!("Copying Plugin From: {}", plugin.source);
+        tokio::fs::copy(&plugin.source, &file_path).await?;
+        tokio::fs::set_permissions(&file_path, Permissions::from_mode(0o755)).await?;
+        info!("Created File at: {file_path:?}");
+    } else if installed {
         let url = format!("{}/{}/{}", &plugin.repo, &plugin.tag, &plugin.source)
             .replace("//", "/")
             .replace("//", "/");
@@ -498,39 +1211,180 @@ pub async fn start_file_plugin(
     };
     command.current_dir(working_directory);
     command.kill_on_drop(true);
+    command.stdin(Stdio::null());
+    command.stdout(Stdio::null());
+    command.stderr(Stdio::null());
+    //A pgid of 0 makes the plugin the leader of a new group with the same id as its pid
+    command.process_group(0);
+    if let Some(cgroup) = &cgroup {
+        spawn_in_cgroup(&mut command, cgroup)?;
+    }
+    info!("Calling Command: {command:?}");
+    let child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            if let Some(cgroup) = &cgroup {
+                remove_plugin_cgroup(cgroup).await;
+            }
+            return Err(e);
+        }
+    };
+    let pgid = child
+        .id()
+        .ok_or_else(|| Error::other(format!("Plugin {} exited on start", plugin.name)))?;
+    let process = Arc::new(PluginProcess {
+        pgid,
+        child: Mutex::new(child),
+    });
     let run = Arc::new(AtomicBool::new(true));
     let handle_run = run.clone();
+    let handle_process = process.clone();
     let plugin_name = plugin.name.clone();
-    entry.insert(PluginRuntime::File(RuntimeMetadata {
-        run: Some(run),
-        join_handle: Some(tokio::spawn(async move {
-            info!("Calling Command: {command:?}");
-            select! {
-                output = command.output() => {
-                    if let Err(e) = output {
-                        error!("Plugin {plugin_name} Exited: {e}");
-                    }
-                },
-                () = async move {
-                    while handle_run.load(Ordering::Relaxed) {
-                        tokio::time::sleep(Duration::from_millis(100)).await;
+    entry.insert(PluginRuntime::ProcessGroup(
+        RuntimeMetadata {
+            run: Some(run),
+            join_handle: Some(tokio::spawn(async move {
+                //Stops clear the run flag first, so any failed exit seen here is a crash
+                while handle_run.load(Ordering::Relaxed) {
+                    let reason = match handle_process.try_wait() {
+                        Ok(None) => {
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            continue;
+                        }
+                        Ok(Some(status)) if status.success() => None,
+                        Ok(Some(status)) => Some(status.to_string()),
+                        Err(e) => Some(e.to_string()),
+                    };
+                    if let Some(reason) = reason.filter(|_| handle_run.load(Ordering::Relaxed)) {
+                        error!("Plugin {plugin_name} Exited: {reason}");
+                        plugin_crashed(&database, &notifier, &plugin_name, &reason).await;
                     }
-                } => (),
-            }
-            Ok(())
-        })),
-        started: Arc::new(OffsetDateTime::now_utc()),
-    }));
+                    break;
+                }
+                Ok(())
+            })),
+            started: Arc::new(OffsetDateTime::now_utc()),
+            cgroup,
+        },
+        process,
+    ));
+    Ok(installed)
+}
+
+async fn plugin_crashed(
+    database: &SqlitePool,
+    notifier: &WebhookNotifier,
+    plugin_name: &str,
+    reason: &str,
+) {
+    let metadata = json!({ "plugin": plugin_name, "reason": reason });
+    if let Err(e) = log_event(
+        database,
+        EventSeverity::Error,
+        "plugin",
+        &format!("Plugin {plugin_name} Crashed"),
+        Some(metadata.clone()),
+    )
+    .await
+    {
+        warn!("Failed to record Plugin Crashed event: {e:?}");
+    }
+    notifier.send("plugin_crashed", metadata).await;
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ComposeService {
+    #[serde(rename = "Service", default)]
+    pub service: String,
+    #[serde(rename = "State", default)]
+    pub state: String,
+}
+impl ComposeService {
+    pub fn is_running(&self) -> bool {
+        self.state.eq_ignore_ascii_case("running")
+    }
+}
+
+//Older compose releases print a JSON array, newer ones print one object per line
+pub fn parse_compose_ps(output: &str) -> Result<Vec<ComposeService>, Error> {
+    let output = output.trim();
+    if output.starts_with('[') {
+        serde_json::from_str(output).map_err(Error::other)
+    } else {
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(Error::other))
+            .collect()
+    }
+}
+
+pub async fn docker_compose(compose_file: &Path, args: &[&str]) -> Result<String, Error> {
+    let output = Command::new("docker")
+        .arg("compose")
+        .arg("-f")
+        .arg(compose_file)
+        .args(args)
+        .output()
+        .await?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(Error::other(format!(
+            "docker compose {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+pub async fn start_compose_plugin(
+    bin_folder: PathBuf,
+    entry: VacantEntry<'_, String, PluginRuntime>,
+    plugin: Plugin,
+) -> Result<(), Error> {
+    info!("Starting Compose Plugin: {}", plugin.name);
+    let working_directory = bin_folder.join(&plugin.name);
+    tokio::fs::create_dir_all(&working_directory).await?;
+    let compose_file = working_directory.join("docker-compose.yml");
+    if is_local_plugin_source(&plugin.source) {
+        info!("Copying Compose File From: {}", plugin.source);
+        tokio::fs::copy(&plugin.source, &compose_file).await?;
+    } else {
+        info!("Fetching Compose File From: {}", plugin.source);
+        let contents = reqwest::get(&plugin.source)
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| Error::other(format!("Failed to fetch compose file: {e}")))?
+            .bytes()
+            .await
+            .map_err(|e| Error::other(format!("Failed to read compose file: {e}")))?;
+        tokio::fs::write(&compose_file, contents).await?;
+    }
+    docker_compose(&compose_file, &["up", "-d"]).await?;
+    entry.insert(PluginRuntime::DockerCompose(
+        RuntimeMetadata {
+            run: Some(Arc::new(AtomicBool::new(true))),
+            join_handle: None,
+            started: Arc::new(OffsetDateTime::now_utc()),
+            cgroup: None,
+        },
+        compose_file,
+    ));
     Ok(())
 }
 
+//Returns true when a new container was created rather than an existing one replaced
 pub async fn start_docker_plugin(
     entry: VacantEntry<'_, String, PluginRuntime>,
     plugin: Plugin,
-) -> Result<(), Error> {
+) -> Result<bool, Error> {
     info!("Connecting to Docker");
     let docker = Docker::connect_with_defaults()
         .map_err(|e| Error::other(format!("Failed to connect to docker: {e}")))?;
+    //Only a pull of an image that wasn't already local counts as a download
+    let image = format!("{}:{}", plugin.source, plugin.tag);
+    let installed = docker.inspect_image(&image).await.is_err();
     let mut image_progress = docker.create_image(
         Some(CreateImageOptions {
             from_image: plugin.source.clone(),
@@ -629,6 +1483,22 @@ pub async fn start_docker_plugin(
         run: Some(Arc::new(AtomicBool::new(true))),
         join_handle: None,
         started: Arc::new(OffsetDateTime::now_utc()),
+        cgroup: None,
     }));
-    Ok(())
+    Ok(installed)
+}
+
+#[test]
+fn test_parse_compose_ps() {
+    let lines =
+        "{\"Service\":\"app\",\"State\":\"running\"}\n{\"Service\":\"db\",\"State\":\"exited\"}\n";
+    let services = parse_compose_ps(lines).unwrap();
+    assert_eq!(services.len(), 2);
+    assert!(services[0].is_running());
+    assert!(!services[1].is_running());
+    let array = r#"[{"Service":"app","State":"running"}]"#;
+    let services = parse_compose_ps(array).unwrap();
+    assert_eq!(services[0].service, "app");
+    assert!(services[0].is_running());
+    assert!(parse_compose_ps("").unwrap().is_empty());
 }
@@ -0,0 +1,213 @@
+use crate::database::config::get_config_key;
+use log::{info, warn};
+use portfu::prelude::serde_json;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::fs::OpenOptions;
+use std::io::{Error, ErrorKind};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+pub const PLUGIN_CGROUP_NAME: &str = "druid-garden";
+pub const PLUGIN_MAX_CPU_PERCENT_CONFIG_KEY: &str = "plugin_max_cpu_percent";
+pub const PLUGIN_MAX_MEMORY_CONFIG_KEY: &str = "plugin_max_memory_bytes";
+const CPU_MAX_PERIOD_USEC: u64 = 100_000;
+//The kernel rejects cpu.max quotas below 1ms
+const CPU_MAX_MIN_QUOTA_USEC: u64 = 1_000;
+
+//Limits shared by all plugins together, applied to the druid-garden parent cgroup
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct ResourcePolicy {
+    //Percent of the total CPU time across all cores, 0 is unlimited
+    pub max_plugin_cpu_percent: f32,
+    //0 is unlimited
+    pub max_plugin_memory_bytes: u64,
+}
+impl ResourcePolicy {
+    pub async fn load(db: &SqlitePool) -> Self {
+        Self {
+            max_plugin_cpu_percent: load_config_value(db, PLUGIN_MAX_CPU_PERCENT_CONFIG_KEY)
+                .await
+                .unwrap_or_default(),
+            max_plugin_memory_bytes: load_config_value(db, PLUGIN_MAX_MEMORY_CONFIG_KEY)
+                .await
+                .unwrap_or_default(),
+        }
+    }
+}
+
+async fn load_config_value<T: DeserializeOwned>(db: &SqlitePool, key: &str) -> Option<T> {
+    match get_config_key(db, key).await {
+        Ok(Some(entry)) => serde_json::from_str(&entry.value).ok(),
+        _ => None,
+    }
+}
+
+pub fn cpu_max_value(percent: f32, cpus: usize) -> String {
+    if percent <= 0.0 {
+        return "max".to_string();
+    }
+    let quota = (CPU_MAX_PERIOD_USEC as f64 * cpus.max(1) as f64 * percent as f64 / 100.0) as u64;
+    format!(
+        "{} {CPU_MAX_PERIOD_USEC}",
+        quota.max(CPU_MAX_MIN_QUOTA_USEC)
+    )
+}
+
+pub fn memory_max_value(bytes: u64) -> String {
+    if bytes == 0 {
+        "max".to_string()
+    } else {
+        bytes.to_string()
+    }
+}
+
+/// Creates the cgroup plugins are placed under, returns None when cgroups v2 can't be used
+pub async fn init_plugin_cgroup(policy: &ResourcePolicy) -> Option<PathBuf> {
+    let root = Path::new(CGROUP_ROOT);
+    let controllers = match tokio::fs::read_to_string(root.join("cgroup.controllers")).await {
+        Ok(controllers) => controllers,
+        Err(e) => {
+            warn!("cgroups v2 is not available, plugin resource limits will not be enforced: {e}");
+            return None;
+        }
+    };
+    let controllers: Vec<&str> = controllers.split_whitespace().collect();
+    if !controllers.contains(&"cpu") || !controllers.contains(&"memory") {
+        warn!("cgroups v2 cpu and memory controllers are not available, plugin resource limits will not be enforced");
+        return None;
+    }
+    let parent = root.join(PLUGIN_CGROUP_NAME);
+    let result = async {
+        tokio::fs::create_dir_all(&parent).await?;
+        //Controllers have to be enabled on every level above the plugin cgroups
+        tokio::fs::write(root.join("cgroup.subtree_control"), "+cpu +memory").await?;
+        tokio::fs::write(parent.join("cgroup.subtree_control"), "+cpu +memory").await?;
+        apply_plugin_limits(&parent, policy).await
+    }
+    .await;
+    match result {
+        Ok(()) => {
+            info!("Plugin cgroup created at {parent:?}");
+            Some(parent)
+        }
+        Err(e) => {
+            warn!(
+                "Failed to create plugin cgroup, plugin resource limits will not be enforced: {e}"
+            );
+            None
+        }
+    }
+}
+
+/// Writes the policy limits to the parent cgroup so they cap all plugins combined
+pub async fn apply_plugin_limits(parent: &Path, policy: &ResourcePolicy) -> Result<(), Error> {
+    let cpus = std::thread::available_parallelism()
+        .map(|v| v.get())
+        .unwrap_or(1);
+    tokio::fs::write(
+        parent.join("cpu.max"),
+        cpu_max_value(policy.max_plugin_cpu_percent, cpus),
+    )
+    .await?;
+    tokio::fs::write(
+        parent.join("memory.max"),
+        memory_max_value(policy.max_plugin_memory_bytes),
+    )
+    .await
+}
+
+/// Creates the cgroup a plugin is spawned into, it has no limits of its own and is only
+/// used to read the plugin's usage
+pub async fn create_plugin_cgroup(parent: &Path, plugin_name: &str) -> Result<PathBuf, Error> {
+    let cgroup = parent.join(plugin_name);
+    tokio::fs::create_dir_all(&cgroup).await?;
+    Ok(cgroup)
+}
+
+/// Makes the command join `cgroup` between fork and exec, so nothing it forks can escape the limits
+pub fn spawn_in_cgroup(command: &mut Command, cgroup: &Path) -> Result<(), Error> {
+    //Opened before the fork, the child only does a write which is safe between fork and exec
+    let procs = OpenOptions::new()
+        .write(true)
+        .open(cgroup.join("cgroup.procs"))?;
+    unsafe {
+        command.pre_exec(move || {
+            //Writing 0 moves the writing process itself
+            if libc::write(procs.as_raw_fd(), b"0".as_ptr().cast(), 1) < 0 {
+                return Err(Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+pub async fn remove_plugin_cgroup(cgroup: &Path) {
+    if let Err(e) = tokio::fs::remove_dir(cgroup).await {
+        warn!("Failed to remove plugin cgroup {cgroup:?}: {e}");
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginResourceUsage {
+    pub cgroup: String,
+    pub cpu_usage_usec: u64,
+    pub cpu_max: String,
+    pub memory_current: u64,
+    pub memory_max: String,
+}
+
+fn parse_cpu_stat_usage(stat: &str) -> Option<u64> {
+    stat.lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+async fn read_cgroup_file(cgroup: &Path, file: &str) -> Result<String, Error> {
+    tokio::fs::read_to_string(cgroup.join(file))
+        .await
+        .map(|v| v.trim().to_string())
+        .map_err(|e| Error::new(e.kind(), format!("Failed to read {file}: {e}")))
+}
+
+pub async fn read_cgroup_usage(cgroup: &Path) -> Result<PluginResourceUsage, Error> {
+    let cpu_stat = read_cgroup_file(cgroup, "cpu.stat").await?;
+    let memory_current = read_cgroup_file(cgroup, "memory.current").await?;
+    Ok(PluginResourceUsage {
+        cgroup: cgroup.to_string_lossy().to_string(),
+        cpu_usage_usec: parse_cpu_stat_usage(&cpu_stat).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                "Failed to parse cpu.stat usage_usec",
+            )
+        })?,
+        cpu_max: read_cgroup_file(cgroup, "cpu.max").await?,
+        memory_current: memory_current.parse().map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Failed to parse memory.current: {e}"),
+            )
+        })?,
+        memory_max: read_cgroup_file(cgroup, "memory.max").await?,
+    })
+}
+
+#[test]
+fn test_cgroup_limit_values() {
+    assert_eq!(cpu_max_value(0.0, 4), "max");
+    assert_eq!(cpu_max_value(50.0, 4), "200000 100000");
+    assert_eq!(cpu_max_value(0.1, 1), "1000 100000");
+    assert_eq!(memory_max_value(0), "max");
+    assert_eq!(memory_max_value(536_870_912), "536870912");
+}
+
+#[test]
+fn test_parse_cpu_stat_usage() {
+    let stat = "usage_usec 123456\nuser_usec 100000\nsystem_usec 23456\n";
+    assert_eq!(parse_cpu_stat_usage(stat), Some(123456));
+    assert_eq!(parse_cpu_stat_usage("user_usec 1"), None);
+}
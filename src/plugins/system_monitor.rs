@@ -1,20 +1,32 @@
+use crate::config::ConfigManager;
+use crate::database::events::{log_event, EventSeverity};
+use crate::database::plugins::get_all_plugins;
+use crate::models::pagination::{ListQuery, ListResponse, SortOrder};
+use crate::web::notifications::WebhookNotifier;
 use dg_network_manager::all_devices;
 use dg_network_manager::dbus_api::devices::Device;
 use dg_sysfs::classes::block::disk::{DiskType, FileSystem, Partition};
 use dg_sysfs::classes::block::BlockEnumerator;
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
 use nvml_wrapper::enum_wrappers::device::TemperatureSensor;
 use nvml_wrapper::Nvml;
-use portfu::prelude::serde_json::Value;
-use portfu::prelude::{serde_json, State};
-use portfu_macros::{get, interval};
+use portfu::prelude::http::{HeaderName, HeaderValue};
+use portfu::prelude::serde_json::{json, Value};
+use portfu::prelude::{serde_json, Path, State};
+use portfu_core::{Json, ServiceData};
+use portfu_macros::{get, interval, post};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::io::Error;
-use std::net::{IpAddr, Ipv4Addr};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
-use sysinfo::System;
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use sysinfo::{Components, Pid, Process, ProcessesToUpdate, System};
+use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tokio::sync::RwLock;
 
@@ -28,6 +40,41 @@ pub struct ProcessInfo {
     pub memory_usage: u64,
 }
 
+impl ProcessInfo {
+    fn new(pid: &Pid, process: &Process) -> Self {
+        Self {
+            pid: pid.as_u32(),
+            name: process.name().to_string_lossy().to_string(),
+            parent: process.parent().map(|p| p.as_u32()),
+            started: process.start_time(),
+            cpu_usage: process.cpu_usage(),
+            memory_usage: process.memory(),
+        }
+    }
+}
+
+pub const DEFAULT_TOP_PROCESSES: usize = 10;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessSort {
+    #[default]
+    Cpu,
+    Memory,
+}
+impl FromStr for ProcessSort {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cpu" => Ok(ProcessSort::Cpu),
+            "memory" => Ok(ProcessSort::Memory),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid process sort: {s}, expected cpu or memory"),
+            )),
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct SystemInfo {
     name: String,
@@ -63,6 +110,116 @@ pub struct DiskInfo {
     pub vendor: Option<String>,
     pub model: Option<String>,
     pub disk_type: DiskType,
+    pub temperature_celsius: Option<u32>,
+    pub nvme_health: Option<NvmeHealth>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NvmeHealth {
+    pub critical_warning: u64,
+    pub available_spare: u64,
+    pub available_spare_threshold: u64,
+    pub percentage_used: u64,
+}
+
+const NVME_PERCENTAGE_USED_WARNING: u64 = 90;
+
+/// Reads the first of `keys` present in `nvme smart-log -o json` output,
+/// nvme-cli has renamed these fields between releases and may wrap them as `{"value": n}`
+fn nvme_field(output: &Value, keys: &[&str]) -> Option<u64> {
+    keys.iter().find_map(|key| {
+        let value = output.get(*key)?;
+        value
+            .as_u64()
+            .or_else(|| value.get("value").and_then(Value::as_u64))
+    })
+}
+
+fn parse_nvme_health(output: &Value) -> Option<NvmeHealth> {
+    Some(NvmeHealth {
+        critical_warning: nvme_field(output, &["critical_warning"])?,
+        available_spare: nvme_field(output, &["avail_spare", "available_spare"])?,
+        available_spare_threshold: nvme_field(
+            output,
+            &["spare_thresh", "available_spare_threshold"],
+        )?,
+        percentage_used: nvme_field(output, &["percent_used", "percentage_used"])?,
+    })
+}
+
+async fn read_nvme_smart_log(dev_path: &str) -> Result<Value, Error> {
+    let output = Command::new("nvme")
+        .args(["smart-log", dev_path, "-o", "json"])
+        .output()
+        .await
+        .map_err(|e| Error::other(format!("Failed to run nvme, is nvme-cli installed? {e:?}")))?;
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "nvme smart-log failed for {dev_path}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Failed to parse nvme smart-log output: {e}"),
+        )
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiskTemperature {
+    pub name: String,
+    pub temperature_celsius: Option<u32>,
+}
+
+const SMART_TEMPERATURE_ATTRIBUTES: [&str; 2] = ["Temperature_Celsius", "Drive_Temperature"];
+
+/// Pulls the drive temperature out of `smartctl -j -A` output, preferring the
+/// normalised `temperature.current` and falling back to the ATA attribute table
+fn parse_smartctl_temperature(output: &Value) -> Option<u32> {
+    if let Some(current) = output
+        .pointer("/temperature/current")
+        .and_then(Value::as_u64)
+    {
+        return Some(current as u32);
+    }
+    output
+        .pointer("/ata_smart_attributes/table")
+        .and_then(Value::as_array)?
+        .iter()
+        .find(|attribute| {
+            attribute
+                .get("name")
+                .and_then(Value::as_str)
+                .map(|name| SMART_TEMPERATURE_ATTRIBUTES.contains(&name))
+                .unwrap_or_default()
+        })
+        .and_then(|attribute| attribute.pointer("/raw/value"))
+        .and_then(Value::as_u64)
+        //Some drives pack the min/max temperatures into the upper bytes of the raw value
+        .map(|raw| (raw & 0xff) as u32)
+}
+
+async fn read_disk_temperature(dev_path: &str) -> Option<u32> {
+    //smartctl uses its exit code as a bit mask of drive health, so parse the output regardless
+    match Command::new("smartctl")
+        .args(["-j", "-A", dev_path])
+        .output()
+        .await
+    {
+        Ok(output) => match serde_json::from_slice::<Value>(&output.stdout) {
+            Ok(parsed) => parse_smartctl_temperature(&parsed),
+            Err(e) => {
+                debug!("Failed to parse smartctl output for {dev_path}: {e:?}");
+                None
+            }
+        },
+        Err(e) => {
+            debug!("Failed to run smartctl for {dev_path}. Is smartctl installed? {e:?}");
+            None
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -73,17 +230,124 @@ pub struct DiskUsage {
     pub total_writen: u64,
 }
 
+#[derive(Serialize)]
+pub enum AddressType {
+    IPv4,
+    IPv6,
+}
+
 #[derive(Serialize)]
 pub struct IPAddressInfo {
     pub address: IpAddr,
+    pub address_type: AddressType,
     pub net_mask: u8,
     pub gateway: IpAddr,
+    pub link_local: bool,
+}
+
+fn parse_ipv4_addresses(addresses: &[Vec<u32>]) -> Vec<IPAddressInfo> {
+    addresses
+        .iter()
+        .filter_map(|v| {
+            if v.len() == 3 {
+                let address = Ipv4Addr::from(v[0].to_be());
+                Some(IPAddressInfo {
+                    address: IpAddr::V4(address),
+                    address_type: AddressType::IPv4,
+                    net_mask: v[1] as u8,
+                    gateway: IpAddr::V4(Ipv4Addr::from(v[2].to_be())),
+                    link_local: address.is_link_local(),
+                })
+            } else {
+                warn!("Invalid Address in Network Manager Ipv4: {v:?}");
+                None
+            }
+        })
+        .collect()
+}
+
+fn parse_ipv6_addresses(addresses: &[Vec<String>]) -> Vec<IPAddressInfo> {
+    addresses
+        .iter()
+        .filter_map(|v| {
+            if v.len() != 3 {
+                warn!("Invalid Address in Network Manager Ipv6: {v:?}");
+                return None;
+            }
+            let (Ok(address), Ok(net_mask)) = (Ipv6Addr::from_str(&v[0]), u8::from_str(&v[1]))
+            else {
+                warn!("Invalid Address in Network Manager Ipv6: {v:?}");
+                return None;
+            };
+            //Links without a router report an empty gateway
+            let gateway = Ipv6Addr::from_str(&v[2]).unwrap_or(Ipv6Addr::UNSPECIFIED);
+            Some(IPAddressInfo {
+                address: IpAddr::V6(address),
+                address_type: AddressType::IPv6,
+                net_mask,
+                gateway: IpAddr::V6(gateway),
+                //fe80::/10, Ipv6Addr::is_unicast_link_local is not yet stable
+                link_local: (address.segments()[0] & 0xffc0) == 0xfe80,
+            })
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+pub struct DhcpLease {
+    pub server_address: IpAddr,
+    pub lease_expiry: OffsetDateTime,
+    pub domain: Option<String>,
+}
+
+const DHCP_LEASE_EXPIRY_WARNING: Duration = Duration::from_secs(60 * 60);
+
+/// Builds a lease from NetworkManager's DHCP4 options,
+/// `expiry` is a unix timestamp and `dhcp_server_identifier` the server address
+fn parse_dhcp_lease(options: &HashMap<String, String>) -> Option<DhcpLease> {
+    let server_address = options
+        .get("dhcp_server_identifier")
+        .and_then(|v| IpAddr::from_str(v.trim()).ok())?;
+    let lease_expiry = options
+        .get("expiry")
+        .and_then(|v| i64::from_str(v.trim()).ok())
+        .and_then(|v| OffsetDateTime::from_unix_timestamp(v).ok())?;
+    Some(DhcpLease {
+        server_address,
+        lease_expiry,
+        domain: options
+            .get("domain_name")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty()),
+    })
+}
+
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// Reads the negotiated speed and duplex of a wired interface from sysfs,
+/// both are unavailable while the cable is unplugged
+async fn link_info(interface_name: &str) -> (Option<u32>, Option<String>) {
+    let base = PathBuf::from(SYS_CLASS_NET).join(interface_name);
+    //Down links report a speed of -1
+    let speed = tokio::fs::read_to_string(base.join("speed"))
+        .await
+        .ok()
+        .and_then(|v| u32::from_str(v.trim()).ok());
+    let duplex = tokio::fs::read_to_string(base.join("duplex"))
+        .await
+        .ok()
+        .map(|v| v.trim().to_ascii_lowercase())
+        .filter(|v| v == "full" || v == "half");
+    (speed, duplex)
 }
 
 #[derive(Serialize)]
 pub struct NetworkInfo {
     pub name: String,
     pub ip_addresses: Vec<IPAddressInfo>,
+    pub dhcp_lease: Option<DhcpLease>,
+    pub link_speed_mbps: Option<u32>,
+    pub duplex: Option<String>,
     pub mac_address: String,
     pub data_downloaded: u64,
     pub data_uploaded: u64,
@@ -104,6 +368,8 @@ pub struct GpuInfo {
     pub gpu_usage: u32,
     pub memory_usage: u32,
     pub temperature: u32,
+    pub power_watts: Option<f32>,
+    pub power_limit_watts: Option<f32>,
 }
 
 #[derive(Serialize)]
@@ -113,6 +379,7 @@ pub struct CpuUsage {
     pub vendor: String,
     pub usage: f32,
     pub freq: u64,
+    pub temperature_celsius: Option<f32>,
 }
 
 #[derive(Serialize)]
@@ -122,6 +389,272 @@ pub struct CpuInfo {
     pub thread_count: usize,
     pub cpu_usage: Vec<CpuUsage>,
     pub load_averages: (f64, f64, f64),
+    pub max_temperature_celsius: Option<f32>,
+}
+
+const SYS_CLASS_HWMON: &str = "/sys/class/hwmon";
+const SYS_DEVICES_CPU: &str = "/sys/devices/system/cpu";
+const CPU_PACKAGE_LABELS: [&str; 3] = ["Package id 0", "Tctl", "Tdie"];
+
+#[derive(Debug, Default)]
+pub struct CpuTemperatures {
+    pub cores: HashMap<u32, f32>,
+    pub package: Option<f32>,
+}
+
+/// Scans hwmon devices for `Core N` labels (Intel coretemp) and the
+/// package sensor (`Package id 0` on Intel, `Tctl`/`Tdie` on AMD k10temp)
+pub async fn read_cpu_temperatures(hwmon_root: &Path) -> Result<CpuTemperatures, Error> {
+    let mut temperatures = CpuTemperatures::default();
+    let mut devices = tokio::fs::read_dir(hwmon_root).await?;
+    while let Some(device) = devices.next_entry().await? {
+        let mut entries = match tokio::fs::read_dir(device.path()).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("Failed to read hwmon device {:?}: {e:?}", device.path());
+                continue;
+            }
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(sensor) = file_name.strip_suffix("_label") else {
+                continue;
+            };
+            let Ok(label) = tokio::fs::read_to_string(entry.path()).await else {
+                continue;
+            };
+            let Ok(input) =
+                tokio::fs::read_to_string(device.path().join(format!("{sensor}_input"))).await
+            else {
+                continue;
+            };
+            //hwmon reports in millidegrees Celsius
+            let Ok(millidegrees) = input.trim().parse::<i64>() else {
+                continue;
+            };
+            let celsius = millidegrees as f32 / 1000.0;
+            let label = label.trim();
+            if let Some(core) = label
+                .strip_prefix("Core ")
+                .and_then(|core| core.trim().parse::<u32>().ok())
+            {
+                temperatures.cores.insert(core, celsius);
+            } else if CPU_PACKAGE_LABELS.contains(&label) {
+                temperatures.package = Some(temperatures.package.unwrap_or(celsius).max(celsius));
+            }
+        }
+    }
+    Ok(temperatures)
+}
+
+async fn cpu_core_id(cpu_name: &str) -> Option<u32> {
+    tokio::fs::read_to_string(
+        PathBuf::from(SYS_DEVICES_CPU)
+            .join(cpu_name)
+            .join("topology")
+            .join("core_id"),
+    )
+    .await
+    .ok()
+    .and_then(|v| v.trim().parse().ok())
+}
+
+fn sysinfo_package_temperature() -> Option<f32> {
+    Components::new_with_refreshed_list()
+        .iter()
+        .filter(|c| {
+            let label = c.label().to_ascii_lowercase();
+            label.contains("package") || label.contains("tctl") || label.contains("cpu")
+        })
+        .filter_map(|c| c.temperature())
+        .reduce(f32::max)
+}
+
+pub const FAN_CURVE_CONFIG_KEY: &str = "fan_curve";
+//Fan ids the curve drives, empty drives every fan that doesn't belong to a GPU
+pub const FAN_CURVE_FANS_CONFIG_KEY: &str = "fan_curve_fans";
+//~30% duty, a curve can never stop the fans it drives
+pub const FAN_CURVE_MIN_PWM: u8 = 77;
+const FAN_CURVE_MAX_TEMP: u8 = 110;
+//GPU fans are left to the GPU driver
+const GPU_HWMON_CHIPS: [&str; 4] = ["amdgpu", "radeon", "nouveau", "i915"];
+
+pub fn validate_fan_curve(value: &str) -> Result<(), String> {
+    let curve: Vec<(u8, u8)> = serde_json::from_str(value)
+        .map_err(|e| format!("{value} is not a valid fan curve: {e}"))?;
+    for (temperature, pwm) in curve {
+        if temperature > FAN_CURVE_MAX_TEMP {
+            return Err(format!(
+                "{temperature}°C is above the {FAN_CURVE_MAX_TEMP}°C fan curve limit"
+            ));
+        }
+        if pwm < FAN_CURVE_MIN_PWM {
+            return Err(format!(
+                "PWM {pwm} at {temperature}°C is below the minimum of {FAN_CURVE_MIN_PWM}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_fan_curve_fans(value: &str) -> Result<(), String> {
+    serde_json::from_str::<Vec<String>>(value)
+        .map(|_| ())
+        .map_err(|e| format!("{value} is not a valid list of fan ids: {e}"))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FanInfo {
+    pub id: String,
+    pub chip: String,
+    pub name: String,
+    pub rpm: u32,
+    pub pwm: u8,
+}
+
+async fn read_trimmed(path: PathBuf) -> Option<String> {
+    tokio::fs::read_to_string(path)
+        .await
+        .ok()
+        .map(|v| v.trim().to_string())
+}
+
+/// Lists the fans reported by hwmon, identified as `{hwmon device}-{fan number}` e.g. `hwmon2-1`
+pub async fn read_fans(hwmon_root: &Path) -> Result<Vec<FanInfo>, Error> {
+    let mut fans = vec![];
+    let mut devices = tokio::fs::read_dir(hwmon_root).await?;
+    while let Some(device) = devices.next_entry().await? {
+        let device_name = device.file_name().to_string_lossy().to_string();
+        let chip = read_trimmed(device.path().join("name"))
+            .await
+            .unwrap_or(device_name.clone());
+        let Ok(mut entries) = tokio::fs::read_dir(device.path()).await else {
+            continue;
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(number) = file_name
+                .strip_prefix("fan")
+                .and_then(|v| v.strip_suffix("_input"))
+            else {
+                continue;
+            };
+            let rpm = read_trimmed(entry.path())
+                .await
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default();
+            let pwm = read_trimmed(device.path().join(format!("pwm{number}")))
+                .await
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default();
+            let name = read_trimmed(device.path().join(format!("fan{number}_label")))
+                .await
+                .unwrap_or(format!("{chip} fan{number}"));
+            fans.push(FanInfo {
+                id: format!("{device_name}-{number}"),
+                chip: chip.clone(),
+                name,
+                rpm,
+                pwm,
+            });
+        }
+    }
+    fans.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(fans)
+}
+
+fn fan_device(hwmon_root: &Path, id: &str) -> Result<(PathBuf, String), Error> {
+    let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid fan id: {id}"));
+    let (device, number) = id.rsplit_once('-').ok_or_else(invalid)?;
+    if !device.starts_with("hwmon")
+        || !device[5..].chars().all(|c| c.is_ascii_digit())
+        || number.parse::<u32>().is_err()
+    {
+        return Err(invalid());
+    }
+    Ok((hwmon_root.join(device), number.to_string()))
+}
+
+pub async fn write_fan_pwm(hwmon_root: &Path, id: &str, pwm: u8) -> Result<(), Error> {
+    let (device, number) = fan_device(hwmon_root, id)?;
+    let pwm_path = device.join(format!("pwm{number}"));
+    if !tokio::fs::try_exists(&pwm_path).await? {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("Fan {id} has no PWM control"),
+        ));
+    }
+    //Writing 1 to pwm_enable switches the fan to manual control
+    let enable_path = device.join(format!("pwm{number}_enable"));
+    let mut enable = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(&enable_path)
+        .await
+        .map_err(|e| {
+            Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Fan {id} PWM control is not writable: {e}"),
+            )
+        })?;
+    enable.write_all(b"1").await?;
+    tokio::fs::write(&pwm_path, pwm.to_string()).await
+}
+
+/// Hands the fan back to the chip's automatic control, or full speed if the chip has none
+pub async fn restore_fan_auto(hwmon_root: &Path, id: &str) -> Result<(), Error> {
+    let (device, number) = fan_device(hwmon_root, id)?;
+    if tokio::fs::write(device.join(format!("pwm{number}_enable")), "2")
+        .await
+        .is_ok()
+    {
+        return Ok(());
+    }
+    tokio::fs::write(device.join(format!("pwm{number}")), "255").await
+}
+
+/// Linearly interpolates the PWM value for `temperature` between the points of `curve`,
+/// temperatures outside the curve use the nearest point
+pub fn fan_curve_pwm(curve: &[(u8, u8)], temperature: f32) -> Option<u8> {
+    let mut curve = curve.to_vec();
+    curve.sort_by_key(|(temp, _)| *temp);
+    let (first, last) = (curve.first()?, curve.last()?);
+    if temperature <= first.0 as f32 {
+        return Some(first.1);
+    }
+    if temperature >= last.0 as f32 {
+        return Some(last.1);
+    }
+    curve.windows(2).find_map(|points| {
+        let ((low_temp, low_pwm), (high_temp, high_pwm)) = (points[0], points[1]);
+        if temperature > high_temp as f32 || high_temp == low_temp {
+            return None;
+        }
+        let ratio = (temperature - low_temp as f32) / (high_temp - low_temp) as f32;
+        Some((low_pwm as f32 + ratio * (high_pwm as f32 - low_pwm as f32)).round() as u8)
+    })
+}
+
+#[derive(Serialize)]
+pub struct InstalledPluginInfo {
+    pub name: String,
+    pub label: String,
+    pub version: String,
+    pub enabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct SystemInventory {
+    pub os_version: String,
+    pub kernel: String,
+    pub hostname: String,
+    pub arch: String,
+    pub cpu: CpuInfo,
+    pub memory: MemoryInfo,
+    pub disks: Vec<DiskInfo>,
+    pub gpus: Vec<GpuInfo>,
+    pub networks: Vec<NetworkInfo>,
+    pub plugins: Vec<InstalledPluginInfo>,
+    pub generated: u64,
 }
 #[derive(Deserialize)]
 struct UnitValue {
@@ -180,10 +713,21 @@ struct AmdGPUAsic {
     // target_graphics_version: Option<String>
 }
 
+#[derive(Deserialize)]
+struct AmdGPULimit {
+    max_power: Option<UnitValue>,
+}
+
 #[derive(Deserialize)]
 struct AmdGPUInfo {
     gpu: Option<u32>,
     asic: Option<AmdGPUAsic>,
+    limit: Option<AmdGPULimit>,
+}
+
+#[derive(Deserialize)]
+struct AmdPower {
+    socket_power: Option<UnitValue>,
 }
 
 #[derive(Deserialize)]
@@ -193,6 +737,7 @@ struct AmdGPUUsage {
     temperature: Option<AmdTemperature>,
     fan: Option<AmdFanUsage>,
     mem_usage: Option<AmdMemoryUsage>,
+    power: Option<AmdPower>,
 }
 
 #[derive(Debug)]
@@ -205,7 +750,24 @@ pub struct SystemMonitorPlugin {
     cpu_count: usize,
     last_disk_update: AtomicU64,
     last_net_update: AtomicU64,
+    last_gpu_update: AtomicU64,
     detected_amd_gpu: AtomicBool,
+    inventory: RwLock<Option<(Instant, SystemInventory)>>,
+    disk_temperatures: RwLock<HashMap<String, u32>>,
+    disk_temp_alerts: RwLock<HashSet<String>>,
+    disk_full_alerts: RwLock<HashSet<String>>,
+    //Conditions that were already logged, so they aren't logged again on every refresh
+    active_warnings: RwLock<HashSet<String>>,
+    //f32 bits of the hottest CPU temperature from the previous refresh
+    last_cpu_temperature: AtomicU32,
+    cpu_temperatures: RwLock<HashMap<String, f32>>,
+    nvme_health: RwLock<HashMap<String, NvmeHealth>>,
+    fan_curve_pwm: RwLock<Option<u8>>,
+    //Fans the curve switched to manual control, restored to automatic when it stops driving them
+    fan_curve_fans: RwLock<HashSet<String>>,
+    //f64 bits of the RAM and swap utilization from the previous refresh
+    last_memory_percent: AtomicU64,
+    last_swap_percent: AtomicU64,
 }
 impl SystemMonitorPlugin {
     pub async fn new() -> SystemMonitorPlugin {
@@ -241,7 +803,20 @@ impl SystemMonitorPlugin {
             cpu_count,
             last_disk_update: AtomicU64::new(0),
             last_net_update: AtomicU64::new(0),
+            last_gpu_update: AtomicU64::new(0),
             detected_amd_gpu,
+            inventory: RwLock::new(None),
+            disk_temperatures: RwLock::new(HashMap::new()),
+            disk_temp_alerts: RwLock::new(HashSet::new()),
+            disk_full_alerts: RwLock::new(HashSet::new()),
+            active_warnings: RwLock::new(HashSet::new()),
+            last_cpu_temperature: AtomicU32::new(0f32.to_bits()),
+            cpu_temperatures: RwLock::new(HashMap::new()),
+            nvme_health: RwLock::new(HashMap::new()),
+            fan_curve_pwm: RwLock::new(None),
+            fan_curve_fans: RwLock::new(HashSet::new()),
+            last_memory_percent: AtomicU64::new(0f64.to_bits()),
+            last_swap_percent: AtomicU64::new(0f64.to_bits()),
         }
     }
     fn get_nvidia_gpu_info(nvml: &Nvml) -> Vec<GpuInfo> {
@@ -269,6 +844,12 @@ impl SystemMonitorPlugin {
                                 temperature: device
                                     .temperature(TemperatureSensor::Gpu)
                                     .unwrap_or_default(),
+                                //NVML reports power in milliwatts
+                                power_watts: device.power_usage().ok().map(|mw| mw as f32 / 1000.0),
+                                power_limit_watts: device
+                                    .power_management_limit()
+                                    .ok()
+                                    .map(|mw| mw as f32 / 1000.0),
                             });
                         }
                         Err(e) => error!("Error Loading GPU at Index {device_id}. {e:?}"),
@@ -293,6 +874,7 @@ impl SystemMonitorPlugin {
                 "--usage",
                 "--mem-usage",
                 "--temperature",
+                "--power",
                 "--json",
             ])
             .output()
@@ -304,7 +886,7 @@ impl SystemMonitorPlugin {
                     return vec![];
                 }
                 let gpu_info = match Command::new("amd-smi")
-                    .args(["static", "--asic", "--json"])
+                    .args(["static", "--asic", "--limit", "--json"])
                     .output()
                     .await
                 {
@@ -399,6 +981,17 @@ impl SystemMonitorPlugin {
                                     }
                                 })
                                 .unwrap_or_default(),
+                            power_watts: x
+                                .power
+                                .as_ref()
+                                .and_then(|v| v.socket_power.as_ref())
+                                .and_then(|v| value_to_f32(&v.value)),
+                            power_limit_watts: gpu_info
+                                .iter()
+                                .find(|v| v.gpu == x.gpu && x.gpu.is_some())
+                                .and_then(|v| v.limit.as_ref())
+                                .and_then(|v| v.max_power.as_ref())
+                                .and_then(|v| value_to_f32(&v.value)),
                         })
                         .collect(),
                     Err(e) => {
@@ -427,21 +1020,111 @@ impl SystemMonitorPlugin {
                 .await
                 .processes()
                 .iter()
-                .map(|(pid, p)| ProcessInfo {
-                    pid: pid.as_u32(),
-                    name: p.name().to_string_lossy().to_string(),
-                    parent: p.parent().map(|p| p.as_u32()),
-                    started: p.start_time(),
-                    cpu_usage: p.cpu_usage(),
-                    memory_usage: p.memory(),
-                })
+                .map(|(pid, p)| ProcessInfo::new(pid, p))
                 .collect(),
         })
     }
+    /// Returns the `limit` processes using the most CPU or memory, highest first
+    pub async fn top_processes(&self, sort: ProcessSort, limit: usize) -> Vec<ProcessInfo> {
+        let system = self.system.read().await;
+        let mut processes: Vec<(&Pid, &Process)> = system.processes().iter().collect();
+        match sort {
+            ProcessSort::Cpu => {
+                processes.sort_unstable_by(|(_, a), (_, b)| b.cpu_usage().total_cmp(&a.cpu_usage()))
+            }
+            ProcessSort::Memory => {
+                processes.sort_unstable_by_key(|(_, p)| std::cmp::Reverse(p.memory()))
+            }
+        }
+        processes
+            .into_iter()
+            .take(limit)
+            .map(|(pid, p)| ProcessInfo::new(pid, p))
+            .collect()
+    }
+    pub async fn search_processes(&self, prefix: &str) -> Vec<ProcessInfo> {
+        let mut processes: Vec<ProcessInfo> = self
+            .system
+            .read()
+            .await
+            .processes()
+            .iter()
+            .filter(|(_, p)| p.name().to_string_lossy().starts_with(prefix))
+            .map(|(pid, p)| ProcessInfo::new(pid, p))
+            .collect();
+        processes.sort_by_key(|p| p.pid);
+        processes
+    }
     pub async fn get_gpu_info(&self) -> Result<Vec<GpuInfo>, Error> {
         Ok(self.gpus.read().await.clone())
     }
+    /// Sets the power limit of an NVIDIA GPU, returning the limit the driver applied
+    pub async fn set_gpu_power_limit(&self, index: u32, watts: f32) -> Result<f32, Error> {
+        let nvml = self.nvml.read().await;
+        let Some(nvml) = nvml.as_ref() else {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Power limits are only supported on NVIDIA GPUs",
+            ));
+        };
+        let mut device = nvml.device_by_index(index).map_err(|e| {
+            Error::new(ErrorKind::NotFound, format!("GPU {index} not found: {e:?}"))
+        })?;
+        let constraints = device
+            .power_management_limit_constraints()
+            .map_err(|e| Error::other(format!("Failed to load power limits: {e:?}")))?;
+        let milliwatts = (watts * 1000.0).round() as u32;
+        if !(constraints.min_limit..=constraints.max_limit).contains(&milliwatts) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Power limit must be between {}W and {}W",
+                    constraints.min_limit as f32 / 1000.0,
+                    constraints.max_limit as f32 / 1000.0
+                ),
+            ));
+        }
+        device
+            .set_power_management_limit(milliwatts)
+            .map_err(|e| Error::other(format!("Failed to set power limit: {e:?}")))?;
+        let applied = device
+            .power_management_limit()
+            .map_err(|e| Error::other(format!("Failed to load power limit: {e:?}")))?;
+        info!(
+            "Set GPU {index} power limit to {}W",
+            applied as f32 / 1000.0
+        );
+        Ok(applied as f32 / 1000.0)
+    }
+    pub async fn get_max_temperature(&self) -> Result<u32, Error> {
+        let mut max_temp = self
+            .gpus
+            .read()
+            .await
+            .iter()
+            .map(|g| g.temperature)
+            .max()
+            .unwrap_or_default();
+        let mut zones = tokio::fs::read_dir("/sys/class/thermal").await?;
+        while let Some(zone) = zones.next_entry().await? {
+            if !zone
+                .file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+            {
+                continue;
+            }
+            if let Ok(temp) = tokio::fs::read_to_string(zone.path().join("temp")).await {
+                //Thermal zones report in millidegrees Celsius
+                if let Ok(temp) = temp.trim().parse::<i64>() {
+                    max_temp = max_temp.max((temp / 1000).max(0) as u32);
+                }
+            }
+        }
+        Ok(max_temp)
+    }
     pub async fn get_cpu_info(&self) -> Result<CpuInfo, Error> {
+        let cpu_temperatures = self.cpu_temperatures.read().await;
         let cpu_usage = self
             .system
             .read()
@@ -454,6 +1137,7 @@ impl SystemMonitorPlugin {
                 vendor: c.vendor_id().to_string(),
                 usage: c.cpu_usage(),
                 freq: c.frequency(),
+                temperature_celsius: cpu_temperatures.get(c.name()).copied(),
             })
             .collect::<Vec<CpuUsage>>();
         let avg = System::load_average();
@@ -462,9 +1146,138 @@ impl SystemMonitorPlugin {
             physical_count: self.cpu_count,
             thread_count: cpu_usage.len(),
             load_averages: (avg.one, avg.five, avg.fifteen),
+            max_temperature_celsius: cpu_usage
+                .iter()
+                .filter_map(|c| c.temperature_celsius)
+                .reduce(f32::max),
             cpu_usage,
         })
     }
+    pub async fn get_fans(&self) -> Result<Vec<FanInfo>, Error> {
+        read_fans(Path::new(SYS_CLASS_HWMON)).await
+    }
+    pub async fn set_fan_pwm(&self, id: &str, pwm: u8) -> Result<(), Error> {
+        write_fan_pwm(Path::new(SYS_CLASS_HWMON), id, pwm).await?;
+        info!("Set fan {id} PWM to {pwm}");
+        Ok(())
+    }
+    /// Drives every PWM controllable fan from the hottest CPU core
+    async fn apply_fan_curve(&self, curve: &[(u8, u8)], fan_ids: &[String]) {
+        let Some(temperature) = self
+            .cpu_temperatures
+            .read()
+            .await
+            .values()
+            .copied()
+            .reduce(f32::max)
+        else {
+            //Without a temperature the curve can't react to load, let the hardware decide
+            self.restore_fan_control().await;
+            return;
+        };
+        let Some(pwm) = fan_curve_pwm(curve, temperature) else {
+            self.restore_fan_control().await;
+            return;
+        };
+        let pwm = pwm.max(FAN_CURVE_MIN_PWM);
+        let fans = match self.get_fans().await {
+            Ok(fans) => fans,
+            Err(e) => {
+                warn!("Failed to load fans for fan curve: {e:?}");
+                self.restore_fan_control().await;
+                return;
+            }
+        };
+        let targets: HashSet<String> = fans
+            .into_iter()
+            .filter(|fan| {
+                if fan_ids.is_empty() {
+                    !GPU_HWMON_CHIPS.contains(&fan.chip.as_str())
+                } else {
+                    fan_ids.contains(&fan.id)
+                }
+            })
+            .map(|fan| fan.id)
+            .collect();
+        let mut last_pwm = self.fan_curve_pwm.write().await;
+        let mut managed = self.fan_curve_fans.write().await;
+        for id in managed.difference(&targets).cloned().collect::<Vec<_>>() {
+            if let Err(e) = restore_fan_auto(Path::new(SYS_CLASS_HWMON), &id).await {
+                warn!("Failed to restore automatic control of fan {id}: {e:?}");
+            }
+            managed.remove(&id);
+        }
+        if *last_pwm == Some(pwm) && targets == *managed {
+            return;
+        }
+        for id in targets {
+            match write_fan_pwm(Path::new(SYS_CLASS_HWMON), &id, pwm).await {
+                Ok(()) => {
+                    managed.insert(id);
+                }
+                Err(e) => {
+                    warn!("Failed to apply fan curve to {id}: {e:?}");
+                    if let Err(e) = restore_fan_auto(Path::new(SYS_CLASS_HWMON), &id).await {
+                        warn!("Failed to restore automatic control of fan {id}: {e:?}");
+                    }
+                    managed.remove(&id);
+                }
+            }
+        }
+        debug!("Applied fan curve PWM {pwm} at {temperature}°C");
+        *last_pwm = Some(pwm);
+    }
+    /// Returns every fan the curve was driving to automatic control
+    pub async fn restore_fan_control(&self) {
+        let mut managed = self.fan_curve_fans.write().await;
+        if managed.is_empty() {
+            return;
+        }
+        for id in managed.drain() {
+            match restore_fan_auto(Path::new(SYS_CLASS_HWMON), &id).await {
+                Ok(()) => info!("Restored automatic control of fan {id}"),
+                Err(e) => warn!("Failed to restore automatic control of fan {id}: {e:?}"),
+            }
+        }
+        *self.fan_curve_pwm.write().await = None;
+    }
+    async fn refresh_cpu_temperatures(&self) {
+        let temperatures = match read_cpu_temperatures(Path::new(SYS_CLASS_HWMON)).await {
+            Ok(temperatures) => temperatures,
+            Err(e) => {
+                debug!("Failed to read hwmon temperatures: {e:?}");
+                CpuTemperatures::default()
+            }
+        };
+        let cpu_names: Vec<String> = self
+            .system
+            .read()
+            .await
+            .cpus()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect();
+        //Without per core sensors every core reports the package temperature
+        let package = if temperatures.cores.is_empty() {
+            temperatures.package.or_else(sysinfo_package_temperature)
+        } else {
+            None
+        };
+        let mut by_name = HashMap::new();
+        for name in cpu_names {
+            let temperature = match package {
+                Some(package) => Some(package),
+                None => match cpu_core_id(&name).await {
+                    Some(core_id) => temperatures.cores.get(&core_id).copied(),
+                    None => None,
+                },
+            };
+            if let Some(temperature) = temperature {
+                by_name.insert(name, temperature);
+            }
+        }
+        *self.cpu_temperatures.write().await = by_name;
+    }
     pub async fn get_memory_info(&self) -> Result<MemoryInfo, Error> {
         let (free, available, total, used, free_swap, total_swap, used_swap) = {
             let system = self.system.read().await;
@@ -490,6 +1303,8 @@ impl SystemMonitorPlugin {
     }
     pub async fn get_disk_info(&self) -> Result<Vec<DiskInfo>, Error> {
         let disks = self.disks.read().await;
+        let temperatures = self.disk_temperatures.read().await;
+        let nvme_health = self.nvme_health.read().await;
         let mut disk_info = vec![];
         for disk in disks.get_all_disks() {
             let usage = disks.get_disk_usage(&disk.name);
@@ -505,6 +1320,8 @@ impl SystemMonitorPlugin {
                 vendor: disk.vendor.clone(),
                 model: disk.model.clone(),
                 disk_type: disk.disk_type,
+                temperature_celsius: temperatures.get(&disk.name).copied(),
+                nvme_health: nvme_health.get(&disk.name).cloned(),
                 total: disk.space_info.map(|v| v.total_space).unwrap_or(0),
                 used: disk.space_info.map(|v| v.used_space).unwrap_or(0),
                 usage: DiskUsage {
@@ -521,6 +1338,254 @@ impl SystemMonitorPlugin {
         Ok(disk_info)
     }
 
+    pub async fn get_disk_temperature(&self, name: &str) -> Result<DiskTemperature, Error> {
+        let dev_path = self
+            .disks
+            .read()
+            .await
+            .get_all_disks()
+            .into_iter()
+            .find(|disk| disk.name == name)
+            .map(|disk| disk.device.display().to_string())
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Disk not found: {name}")))?;
+        let temperature_celsius = read_disk_temperature(&dev_path).await;
+        if let Some(temperature) = temperature_celsius {
+            self.disk_temperatures
+                .write()
+                .await
+                .insert(name.to_string(), temperature);
+        }
+        Ok(DiskTemperature {
+            name: name.to_string(),
+            temperature_celsius,
+        })
+    }
+    pub async fn get_nvme_smart_log(&self, name: &str) -> Result<Value, Error> {
+        if !name.starts_with("nvme") {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{name} is not an NVMe drive"),
+            ));
+        }
+        let dev_path = self
+            .disks
+            .read()
+            .await
+            .get_all_disks()
+            .into_iter()
+            .find(|disk| disk.name == name)
+            .map(|disk| disk.device.display().to_string())
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Disk not found: {name}")))?;
+        read_nvme_smart_log(&dev_path).await
+    }
+    async fn refresh_nvme_health(&self) {
+        let disks: Vec<(String, String)> = self
+            .disks
+            .read()
+            .await
+            .get_all_disks()
+            .into_iter()
+            .filter(|disk| disk.name.starts_with("nvme"))
+            .map(|disk| (disk.name.clone(), disk.device.display().to_string()))
+            .collect();
+        let mut health = HashMap::new();
+        for (name, dev_path) in disks {
+            let smart_log = match read_nvme_smart_log(&dev_path).await {
+                Ok(smart_log) => smart_log,
+                Err(e) => {
+                    debug!("Failed to read NVMe health for {name}: {e:?}");
+                    continue;
+                }
+            };
+            let Some(nvme_health) = parse_nvme_health(&smart_log) else {
+                debug!("Missing NVMe health fields for {name}");
+                continue;
+            };
+            if self
+                .warning_started(
+                    format!("nvme_spare:{name}"),
+                    nvme_health.available_spare < nvme_health.available_spare_threshold,
+                )
+                .await
+            {
+                warn!(
+                    "NVMe {name} available spare {}% is below the {}% threshold",
+                    nvme_health.available_spare, nvme_health.available_spare_threshold
+                );
+            }
+            if self
+                .warning_started(
+                    format!("nvme_used:{name}"),
+                    nvme_health.percentage_used > NVME_PERCENTAGE_USED_WARNING,
+                )
+                .await
+            {
+                warn!(
+                    "NVMe {name} has used {}% of its rated endurance",
+                    nvme_health.percentage_used
+                );
+            }
+            health.insert(name, nvme_health);
+        }
+        *self.nvme_health.write().await = health;
+    }
+    async fn refresh_disk_temperatures(&self) {
+        let disks: Vec<(String, String)> = self
+            .disks
+            .read()
+            .await
+            .get_all_disks()
+            .into_iter()
+            .filter(|disk| !matches!(disk.disk_type, DiskType::Unknown))
+            .map(|disk| (disk.name.clone(), disk.device.display().to_string()))
+            .collect();
+        let mut temperatures = HashMap::new();
+        for (name, dev_path) in disks {
+            if let Some(temperature) = read_disk_temperature(&dev_path).await {
+                temperatures.insert(name, temperature);
+            }
+        }
+        *self.disk_temperatures.write().await = temperatures;
+    }
+    /// Returns true when the condition for `key` is active and wasn't on the last check
+    async fn warning_started(&self, key: String, active: bool) -> bool {
+        let mut warnings = self.active_warnings.write().await;
+        if active {
+            warnings.insert(key)
+        } else {
+            warnings.remove(&key);
+            false
+        }
+    }
+    /// Returns the disks over `threshold` that were not already over it on the last check
+    async fn check_disk_temperatures(&self, threshold: u32) -> Vec<(String, u32)> {
+        let temperatures = self.disk_temperatures.read().await;
+        let mut alerts = self.disk_temp_alerts.write().await;
+        let mut new_alerts = vec![];
+        for (name, temperature) in temperatures.iter() {
+            if *temperature > threshold {
+                if alerts.insert(name.clone()) {
+                    error!(
+                        "Disk {name} is at {temperature}°C, above the {threshold}°C alert threshold"
+                    );
+                    new_alerts.push((name.clone(), *temperature));
+                }
+            } else {
+                alerts.remove(name);
+            }
+        }
+        new_alerts
+    }
+    /// Returns the disks whose usage just went over `threshold` percent
+    async fn check_disk_usage(&self, threshold: u64) -> Vec<(String, f64)> {
+        let disks = self.disks.read().await;
+        let mut alerts = self.disk_full_alerts.write().await;
+        let mut new_alerts = vec![];
+        for disk in disks.get_all_disks() {
+            let Some(space) = disk.space_info else {
+                continue;
+            };
+            let percent = usage_percent(space.used_space, space.total_space);
+            if percent > threshold as f64 {
+                if alerts.insert(disk.name.clone()) {
+                    warn!(
+                        "Disk {} is {percent:.1}% full, above the {threshold}% alert threshold",
+                        disk.name
+                    );
+                    new_alerts.push((disk.name.clone(), percent));
+                }
+            } else {
+                alerts.remove(&disk.name);
+            }
+        }
+        new_alerts
+    }
+    /// Returns the hottest CPU temperature when it just went over `threshold`
+    async fn check_cpu_temperature(&self, threshold: f32) -> Option<f32> {
+        let current = self
+            .cpu_temperatures
+            .read()
+            .await
+            .values()
+            .copied()
+            .reduce(f32::max)?;
+        let previous = f32::from_bits(
+            self.last_cpu_temperature
+                .swap(current.to_bits(), Ordering::Relaxed),
+        );
+        if crossed_threshold(previous as f64, current as f64, threshold as f64) {
+            warn!("CPU is at {current:.1}°C, above the {threshold}°C alert threshold");
+            Some(current)
+        } else {
+            None
+        }
+    }
+
+    pub async fn memory_percentages(&self) -> (f64, f64) {
+        let system = self.system.read().await;
+        (
+            usage_percent(system.used_memory(), system.total_memory()),
+            usage_percent(system.used_swap(), system.total_swap()),
+        )
+    }
+    /// Records the current RAM and swap utilization, returning the ones that just crossed their threshold
+    async fn check_memory_pressure(
+        &self,
+        memory_threshold: u64,
+        swap_threshold: u64,
+    ) -> Vec<(&'static str, f64, u64)> {
+        let (memory_percent, swap_percent) = self.memory_percentages().await;
+        let mut alerts = vec![];
+        for (name, last, current, threshold) in [
+            (
+                "memory",
+                &self.last_memory_percent,
+                memory_percent,
+                memory_threshold,
+            ),
+            (
+                "swap",
+                &self.last_swap_percent,
+                swap_percent,
+                swap_threshold,
+            ),
+        ] {
+            let previous = f64::from_bits(last.swap(current.to_bits(), Ordering::Relaxed));
+            if crossed_threshold(previous, current, threshold as f64) {
+                warn!("System {name} usage is at {current:.1}%, above the {threshold}% alert threshold");
+                alerts.push((name, current, threshold));
+            }
+        }
+        alerts
+    }
+
+    pub async fn get_inventory(&self, pool: &SqlitePool) -> Result<SystemInventory, Error> {
+        Ok(SystemInventory {
+            os_version: System::long_os_version().unwrap_or("Unknown".to_string()),
+            kernel: System::kernel_version().unwrap_or("Unknown".to_string()),
+            hostname: System::host_name().unwrap_or("Unknown".to_string()),
+            arch: System::cpu_arch(),
+            cpu: self.get_cpu_info().await?,
+            memory: self.get_memory_info().await?,
+            disks: self.get_disk_info().await?,
+            gpus: self.get_gpu_info().await?,
+            networks: self.get_network_info().await?,
+            plugins: get_all_plugins(pool)
+                .await?
+                .into_iter()
+                .map(|plugin| InstalledPluginInfo {
+                    name: plugin.name,
+                    label: plugin.label,
+                    version: plugin.version,
+                    enabled: plugin.enabled != 0,
+                })
+                .collect(),
+            generated: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        })
+    }
     pub async fn reload_disks(&self) -> Result<(), Error> {
         if let Err(e) = self.disks.write().await.reload_disks().await {
             error!("Failed to Update Disk Usage: {e:?}");
@@ -530,9 +1595,30 @@ impl SystemMonitorPlugin {
                 .expect("Expected System Time to be After EPOCH")
                 .as_secs();
             self.last_disk_update.store(now_seconds, Ordering::Relaxed);
+            self.refresh_nvme_health().await;
         }
         Ok(())
     }
+    /// Unix seconds of the last network device refresh, network info only changes after one
+    pub fn last_network_update(&self) -> u64 {
+        self.last_net_update.load(Ordering::Relaxed)
+    }
+    async fn warn_lease_expiry(&self, interface_name: &str, lease: Option<&DhcpLease>) {
+        let expiring = lease.filter(|lease| {
+            lease.lease_expiry - OffsetDateTime::now_utc() < DHCP_LEASE_EXPIRY_WARNING
+        });
+        if self
+            .warning_started(format!("dhcp_lease:{interface_name}"), expiring.is_some())
+            .await
+        {
+            if let Some(lease) = expiring {
+                warn!(
+                    "DHCP Lease for {interface_name} expires at {}",
+                    lease.lease_expiry
+                );
+            }
+        }
+    }
     pub async fn get_network_info(&self) -> Result<Vec<NetworkInfo>, Error> {
         let networks = self.networks.read().await.clone();
         let mut net_info = vec![];
@@ -542,13 +1628,37 @@ impl SystemMonitorPlugin {
                     debug!("Loading Wired Connection Info");
                     let active_connection = dev.active_connection().await.map_err(Error::other)?;
                     debug!("Loading Wired IpAddress Info");
-                    let ip_addresses = match active_connection {
+                    let (ip_addresses, dhcp_lease) = match active_connection {
                         Some(active_connection) => {
                             let ip_config =
                                 active_connection.ip4_config().await.map_err(Error::other)?;
-                            ip_config.addresses().await.map_err(Error::other)?
+                            let mut addresses = parse_ipv4_addresses(
+                                &ip_config.addresses().await.map_err(Error::other)?,
+                            );
+                            match active_connection.ip6_config().await {
+                                Ok(ip6_config) => match ip6_config.addresses().await {
+                                    Ok(v6) => addresses.extend(parse_ipv6_addresses(&v6)),
+                                    Err(e) => warn!("Failed to load Ipv6 Addresses: {e:?}"),
+                                },
+                                Err(e) => debug!("No Ipv6 Config for Connection: {e:?}"),
+                            }
+                            //Static configurations have no DHCP4 config
+                            let dhcp_lease = match active_connection.dhcp4_config().await {
+                                Ok(dhcp_config) => match dhcp_config.options().await {
+                                    Ok(options) => parse_dhcp_lease(&options),
+                                    Err(e) => {
+                                        warn!("Failed to load DHCP Lease Options: {e:?}");
+                                        None
+                                    }
+                                },
+                                Err(e) => {
+                                    debug!("No DHCP4 Config for Connection: {e:?}");
+                                    None
+                                }
+                            };
+                            (addresses, dhcp_lease)
                         }
-                        None => Vec::with_capacity(0),
+                        None => (Vec::with_capacity(0), None),
                     };
                     debug!("Loading Wired Mac Address Info");
                     let mac_address = dev.hw_address().await.map_err(Error::other)?;
@@ -557,23 +1667,27 @@ impl SystemMonitorPlugin {
                     debug!("Loading Wired Interface Name");
                     let interface_name = dev.interface().await?;
                     debug!("Found Interface Name: {interface_name}");
+                    self.warn_lease_expiry(&interface_name, dhcp_lease.as_ref())
+                        .await;
+                    let (link_speed_mbps, duplex) = link_info(&interface_name).await;
+                    let slow_link = link_speed_mbps.filter(|speed| *speed < 1000);
+                    if self
+                        .warning_started(
+                            format!("link_speed:{interface_name}"),
+                            slow_link.is_some(),
+                        )
+                        .await
+                    {
+                        if let Some(speed) = slow_link {
+                            warn!("Wired Interface {interface_name} is linked at {speed} Mbps");
+                        }
+                    }
                     net_info.push(NetworkInfo {
                         name: interface_name,
-                        ip_addresses: ip_addresses
-                            .iter()
-                            .filter_map(|v| {
-                                if v.len() == 3 {
-                                    Some(IPAddressInfo {
-                                        address: IpAddr::V4(Ipv4Addr::from(v[0].to_be())),
-                                        net_mask: v[1] as u8,
-                                        gateway: IpAddr::V4(Ipv4Addr::from(v[2].to_be())),
-                                    })
-                                } else {
-                                    warn!("Invalid Address in Network Manager Ipv4: {v:?}");
-                                    None
-                                }
-                            })
-                            .collect(),
+                        ip_addresses,
+                        dhcp_lease,
+                        link_speed_mbps,
+                        duplex,
                         mac_address,
                         data_downloaded: statistics.rx_bytes().await.unwrap_or_default(),
                         data_uploaded: statistics.tx_bytes().await.unwrap_or_default(),
@@ -583,13 +1697,37 @@ impl SystemMonitorPlugin {
                     debug!("Loading Wireless Connection Info");
                     let active_connection = dev.active_connection().await.map_err(Error::other)?;
                     debug!("Loading Wireless IpAddress Info");
-                    let ip_addresses = match active_connection {
+                    let (ip_addresses, dhcp_lease) = match active_connection {
                         Some(active_connection) => {
                             let ip_config =
                                 active_connection.ip4_config().await.map_err(Error::other)?;
-                            ip_config.addresses().await.map_err(Error::other)?
+                            let mut addresses = parse_ipv4_addresses(
+                                &ip_config.addresses().await.map_err(Error::other)?,
+                            );
+                            match active_connection.ip6_config().await {
+                                Ok(ip6_config) => match ip6_config.addresses().await {
+                                    Ok(v6) => addresses.extend(parse_ipv6_addresses(&v6)),
+                                    Err(e) => warn!("Failed to load Ipv6 Addresses: {e:?}"),
+                                },
+                                Err(e) => debug!("No Ipv6 Config for Connection: {e:?}"),
+                            }
+                            //Static configurations have no DHCP4 config
+                            let dhcp_lease = match active_connection.dhcp4_config().await {
+                                Ok(dhcp_config) => match dhcp_config.options().await {
+                                    Ok(options) => parse_dhcp_lease(&options),
+                                    Err(e) => {
+                                        warn!("Failed to load DHCP Lease Options: {e:?}");
+                                        None
+                                    }
+                                },
+                                Err(e) => {
+                                    debug!("No DHCP4 Config for Connection: {e:?}");
+                                    None
+                                }
+                            };
+                            (addresses, dhcp_lease)
                         }
-                        None => Vec::with_capacity(0),
+                        None => (Vec::with_capacity(0), None),
                     };
                     debug!("Loading Wireless Mac Address Info");
                     let mac_address = dev.hw_address().await.map_err(Error::other)?;
@@ -598,23 +1736,14 @@ impl SystemMonitorPlugin {
                     debug!("Loading Wireless Interface Name");
                     let interface_name = dev.interface().await?;
                     debug!("Found Interface Name: {interface_name}");
+                    self.warn_lease_expiry(&interface_name, dhcp_lease.as_ref())
+                        .await;
                     net_info.push(NetworkInfo {
                         name: interface_name,
-                        ip_addresses: ip_addresses
-                            .iter()
-                            .filter_map(|v| {
-                                if v.len() == 3 {
-                                    Some(IPAddressInfo {
-                                        address: IpAddr::V4(Ipv4Addr::from(v[0].to_be())),
-                                        net_mask: v[1] as u8,
-                                        gateway: IpAddr::V4(Ipv4Addr::from(v[2].to_be())),
-                                    })
-                                } else {
-                                    warn!("Invalid Address in Network Manager Ipv4: {v:?}");
-                                    None
-                                }
-                            })
-                            .collect(),
+                        ip_addresses,
+                        dhcp_lease,
+                        link_speed_mbps: None,
+                        duplex: None,
                         mac_address,
                         data_downloaded: statistics.rx_bytes().await.unwrap_or_default(),
                         data_uploaded: statistics.tx_bytes().await.unwrap_or_default(),
@@ -627,6 +1756,10 @@ impl SystemMonitorPlugin {
     }
 }
 
+pub fn value_to_f32(value: &Value) -> Option<f32> {
+    value.as_f64().map(|v| v as f32)
+}
+
 pub fn value_to_u32(value: &Value) -> u32 {
     if value.is_number() {
         if let Some(v) = value.as_u64() {
@@ -648,6 +1781,46 @@ pub async fn get_info(state: State<SystemMonitorPlugin>) -> Result<SystemInfo, E
     state.0.get_system_info().await
 }
 
+#[get("/api/system/processes/top", output = "json", eoutput = "bytes")]
+pub async fn get_top_processes(
+    data: &mut ServiceData,
+    state: State<SystemMonitorPlugin>,
+) -> Result<Vec<ProcessInfo>, Error> {
+    let query = ListQuery::parse(data.request.request.uri().query())?;
+    let sort = query
+        .filters
+        .get("by")
+        .map(|by| ProcessSort::from_str(by))
+        .transpose()?
+        .unwrap_or_default();
+    let limit = query
+        .filters
+        .get("limit")
+        .map(|limit| {
+            limit
+                .parse::<usize>()
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid limit: {e}")))
+        })
+        .transpose()?
+        .unwrap_or(DEFAULT_TOP_PROCESSES);
+    Ok(state.0.top_processes(sort, limit).await)
+}
+
+#[get("/api/system/processes/search", output = "json", eoutput = "bytes")]
+pub async fn search_processes(
+    data: &mut ServiceData,
+    state: State<SystemMonitorPlugin>,
+) -> Result<Vec<ProcessInfo>, Error> {
+    let query = ListQuery::parse(data.request.request.uri().query())?;
+    let Some(name) = query.filters.get("name") else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Missing name query parameter",
+        ));
+    };
+    Ok(state.0.search_processes(name).await)
+}
+
 #[get("/api/system/cpu", output = "json", eoutput = "bytes")]
 pub async fn get_cpu(state: State<SystemMonitorPlugin>) -> Result<CpuInfo, Error> {
     state.0.get_cpu_info().await
@@ -658,14 +1831,113 @@ pub async fn get_gpus(state: State<SystemMonitorPlugin>) -> Result<Vec<GpuInfo>,
     state.0.get_gpu_info().await
 }
 
+#[derive(Deserialize)]
+pub struct GpuPowerLimitPayload {
+    pub watts: f32,
+}
+
+#[post(
+    "/api/system/gpus/{index}/power_limit",
+    output = "json",
+    eoutput = "bytes"
+)]
+pub async fn set_gpu_power_limit(
+    state: State<SystemMonitorPlugin>,
+    index: Path,
+    payload: Json<Option<GpuPowerLimitPayload>>,
+) -> Result<f32, Error> {
+    let index = u32::from_str(&index.inner())
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid GPU index: {e}")))?;
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Power Limit Payload",
+        ));
+    };
+    state.0.set_gpu_power_limit(index, payload.watts).await
+}
+
+#[get("/api/system/fans", output = "json", eoutput = "bytes")]
+pub async fn get_fans(state: State<SystemMonitorPlugin>) -> Result<Vec<FanInfo>, Error> {
+    state.0.get_fans().await
+}
+
+#[derive(Deserialize)]
+pub struct FanPwmPayload {
+    pub pwm: u8,
+}
+
+#[post("/api/system/fans/{id}/pwm", output = "json", eoutput = "bytes")]
+pub async fn set_fan_pwm(
+    state: State<SystemMonitorPlugin>,
+    id: Path,
+    payload: Json<Option<FanPwmPayload>>,
+) -> Result<(), Error> {
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Fan PWM Payload",
+        ));
+    };
+    state.0.set_fan_pwm(&id.inner(), payload.pwm).await
+}
+
 #[get("/api/system/memory", output = "json", eoutput = "bytes")]
 pub async fn get_memory(state: State<SystemMonitorPlugin>) -> Result<MemoryInfo, Error> {
     state.0.get_memory_info().await
 }
 
+pub const DISK_SORT_FIELDS: [&str; 4] = ["name", "total", "used", "mount_path"];
+
 #[get("/api/system/disks", output = "json", eoutput = "bytes")]
-pub async fn get_disks(state: State<SystemMonitorPlugin>) -> Result<Vec<DiskInfo>, Error> {
-    state.0.get_disk_info().await
+pub async fn get_disks(
+    data: &mut ServiceData,
+    state: State<SystemMonitorPlugin>,
+) -> Result<ListResponse<DiskInfo>, Error> {
+    let query = ListQuery::parse(data.request.request.uri().query())?;
+    let sort = query.sort_field(&DISK_SORT_FIELDS, "name")?;
+    let mounted = query.bool_filter("mounted")?;
+    let mut disks: Vec<DiskInfo> = state
+        .0
+        .get_disk_info()
+        .await?
+        .into_iter()
+        .filter(|disk| {
+            mounted
+                .map(|mounted| disk.mount_path.is_some() == mounted)
+                .unwrap_or(true)
+        })
+        .collect();
+    disks.sort_by(|a, b| match sort {
+        "total" => a.total.cmp(&b.total),
+        "used" => a.used.cmp(&b.used),
+        "mount_path" => a.mount_path.cmp(&b.mount_path),
+        _ => a.name.cmp(&b.name),
+    });
+    if query.order_or(SortOrder::Asc) == SortOrder::Desc {
+        disks.reverse();
+    }
+    Ok(ListResponse::from_vec(disks, &query))
+}
+
+#[get(
+    "/api/system/disks/{name}/temperature",
+    output = "json",
+    eoutput = "bytes"
+)]
+pub async fn get_disk_temperature(
+    state: State<SystemMonitorPlugin>,
+    name: Path,
+) -> Result<DiskTemperature, Error> {
+    state.0.get_disk_temperature(&name.inner()).await
+}
+
+#[get("/api/system/disks/{name}/nvme", output = "json", eoutput = "bytes")]
+pub async fn get_disk_nvme_health(
+    state: State<SystemMonitorPlugin>,
+    name: Path,
+) -> Result<Value, Error> {
+    state.0.get_nvme_smart_log(&name.inner()).await
 }
 
 #[get("/api/system/networks", output = "json", eoutput = "bytes")]
@@ -673,17 +1945,278 @@ pub async fn get_networks(state: State<SystemMonitorPlugin>) -> Result<Vec<Netwo
     state.0.get_network_info().await
 }
 
+const INVENTORY_CACHE_DURATION: Duration = Duration::from_secs(30);
+
+#[get("/api/system/inventory", output = "bytes", eoutput = "bytes")]
+pub async fn get_inventory(
+    data: &mut ServiceData,
+    state: State<SystemMonitorPlugin>,
+    pool: State<SqlitePool>,
+) -> Result<String, Error> {
+    let as_yaml = data
+        .request
+        .request
+        .uri()
+        .query()
+        .map(|query| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .any(|(key, value)| key == "format" && value == "yaml")
+        })
+        .unwrap_or_default();
+    let mut cache = state.0.inventory.write().await;
+    let expired = cache
+        .as_ref()
+        .map(|(generated, _)| generated.elapsed() >= INVENTORY_CACHE_DURATION)
+        .unwrap_or(true);
+    if expired {
+        *cache = Some((Instant::now(), state.0.get_inventory(pool.as_ref()).await?));
+    }
+    let Some((_, inventory)) = cache.as_ref() else {
+        return Err(Error::other("Failed to load System Inventory"));
+    };
+    let (content_type, body) = if as_yaml {
+        (
+            "application/yaml",
+            serde_yaml::to_string(inventory).map_err(Error::other)?,
+        )
+    } else {
+        (
+            "application/json",
+            serde_json::to_string(inventory).map_err(Error::other)?,
+        )
+    };
+    data.response.headers_mut().insert(
+        HeaderName::from_static("content-type"),
+        HeaderValue::from_static(content_type),
+    );
+    Ok(body)
+}
+
+pub const SYSTEM_DISK_REFRESH_CONFIG_KEY: &str = "system_disk_refresh_secs";
+pub const SYSTEM_NET_REFRESH_CONFIG_KEY: &str = "system_net_refresh_secs";
+pub const SYSTEM_GPU_REFRESH_CONFIG_KEY: &str = "system_gpu_refresh_secs";
+const DEFAULT_DISK_REFRESH_SECS: u64 = 30;
+const DEFAULT_NET_REFRESH_SECS: u64 = 5;
+const DEFAULT_GPU_REFRESH_SECS: u64 = 1;
+pub const DISK_TEMP_ALERT_CONFIG_KEY: &str = "disk_temp_alert_celsius";
+const DEFAULT_DISK_TEMP_ALERT_CELSIUS: u32 = 55;
+pub const DISK_FULL_ALERT_CONFIG_KEY: &str = "disk_full_alert_percent";
+const DEFAULT_DISK_FULL_ALERT_PERCENT: u64 = 95;
+pub const CPU_TEMP_ALERT_CONFIG_KEY: &str = "cpu_temp_alert_celsius";
+const DEFAULT_CPU_TEMP_ALERT_CELSIUS: f32 = 85.0;
+pub const MEMORY_ALERT_CONFIG_KEY: &str = "memory_alert_percent";
+pub const SWAP_ALERT_CONFIG_KEY: &str = "swap_alert_percent";
+const DEFAULT_MEMORY_ALERT_PERCENT: u64 = 90;
+const DEFAULT_SWAP_ALERT_PERCENT: u64 = 80;
+
+fn usage_percent(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        used as f64 / total as f64 * 100.0
+    }
+}
+
+//Alerts only fire on the refresh where usage goes over the threshold, not on every refresh after
+fn crossed_threshold(previous: f64, current: f64, threshold: f64) -> bool {
+    current > threshold && previous <= threshold
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryAlerts {
+    pub memory_alert_percent: u64,
+    pub swap_alert_percent: u64,
+    pub memory_percent: f64,
+    pub swap_percent: f64,
+}
+
+async fn memory_alert_thresholds(config: &ConfigManager) -> (u64, u64) {
+    (
+        config
+            .get_as(MEMORY_ALERT_CONFIG_KEY)
+            .await
+            .unwrap_or(DEFAULT_MEMORY_ALERT_PERCENT),
+        config
+            .get_as(SWAP_ALERT_CONFIG_KEY)
+            .await
+            .unwrap_or(DEFAULT_SWAP_ALERT_PERCENT),
+    )
+}
+
+#[get("/api/system/memory/alerts", output = "json", eoutput = "bytes")]
+pub async fn get_memory_alerts(
+    state: State<SystemMonitorPlugin>,
+    config: State<RwLock<ConfigManager>>,
+) -> Result<MemoryAlerts, Error> {
+    let (memory_alert_percent, swap_alert_percent) =
+        memory_alert_thresholds(&*config.0.read().await).await;
+    let (memory_percent, swap_percent) = state.0.memory_percentages().await;
+    Ok(MemoryAlerts {
+        memory_alert_percent,
+        swap_alert_percent,
+        memory_percent,
+        swap_percent,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemRefreshConfig {
+    pub disk_refresh_secs: u64,
+    pub net_refresh_secs: u64,
+    pub gpu_refresh_secs: u64,
+}
+impl SystemRefreshConfig {
+    pub async fn load(config: &ConfigManager) -> Self {
+        Self {
+            disk_refresh_secs: config
+                .get_as(SYSTEM_DISK_REFRESH_CONFIG_KEY)
+                .await
+                .unwrap_or(DEFAULT_DISK_REFRESH_SECS),
+            net_refresh_secs: config
+                .get_as(SYSTEM_NET_REFRESH_CONFIG_KEY)
+                .await
+                .unwrap_or(DEFAULT_NET_REFRESH_SECS),
+            gpu_refresh_secs: config
+                .get_as(SYSTEM_GPU_REFRESH_CONFIG_KEY)
+                .await
+                .unwrap_or(DEFAULT_GPU_REFRESH_SECS),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SystemRefreshConfigPayload {
+    pub disk_refresh_secs: Option<u64>,
+    pub net_refresh_secs: Option<u64>,
+    pub gpu_refresh_secs: Option<u64>,
+}
+
+#[get("/api/system/refresh_config", output = "json", eoutput = "bytes")]
+pub async fn get_refresh_config(
+    config: State<RwLock<ConfigManager>>,
+) -> Result<SystemRefreshConfig, Error> {
+    Ok(SystemRefreshConfig::load(&*config.0.read().await).await)
+}
+
+#[post("/api/system/refresh_config", output = "json", eoutput = "bytes")]
+pub async fn set_refresh_config(
+    pool: State<SqlitePool>,
+    config: State<RwLock<ConfigManager>>,
+    payload: Json<Option<SystemRefreshConfigPayload>>,
+) -> Result<SystemRefreshConfig, Error> {
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Refresh Config Payload",
+        ));
+    };
+    let mut config = config.0.write().await;
+    for (key, value) in [
+        (SYSTEM_DISK_REFRESH_CONFIG_KEY, payload.disk_refresh_secs),
+        (SYSTEM_NET_REFRESH_CONFIG_KEY, payload.net_refresh_secs),
+        (SYSTEM_GPU_REFRESH_CONFIG_KEY, payload.gpu_refresh_secs),
+    ] {
+        if let Some(value) = value {
+            config
+                .set_as(key, &value, "system", Some(pool.as_ref()))
+                .await?;
+        }
+    }
+    Ok(SystemRefreshConfig::load(&config).await)
+}
+
 #[interval(1000)]
-pub async fn refresh_system_info(state: State<SystemMonitorPlugin>) -> Result<(), Error> {
+pub async fn refresh_system_info(
+    state: State<SystemMonitorPlugin>,
+    config: State<RwLock<ConfigManager>>,
+    notifier: State<WebhookNotifier>,
+    database: State<SqlitePool>,
+) -> Result<(), Error> {
+    let refresh_config = SystemRefreshConfig::load(&*config.0.read().await).await;
     debug!("Refreshing CPU usage");
     state.0.system.write().await.refresh_cpu_all();
+    state.0.refresh_cpu_temperatures().await;
+    let cpu_threshold = config
+        .0
+        .read()
+        .await
+        .get_as::<f32>(CPU_TEMP_ALERT_CONFIG_KEY)
+        .await
+        .unwrap_or(DEFAULT_CPU_TEMP_ALERT_CELSIUS);
+    if let Some(temperature) = state.0.check_cpu_temperature(cpu_threshold).await {
+        let metadata = json!({
+            "temperature_celsius": temperature,
+            "threshold_celsius": cpu_threshold,
+        });
+        if let Err(e) = log_event(
+            &database,
+            EventSeverity::Error,
+            "temperature",
+            &format!("CPU is at {temperature:.1}°C, above the {cpu_threshold}°C alert threshold"),
+            Some(metadata.clone()),
+        )
+        .await
+        {
+            warn!("Failed to record CPU Temperature event: {e:?}");
+        }
+        notifier.0.send("high_temp", metadata).await;
+    }
+    let (fan_curve, fan_ids): (Vec<(u8, u8)>, Vec<String>) = {
+        let config = config.0.read().await;
+        (
+            config.get_or_default(FAN_CURVE_CONFIG_KEY).await,
+            config.get_or_default(FAN_CURVE_FANS_CONFIG_KEY).await,
+        )
+    };
+    if fan_curve.is_empty() {
+        state.0.restore_fan_control().await;
+    } else {
+        state.0.apply_fan_curve(&fan_curve, &fan_ids).await;
+    }
     debug!("Refreshing Memory usage");
     state.0.system.write().await.refresh_memory();
+    let (memory_threshold, swap_threshold) = memory_alert_thresholds(&*config.0.read().await).await;
+    for (name, percent, threshold) in state
+        .0
+        .check_memory_pressure(memory_threshold, swap_threshold)
+        .await
+    {
+        let metadata = json!({
+            "kind": name,
+            "percent": percent,
+            "threshold_percent": threshold,
+        });
+        if let Err(e) = log_event(
+            &database,
+            EventSeverity::Warn,
+            "memory_pressure",
+            &format!(
+                "System {name} usage is at {percent:.1}%, above the {threshold}% alert threshold"
+            ),
+            Some(metadata.clone()),
+        )
+        .await
+        {
+            warn!("Failed to record Memory Pressure event: {e:?}");
+        }
+        notifier.0.send("memory_pressure", metadata).await;
+    }
+    debug!("Refreshing Process usage");
+    state
+        .0
+        .system
+        .write()
+        .await
+        .refresh_processes(ProcessesToUpdate::All, true);
     let now_seconds = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Expected System Time to be After EPOCH")
         .as_secs();
-    if now_seconds - state.0.last_disk_update.load(Ordering::Relaxed) >= 30 {
+    if now_seconds - state.0.last_disk_update.load(Ordering::Relaxed)
+        >= refresh_config.disk_refresh_secs
+    {
         debug!("Refreshing Disk usage");
         if let Err(e) = state.0.disks.write().await.reload_disks().await {
             error!("Failed to Update Disk Usage: {e:?}");
@@ -692,9 +2225,68 @@ pub async fn refresh_system_info(state: State<SystemMonitorPlugin>) -> Result<()
                 .0
                 .last_disk_update
                 .store(now_seconds, Ordering::Relaxed);
+            debug!("Refreshing Disk temperatures");
+            state.0.refresh_disk_temperatures().await;
+            state.0.refresh_nvme_health().await;
+            let threshold = config
+                .0
+                .read()
+                .await
+                .get_as::<u32>(DISK_TEMP_ALERT_CONFIG_KEY)
+                .await
+                .unwrap_or(DEFAULT_DISK_TEMP_ALERT_CELSIUS);
+            for (name, temperature) in state.0.check_disk_temperatures(threshold).await {
+                let metadata = json!({
+                    "disk": name,
+                    "temperature_celsius": temperature,
+                    "threshold_celsius": threshold,
+                });
+                if let Err(e) = log_event(
+                    &database,
+                    EventSeverity::Error,
+                    "temperature",
+                    &format!("Disk {name} is at {temperature}°C, above the {threshold}°C alert threshold"),
+                    Some(metadata.clone()),
+                )
+                .await
+                {
+                    warn!("Failed to record Disk Temperature event: {e:?}");
+                }
+                notifier.0.send("disk_temperature_alert", metadata).await;
+            }
+            let threshold = config
+                .0
+                .read()
+                .await
+                .get_as::<u64>(DISK_FULL_ALERT_CONFIG_KEY)
+                .await
+                .unwrap_or(DEFAULT_DISK_FULL_ALERT_PERCENT);
+            for (name, percent) in state.0.check_disk_usage(threshold).await {
+                let metadata = json!({
+                    "disk": name,
+                    "used_percent": percent,
+                    "threshold_percent": threshold,
+                });
+                if let Err(e) = log_event(
+                    &database,
+                    EventSeverity::Error,
+                    "disk_full",
+                    &format!(
+                        "Disk {name} is {percent:.1}% full, above the {threshold}% alert threshold"
+                    ),
+                    Some(metadata.clone()),
+                )
+                .await
+                {
+                    warn!("Failed to record Disk Full event: {e:?}");
+                }
+                notifier.0.send("disk_full", metadata).await;
+            }
         }
     }
-    if now_seconds - state.0.last_net_update.load(Ordering::Relaxed) >= 5 {
+    if now_seconds - state.0.last_net_update.load(Ordering::Relaxed)
+        >= refresh_config.net_refresh_secs
+    {
         debug!("Refreshing Network usage");
         for device in all_devices().await? {
             match &device {
@@ -722,17 +2314,218 @@ pub async fn refresh_system_info(state: State<SystemMonitorPlugin>) -> Result<()
             .last_net_update
             .store(now_seconds, Ordering::Relaxed);
     }
-    debug!("Refreshing GPU usage");
-    let mut gpus = vec![];
-    if let Some(nvml) = state.0.nvml.read().await.as_ref() {
-        gpus.extend(SystemMonitorPlugin::get_nvidia_gpu_info(nvml));
-        debug!("Finished Nvidia GPU refresh");
-    }
-    if state.0.detected_amd_gpu.load(Ordering::Relaxed) {
-        gpus.extend(SystemMonitorPlugin::get_amd_gpu_info().await);
-        debug!("Finished AMD GPU refresh");
+    if now_seconds - state.0.last_gpu_update.load(Ordering::Relaxed)
+        >= refresh_config.gpu_refresh_secs
+    {
+        debug!("Refreshing GPU usage");
+        let mut gpus = vec![];
+        if let Some(nvml) = state.0.nvml.read().await.as_ref() {
+            gpus.extend(SystemMonitorPlugin::get_nvidia_gpu_info(nvml));
+            debug!("Finished Nvidia GPU refresh");
+        }
+        if state.0.detected_amd_gpu.load(Ordering::Relaxed) {
+            gpus.extend(SystemMonitorPlugin::get_amd_gpu_info().await);
+            debug!("Finished AMD GPU refresh");
+        }
+        *state.0.gpus.write().await = gpus;
+        state
+            .0
+            .last_gpu_update
+            .store(now_seconds, Ordering::Relaxed);
     }
-    *state.0.gpus.write().await = gpus;
     debug!("Refreshed System Values");
     Ok(())
 }
+
+#[test]
+fn test_parse_ipv6_addresses() {
+    let addresses = parse_ipv6_addresses(&[
+        vec![
+            "2001:db8::10".to_string(),
+            "64".to_string(),
+            "2001:db8::1".to_string(),
+        ],
+        vec![
+            "fe80::1c2b:3cff:fe4d:5e6f".to_string(),
+            "64".to_string(),
+            "".to_string(),
+        ],
+        vec![
+            "not an address".to_string(),
+            "64".to_string(),
+            "".to_string(),
+        ],
+    ]);
+    assert_eq!(addresses.len(), 2);
+    assert!(!addresses[0].link_local);
+    assert_eq!(addresses[0].net_mask, 64);
+    assert!(addresses[1].link_local);
+    assert_eq!(addresses[1].gateway, IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+}
+
+#[test]
+fn test_parse_dhcp_lease() {
+    let options = HashMap::from([
+        (
+            "dhcp_server_identifier".to_string(),
+            "192.168.1.1".to_string(),
+        ),
+        ("expiry".to_string(), "1760630400".to_string()),
+        ("domain_name".to_string(), "lan".to_string()),
+    ]);
+    let lease = parse_dhcp_lease(&options).expect("Expected DHCP Lease");
+    assert_eq!(
+        lease.server_address,
+        IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))
+    );
+    assert_eq!(lease.lease_expiry.unix_timestamp(), 1760630400);
+    assert_eq!(lease.domain.as_deref(), Some("lan"));
+    assert!(parse_dhcp_lease(&HashMap::new()).is_none());
+}
+
+#[test]
+fn test_parse_smartctl_temperature() {
+    let current = json!({ "temperature": { "current": 41 } });
+    assert_eq!(parse_smartctl_temperature(&current), Some(41));
+    let attributes = json!({
+        "ata_smart_attributes": {
+            "table": [
+                { "name": "Power_On_Hours", "raw": { "value": 12000 } },
+                { "name": "Temperature_Celsius", "raw": { "value": 163209314350u64 } }
+            ]
+        }
+    });
+    assert_eq!(parse_smartctl_temperature(&attributes), Some(46));
+    assert_eq!(parse_smartctl_temperature(&json!({})), None);
+}
+
+#[tokio::test]
+async fn test_read_cpu_temperatures() {
+    let hwmon_root = std::env::temp_dir().join(format!("dg_hwmon_test_{}", std::process::id()));
+    let coretemp = hwmon_root.join("hwmon0");
+    let nvme = hwmon_root.join("hwmon1");
+    tokio::fs::create_dir_all(&coretemp).await.unwrap();
+    tokio::fs::create_dir_all(&nvme).await.unwrap();
+    for (sensor, label, input) in [
+        ("temp1", "Package id 0", "52000"),
+        ("temp2", "Core 0", "48000"),
+        ("temp3", "Core 1", "51500"),
+    ] {
+        tokio::fs::write(coretemp.join(format!("{sensor}_label")), label)
+            .await
+            .unwrap();
+        tokio::fs::write(coretemp.join(format!("{sensor}_input")), input)
+            .await
+            .unwrap();
+    }
+    tokio::fs::write(nvme.join("temp1_label"), "Composite")
+        .await
+        .unwrap();
+    tokio::fs::write(nvme.join("temp1_input"), "39850")
+        .await
+        .unwrap();
+    let temperatures = read_cpu_temperatures(&hwmon_root).await;
+    tokio::fs::remove_dir_all(&hwmon_root).await.unwrap();
+    let temperatures = temperatures.unwrap();
+    assert_eq!(temperatures.package, Some(52.0));
+    assert_eq!(temperatures.cores.len(), 2);
+    assert_eq!(temperatures.cores.get(&0), Some(&48.0));
+    assert_eq!(temperatures.cores.get(&1), Some(&51.5));
+}
+
+#[test]
+fn test_parse_nvme_health() {
+    let smart_log = json!({
+        "critical_warning": 0,
+        "temperature": 310,
+        "avail_spare": 8,
+        "spare_thresh": 10,
+        "percent_used": 93
+    });
+    assert_eq!(
+        parse_nvme_health(&smart_log),
+        Some(NvmeHealth {
+            critical_warning: 0,
+            available_spare: 8,
+            available_spare_threshold: 10,
+            percentage_used: 93,
+        })
+    );
+    let wrapped = json!({
+        "critical_warning": { "value": 4 },
+        "available_spare": 100,
+        "available_spare_threshold": 10,
+        "percentage_used": 1
+    });
+    assert_eq!(parse_nvme_health(&wrapped).unwrap().critical_warning, 4);
+    assert_eq!(parse_nvme_health(&json!({})), None);
+}
+
+#[test]
+fn test_fan_curve_pwm() {
+    let curve = [(60, 255), (30, 64), (45, 128)];
+    assert_eq!(fan_curve_pwm(&curve, 20.0), Some(64));
+    assert_eq!(fan_curve_pwm(&curve, 30.0), Some(64));
+    assert_eq!(fan_curve_pwm(&curve, 37.5), Some(96));
+    assert_eq!(fan_curve_pwm(&curve, 52.5), Some(192));
+    assert_eq!(fan_curve_pwm(&curve, 90.0), Some(255));
+    assert_eq!(fan_curve_pwm(&[], 40.0), None);
+}
+
+#[test]
+fn test_validate_fan_curve() {
+    assert!(validate_fan_curve("[[30, 96], [60, 255]]").is_ok());
+    assert!(validate_fan_curve("[]").is_ok());
+    assert!(validate_fan_curve("[[0, 0]]").is_err());
+    assert!(validate_fan_curve("[[120, 255]]").is_err());
+    assert!(validate_fan_curve("not a curve").is_err());
+}
+
+#[tokio::test]
+async fn test_read_fans() {
+    let hwmon_root = std::env::temp_dir().join(format!("dg_fans_test_{}", std::process::id()));
+    let device = hwmon_root.join("hwmon3");
+    tokio::fs::create_dir_all(&device).await.unwrap();
+    for (file, value) in [
+        ("name", "nct6775"),
+        ("fan1_input", "1250"),
+        ("fan1_label", "CPU Fan"),
+        ("pwm1", "128"),
+        ("fan2_input", "0"),
+    ] {
+        tokio::fs::write(device.join(file), value).await.unwrap();
+    }
+    let fans = read_fans(&hwmon_root).await;
+    let set_invalid = write_fan_pwm(&hwmon_root, "../hwmon3-1", 10).await;
+    tokio::fs::remove_dir_all(&hwmon_root).await.unwrap();
+    let fans = fans.unwrap();
+    assert_eq!(fans.len(), 2);
+    assert_eq!(fans[0].id, "hwmon3-1");
+    assert_eq!(fans[0].name, "CPU Fan");
+    assert_eq!(fans[0].chip, "nct6775");
+    assert_eq!(fans[0].rpm, 1250);
+    assert_eq!(fans[0].pwm, 128);
+    assert_eq!(fans[1].name, "nct6775 fan2");
+    assert_eq!(fans[1].pwm, 0);
+    assert_eq!(set_invalid.unwrap_err().kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_process_sort_from_str() {
+    assert_eq!(ProcessSort::from_str("cpu").unwrap(), ProcessSort::Cpu);
+    assert_eq!(
+        ProcessSort::from_str("Memory").unwrap(),
+        ProcessSort::Memory
+    );
+    assert!(ProcessSort::from_str("disk").is_err());
+}
+
+#[test]
+fn test_memory_pressure_threshold() {
+    assert_eq!(usage_percent(0, 0), 0.0);
+    assert_eq!(usage_percent(45, 50), 90.0);
+    assert!(crossed_threshold(85.0, 91.0, 90.0));
+    assert!(!crossed_threshold(92.0, 95.0, 90.0));
+    assert!(!crossed_threshold(85.0, 90.0, 90.0));
+    assert!(crossed_threshold(80.0, 96.0, 90.0));
+}
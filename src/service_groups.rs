@@ -1,31 +1,71 @@
-use crate::plugins::disk_management::{mount, unmount};
+use crate::plugins::disk_management::{disk_events, mount, unmount};
 use crate::plugins::file_manager::{
-    create_directory, create_file, get_file, list_files, remove, rename, update_file,
+    archive_progress, compress_files, create_directory, create_file, empty_trash, extract_archive,
+    file_metadata, get_file, list_files, list_trash, remove, rename, restore_trash,
+    set_permissions, trash_file, update_file, watch_files,
 };
+use crate::plugins::gpio_manager::{get_gpio_chips, get_input_value, watch_input_pin};
 use crate::plugins::system_monitor::{
-    get_cpu, get_disks, get_gpus, get_info, get_memory, get_networks,
+    get_cpu, get_disk_nvme_health, get_disk_temperature, get_disks, get_fans, get_gpus, get_info,
+    get_inventory, get_memory, get_memory_alerts, get_networks, get_refresh_config,
+    get_top_processes, search_processes, set_fan_pwm, set_gpu_power_limit, set_refresh_config,
 };
+use crate::web::audit::get_audit_log;
 use crate::web::auth::{
-    register_endpoint, user_requires_password_update, user_update_password, BasicAuthHandle,
+    argon2_params, clear_lockout, delete_my_preference, get_lockout, get_my_preference,
+    get_my_preferences, get_session_timeout, list_users, refresh_jwt, register_endpoint,
+    remove_user, set_my_preference, set_session_timeout, set_user_role, totp_confirm, totp_disable,
+    totp_setup, totp_verify, user_requires_password_update, user_update_password, BasicAuthHandle,
 };
-use crate::web::config::{config_entry, configs, del_config, upload_config};
+use crate::web::config::{
+    bulk_config, config_entry, config_history, config_schema, configs, del_config, export_config,
+    import_config, reload_config, rollback_config, upload_config, watch_config,
+};
+use crate::web::database::{
+    backup_database, list_backups, restore_database, restore_database_upload,
+};
+use crate::web::events::get_system_events;
 use crate::web::farmer::{
-    farmer_log_stream, farmer_status, generate_from_mnemonic, get_config, get_farmer_metrics,
-    get_farmer_state, get_farmer_stats, get_farmer_stats_range, get_pool_login, is_config_ready,
-    restart_farmer, scan_for_legacy_configs, start_farmer, stop_farmer, update_config,
+    add_plot_directory, diff_config, export_farmer_stats, farmer_install_progress,
+    farmer_log_stream, farmer_stats_by_launcher, farmer_stats_launchers, farmer_stats_size,
+    farmer_stats_stream, farmer_stats_summary, farmer_status, generate_from_mnemonic, get_config,
+    get_config_yaml, get_farmer_connections, get_farmer_metrics, get_farmer_rewards,
+    get_farmer_rewards_total, get_farmer_state, get_farmer_stats, get_farmer_stats_range,
+    get_farmer_stderr_log, get_plot_directories, get_plot_directory_stats, get_pool_login,
+    get_stats_policy, import_legacy_configs, is_config_ready, refresh_farmer_connections,
+    remove_plot_directory, restart_farmer, scan_for_legacy_configs, set_stats_policy, start_farmer,
+    stop_farmer, update_config, update_config_yaml,
 };
 use crate::web::leds::{
-    clear_pin_modes, get_brightness, get_pin_value, set_brightness, set_color_mode, set_pin_mode
+    apply_preset, clear_pin_modes, delete_preset, get_brightness, get_pin_value, get_presets,
+    get_pwm_period, get_sequence, leds_off, save_preset, set_brightness, set_color_mode,
+    set_color_temperature, set_pin_mode, set_pwm_period, set_sequence, set_status_mode,
+};
+use crate::web::logs::{clear_log_buffer, export_logs, log_buffer_size};
+use crate::web::metrics::prometheus_metrics;
+use crate::web::notifications::{
+    get_email_notifier, get_webhooks, set_email_notifier, set_webhooks, test_email_notifier,
 };
 use crate::web::plugins::{
-    add_plugin, all_plugins, available_plugins, del_plugin, del_plugin_environment_value,
-    get_plugin_environment, get_plugin_environment_value, plugin, plugin_status, plugin_updates,
-    refresh_plugins, set_plugin_environment_value, start_plugin, stop_plugin, update_plugin,
+    add_plugin, all_plugins, apply_plugin_updates, available_plugins, del_plugin,
+    del_plugin_environment_value, docker_disk_usage, docker_images, get_plugin_environment,
+    get_plugin_environment_value, plugin, plugin_resource_usage, plugin_status,
+    plugin_store_cache_age, plugin_store_url, plugin_update_available, plugin_updates,
+    popular_plugins, prune_docker_images, refresh_plugins, remove_docker_image, restart_plugin,
+    running_plugins, set_plugin_enabled, set_plugin_environment_value, set_plugin_store_url,
+    start_plugin, stop_plugin, stopped_plugins, uninstall_plugin, update_plugin,
+    update_plugin_binary,
 };
 use crate::web::system::{
-    do_updates, find_device, find_updates, hotspot_active, hotspot_clean, hotspot_restart,
-    hotspot_start, hotspot_stop, is_online, wifi_connect, wifi_scan,
+    delete_saved_wifi, do_updates, find_device, find_updates, get_available_timezones,
+    get_hotspot_config, get_internet_check_config, get_saved_wifi, get_time_status, get_timezone,
+    get_tls_status, get_uptime, health, health_ready, hotspot_active, hotspot_clean,
+    hotspot_restart, hotspot_start, hotspot_stop, internet_check, is_online, log_event_stream,
+    log_stream, reboot_system, request_acme_certificate, set_hotspot_config,
+    set_internet_check_config, set_time, set_timezone, shutdown_system, sync_time, system_stream,
+    update_saved_wifi, upload_tls_certificate, wifi_connect, wifi_scan,
 };
+use crate::web::wizard::{complete_wizard_step, wizard_state};
 use portfu::prelude::ServiceGroup;
 use portfu_admin::auth::{basic_login, get_jwt};
 
@@ -34,8 +74,14 @@ pub fn none_group(basic_auth: BasicAuthHandle) -> ServiceGroup {
         .shared_state(basic_auth)
         .service(find_device)
         .service(find_updates)
+        .service(health)
+        .service(health_ready)
         .service(get_jwt)
+        .service(refresh_jwt)
+        .service(totp_verify)
         .service(register_endpoint)
+        .service(prometheus_metrics)
+        .service(wizard_state)
         .service(basic_login::<BasicAuthHandle>::default())
 }
 
@@ -43,18 +89,50 @@ pub fn user_groups() -> ServiceGroup {
     ServiceGroup::default()
         .service(user_update_password)
         .service(user_requires_password_update)
+        .service(totp_setup)
+        .service(totp_confirm)
+        .service(totp_disable)
+        .service(get_my_preferences)
+        .service(get_my_preference)
+        .service(set_my_preference)
+        .service(delete_my_preference)
+        .service(complete_wizard_step)
 }
 
 pub fn viewer_group() -> ServiceGroup {
     ServiceGroup::default()
         .service(is_online)
+        .service(internet_check)
         .service(hotspot_active)
         .service(is_config_ready)
         .service(farmer_status)
         .service(get_farmer_metrics)
+        .service(get_farmer_stderr_log)
+        .service(get_system_events)
+        .service(log_event_stream)
+        .service(log_stream {
+            peers: Default::default(),
+        })
+        .service(system_stream {
+            peers: Default::default(),
+        })
         .service(get_farmer_stats)
         .service(get_farmer_state)
         .service(get_farmer_stats_range)
+        .service(export_farmer_stats)
+        .service(farmer_stats_summary)
+        .service(farmer_stats_launchers)
+        .service(farmer_install_progress)
+        .service(get_stats_policy)
+        .service(farmer_stats_size)
+        .service(farmer_stats_stream {
+            peers: Default::default(),
+        })
+        .service(farmer_stats_by_launcher)
+        .service(get_farmer_rewards)
+        .service(get_farmer_rewards_total)
+        .service(get_farmer_connections)
+        .service(refresh_farmer_connections)
         .service(get_pool_login)
         .service(farmer_log_stream {
             peers: Default::default(),
@@ -62,18 +140,31 @@ pub fn viewer_group() -> ServiceGroup {
         .service(start_farmer)
         .service(stop_farmer)
         .service(restart_farmer)
+        .service(plugin_store_cache_age)
+        .service(plugin_store_url)
+        .service(running_plugins)
+        .service(stopped_plugins)
+        .service(popular_plugins)
         .service(plugin)
         .service(all_plugins)
         .service(available_plugins)
         .service(plugin_updates)
         .service(refresh_plugins)
         .service(plugin_status)
+        .service(plugin_resource_usage)
         .service(get_info)
+        .service(get_top_processes)
+        .service(search_processes)
         .service(get_cpu)
         .service(get_gpus)
+        .service(get_fans)
+        .service(get_memory_alerts)
         .service(get_memory)
         .service(get_disks)
+        .service(get_disk_temperature)
+        .service(get_disk_nvme_health)
         .service(get_networks)
+        .service(get_inventory)
         .service(scan_for_legacy_configs)
         .service(generate_from_mnemonic)
 }
@@ -82,10 +173,20 @@ pub fn editor_group() -> ServiceGroup {
     ServiceGroup::default()
         .service(set_pin_mode)
         .service(set_color_mode)
+        .service(set_color_temperature)
+        .service(leds_off)
         .service(get_pin_value)
         .service(clear_pin_modes)
         .service(set_brightness)
         .service(get_brightness)
+        .service(get_pwm_period)
+        .service(set_sequence)
+        .service(get_sequence)
+        .service(get_presets)
+        .service(save_preset)
+        .service(delete_preset)
+        .service(apply_preset)
+        .service(set_status_mode)
         .service(do_updates)
         .service(wifi_scan)
         .service(wifi_connect)
@@ -93,8 +194,16 @@ pub fn editor_group() -> ServiceGroup {
         .service(hotspot_stop)
         .service(hotspot_start)
         .service(hotspot_restart)
+        .service(get_config_yaml)
+        .service(update_config_yaml)
         .service(get_config)
+        .service(diff_config)
         .service(update_config)
+        .service(import_legacy_configs)
+        .service(get_plot_directory_stats)
+        .service(get_plot_directories)
+        .service(add_plot_directory)
+        .service(remove_plot_directory)
         .service(get_plugin_environment)
         .service(get_plugin_environment)
         .service(get_plugin_environment)
@@ -103,6 +212,7 @@ pub fn editor_group() -> ServiceGroup {
         .service(del_plugin_environment_value)
         .service(start_plugin)
         .service(stop_plugin)
+        .service(restart_plugin)
 }
 
 pub fn manager_group() -> ServiceGroup {
@@ -110,10 +220,25 @@ pub fn manager_group() -> ServiceGroup {
         .service(add_plugin)
         .service(update_plugin)
         .service(del_plugin)
+        .service(set_plugin_enabled)
+        .service(uninstall_plugin)
+        .service(plugin_update_available)
+        .service(update_plugin_binary)
+        .service(apply_plugin_updates)
+        .service(config_schema)
+        .service(export_config)
+        .service(bulk_config)
+        .service(import_config)
+        .service(config_history)
+        .service(rollback_config)
         .service(config_entry)
         .service(configs)
         .service(upload_config)
         .service(del_config)
+        .service(watch_config {
+            peers: Default::default(),
+        })
+        .service(disk_events)
         .service(mount)
         .service(unmount)
         .service(list_files)
@@ -123,12 +248,83 @@ pub fn manager_group() -> ServiceGroup {
         .service(create_directory)
         .service(rename)
         .service(remove)
+        .service(list_trash)
+        .service(trash_file)
+        .service(restore_trash)
+        .service(empty_trash)
+        .service(compress_files)
+        .service(extract_archive)
+        .service(archive_progress {
+            peers: Default::default(),
+        })
+        .service(set_permissions)
+        .service(file_metadata)
+        .service(watch_files {
+            peers: Default::default(),
+        })
 }
 
 pub fn admin_group() -> ServiceGroup {
     ServiceGroup::default()
+        .service(list_users)
+        .service(remove_user)
+        .service(get_session_timeout)
+        .service(set_session_timeout)
+        .service(get_refresh_config)
+        .service(set_refresh_config)
+        .service(export_logs)
+        .service(log_buffer_size)
+        .service(clear_log_buffer)
+        .service(set_gpu_power_limit)
+        .service(set_fan_pwm)
+        .service(set_pwm_period)
+        .service(set_plugin_store_url)
+        .service(get_lockout)
+        .service(clear_lockout)
+        .service(backup_database)
+        .service(list_backups)
+        .service(restore_database)
+        .service(restore_database_upload)
+        .service(get_webhooks)
+        .service(set_webhooks)
+        .service(get_email_notifier)
+        .service(test_email_notifier)
+        .service(set_email_notifier)
+        .service(get_audit_log)
+        .service(reload_config)
+        .service(set_stats_policy)
+        .service(reboot_system)
+        .service(shutdown_system)
+        .service(get_uptime)
+        .service(get_available_timezones)
+        .service(get_timezone)
+        .service(set_timezone)
+        .service(get_time_status)
+        .service(sync_time)
+        .service(set_time)
+        .service(get_tls_status)
+        .service(upload_tls_certificate)
+        .service(request_acme_certificate)
+        .service(get_saved_wifi)
+        .service(delete_saved_wifi)
+        .service(update_saved_wifi)
+        .service(get_hotspot_config)
+        .service(set_hotspot_config)
+        .service(get_internet_check_config)
+        .service(set_internet_check_config)
+        .service(docker_images)
+        .service(docker_disk_usage)
+        .service(prune_docker_images)
+        .service(remove_docker_image)
+        .service(get_gpio_chips)
+        .service(get_input_value)
+        .service(watch_input_pin {
+            peers: Default::default(),
+        })
 }
 
 pub fn super_group() -> ServiceGroup {
     ServiceGroup::default()
+        .service(set_user_role)
+        .service(argon2_params)
 }
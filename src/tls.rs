@@ -0,0 +1,464 @@
+use crate::config::ConfigManager;
+use dashmap::DashMap;
+use instant_acme::{
+    Account, AuthorizationStatus, ChallengeType, Identifier, LetsEncrypt, NewAccount, NewOrder,
+    OrderStatus,
+};
+use log::{debug, error, info, warn};
+use rcgen::{CertificateParams, DistinguishedName, KeyPair};
+use rustls::crypto::aws_lc_rs::sign::any_supported_type;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+
+//Only the TLS endpoints may write the paths, see PROTECTED_CONFIG_KEYS
+pub const TLS_CERT_PATH_CONFIG_KEY: &str = "tls_cert_path";
+pub const TLS_KEY_PATH_CONFIG_KEY: &str = "tls_key_path";
+pub const TLS_HOSTNAME_CONFIG_KEY: &str = "tls_hostname";
+pub const ACME_HTTP_PORT: u16 = 80;
+const ACME_CHALLENGE_PREFIX: &str = "/.well-known/acme-challenge/";
+const ACME_POLL_ATTEMPTS: u32 = 10;
+
+//Maps the local address of each proxied connection to the client that opened the TLS connection
+static TLS_CLIENTS: LazyLock<DashMap<SocketAddr, SocketAddr>> = LazyLock::new(DashMap::new);
+
+/// Returns the real client of a connection made by the TLS listener to the http server
+pub fn tls_client_address(peer: &SocketAddr) -> Option<SocketAddr> {
+    TLS_CLIENTS.get(peer).map(|client| *client)
+}
+
+pub fn load_certified_key(cert_pem: &[u8], key_pem: &[u8]) -> Result<CertifiedKey, Error> {
+    let certs = CertificateDer::pem_slice_iter(cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid Certificate: {e:?}"),
+            )
+        })?;
+    if certs.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "No Certificate found in PEM",
+        ));
+    }
+    let key = PrivateKeyDer::from_pem_slice(key_pem).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid Private Key: {e:?}"),
+        )
+    })?;
+    let signing_key = any_supported_type(&key).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Unsupported Private Key: {e}"),
+        )
+    })?;
+    let certified_key = CertifiedKey::new(certs, signing_key);
+    certified_key.keys_match().map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Private Key does not match Certificate: {e}"),
+        )
+    })?;
+    Ok(certified_key)
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsPaths {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+impl TlsPaths {
+    /// Reads the paths stored in tls_cert_path and tls_key_path, falling back to the defaults
+    pub async fn from_config(
+        config_manager: &ConfigManager,
+        default_cert: &str,
+        default_key: &str,
+    ) -> Self {
+        let path = |entry: Option<String>, default: &str| {
+            PathBuf::from(
+                entry
+                    .filter(|value| !value.is_empty())
+                    .unwrap_or_else(|| default.to_string()),
+            )
+        };
+        Self {
+            cert_path: path(
+                config_manager
+                    .get(TLS_CERT_PATH_CONFIG_KEY)
+                    .await
+                    .map(|entry| entry.value),
+                default_cert,
+            ),
+            key_path: path(
+                config_manager
+                    .get(TLS_KEY_PATH_CONFIG_KEY)
+                    .await
+                    .map(|entry| entry.value),
+                default_key,
+            ),
+        }
+    }
+}
+
+/// The certificate served by the TLS listener
+#[derive(Debug)]
+pub struct TlsCertificates {
+    paths: RwLock<TlsPaths>,
+    certified_key: RwLock<Option<Arc<CertifiedKey>>>,
+}
+impl TlsCertificates {
+    pub async fn new(paths: TlsPaths) -> Self {
+        let certificates = Self {
+            paths: RwLock::new(paths),
+            certified_key: RwLock::new(None),
+        };
+        if let Err(e) = certificates.reload().await {
+            warn!("No TLS Certificate loaded, HTTPS is disabled until one is installed: {e}");
+        }
+        certificates
+    }
+    pub fn paths(&self) -> Result<TlsPaths, Error> {
+        Ok(self
+            .paths
+            .read()
+            .map_err(|_| Error::other("TLS Paths Lock Poisoned"))?
+            .clone())
+    }
+    //Swaps in the files on disk, new handshakes use them without restarting the server
+    pub async fn reload(&self) -> Result<(), Error> {
+        let paths = self.paths()?;
+        let cert_pem = tokio::fs::read(&paths.cert_path).await?;
+        let key_pem = tokio::fs::read(&paths.key_path).await?;
+        self.set_certified_key(load_certified_key(&cert_pem, &key_pem)?)?;
+        info!("Loaded TLS Certificate {}", paths.cert_path.display());
+        Ok(())
+    }
+    /// Writes a certificate and key to `paths` and serves them from the next handshake
+    pub async fn install(
+        &self,
+        cert_pem: &[u8],
+        key_pem: &[u8],
+        paths: TlsPaths,
+    ) -> Result<(), Error> {
+        let certified_key = load_certified_key(cert_pem, key_pem)?;
+        for path in [&paths.cert_path, &paths.key_path] {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+        }
+        tokio::fs::write(&paths.cert_path, cert_pem).await?;
+        let mut key_file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&paths.key_path)
+            .await?;
+        key_file.write_all(key_pem).await?;
+        key_file.flush().await?;
+        self.set_certified_key(certified_key)?;
+        info!("Installed TLS Certificate {}", paths.cert_path.display());
+        *self
+            .paths
+            .write()
+            .map_err(|_| Error::other("TLS Paths Lock Poisoned"))? = paths;
+        Ok(())
+    }
+    fn set_certified_key(&self, certified_key: CertifiedKey) -> Result<(), Error> {
+        *self
+            .certified_key
+            .write()
+            .map_err(|_| Error::other("TLS Certificate Lock Poisoned"))? =
+            Some(Arc::new(certified_key));
+        Ok(())
+    }
+}
+impl ResolvesServerCert for TlsCertificates {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.certified_key.read().ok()?.clone()
+    }
+}
+
+/// Terminates TLS on tls_port and proxies the connection to the http server on http_port
+pub async fn run_tls_listener(
+    hostname: String,
+    tls_port: u16,
+    http_port: u16,
+    certificates: Arc<TlsCertificates>,
+) -> Result<(), Error> {
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_cert_resolver(certificates);
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let upstream = match hostname.parse::<IpAddr>() {
+        Ok(address) if !address.is_unspecified() => SocketAddr::new(address, http_port),
+        _ => SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), http_port),
+    };
+    let listener = TcpListener::bind((hostname.as_str(), tls_port)).await?;
+    info!("Serving HTTPS on {hostname}:{tls_port}");
+    loop {
+        let (stream, client) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Failed to accept TLS connection: {e}");
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        tokio::spawn(async move {
+            if let Err(e) = proxy_tls_connection(acceptor, stream, client, upstream).await {
+                debug!("TLS connection from {client} closed: {e}");
+            }
+        });
+    }
+}
+
+async fn proxy_tls_connection(
+    acceptor: TlsAcceptor,
+    stream: TcpStream,
+    client: SocketAddr,
+    upstream: SocketAddr,
+) -> Result<(), Error> {
+    let mut tls_stream = acceptor.accept(stream).await?;
+    let mut http_stream = TcpStream::connect(upstream).await?;
+    //Registered before any bytes are sent so the first request already sees the real client
+    let local = http_stream.local_addr()?;
+    TLS_CLIENTS.insert(local, client);
+    let result = tokio::io::copy_bidirectional(&mut tls_stream, &mut http_stream).await;
+    TLS_CLIENTS.remove(&local);
+    result.map(|_| ())
+}
+
+pub fn validate_tls_hostname(hostname: &str) -> Result<(), Error> {
+    let valid = !hostname.is_empty()
+        && hostname.len() <= 253
+        && hostname.split('.').count() > 1
+        && hostname.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{hostname} is not a valid DNS Hostname"),
+        ))
+    }
+}
+
+fn acme_error(action: &str) -> impl Fn(instant_acme::Error) -> Error + '_ {
+    move |e| Error::other(format!("ACME failed to {action}: {e}"))
+}
+
+/// Issues a certificate for `hostname` from Let's Encrypt and returns the PEM chain and key.
+/// The HTTP-01 challenge is answered on port 80, so the hostname must resolve to this device
+pub async fn issue_acme_certificate(
+    hostname: &str,
+    contact_email: Option<&str>,
+    staging: bool,
+) -> Result<(String, String), Error> {
+    validate_tls_hostname(hostname)?;
+    let contact = contact_email.map(|email| format!("mailto:{email}"));
+    let contact: Vec<&str> = contact.iter().map(String::as_str).collect();
+    let directory = if staging {
+        LetsEncrypt::Staging.url()
+    } else {
+        LetsEncrypt::Production.url()
+    };
+    let (account, _) = Account::create(
+        &NewAccount {
+            contact: &contact,
+            terms_of_service_agreed: true,
+            only_return_existing: false,
+        },
+        directory,
+        None,
+    )
+    .await
+    .map_err(acme_error("create account"))?;
+    let mut order = account
+        .new_order(&NewOrder {
+            identifiers: &[Identifier::Dns(hostname.to_string())],
+        })
+        .await
+        .map_err(acme_error("create order"))?;
+    let mut challenges = HashMap::new();
+    let mut ready = vec![];
+    for authorization in order
+        .authorizations()
+        .await
+        .map_err(acme_error("load authorizations"))?
+    {
+        match authorization.status {
+            AuthorizationStatus::Pending => {}
+            AuthorizationStatus::Valid => continue,
+            status => return Err(Error::other(format!("ACME authorization is {status:?}"))),
+        }
+        let challenge = authorization
+            .challenges
+            .iter()
+            .find(|challenge| challenge.r#type == ChallengeType::Http01)
+            .ok_or_else(|| Error::other("ACME server offered no HTTP-01 challenge"))?;
+        challenges.insert(
+            challenge.token.clone(),
+            order.key_authorization(challenge).as_str().to_string(),
+        );
+        ready.push(challenge.url.clone());
+    }
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, ACME_HTTP_PORT)).await?;
+    let responder = tokio::spawn(serve_acme_challenges(listener, Arc::new(challenges)));
+    let result = finish_acme_order(&mut order, hostname, &ready).await;
+    responder.abort();
+    result
+}
+
+async fn finish_acme_order(
+    order: &mut instant_acme::Order,
+    hostname: &str,
+    ready: &[String],
+) -> Result<(String, String), Error> {
+    for url in ready {
+        order
+            .set_challenge_ready(url)
+            .await
+            .map_err(acme_error("start challenge"))?;
+    }
+    let mut delay = Duration::from_millis(500);
+    let mut attempt = 0;
+    loop {
+        tokio::time::sleep(delay).await;
+        match order
+            .refresh()
+            .await
+            .map_err(acme_error("refresh order"))?
+            .status
+        {
+            OrderStatus::Ready => break,
+            OrderStatus::Invalid => {
+                return Err(Error::other(format!(
+                    "ACME validation of {hostname} failed, port {ACME_HTTP_PORT} must be reachable"
+                )))
+            }
+            _ => {}
+        }
+        attempt += 1;
+        if attempt >= ACME_POLL_ATTEMPTS {
+            return Err(Error::new(
+                ErrorKind::TimedOut,
+                "Timed out waiting for ACME validation",
+            ));
+        }
+        delay = (delay * 2).min(Duration::from_secs(10));
+    }
+    let mut params = CertificateParams::new(vec![hostname.to_string()]).map_err(Error::other)?;
+    params.distinguished_name = DistinguishedName::new();
+    let key_pair = KeyPair::generate().map_err(Error::other)?;
+    let csr = params.serialize_request(&key_pair).map_err(Error::other)?;
+    order
+        .finalize(csr.der())
+        .await
+        .map_err(acme_error("finalize order"))?;
+    for _ in 0..ACME_POLL_ATTEMPTS {
+        if let Some(cert_chain) = order
+            .certificate()
+            .await
+            .map_err(acme_error("download certificate"))?
+        {
+            return Ok((cert_chain, key_pair.serialize_pem()));
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    Err(Error::new(
+        ErrorKind::TimedOut,
+        "Timed out waiting for the ACME certificate",
+    ))
+}
+
+async fn serve_acme_challenges(listener: TcpListener, challenges: Arc<HashMap<String, String>>) {
+    loop {
+        let (mut stream, client) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Failed to accept ACME challenge connection: {e}");
+                continue;
+            }
+        };
+        let challenges = challenges.clone();
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 2048];
+            let read = match stream.read(&mut buffer).await {
+                Ok(read) => read,
+                Err(e) => {
+                    debug!("Failed to read ACME challenge request from {client}: {e}");
+                    return;
+                }
+            };
+            let request = String::from_utf8_lossy(&buffer[..read]);
+            let response = acme_challenge_response(&request, &challenges);
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                debug!("Failed to answer ACME challenge request from {client}: {e}");
+            }
+        });
+    }
+}
+
+fn acme_challenge_response(request: &str, challenges: &HashMap<String, String>) -> String {
+    let key_authorization = request
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("GET "))
+        .and_then(|line| line.split(' ').next())
+        .and_then(|path| path.strip_prefix(ACME_CHALLENGE_PREFIX))
+        .and_then(|token| challenges.get(token));
+    match key_authorization {
+        Some(body) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        ),
+        None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            .to_string(),
+    }
+}
+
+#[test]
+fn test_acme_challenge_response() {
+    let challenges = HashMap::from([("token".to_string(), "token.thumbprint".to_string())]);
+    let response = acme_challenge_response(
+        "GET /.well-known/acme-challenge/token HTTP/1.1\r\nHost: example.com\r\n\r\n",
+        &challenges,
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("\r\n\r\ntoken.thumbprint"));
+    let response = acme_challenge_response(
+        "GET /.well-known/acme-challenge/other HTTP/1.1\r\n\r\n",
+        &challenges,
+    );
+    assert!(response.starts_with("HTTP/1.1 404"));
+}
+
+#[test]
+fn test_validate_tls_hostname() {
+    assert!(validate_tls_hostname("farm.example.com").is_ok());
+    assert!(validate_tls_hostname("localhost").is_err());
+    assert!(validate_tls_hostname("-bad.example.com").is_err());
+    assert!(validate_tls_hostname("bad..example.com").is_err());
+    assert!(validate_tls_hostname("").is_err());
+}
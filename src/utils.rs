@@ -1,5 +1,13 @@
 use crate::database::config::get_config_key;
-use crate::first_run::{check_for_default_admin_account, validate_config_table};
+use crate::first_run::{
+    check_for_default_admin_account, detect_hardware_capabilities, validate_config_table,
+};
+use crate::models::hotspot::{
+    HotspotBand, HotspotConfig, HOTSPOT_BAND_CONFIG_KEY, HOTSPOT_CHANNEL_CONFIG_KEY,
+    HOTSPOT_PASSWORD_CONFIG_KEY, HOTSPOT_SSID_CONFIG_KEY,
+};
+use crate::models::{env_number, DatabasePoolSettings};
+use crate::plugins::disk_management::recover_auto_mounts;
 use argon2::{Algorithm, Argon2, Params, Version};
 use bollard::Docker;
 use dg_network_manager::dbus_api::devices::Device;
@@ -9,49 +17,148 @@ use dg_network_manager::{
 };
 use log::{debug, error, info, warn};
 use portfu::prelude::{Service, ServiceGroup};
-use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteSynchronous};
 use sqlx::SqlitePool;
-use std::io::Error;
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind};
 use std::path::Path;
-use std::time::Duration;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 use tokio::fs;
 use tokio::net::TcpStream;
+use tokio::task::JoinSet;
 use tokio::time::timeout;
 
-pub async fn create_pool(database_path: &str) -> Result<SqlitePool, Error> {
+pub async fn create_pool(
+    database_path: &str,
+    pool_settings: &DatabasePoolSettings,
+) -> Result<SqlitePool, Error> {
     if let Some(parent) = Path::new(database_path).parent() {
         fs::create_dir_all(parent).await?;
     }
     let connect_opts = SqliteConnectOptions::new()
         .filename(database_path)
         .create_if_missing(true)
-        // PERSIST is for less wear on SD card systems
-        .journal_mode(SqliteJournalMode::Persist)
+        //WAL lets the system monitor, farmer and web handlers read while a write is in progress,
+        //PERSIST remains available for less wear on SD card systems
+        .journal_mode(pool_settings.journal_mode)
+        //FULL syncs on every commit, NORMAL can lose the latest commits when power is cut,
+        //which is common on these devices, so durability is worth the extra writes
         .synchronous(SqliteSynchronous::Full);
-    SqlitePoolOptions::new()
-        .max_connections(50)
-        .connect_with(connect_opts)
-        .await
-        .map_err(|e| Error::other(format!("Failed to connect to SQLite database: {e}")))
+    //The SD card may not be ready this early in boot, so retry with a growing delay
+    let mut attempt = 0;
+    loop {
+        let result = SqlitePoolOptions::new()
+            .max_connections(pool_settings.max_connections)
+            .min_connections(pool_settings.min_connections)
+            .acquire_timeout(pool_settings.acquire_timeout)
+            .idle_timeout(pool_settings.idle_timeout)
+            .max_lifetime(pool_settings.max_lifetime)
+            .connect_with(connect_opts.clone())
+            .await;
+        match result {
+            Ok(pool) => return Ok(pool),
+            Err(e) if attempt + 1 < pool_settings.max_retries => {
+                let delay = db_retry_delay(pool_settings.retry_interval, attempt);
+                attempt += 1;
+                warn!(
+                    "Failed to connect to SQLite database (attempt {attempt}/{}), retrying in {delay:?}: {e}",
+                    pool_settings.max_retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => {
+                return Err(Error::other(format!(
+                    "Failed to connect to SQLite database: {e}"
+                )))
+            }
+        }
+    }
+}
+
+fn db_retry_delay(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(attempt))
+}
+
+//https://cheatsheetseries.owasp.org/cheatsheets/Password_Storage_Cheat_Sheet.html#argon2id
+pub const DEFAULT_ARGON_MEMORY_KB: u32 = 47104;
+pub const DEFAULT_ARGON_ITERATIONS: u32 = 1;
+pub const DEFAULT_ARGON_PARALLELISM: u32 = 1;
+pub const DEFAULT_ARGON_OUTPUT_LEN: usize = 32;
+
+pub fn argon_params(
+    memory_kb: u32,
+    iterations: u32,
+    parallelism: u32,
+    output_len: usize,
+) -> Result<Params, Error> {
+    Params::new(memory_kb, iterations, parallelism, Some(output_len)).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Invalid Argon2 params (memory {memory_kb} KiB, iterations {iterations}, \
+                parallelism {parallelism}, output length {output_len}): {e}. \
+                Memory must be at least {} KiB per lane, iterations at least {}, \
+                parallelism between {} and {} and output length between {} and {}",
+                Params::MIN_M_COST,
+                Params::MIN_T_COST,
+                Params::MIN_P_COST,
+                Params::MAX_P_COST,
+                Params::MIN_OUTPUT_LEN,
+                Params::MAX_OUTPUT_LEN,
+            ),
+        )
+    })
 }
 
 pub fn create_argon() -> Result<Argon2<'static>, Error> {
-    Ok(Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        //https://cheatsheetseries.owasp.org/cheatsheets/Password_Storage_Cheat_Sheet.html#argon2id
-        Params::new(47104, 1, 1, Some(32))
-            .map_err(|e| Error::other(format!("Invalid Argon params: {e}")))?,
-    ))
+    let memory_kb = env_number("DG_ARGON_MEMORY_KB")?.unwrap_or(DEFAULT_ARGON_MEMORY_KB);
+    let iterations = env_number("DG_ARGON_ITERATIONS")?.unwrap_or(DEFAULT_ARGON_ITERATIONS);
+    let parallelism = env_number("DG_ARGON_PARALLELISM")?.unwrap_or(DEFAULT_ARGON_PARALLELISM);
+    let output_len = env_number("DG_ARGON_OUTPUT_LEN")?.unwrap_or(DEFAULT_ARGON_OUTPUT_LEN);
+    let params = argon_params(memory_kb, iterations, parallelism, output_len)?;
+    info!(
+        "Using Argon2id params: memory {memory_kb} KiB, iterations {iterations}, parallelism {parallelism}, output length {output_len}"
+    );
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
 }
 
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
 pub async fn run_migrations(pool: &SqlitePool) -> Result<(), Error> {
-    sqlx::migrate!()
+    MIGRATOR
         .run(pool)
         .await
         .map_err(|e| Error::other(format!("Failed to Migrate Database: {e:?}")))
 }
 
+/// Returns the `{version}_{description}` of each migration not yet applied to the database
+pub async fn pending_migrations(pool: &SqlitePool) -> Result<Vec<String>, Error> {
+    let has_migrations_table: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| Error::other(format!("Failed to check Migrations: {e:?}")))?;
+    let applied: HashSet<i64> = if has_migrations_table {
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = 1")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| Error::other(format!("Failed to list applied Migrations: {e:?}")))?
+            .into_iter()
+            .collect()
+    } else {
+        HashSet::new()
+    };
+    Ok(MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+        .map(|m| format!("{}_{}", m.version, m.description))
+        .collect())
+}
+
 pub fn connect_to_docker() -> Result<Docker, Error> {
     Docker::connect_with_defaults()
         .map_err(|e| Error::other(format!("Failed to connect to docker: {e}")))
@@ -71,28 +178,128 @@ pub fn find_index_service(static_files: &ServiceGroup) -> Option<Service> {
         .next()
 }
 
-pub async fn has_internet_connection() -> bool {
-    let endpoints = [
-        "8.8.8.8:53",        // Google's DNS
-        "1.1.1.1:53",        // Cloudflare's DNS
-        "208.67.222.222:53", // OpenDNS
-    ];
-    let mut connection_established = false;
-    for &address in &endpoints {
-        debug!("Attempting to connect to {address}...");
-        if let Ok(Ok(_stream)) = timeout(Duration::from_secs(5), TcpStream::connect(address)).await
-        {
-            debug!("Successfully connected via {address}!");
-            connection_established = true;
-            break;
-        } else {
+pub const DEFAULT_INTERNET_CHECK_ENDPOINTS: [&str; 3] = [
+    "8.8.8.8:53",        // Google's DNS
+    "1.1.1.1:53",        // Cloudflare's DNS
+    "208.67.222.222:53", // OpenDNS
+];
+pub const INTERNET_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointCheck {
+    pub address: String,
+    pub reachable: bool,
+    pub latency_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InternetCheckResult {
+    pub connected: bool,
+    pub latency_ms: Option<u64>,
+    pub checked_endpoints: Vec<EndpointCheck>,
+}
+
+async fn check_endpoint(address: String) -> EndpointCheck {
+    debug!("Attempting to connect to {address}...");
+    let start = Instant::now();
+    match timeout(INTERNET_CHECK_TIMEOUT, TcpStream::connect(address.as_str())).await {
+        Ok(Ok(_stream)) => {
+            let latency_ms = start.elapsed().as_millis() as u64;
+            debug!("Successfully connected via {address} in {latency_ms}ms");
+            EndpointCheck {
+                address,
+                reachable: true,
+                latency_ms: Some(latency_ms),
+            }
+        }
+        _ => {
             warn!("Connection to {address} failed.");
+            EndpointCheck {
+                address,
+                reachable: false,
+                latency_ms: None,
+            }
         }
     }
-    if !connection_established {
+}
+
+/// Checks the default endpoints along with any `custom_endpoints`, all checks run concurrently
+pub async fn check_internet_connection(custom_endpoints: &[String]) -> InternetCheckResult {
+    let [google, cloudflare, opendns] = DEFAULT_INTERNET_CHECK_ENDPOINTS;
+    let mut custom_checks = JoinSet::new();
+    for (index, address) in custom_endpoints.iter().enumerate() {
+        let address = address.clone();
+        custom_checks.spawn(async move { (index, check_endpoint(address).await) });
+    }
+    let (google, cloudflare, opendns) = tokio::join!(
+        check_endpoint(google.to_string()),
+        check_endpoint(cloudflare.to_string()),
+        check_endpoint(opendns.to_string()),
+    );
+    let mut custom_results = custom_checks.join_all().await;
+    custom_results.sort_by_key(|(index, _)| *index);
+    let checked_endpoints: Vec<EndpointCheck> = [google, cloudflare, opendns]
+        .into_iter()
+        .chain(custom_results.into_iter().map(|(_, check)| check))
+        .collect();
+    let latency_ms = checked_endpoints
+        .iter()
+        .filter_map(|check| check.latency_ms)
+        .min();
+    let connected = checked_endpoints.iter().any(|check| check.reachable);
+    if !connected {
         error!("No internet connection detected on any endpoint.");
     }
-    connection_established
+    InternetCheckResult {
+        connected,
+        latency_ms,
+        checked_endpoints,
+    }
+}
+
+pub async fn has_internet_connection() -> bool {
+    check_internet_connection(&[]).await.connected
+}
+
+/// Endpoints must be `host:port`, hostnames are resolved when checked
+pub fn validate_check_endpoint(address: &str) -> Result<(), Error> {
+    let valid = address.rsplit_once(':').is_some_and(|(host, port)| {
+        !host.is_empty() && !host.contains(char::is_whitespace) && port.parse::<u16>().is_ok()
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid Endpoint: {address}, expected host:port"),
+        ))
+    }
+}
+
+pub async fn load_hotspot_config(pool: &SqlitePool) -> Result<HotspotConfig, Error> {
+    let mut config = HotspotConfig::default();
+    if let Some(entry) = get_config_key(pool, HOTSPOT_SSID_CONFIG_KEY).await? {
+        config.ssid = entry.value;
+    }
+    if let Some(entry) = get_config_key(pool, HOTSPOT_PASSWORD_CONFIG_KEY).await? {
+        config.password = entry.value;
+    }
+    if let Some(entry) = get_config_key(pool, HOTSPOT_BAND_CONFIG_KEY).await? {
+        match HotspotBand::from_str(&entry.value) {
+            Ok(band) => config.band = band,
+            Err(e) => warn!("{e}, using {}", config.band),
+        }
+    }
+    if let Some(entry) = get_config_key(pool, HOTSPOT_CHANNEL_CONFIG_KEY).await? {
+        //An empty or zero channel lets NetworkManager pick one
+        match entry.value.trim().parse::<u32>() {
+            Ok(0) => {}
+            Ok(channel) => config.channel = Some(channel),
+            Err(_) if entry.value.trim().is_empty() => {}
+            Err(e) => warn!("Invalid Hotspot Channel {}: {e}", entry.value),
+        }
+    }
+    Ok(config)
 }
 
 pub async fn perform_startup_checks(
@@ -105,6 +312,14 @@ pub async fn perform_startup_checks(
     //Check for Default config Entries
     validate_config_table(pool).await?;
 
+    //Detect GPUs and CPU cores, seeding the Farmer Config with the found CUDA devices
+    detect_hardware_capabilities(pool).await?;
+
+    //Fix auto mounts left half mounted by a crash before anything reads the plot drives
+    if let Err(e) = recover_auto_mounts(pool).await {
+        warn!("Failed to recover auto mounts: {e:?}");
+    }
+
     //First Check if we are connected to internet
     if !has_internet_connection().await {
         let network_manager = NetworkManagerClient::new().await?;
@@ -130,14 +345,9 @@ pub async fn perform_startup_checks(
                 .await?
                 .map(|c| c.value)
                 .unwrap_or_default();
-            let hotspot_ssid = get_config_key(pool, "hotspot_ssid")
-                .await?
-                .map(|c| c.value)
-                .unwrap_or("DG_OS_SETUP".to_string());
-            let hotspot_password = get_config_key(pool, "hotspot_password")
-                .await?
-                .map(|c| c.value)
-                .unwrap_or("DG_xch1234!".to_string());
+            let hotspot_config = load_hotspot_config(pool).await?;
+            let hotspot_ssid = hotspot_config.ssid;
+            let hotspot_password = hotspot_config.password;
             let mut found_wireless_device: Option<Device> = None;
             for device in wireless_devices().await? {
                 let wireless_device = match &device {
@@ -202,3 +412,51 @@ pub async fn perform_startup_checks(
     }
     Ok(())
 }
+
+#[test]
+fn test_db_retry_delay() {
+    let base = Duration::from_millis(200);
+    assert_eq!(db_retry_delay(base, 0), Duration::from_millis(200));
+    assert_eq!(db_retry_delay(base, 1), Duration::from_millis(400));
+    assert_eq!(db_retry_delay(base, 4), Duration::from_millis(3200));
+    assert_eq!(db_retry_delay(base, u32::MAX), Duration::MAX);
+}
+
+#[test]
+fn test_validate_check_endpoint() {
+    assert!(validate_check_endpoint("8.8.8.8:53").is_ok());
+    assert!(validate_check_endpoint("pool.example.com:443").is_ok());
+    assert!(validate_check_endpoint("[::1]:53").is_ok());
+    assert!(validate_check_endpoint("pool.example.com").is_err());
+    assert!(validate_check_endpoint(":53").is_err());
+    assert!(validate_check_endpoint("host:99999").is_err());
+}
+
+#[test]
+fn test_argon_params() {
+    assert!(argon_params(
+        DEFAULT_ARGON_MEMORY_KB,
+        DEFAULT_ARGON_ITERATIONS,
+        DEFAULT_ARGON_PARALLELISM,
+        DEFAULT_ARGON_OUTPUT_LEN
+    )
+    .is_ok());
+    assert!(argon_params(4, 1, 1, 32).is_err());
+    assert!(argon_params(47104, 0, 1, 32).is_err());
+    assert!(argon_params(47104, 1, 1, 2).is_err());
+}
+
+#[tokio::test]
+async fn test_pending_migrations() {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    assert_eq!(
+        pending_migrations(&pool).await.unwrap().len(),
+        MIGRATOR.iter().count()
+    );
+    run_migrations(&pool).await.unwrap();
+    assert!(pending_migrations(&pool).await.unwrap().is_empty());
+}
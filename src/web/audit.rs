@@ -0,0 +1,80 @@
+use crate::config::ConfigManager;
+use crate::database::audit::{get_audit_log_page, prune_audit_log, AuditLogEntry, AuditLogFilter};
+use crate::models::pagination::{ListQuery, Paginated};
+use log::info;
+use portfu::prelude::State;
+use portfu_core::ServiceData;
+use portfu_macros::{get, interval};
+use sqlx::SqlitePool;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::RwLock;
+
+pub const AUDIT_LOG_DAYS_CONFIG_KEY: &str = "audit_log_days";
+pub const DEFAULT_AUDIT_LOG_DAYS: u64 = 90;
+
+pub fn timestamp_filter(query: &ListQuery, name: &str) -> Result<Option<OffsetDateTime>, Error> {
+    query
+        .filter(name)
+        .map(|value| {
+            i64::from_str(value)
+                .map_err(|e| e.to_string())
+                .and_then(|timestamp| {
+                    OffsetDateTime::from_unix_timestamp(timestamp).map_err(|e| e.to_string())
+                })
+                .map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Failed to parse {name}: {e}"),
+                    )
+                })
+        })
+        .transpose()
+}
+
+#[get("/api/audit", output = "json", eoutput = "bytes")]
+pub async fn get_audit_log(
+    data: &mut ServiceData,
+    database: State<SqlitePool>,
+) -> Result<Paginated<AuditLogEntry>, Error> {
+    let query = ListQuery::parse(data.request.request.uri().query())?;
+    let filter = AuditLogFilter {
+        start: timestamp_filter(&query, "start")?,
+        end: timestamp_filter(&query, "end")?,
+        user_id: query
+            .filter("user_id")
+            .map(|value| {
+                i64::from_str(value).map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Failed to parse user_id: {e}"),
+                    )
+                })
+            })
+            .transpose()?,
+        path: query.filter("path").map(str::to_string),
+    };
+    get_audit_log_page(database.as_ref(), &filter, &query).await
+}
+
+#[interval(3_600_000)]
+pub async fn prune_audit_log_task(
+    database: State<SqlitePool>,
+    config: State<RwLock<ConfigManager>>,
+) -> Result<(), Error> {
+    let days_to_keep = config
+        .0
+        .read()
+        .await
+        .get_as::<u64>(AUDIT_LOG_DAYS_CONFIG_KEY)
+        .await
+        .unwrap_or(DEFAULT_AUDIT_LOG_DAYS);
+    let older_than = OffsetDateTime::now_utc() - Duration::from_secs(days_to_keep * 24 * 60 * 60);
+    let pruned = prune_audit_log(&database, older_than).await?;
+    if pruned > 0 {
+        info!("Pruned {pruned} Audit Log entries older than {days_to_keep} days");
+    }
+    Ok(())
+}
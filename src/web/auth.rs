@@ -1,38 +1,294 @@
+use crate::config::ConfigManager;
+use crate::database::audit::{insert_audit_log, AddAuditLogEntry};
+use crate::database::config::get_config_key;
 use crate::database::users::{
-    login, register, update_password, UserPasswordUpdate, UserWithInfoWithPassword,
-    UsernameWithPassword,
+    confirm_totp_secret, delete_user, delete_user_preference, get_all_users,
+    get_pending_totp_secret, get_totp_secret, get_user_preference, get_user_preferences, login,
+    register, set_totp_secret, set_user_preference, update_password, update_user_role,
+    UserPasswordUpdate, UserPublicInfo, UserWithInfoWithPassword, UsernameWithPassword,
 };
+use crate::models::config::AddConfigEntry;
+use crate::tls::tls_client_address;
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::{Salt, SaltString};
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use log::{error, warn};
+use dashmap::DashMap;
+use jsonwebtoken::errors::ErrorKind as JwtErrorKind;
+use jsonwebtoken::{
+    decode, encode, Algorithm as JwtAlgorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use log::{debug, error, info, warn};
 use portfu::prelude::async_trait::async_trait;
-use portfu::prelude::http::{HeaderName, HeaderValue, StatusCode};
+use portfu::prelude::http::{HeaderName, HeaderValue, Method, StatusCode};
+use portfu::prelude::serde_json;
 use portfu::prelude::{Path, State};
 use portfu::wrappers::sessions::Session;
 use portfu_admin::auth::{BasicAuth, Claims};
 use portfu_admin::users::UserRole;
 use portfu_core::wrappers::{WrapperFn, WrapperResult};
 use portfu_core::{FromRequest, Json, ServiceData};
-use portfu_macros::{get, post, put};
+use portfu_macros::{delete, get, post, put};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
 use sha2::digest::Output;
 use sha2::{Digest, Sha256, Sha256VarCore};
 use sqlx::types::time::OffsetDateTime;
 use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::env;
 use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+pub const SESSION_TIMEOUT_CONFIG_KEY: &str = "session_timeout_seconds";
+pub const DEFAULT_SESSION_TIMEOUT_SECONDS: usize = 30 * 60; //30 Minutes
+pub const MIN_SESSION_TIMEOUT_SECONDS: usize = 60;
+pub const MAX_SESSION_TIMEOUT_SECONDS: usize = 24 * 60 * 60; //1 Day
+pub const TOTP_ISSUER: &str = "Druid Garden";
+pub const MAX_LOGIN_FAILURES: u32 = 5;
+pub const LOGIN_LOCKOUT_DURATION: Duration = Duration::from_secs(15 * 60);
+//Stale records are evicted once the tracker holds this many keys
+pub const MAX_TRACKED_LOGIN_KEYS: usize = 10_000;
+
+//portfu_admin signs the tokens from get_jwt and basic_login with JWT_SECRET, tokens issued here must match
+pub const JWT_SECRET_ENV: &str = "JWT_SECRET";
+static JWT_SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Loads the token signing secret, called at startup so a missing secret fails fast
+pub fn init_jwt_secret() -> Result<(), Error> {
+    let secret = env::var(JWT_SECRET_ENV).map_err(|e| {
+        Error::other(format!(
+            "Failed to load JWT Secret from {JWT_SECRET_ENV}: {e:?}"
+        ))
+    })?;
+    if secret.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{JWT_SECRET_ENV} cannot be empty"),
+        ));
+    }
+    let _ = JWT_SECRET.set(secret.into_bytes());
+    Ok(())
+}
+
+fn jwt_secret() -> Result<&'static [u8], Error> {
+    JWT_SECRET
+        .get()
+        .map(Vec::as_slice)
+        .ok_or_else(|| Error::other("JWT Secret has not been loaded"))
+}
+
+fn jwt_validation() -> Validation {
+    let mut validation = Validation::new(JwtAlgorithm::HS256);
+    validation.set_audience(&["localhost"]);
+    validation.set_issuer(&["localhost"]);
+    validation.set_required_spec_claims(&["exp", "nbf", "aud", "iss", "sub"]);
+    validation.validate_nbf = true;
+    validation
+}
+
+pub async fn session_timeout(pool: &SqlitePool) -> Result<usize, Error> {
+    Ok(get_config_key(pool, SESSION_TIMEOUT_CONFIG_KEY)
+        .await?
+        .and_then(|entry| usize::from_str(&entry.value).ok())
+        //Rows written before the upper bound existed could overflow the token expiry
+        .map(|seconds| seconds.clamp(MIN_SESSION_TIMEOUT_SECONDS, MAX_SESSION_TIMEOUT_SECONDS))
+        .unwrap_or(DEFAULT_SESSION_TIMEOUT_SECONDS))
+}
+
+fn encode_jwt(claims: &Claims) -> Result<String, Error> {
+    encode(
+        &Header::new(JwtAlgorithm::HS256),
+        claims,
+        &EncodingKey::from_secret(jwt_secret()?),
+    )
+    .map_err(|e| Error::other(format!("Failed to Encode JWT: {e:?}")))
+}
+
+fn build_totp(secret: &str, account_name: &str) -> Result<TOTP, Error> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| Error::other(format!("Invalid TOTP Secret: {e:?}")))?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name.to_string(),
+    )
+    .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{e:?}")))
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AttemptRecord {
+    pub failures: u32,
+    pub locked_until: Option<Instant>,
+    pub last_failure: Option<Instant>,
+}
+impl AttemptRecord {
+    fn is_stale(&self, now: Instant) -> bool {
+        let lock_expired = self
+            .locked_until
+            .map(|locked_until| locked_until <= now)
+            .unwrap_or(true);
+        let failures_expired = self
+            .last_failure
+            .map(|last| now.duration_since(last) >= LOGIN_LOCKOUT_DURATION)
+            .unwrap_or(true);
+        lock_expired && failures_expired
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LockoutStatus {
+    pub username: String,
+    pub locked: bool,
+    pub failures: u32,
+    pub remaining_seconds: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct LoginAttemptTracker {
+    attempts: DashMap<String, AttemptRecord>,
+}
+impl LoginAttemptTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn remaining_lockout(&self, key: &str) -> Option<Duration> {
+        self.attempts
+            .get(key)
+            .and_then(|record| record.locked_until)
+            .and_then(|locked_until| locked_until.checked_duration_since(Instant::now()))
+    }
+    pub fn is_locked(&self, key: &str) -> bool {
+        self.remaining_lockout(key).is_some()
+    }
+    pub fn record_failure(&self, key: &str) {
+        if self.attempts.len() >= MAX_TRACKED_LOGIN_KEYS {
+            self.evict_stale();
+        }
+        let mut record = self.attempts.entry(key.to_string()).or_default();
+        if record
+            .locked_until
+            .is_some_and(|locked_until| locked_until <= Instant::now())
+        {
+            //The previous lock has expired, start counting again
+            *record = AttemptRecord::default();
+        }
+        record.failures += 1;
+        record.last_failure = Some(Instant::now());
+        if record.failures >= MAX_LOGIN_FAILURES {
+            warn!("Locking Login for {key} after {} failures", record.failures);
+            record.locked_until = Some(Instant::now() + LOGIN_LOCKOUT_DURATION);
+        }
+    }
+    pub fn record_success(&self, key: &str) {
+        self.attempts.remove(key);
+    }
+    pub fn unlock(&self, key: &str) -> bool {
+        self.attempts.remove(key).is_some()
+    }
+    /// Drops records that are neither locked nor have a recent failure
+    pub fn evict_stale(&self) -> usize {
+        let before = self.attempts.len();
+        let now = Instant::now();
+        self.attempts.retain(|_, record| !record.is_stale(now));
+        before.saturating_sub(self.attempts.len())
+    }
+    pub fn status(&self, username: &str) -> LockoutStatus {
+        let failures = self
+            .attempts
+            .get(username)
+            .map(|record| record.failures)
+            .unwrap_or_default();
+        let remaining = self.remaining_lockout(username);
+        LockoutStatus {
+            username: username.to_string(),
+            locked: remaining.is_some(),
+            failures,
+            remaining_seconds: remaining.map(|r| r.as_secs()).unwrap_or_default(),
+        }
+    }
+}
+
+fn address_key(address: &str) -> String {
+    format!("ip:{address}")
+}
+
+fn totp_key(user_id: &str) -> String {
+    format!("totp:{user_id}")
+}
+
+/// Resolves the client address from the socket peer, proxy headers are only used when the peer is a trusted proxy
+pub fn resolve_client_address(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: &[IpAddr],
+) -> Option<String> {
+    let peer = peer?;
+    if trusted_proxies.contains(&peer) {
+        //The last hop that isn't one of our proxies is the client, earlier entries can be forged
+        let forwarded = forwarded_for
+            .into_iter()
+            .flat_map(|v| v.split(','))
+            .filter_map(|v| IpAddr::from_str(v.trim()).ok())
+            .rev()
+            .find(|address| !trusted_proxies.contains(address));
+        if let Some(forwarded) = forwarded {
+            return Some(forwarded.to_string());
+        }
+    }
+    Some(peer.to_string())
+}
+
+#[derive(Clone)]
+pub struct ClientAddress(pub String);
+
+fn user_claims(
+    user_id: i64,
+    username: String,
+    role: UserRole,
+    now: usize,
+    session_timeout: usize,
+) -> Claims {
+    Claims {
+        aud: "localhost".to_string(),
+        exp: now + session_timeout,
+        iat: now,
+        iss: "localhost".to_string(),
+        nbf: now,
+        sub: user_id.to_string(),
+        eml: username,
+        rol: role,
+        org: vec![],
+    }
+}
 
 pub struct BasicAuthHandle {
     pool: SqlitePool,
     argon: Argon2<'static>,
+    attempts: Arc<LoginAttemptTracker>,
 }
 impl BasicAuthHandle {
-    pub fn new(pool: SqlitePool, argon: Argon2<'static>) -> Self {
-        Self { pool, argon }
+    pub fn new(
+        pool: SqlitePool,
+        argon: Argon2<'static>,
+        attempts: Arc<LoginAttemptTracker>,
+    ) -> Self {
+        Self {
+            pool,
+            argon,
+            attempts,
+        }
     }
 }
 #[async_trait]
@@ -46,6 +302,17 @@ impl BasicAuth for BasicAuthHandle {
         //Get all Data before All Comparisons
         let username = username.as_ref();
         let password = password.as_ref();
+        let address = session
+            .read()
+            .await
+            .data
+            .get::<ClientAddress>()
+            .map(|v| address_key(&v.0));
+        if self.attempts.is_locked(username)
+            || address.as_ref().is_some_and(|a| self.attempts.is_locked(a))
+        {
+            return Err(Error::new(ErrorKind::PermissionDenied, "account locked"));
+        }
         //Fake Data Generated Every Time
         let bad_password = format!("bad_{password}");
         let fake_salt = SaltString::generate(&mut OsRng);
@@ -61,22 +328,19 @@ impl BasicAuth for BasicAuthHandle {
         tokio::time::sleep(std::time::Duration::from_millis(range)).await;
         let pch_string;
         let claims;
+        let session_timeout = session_timeout(&self.pool).await?;
         let now = OffsetDateTime::now_utc().unix_timestamp() as usize;
         let (user_id, hash_to_use) = match maybe_user_info {
             Some(user_info) => {
                 //Found a User, Do a real compare
                 pch_string = String::from_utf8_lossy(&user_info.password).to_string();
-                claims = Claims {
-                    aud: "localhost".to_string(),
-                    exp: now + 30 * 60, //30 Minutes
-                    iat: now,
-                    iss: "localhost".to_string(),
-                    nbf: now,
-                    sub: user_info.id.to_string(),
-                    eml: user_info.username,
-                    rol: user_info.role,
-                    org: vec![],
-                };
+                claims = user_claims(
+                    user_info.id,
+                    user_info.username,
+                    user_info.role,
+                    now,
+                    session_timeout,
+                );
                 (
                     user_info.id,
                     PasswordHash::new(pch_string.as_ref()).map_err(|e| {
@@ -90,7 +354,7 @@ impl BasicAuth for BasicAuthHandle {
                 pch_string = String::from_utf8_lossy(fake_hash_pch_bytes.as_bytes()).to_string();
                 claims = Claims {
                     aud: "localhost".to_string(),
-                    exp: now + 30 * 60, //30 Minutes
+                    exp: now + session_timeout,
                     iat: now,
                     iss: "localhost".to_string(),
                     nbf: now,
@@ -124,19 +388,39 @@ impl BasicAuth for BasicAuthHandle {
             .verify_password(password.as_ref(), &hash_to_use)
             .is_ok()
         {
+            self.attempts.record_success(username);
+            if let Some(address) = &address {
+                self.attempts.record_success(address);
+            }
             if default_hash == hash_to_use {
-                if let Some(require_update) = session.read().await.data.get::<RequireUpdate>() {
-                    require_update.0.store(true, Ordering::Relaxed);
-                    return Ok(claims);
+                let require_update = session.read().await.data.get::<RequireUpdate>().cloned();
+                match require_update {
+                    Some(require_update) => require_update.0.store(true, Ordering::Relaxed),
+                    None => {
+                        session
+                            .write()
+                            .await
+                            .data
+                            .insert(RequireUpdate(Arc::new(AtomicBool::new(true))));
+                    }
                 }
-                session
-                    .write()
-                    .await
-                    .data
-                    .insert(RequireUpdate(Arc::new(AtomicBool::new(true))));
+            }
+            if get_totp_secret(&self.pool, user_id).await?.is_some() {
+                //Hold the real claims until the TOTP code is verified
+                let mut session = session.write().await;
+                session.data.insert(RequiresTotp(true));
+                session.data.insert(PendingTotpClaims(claims.clone()));
+                return Ok(Claims {
+                    rol: UserRole::None,
+                    ..claims
+                });
             }
             Ok(claims)
         } else {
+            self.attempts.record_failure(username);
+            if let Some(address) = &address {
+                self.attempts.record_failure(address);
+            }
             Err(Error::new(ErrorKind::NotFound, "User not found"))
         }
     }
@@ -223,6 +507,38 @@ pub async fn user_requires_password_update(
 #[derive(Clone)]
 pub struct RequireUpdate(pub Arc<AtomicBool>);
 
+#[derive(Clone)]
+pub struct RequiresTotp(pub bool);
+
+#[derive(Clone)]
+pub struct PendingTotpClaims(pub Claims);
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TotpVerifyPayload {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SessionTimeoutPayload {
+    pub seconds: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RoleUpdatePayload {
+    pub role: UserRole,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TotpSetupPayload {
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TotpDisablePayload {
+    pub password: String,
+    pub code: String,
+}
+
 pub struct PasswordUpdateWrapper {}
 #[async_trait]
 impl WrapperFn for PasswordUpdateWrapper {
@@ -236,23 +552,34 @@ impl WrapperFn for PasswordUpdateWrapper {
                 warn!("Failed to Find User Session for Password Update Redirect: {e:?}");
                 WrapperResult::Continue
             }
-            Ok(session) => match session.0.read().await.data.get::<RequireUpdate>() {
-                None => WrapperResult::Continue,
-                Some(update_required) => {
-                    if update_required.0.load(Ordering::Relaxed)
-                        && !data.request.path.matches("/account")
-                    {
-                        *data.response.status_mut() = StatusCode::TEMPORARY_REDIRECT;
-                        data.response.headers_mut().insert(
-                            HeaderName::from_static("location"),
-                            HeaderValue::from_static("/user"),
-                        );
-                        WrapperResult::Return
-                    } else {
-                        WrapperResult::Continue
+            Ok(session) => {
+                let session = session.0.read().await;
+                match session.data.get::<RequireUpdate>() {
+                    None => WrapperResult::Continue,
+                    Some(update_required) => {
+                        //Only redirect while the token is still valid, expired tokens are rejected by auth
+                        let now = OffsetDateTime::now_utc().unix_timestamp() as usize;
+                        let token_valid = session
+                            .data
+                            .get::<Claims>()
+                            .map(|claims| claims.exp > now)
+                            .unwrap_or(true);
+                        if update_required.0.load(Ordering::Relaxed)
+                            && token_valid
+                            && !data.request.path.matches("/account")
+                        {
+                            *data.response.status_mut() = StatusCode::TEMPORARY_REDIRECT;
+                            data.response.headers_mut().insert(
+                                HeaderName::from_static("location"),
+                                HeaderValue::from_static("/user"),
+                            );
+                            WrapperResult::Return
+                        } else {
+                            WrapperResult::Continue
+                        }
                     }
                 }
-            },
+            }
         }
     }
 
@@ -260,3 +587,692 @@ impl WrapperFn for PasswordUpdateWrapper {
         WrapperResult::Continue
     }
 }
+
+pub struct ClientAddressWrapper {
+    pub trusted_proxies: Vec<IpAddr>,
+}
+#[async_trait]
+impl WrapperFn for ClientAddressWrapper {
+    fn name(&self) -> &str {
+        "ClientAddressWrapper"
+    }
+
+    async fn before(&self, data: &mut ServiceData) -> WrapperResult {
+        let headers = data.request.request.headers();
+        let forwarded_for = ["x-forwarded-for", "x-real-ip"]
+            .iter()
+            .find_map(|header| headers.get(*header).and_then(|v| v.to_str().ok()));
+        let peer = data
+            .request
+            .request
+            .extensions()
+            .get::<SocketAddr>()
+            //Connections proxied by the TLS listener come from it, not the client
+            .map(|address| tls_client_address(address).unwrap_or(*address).ip());
+        let address = resolve_client_address(peer, forwarded_for, &self.trusted_proxies);
+        if let Some(address) = address {
+            match State::<RwLock<Session>>::from_request(&mut data.request, "client_address").await
+            {
+                Ok(session) => {
+                    session.0.write().await.data.insert(ClientAddress(address));
+                }
+                Err(e) => warn!("Failed to Find User Session for Client Address: {e:?}"),
+            }
+        }
+        WrapperResult::Continue
+    }
+
+    async fn after(&self, _: &mut ServiceData) -> WrapperResult {
+        WrapperResult::Continue
+    }
+}
+
+//Bodies on these paths carry credentials, a hash of a short password is easy to reverse
+const AUDIT_UNHASHED_PATHS: [&str; 4] = [
+    "/api/login",
+    "/api/users/register",
+    "/api/users/password",
+    "/api/auth/totp",
+];
+
+fn is_audited(method: &Method, path: &str) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) && !path.starts_with("/health")
+}
+
+pub struct AuditLogWrapper {
+    pub pool: SqlitePool,
+}
+#[async_trait]
+impl WrapperFn for AuditLogWrapper {
+    fn name(&self) -> &str {
+        "AuditLogWrapper"
+    }
+
+    async fn before(&self, _: &mut ServiceData) -> WrapperResult {
+        WrapperResult::Continue
+    }
+
+    async fn after(&self, data: &mut ServiceData) -> WrapperResult {
+        let method = data.request.request.method().clone();
+        let path = data.request.request.uri().path().to_string();
+        if !is_audited(&method, &path) {
+            return WrapperResult::Continue;
+        }
+        let (user_id, username, ip_address) =
+            match State::<RwLock<Session>>::from_request(&mut data.request, "audit_log").await {
+                Ok(session) => {
+                    let session = session.0.read().await;
+                    let claims = session.data.get::<Claims>();
+                    (
+                        claims.and_then(|claims| i64::from_str(&claims.sub).ok()),
+                        claims.map(|claims| claims.eml.clone()),
+                        session
+                            .data
+                            .get::<ClientAddress>()
+                            .map(|address| address.0.clone()),
+                    )
+                }
+                Err(_) => (None, None, None),
+            };
+        //Handlers only take Json payloads, so the parsed body is hashed rather than the raw bytes
+        let request_body_hash = if AUDIT_UNHASHED_PATHS
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+        {
+            None
+        } else {
+            Json::<Option<serde_json::Value>>::from_request(&mut data.request, "audit_body")
+                .await
+                .ok()
+                .and_then(|body| body.inner())
+                .and_then(|body| serde_json::to_vec(&body).ok())
+                .map(|body| {
+                    Sha256::digest(body)
+                        .iter()
+                        .map(|b| format!("{b:02x}"))
+                        .collect()
+                })
+        };
+        let entry = AddAuditLogEntry {
+            user_id,
+            username,
+            method: method.to_string(),
+            path,
+            request_body_hash,
+            response_status: data.response.status().as_u16(),
+            ip_address,
+        };
+        if let Err(e) = insert_audit_log(&self.pool, &entry).await {
+            warn!("Failed to record Audit Log entry: {e:?}");
+        }
+        WrapperResult::Continue
+    }
+}
+
+fn bearer_claims(authorization: Option<&str>) -> Result<Claims, Error> {
+    let Some(token) = authorization
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+    else {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Missing Bearer Token",
+        ));
+    };
+    decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(jwt_secret()?),
+        &jwt_validation(),
+    )
+    .map(|token_data| token_data.claims)
+    .map_err(|e| match e.kind() {
+        JwtErrorKind::ExpiredSignature => Error::new(ErrorKind::PermissionDenied, "Token Expired"),
+        _ => Error::new(ErrorKind::PermissionDenied, "Invalid Token"),
+    })
+}
+
+/// Verifies the request's Bearer token, setting 401 when it is missing or invalid
+fn request_claims(data: &mut ServiceData) -> Result<Claims, Error> {
+    let authorization = data
+        .request
+        .request
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    bearer_claims(authorization.as_deref()).inspect_err(|e| {
+        if e.kind() == ErrorKind::PermissionDenied {
+            *data.response.status_mut() = StatusCode::UNAUTHORIZED;
+        }
+    })
+}
+
+//Password only logins never put Claims in the session, so the user comes from the token
+pub fn request_user_id(data: &mut ServiceData) -> Result<i64, Error> {
+    parse_user_id(&request_claims(data)?.sub)
+}
+
+#[post("/api/auth/refresh", output = "json", eoutput = "bytes")]
+pub async fn refresh_jwt(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    session: State<RwLock<Session>>,
+) -> Result<String, Error> {
+    let mut claims = request_claims(data)?;
+    //Tokens handed out while TOTP is pending carry no role and must not be upgraded here
+    let totp_pending = session
+        .0
+        .read()
+        .await
+        .data
+        .get::<RequiresTotp>()
+        .map(|v| v.0)
+        .unwrap_or_default();
+    if totp_pending || matches!(claims.rol, UserRole::None) {
+        *data.response.status_mut() = StatusCode::UNAUTHORIZED;
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "TOTP Verification Pending",
+        ));
+    }
+    //Deleted users and role changes take effect on the next refresh, not when the old token expires
+    let Some(user) = login(pool.as_ref(), &claims.eml)
+        .await?
+        .filter(|user| user.id.to_string() == claims.sub)
+    else {
+        *data.response.status_mut() = StatusCode::UNAUTHORIZED;
+        return Err(Error::new(ErrorKind::PermissionDenied, "User not found"));
+    };
+    claims.rol = user.role;
+    let now = OffsetDateTime::now_utc().unix_timestamp() as usize;
+    claims.exp = now + session_timeout(pool.as_ref()).await?;
+    claims.iat = now;
+    claims.nbf = now;
+    //Carry over the Password Update Requirement to the Refreshed Session
+    let require_update = session
+        .0
+        .read()
+        .await
+        .data
+        .get::<RequireUpdate>()
+        .map(|v| v.0.load(Ordering::Relaxed))
+        .unwrap_or_default();
+    if require_update {
+        session
+            .0
+            .write()
+            .await
+            .data
+            .insert(RequireUpdate(Arc::new(AtomicBool::new(true))));
+    }
+    debug!("Refreshed JWT for User {}", claims.sub);
+    encode_jwt(&claims)
+}
+
+#[post("/api/auth/totp/verify", output = "json", eoutput = "bytes")]
+pub async fn totp_verify(
+    pool: State<SqlitePool>,
+    session: State<RwLock<Session>>,
+    attempts: State<LoginAttemptTracker>,
+    data: Json<Option<TotpVerifyPayload>>,
+) -> Result<String, Error> {
+    let Some(payload) = data.inner() else {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid TOTP Payload"));
+    };
+    let (requires_totp, pending_claims, address) = {
+        let session = session.0.read().await;
+        (
+            session
+                .data
+                .get::<RequiresTotp>()
+                .map(|v| v.0)
+                .unwrap_or_default(),
+            session.data.get::<PendingTotpClaims>().map(|v| v.0.clone()),
+            session
+                .data
+                .get::<ClientAddress>()
+                .map(|v| address_key(&v.0)),
+        )
+    };
+    let Some(mut claims) = pending_claims.filter(|_| requires_totp) else {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "No TOTP Verification Pending",
+        ));
+    };
+    let user_key = totp_key(&claims.sub);
+    if attempts.0.is_locked(&user_key) || address.as_ref().is_some_and(|a| attempts.0.is_locked(a))
+    {
+        return Err(Error::new(ErrorKind::PermissionDenied, "account locked"));
+    }
+    let user_id = i64::from_str(&claims.sub)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Invalid User Id: {e:?}")))?;
+    let secret = get_totp_secret(pool.as_ref(), user_id)
+        .await?
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "TOTP is not enabled"))?;
+    let totp = build_totp(&secret, &claims.eml)?;
+    if !totp
+        .check_current(&payload.code)
+        .map_err(|e| Error::other(format!("{e:?}")))?
+    {
+        attempts.0.record_failure(&user_key);
+        if let Some(address) = &address {
+            attempts.0.record_failure(address);
+        }
+        return Err(Error::new(ErrorKind::PermissionDenied, "Invalid TOTP Code"));
+    }
+    attempts.0.record_success(&user_key);
+    if let Some(address) = &address {
+        attempts.0.record_success(address);
+    }
+    let now = OffsetDateTime::now_utc().unix_timestamp() as usize;
+    claims.exp = now + session_timeout(pool.as_ref()).await?;
+    claims.iat = now;
+    claims.nbf = now;
+    let mut session = session.0.write().await;
+    session.data.insert(RequiresTotp(false));
+    session.data.insert(claims.clone());
+    debug!("Verified TOTP for User {}", claims.sub);
+    encode_jwt(&claims)
+}
+
+/// Checks the password of the user behind the request's token
+async fn verify_request_user(
+    pool: &SqlitePool,
+    argon: &Argon2<'static>,
+    claims: &Claims,
+    password: &str,
+) -> Result<i64, Error> {
+    let user = login(pool, &claims.eml)
+        .await?
+        .filter(|user| user.id.to_string() == claims.sub)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "User not found"))?;
+    let pch_string = String::from_utf8_lossy(&user.password).to_string();
+    let hash = PasswordHash::new(pch_string.as_ref()).map_err(|e| {
+        error!("{e:?}");
+        Error::new(ErrorKind::NotFound, "User not found")
+    })?;
+    if argon.verify_password(password.as_ref(), &hash).is_err() {
+        return Err(Error::new(ErrorKind::PermissionDenied, "Invalid Password"));
+    }
+    Ok(user.id)
+}
+
+/// Checks a TOTP code against `secret`, failures count towards the user's lockout
+fn check_totp_code(
+    attempts: &LoginAttemptTracker,
+    claims: &Claims,
+    secret: &str,
+    code: &str,
+) -> Result<(), Error> {
+    let user_key = totp_key(&claims.sub);
+    if attempts.is_locked(&user_key) {
+        return Err(Error::new(ErrorKind::PermissionDenied, "account locked"));
+    }
+    let totp = build_totp(secret, &claims.eml)?;
+    if totp
+        .check_current(code)
+        .map_err(|e| Error::other(format!("{e:?}")))?
+    {
+        attempts.record_success(&user_key);
+        Ok(())
+    } else {
+        attempts.record_failure(&user_key);
+        Err(Error::new(ErrorKind::PermissionDenied, "Invalid TOTP Code"))
+    }
+}
+
+#[post("/api/auth/totp/setup", output = "json", eoutput = "bytes")]
+pub async fn totp_setup(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    argon: State<Argon2<'static>>,
+    payload: Json<Option<TotpSetupPayload>>,
+) -> Result<String, Error> {
+    let claims = request_claims(data)?;
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid TOTP Payload"));
+    };
+    let user_id =
+        verify_request_user(pool.as_ref(), argon.as_ref(), &claims, &payload.password).await?;
+    if get_totp_secret(pool.as_ref(), user_id).await?.is_some() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            "TOTP is already enabled, disable it before setting up a new code",
+        ));
+    }
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    let totp = build_totp(&secret, &claims.eml)?;
+    //Stays pending until a code from the authenticator is confirmed
+    set_totp_secret(pool.as_ref(), user_id, Some(&secret)).await?;
+    Ok(totp.get_url())
+}
+
+#[post("/api/auth/totp/confirm", output = "json", eoutput = "bytes")]
+pub async fn totp_confirm(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    attempts: State<LoginAttemptTracker>,
+    payload: Json<Option<TotpVerifyPayload>>,
+) -> Result<bool, Error> {
+    let claims = request_claims(data)?;
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid TOTP Payload"));
+    };
+    let user_id = i64::from_str(&claims.sub)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Invalid User Id: {e:?}")))?;
+    let secret = get_pending_totp_secret(pool.as_ref(), user_id)
+        .await?
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "No TOTP setup is pending"))?;
+    check_totp_code(attempts.0.as_ref(), &claims, &secret, &payload.code)?;
+    info!("Enabled TOTP for User {user_id}");
+    confirm_totp_secret(pool.as_ref(), user_id).await
+}
+
+#[post("/api/auth/totp/disable", output = "json", eoutput = "bytes")]
+pub async fn totp_disable(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    argon: State<Argon2<'static>>,
+    attempts: State<LoginAttemptTracker>,
+    payload: Json<Option<TotpDisablePayload>>,
+) -> Result<(), Error> {
+    let claims = request_claims(data)?;
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid TOTP Payload"));
+    };
+    let user_id =
+        verify_request_user(pool.as_ref(), argon.as_ref(), &claims, &payload.password).await?;
+    let secret = get_totp_secret(pool.as_ref(), user_id)
+        .await?
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "TOTP is not enabled"))?;
+    check_totp_code(attempts.0.as_ref(), &claims, &secret, &payload.code)?;
+    info!("Disabled TOTP for User {user_id}");
+    set_totp_secret(pool.as_ref(), user_id, None).await
+}
+
+fn parse_user_id(id: &str) -> Result<i64, Error> {
+    i64::from_str(id)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid User Id: {e:?}")))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UserPreferencePayload {
+    pub value: String,
+}
+
+#[get("/api/users/me/preferences", output = "json", eoutput = "bytes")]
+pub async fn get_my_preferences(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+) -> Result<HashMap<String, String>, Error> {
+    let user_id = request_user_id(data)?;
+    get_user_preferences(pool.as_ref(), user_id).await
+}
+
+//Null when the user has not set the preference
+#[get("/api/users/me/preferences/{key}", output = "json", eoutput = "bytes")]
+pub async fn get_my_preference(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    key: Path,
+) -> Result<Option<String>, Error> {
+    let user_id = request_user_id(data)?;
+    get_user_preference(pool.as_ref(), user_id, &key.inner()).await
+}
+
+#[put("/api/users/me/preferences/{key}", output = "json", eoutput = "bytes")]
+pub async fn set_my_preference(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    key: Path,
+    payload: Json<Option<UserPreferencePayload>>,
+) -> Result<(), Error> {
+    let user_id = request_user_id(data)?;
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Invalid Preference Payload",
+        ));
+    };
+    let key = key.inner();
+    if key.trim().is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Preference key cannot be empty",
+        ));
+    }
+    set_user_preference(pool.as_ref(), user_id, &key, &payload.value).await
+}
+
+#[delete("/api/users/me/preferences/{key}", output = "json", eoutput = "bytes")]
+pub async fn delete_my_preference(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    key: Path,
+) -> Result<bool, Error> {
+    let user_id = request_user_id(data)?;
+    delete_user_preference(pool.as_ref(), user_id, &key.inner()).await
+}
+
+#[get("/api/users", output = "json", eoutput = "bytes")]
+pub async fn list_users(pool: State<SqlitePool>) -> Result<Vec<UserPublicInfo>, Error> {
+    get_all_users(pool.as_ref()).await
+}
+
+#[delete("/api/users/{id}", output = "json", eoutput = "bytes")]
+pub async fn remove_user(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    id: Path,
+) -> Result<bool, Error> {
+    let claims = request_claims(data)?;
+    let user_id = parse_user_id(&id.inner())?;
+    if parse_user_id(&claims.sub)? == user_id {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Cannot delete your own account",
+        ));
+    }
+    delete_user(pool.as_ref(), user_id, &claims.rol).await
+}
+
+#[put("/api/users/{id}/role", output = "json", eoutput = "bytes")]
+pub async fn set_user_role(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    id: Path,
+    payload: Json<Option<RoleUpdatePayload>>,
+) -> Result<bool, Error> {
+    let claims = request_claims(data)?;
+    let user_id = parse_user_id(&id.inner())?;
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid Role Payload"));
+    };
+    if parse_user_id(&claims.sub)? == user_id && !matches!(payload.role, UserRole::SuperAdmin) {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Cannot change your own role",
+        ));
+    }
+    update_user_role(pool.as_ref(), user_id, payload.role).await
+}
+
+#[derive(Debug, Serialize)]
+pub struct Argon2ParamsInfo {
+    pub algorithm: String,
+    pub version: u32,
+    pub memory_kb: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub output_len: Option<usize>,
+}
+
+#[get("/api/auth/argon2/params", output = "json", eoutput = "bytes")]
+pub async fn argon2_params(argon: State<Argon2<'static>>) -> Result<Argon2ParamsInfo, Error> {
+    let params = argon.0.params();
+    Ok(Argon2ParamsInfo {
+        algorithm: "argon2id".to_string(),
+        version: argon2::Version::V0x13 as u32,
+        memory_kb: params.m_cost(),
+        iterations: params.t_cost(),
+        parallelism: params.p_cost(),
+        output_len: params.output_len(),
+    })
+}
+
+#[get("/api/auth/session/timeout", output = "json", eoutput = "bytes")]
+pub async fn get_session_timeout(pool: State<SqlitePool>) -> Result<usize, Error> {
+    session_timeout(pool.as_ref()).await
+}
+
+#[post("/api/auth/session/timeout", output = "json", eoutput = "bytes")]
+pub async fn set_session_timeout(
+    pool: State<SqlitePool>,
+    config_manager: State<RwLock<ConfigManager>>,
+    data: Json<Option<SessionTimeoutPayload>>,
+) -> Result<usize, Error> {
+    let Some(payload) = data.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Invalid Session Timeout Payload",
+        ));
+    };
+    if !(MIN_SESSION_TIMEOUT_SECONDS..=MAX_SESSION_TIMEOUT_SECONDS).contains(&payload.seconds) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Session Timeout must be between {MIN_SESSION_TIMEOUT_SECONDS} and {MAX_SESSION_TIMEOUT_SECONDS} seconds"),
+        ));
+    }
+    let last_value = session_timeout(pool.as_ref()).await?;
+    config_manager
+        .0
+        .write()
+        .await
+        .set(
+            SESSION_TIMEOUT_CONFIG_KEY,
+            AddConfigEntry {
+                key: SESSION_TIMEOUT_CONFIG_KEY.to_string(),
+                value: payload.seconds.to_string(),
+                last_value: last_value.to_string(),
+                category: "auth".to_string(),
+                system: 0,
+            },
+            Some(pool.as_ref()),
+        )
+        .await?;
+    Ok(payload.seconds)
+}
+
+#[get("/api/auth/lockout/{username}", output = "json", eoutput = "bytes")]
+pub async fn get_lockout(
+    username: Path,
+    attempts: State<LoginAttemptTracker>,
+) -> Result<LockoutStatus, Error> {
+    Ok(attempts.0.status(&username.inner()))
+}
+
+#[delete("/api/auth/lockout/{username}", output = "json", eoutput = "bytes")]
+pub async fn clear_lockout(
+    username: Path,
+    attempts: State<LoginAttemptTracker>,
+) -> Result<bool, Error> {
+    let username = username.inner();
+    info!("Manually Unlocking Login for {username}");
+    Ok(attempts.0.unlock(&username))
+}
+
+#[test]
+fn test_resolve_client_address() {
+    let peer = IpAddr::from([192, 168, 1, 20]);
+    let proxy = IpAddr::from([127, 0, 0, 1]);
+    assert_eq!(resolve_client_address(None, Some("10.0.0.1"), &[]), None);
+    assert_eq!(
+        resolve_client_address(Some(peer), Some("10.0.0.1"), &[proxy]).as_deref(),
+        Some("192.168.1.20")
+    );
+    assert_eq!(
+        resolve_client_address(Some(proxy), Some("10.0.0.9, 10.0.0.1, 127.0.0.1"), &[proxy])
+            .as_deref(),
+        Some("10.0.0.1")
+    );
+    assert_eq!(
+        resolve_client_address(Some(proxy), None, &[proxy]).as_deref(),
+        Some("127.0.0.1")
+    );
+}
+
+#[test]
+fn test_login_attempt_eviction() {
+    let tracker = LoginAttemptTracker::new();
+    tracker.record_failure("ip:10.0.0.1");
+    tracker.attempts.insert(
+        "ip:10.0.0.2".to_string(),
+        AttemptRecord {
+            failures: 1,
+            locked_until: None,
+            last_failure: Instant::now().checked_sub(LOGIN_LOCKOUT_DURATION),
+        },
+    );
+    assert_eq!(tracker.evict_stale(), 1);
+    assert_eq!(tracker.status("ip:10.0.0.1").failures, 1);
+}
+
+#[tokio::test]
+async fn test_password_login_preferences() {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    crate::utils::run_migrations(&pool).await.unwrap();
+    let user = register(
+        &pool,
+        &Argon2::default(),
+        UserWithInfoWithPassword {
+            id: -1,
+            username: "viewer".to_string(),
+            password: b"password".to_vec(),
+            role: UserRole::User,
+        },
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let _ = JWT_SECRET.set(b"test_secret".to_vec());
+    //Without TOTP the login token is the only place the user is recorded
+    assert!(get_totp_secret(&pool, user.id).await.unwrap().is_none());
+    let now = OffsetDateTime::now_utc().unix_timestamp() as usize;
+    let token = encode_jwt(&user_claims(
+        user.id,
+        user.username,
+        user.role,
+        now,
+        DEFAULT_SESSION_TIMEOUT_SECONDS,
+    ))
+    .unwrap();
+    let claims = bearer_claims(Some(&format!("Bearer {token}"))).unwrap();
+    let user_id = parse_user_id(&claims.sub).unwrap();
+    assert_eq!(user_id, user.id);
+    set_user_preference(&pool, user_id, "theme", "dark")
+        .await
+        .unwrap();
+    assert_eq!(
+        get_user_preferences(&pool, user_id)
+            .await
+            .unwrap()
+            .get("theme")
+            .map(String::as_str),
+        Some("dark")
+    );
+    assert_eq!(
+        get_user_preference(&pool, user_id, "theme").await.unwrap(),
+        Some("dark".to_string())
+    );
+    assert!(get_user_preference(&pool, user_id, "language")
+        .await
+        .unwrap()
+        .is_none());
+    assert!(bearer_claims(None).is_err());
+    assert!(bearer_claims(Some("Bearer invalid")).is_err());
+}
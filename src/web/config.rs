@@ -1,16 +1,144 @@
-use crate::config::ConfigManager;
-use crate::database::config::{delete_config_entry, get_config};
-use crate::models::config::{AddConfigEntry, ConfigEntry};
+use crate::config::{
+    ensure_writable, is_protected_key, redact_entry, redact_value, ConfigManager, ConfigSchemaInfo,
+};
+use crate::database::config::{
+    delete_config_entry, get_config, get_config_history, get_config_history_entry,
+};
+use crate::models::config::{AddConfigEntry, ConfigEntry, ConfigHistoryEntry};
+use crate::web::auth::request_user_id;
+use log::debug;
+use portfu::prelude::tokio_tungstenite::tungstenite::Message;
 use portfu::prelude::*;
-use portfu_core::Json;
-use portfu_macros::{delete, get, post};
+use portfu_core::{Json, ServiceData};
+use portfu_macros::{delete, get, interval, post, websocket};
 use sqlx::SqlitePool;
+use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::RwLock;
 
 #[get("/config", output = "json", eoutput = "bytes")]
 pub async fn configs(db: State<SqlitePool>) -> Result<Vec<ConfigEntry>, Error> {
-    get_config(db.as_ref()).await
+    Ok(get_config(db.as_ref())
+        .await?
+        .into_iter()
+        .map(redact_entry)
+        .collect())
+}
+
+#[get("/config/schema", output = "json", eoutput = "bytes")]
+pub async fn config_schema(
+    state: State<RwLock<ConfigManager>>,
+) -> Result<Vec<ConfigSchemaInfo>, Error> {
+    Ok(state.0.read().await.schemas())
+}
+
+#[get("/config/export", output = "json", eoutput = "bytes")]
+pub async fn export_config(db: State<SqlitePool>) -> Result<HashMap<String, String>, Error> {
+    Ok(get_config(db.as_ref())
+        .await?
+        .into_iter()
+        .filter(|e| e.system == 0 && !is_protected_key(&e.key))
+        .map(|e| (e.key, e.value))
+        .collect())
+}
+
+async fn bulk_update(
+    db: &SqlitePool,
+    state: &RwLock<ConfigManager>,
+    mut entries: Vec<AddConfigEntry>,
+    user_id: i64,
+) -> Result<Vec<ConfigEntry>, Error> {
+    for entry in entries.iter_mut() {
+        ensure_writable(&entry.key)?;
+        entry.system = 0;
+    }
+    state
+        .write()
+        .await
+        .set_batch(entries, db, Some(user_id))
+        .await
+        .map_err(|errors| match serde_json::to_string(&errors) {
+            Ok(as_json) => Error::new(ErrorKind::InvalidData, as_json),
+            Err(e) => Error::new(ErrorKind::InvalidData, format!("{e:?}")),
+        })
+}
+
+#[post("/config/bulk", output = "json", eoutput = "bytes")]
+pub async fn bulk_config(
+    data: &mut ServiceData,
+    db: State<SqlitePool>,
+    body: Json<Option<Vec<AddConfigEntry>>>,
+    state: State<RwLock<ConfigManager>>,
+) -> Result<Vec<ConfigEntry>, Error> {
+    let user_id = request_user_id(data)?;
+    match body.inner() {
+        Some(entries) => bulk_update(db.as_ref(), state.0.as_ref(), entries, user_id).await,
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "The provided config is Invalid",
+        )),
+    }
+}
+
+#[post("/config/import", output = "json", eoutput = "bytes")]
+pub async fn import_config(
+    data: &mut ServiceData,
+    db: State<SqlitePool>,
+    body: Json<Option<HashMap<String, String>>>,
+    state: State<RwLock<ConfigManager>>,
+) -> Result<Vec<ConfigEntry>, Error> {
+    let user_id = request_user_id(data)?;
+    match body.inner() {
+        Some(values) => {
+            let mut entries = vec![];
+            {
+                let config_manager = state.0.read().await;
+                for (key, value) in values {
+                    let existing = config_manager.get(&key).await;
+                    entries.push(AddConfigEntry {
+                        last_value: existing
+                            .as_ref()
+                            .map(|e| e.value.clone())
+                            .unwrap_or_default(),
+                        category: existing
+                            .map(|e| e.category)
+                            .unwrap_or_else(|| "imported".to_string()),
+                        key,
+                        value,
+                        system: 0,
+                    });
+                }
+            }
+            bulk_update(db.as_ref(), state.0.as_ref(), entries, user_id).await
+        }
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "The provided config is Invalid",
+        )),
+    }
+}
+
+#[post("/config/reload", output = "json", eoutput = "bytes")]
+pub async fn reload_config(
+    db: State<SqlitePool>,
+    state: State<RwLock<ConfigManager>>,
+) -> Result<usize, Error> {
+    state.0.write().await.reload(db.as_ref()).await
+}
+
+#[interval(60_000)]
+pub async fn refresh_config_cache(
+    db: State<SqlitePool>,
+    state: State<RwLock<ConfigManager>>,
+) -> Result<(), Error> {
+    let changed = state.0.write().await.reload(db.as_ref()).await?;
+    if changed > 0 {
+        debug!("Reloaded {changed} Config entries from the database");
+    }
+    Ok(())
 }
 
 #[get("/config/{key}", output = "json", eoutput = "bytes")]
@@ -18,24 +146,99 @@ pub async fn config_entry(
     key: Path,
     state: State<RwLock<ConfigManager>>,
 ) -> Result<Option<ConfigEntry>, Error> {
-    Ok(state.0.read().await.get(&key.inner()).await)
+    Ok(state
+        .0
+        .read()
+        .await
+        .get(&key.inner())
+        .await
+        .map(redact_entry))
+}
+
+#[get("/config/{key}/history", output = "json", eoutput = "bytes")]
+pub async fn config_history(
+    db: State<SqlitePool>,
+    key: Path,
+) -> Result<Vec<ConfigHistoryEntry>, Error> {
+    Ok(get_config_history(db.as_ref(), &key.inner())
+        .await?
+        .into_iter()
+        .map(|mut entry| {
+            entry.value = redact_value(&entry.key, &entry.value);
+            entry
+        })
+        .collect())
+}
+
+#[post("/config/{key}/rollback/{id}", output = "json", eoutput = "bytes")]
+pub async fn rollback_config(
+    data: &mut ServiceData,
+    db: State<SqlitePool>,
+    key: Path,
+    id: Path,
+    state: State<RwLock<ConfigManager>>,
+) -> Result<Option<ConfigEntry>, Error> {
+    let user_id = request_user_id(data)?;
+    let key = key.inner();
+    ensure_writable(&key)?;
+    let id = i64::from_str(&id.inner()).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid History Id: {e:?}"),
+        )
+    })?;
+    let history = match get_config_history_entry(db.as_ref(), id).await? {
+        Some(history) if history.key == key => history,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Failed to find history entry {id} for key: {key}"),
+            ))
+        }
+    };
+    let mut config_manager = state.0.write().await;
+    let current = config_manager.get(&key).await.ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("Failed to find entry with key: {key}"),
+        )
+    })?;
+    config_manager
+        .set_by(
+            &key,
+            AddConfigEntry {
+                key: key.clone(),
+                value: history.value,
+                last_value: current.value,
+                category: current.category,
+                system: current.system,
+            },
+            Some(db.as_ref()),
+            Some(user_id),
+        )
+        .await
 }
 
 #[post("/config/{key}", output = "json", eoutput = "bytes")]
 pub async fn upload_config(
+    data: &mut ServiceData,
     db: State<SqlitePool>,
     key: Path,
     body: Json<Option<AddConfigEntry>>,
     state: State<RwLock<ConfigManager>>,
 ) -> Result<Option<ConfigEntry>, Error> {
+    let user_id = request_user_id(data)?;
     match body.inner() {
         Some(mut body) => {
+            let key = key.inner();
+            ensure_writable(&key)?;
+            ensure_writable(&body.key)?;
             body.system = 0;
             state
                 .0
                 .write()
                 .await
-                .set(&key.inner(), body, Some(db.as_ref()))
+                .set_by(&key, body, Some(db.as_ref()), Some(user_id))
                 .await
         }
         None => Err(Error::new(
@@ -46,8 +249,75 @@ pub async fn upload_config(
 }
 
 #[delete("/config/{key}", output = "json", eoutput = "bytes")]
-pub async fn del_config(db: State<SqlitePool>, key: Path) -> Result<bool, Error> {
-    delete_config_entry(db.as_ref(), &key.inner())
-        .await
-        .map(|v| v > 0)
+pub async fn del_config(
+    db: State<SqlitePool>,
+    key: Path,
+    state: State<RwLock<ConfigManager>>,
+) -> Result<bool, Error> {
+    let key = key.inner();
+    ensure_writable(&key)?;
+    let mut config_manager = state.0.write().await;
+    if config_manager.get(&key).await.is_some() {
+        config_manager.delete(&key, db.as_ref()).await?;
+        Ok(true)
+    } else {
+        delete_config_entry(db.as_ref(), &key).await.map(|v| v > 0)
+    }
+}
+
+#[websocket("/config/watch")]
+pub async fn watch_config(
+    socket: WebSocket,
+    data: &mut ServiceData,
+    state: State<RwLock<ConfigManager>>,
+) -> Result<(), Error> {
+    let category = data.request.request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == "category")
+            .map(|(_, value)| value.to_string())
+    });
+    let mut receiver = state.0.read().await.subscribe();
+    loop {
+        tokio::select! {
+            result = receiver.recv() => {
+                match result {
+                    Ok(mut event) => {
+                        event.value = redact_value(&event.key, &event.value);
+                        if category.as_ref().is_some_and(|c| *c != event.category) {
+                            continue;
+                        }
+                        let as_json = serde_json::to_string(&event)?;
+                        if let Err(e) = socket.send(Message::Text(as_json.into())).await {
+                            debug!("Failed to send config event: {e:?}");
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(count)) => {
+                        debug!("Config Watcher skipped {count} events");
+                    }
+                    Err(RecvError::Closed) => {
+                        break;
+                    }
+                }
+            }
+            result = socket.next() => {
+                match result? {
+                    Some(Message::Ping(ping_data)) => {
+                        socket.send(Message::Pong(ping_data)).await?;
+                    }
+                    Some(Message::Close(_)) => break,
+                    Some(_) => {
+                        //Ignore Client Messages
+                        continue;
+                    }
+                    None => {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
 }
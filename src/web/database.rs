@@ -0,0 +1,245 @@
+use crate::config::ConfigManager;
+use crate::database::{backup, restore};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use log::{info, warn};
+use portfu::prelude::async_trait::async_trait;
+use portfu::prelude::http::StatusCode;
+use portfu::prelude::State;
+use portfu_core::wrappers::{WrapperFn, WrapperResult};
+use portfu_core::{Json, ServiceData};
+use portfu_macros::{get, post};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::fs;
+use tokio::sync::{OwnedRwLockReadGuard, RwLock};
+
+//Requests still running when a restore starts get this long to finish
+const RESTORE_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+const RESTORE_PATH: &str = "/api/database/restore";
+
+#[derive(Debug, Serialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub size_bytes: u64,
+    pub timestamp: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RestorePayload {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RestoreUploadPayload {
+    //Base64 encoded SQLite database
+    pub data: String,
+}
+
+pub struct DatabaseBackupManager {
+    backup_path: PathBuf,
+    restore_lock: Arc<RwLock<()>>,
+}
+impl DatabaseBackupManager {
+    pub fn new(backup_path: PathBuf) -> Self {
+        Self {
+            backup_path,
+            restore_lock: Default::default(),
+        }
+    }
+    pub fn wrapper(&self) -> DatabaseRestoreWrapper {
+        DatabaseRestoreWrapper {
+            restore_lock: self.restore_lock.clone(),
+        }
+    }
+    pub async fn backup(&self, pool: &SqlitePool) -> Result<BackupInfo, Error> {
+        fs::create_dir_all(&self.backup_path).await?;
+        let timestamp = OffsetDateTime::now_utc();
+        let path = self.backup_path.join(format!(
+            "druid_garden_{}.sqlite",
+            timestamp.unix_timestamp()
+        ));
+        info!("Backing up Database to {}", path.display());
+        backup(pool, &path).await?;
+        let size_bytes = fs::metadata(&path).await?.len();
+        Ok(BackupInfo {
+            path: path.display().to_string(),
+            size_bytes,
+            timestamp,
+        })
+    }
+    pub async fn list(&self) -> Result<Vec<BackupInfo>, Error> {
+        let mut backups = vec![];
+        if !self.backup_path.exists() {
+            return Ok(backups);
+        }
+        let mut entries = fs::read_dir(&self.backup_path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+            backups.push(BackupInfo {
+                path: entry.path().display().to_string(),
+                size_bytes: metadata.len(),
+                timestamp: metadata
+                    .modified()
+                    .map(OffsetDateTime::from)
+                    .unwrap_or(OffsetDateTime::UNIX_EPOCH),
+            });
+        }
+        backups.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        Ok(backups)
+    }
+    pub async fn restore(
+        &self,
+        pool: &SqlitePool,
+        config_manager: &RwLock<ConfigManager>,
+        name: &str,
+    ) -> Result<(), Error> {
+        //Only allow restoring files directly inside the backup directory
+        let file_name = PathBuf::from(name);
+        if file_name.file_name().map(|f| f.to_os_string()) != Some(file_name.as_os_str().into()) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid Backup Name: {name}"),
+            ));
+        }
+        let path = self.backup_path.join(file_name);
+        //New requests are rejected once the write lock is requested, in flight ones finish first
+        let _guard = tokio::time::timeout(RESTORE_DRAIN_TIMEOUT, self.restore_lock.write())
+            .await
+            .map_err(|_| {
+                Error::new(
+                    ErrorKind::TimedOut,
+                    "Timed out waiting for in flight requests before restoring",
+                )
+            })?;
+        warn!("Restoring Database from {}", path.display());
+        restore(pool, &path).await?;
+        //The config cache still holds the old database's values
+        let changed = config_manager.write().await.reload(pool).await?;
+        info!("Reloaded {changed} Config entries from the restored Database");
+        Ok(())
+    }
+    pub async fn restore_upload(
+        &self,
+        pool: &SqlitePool,
+        config_manager: &RwLock<ConfigManager>,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        fs::create_dir_all(&self.backup_path).await?;
+        //Uploads are kept alongside the backups so the restore can be repeated
+        let name = format!(
+            "uploaded_{}.sqlite",
+            OffsetDateTime::now_utc().unix_timestamp()
+        );
+        fs::write(self.backup_path.join(&name), data).await?;
+        self.restore(pool, config_manager, &name).await
+    }
+}
+
+pub struct DatabaseRestoreWrapper {
+    restore_lock: Arc<RwLock<()>>,
+}
+#[async_trait]
+impl WrapperFn for DatabaseRestoreWrapper {
+    fn name(&self) -> &str {
+        "DatabaseRestoreWrapper"
+    }
+
+    async fn before(&self, data: &mut ServiceData) -> WrapperResult {
+        let request = &data.request.request;
+        //The restore waits on this lock itself, and websockets would hold it for their whole life
+        if request.uri().path().starts_with(RESTORE_PATH)
+            || request.headers().contains_key("upgrade")
+        {
+            return WrapperResult::Continue;
+        }
+        match self.restore_lock.clone().try_read_owned() {
+            Ok(guard) => {
+                //Dropped with the request, so a restore waits for it to finish
+                data.request
+                    .request
+                    .extensions_mut()
+                    .insert(Arc::new(guard));
+                WrapperResult::Continue
+            }
+            Err(_) => {
+                *data.response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                WrapperResult::Return
+            }
+        }
+    }
+
+    async fn after(&self, data: &mut ServiceData) -> WrapperResult {
+        data.request
+            .request
+            .extensions_mut()
+            .remove::<Arc<OwnedRwLockReadGuard<()>>>();
+        WrapperResult::Continue
+    }
+}
+
+#[post("/api/database/backup", output = "json", eoutput = "bytes")]
+pub async fn backup_database(
+    pool: State<SqlitePool>,
+    backups: State<DatabaseBackupManager>,
+) -> Result<BackupInfo, Error> {
+    backups.0.backup(pool.as_ref()).await
+}
+
+#[get("/api/database/backups", output = "json", eoutput = "bytes")]
+pub async fn list_backups(backups: State<DatabaseBackupManager>) -> Result<Vec<BackupInfo>, Error> {
+    backups.0.list().await
+}
+
+#[post("/api/database/restore", output = "json", eoutput = "bytes")]
+pub async fn restore_database(
+    pool: State<SqlitePool>,
+    backups: State<DatabaseBackupManager>,
+    config_manager: State<RwLock<ConfigManager>>,
+    payload: Json<Option<RestorePayload>>,
+) -> Result<(), Error> {
+    match payload.inner() {
+        Some(payload) => {
+            backups
+                .0
+                .restore(pool.as_ref(), config_manager.0.as_ref(), &payload.name)
+                .await
+        }
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Restore Payload",
+        )),
+    }
+}
+
+#[post("/api/database/restore/upload", output = "json", eoutput = "bytes")]
+pub async fn restore_database_upload(
+    pool: State<SqlitePool>,
+    backups: State<DatabaseBackupManager>,
+    config_manager: State<RwLock<ConfigManager>>,
+    payload: Json<Option<RestoreUploadPayload>>,
+) -> Result<(), Error> {
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Restore Payload",
+        ));
+    };
+    let data = BASE64_STANDARD.decode(payload.data.trim()).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid Backup Encoding: {e}"),
+        )
+    })?;
+    backups
+        .0
+        .restore_upload(pool.as_ref(), config_manager.0.as_ref(), &data)
+        .await
+}
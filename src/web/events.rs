@@ -0,0 +1,29 @@
+use crate::database::events::{
+    get_system_events_page, EventSeverity, SystemEvent, SystemEventFilter,
+};
+use crate::models::pagination::{ListQuery, Paginated};
+use crate::web::audit::timestamp_filter;
+use portfu::prelude::State;
+use portfu_core::ServiceData;
+use portfu_macros::get;
+use sqlx::SqlitePool;
+use std::io::Error;
+use std::str::FromStr;
+
+#[get("/api/events", output = "json", eoutput = "bytes")]
+pub async fn get_system_events(
+    data: &mut ServiceData,
+    database: State<SqlitePool>,
+) -> Result<Paginated<SystemEvent>, Error> {
+    let query = ListQuery::parse(data.request.request.uri().query())?;
+    let filter = SystemEventFilter {
+        start: timestamp_filter(&query, "start")?,
+        end: timestamp_filter(&query, "end")?,
+        category: query.filter("category").map(str::to_string),
+        severity: query
+            .filter("severity")
+            .map(EventSeverity::from_str)
+            .transpose()?,
+    };
+    get_system_events_page(database.as_ref(), &filter, &query).await
+}
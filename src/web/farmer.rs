@@ -1,12 +1,22 @@
 use crate::config::{
-    DEFAULT_FULLNODE_RPC_HOST, DEFAULT_FULLNODE_RPC_PORT, DEFAULT_FULLNODE_WS_HOST,
+    ConfigManager, DEFAULT_FULLNODE_RPC_HOST, DEFAULT_FULLNODE_RPC_PORT, DEFAULT_FULLNODE_WS_HOST,
     DEFAULT_FULLNODE_WS_PORT,
 };
+use crate::database::stats::{
+    farmer_stats_to_csv, get_farmer_rewards as load_farmer_rewards,
+    get_farmer_rewards_total as load_farmer_rewards_total, get_farmer_stats_by_launcher,
+    get_farmer_stats_launchers, get_farmer_stats_page, get_farmer_stats_size,
+    summarize_farmer_stats, FarmerReward, FarmerStatsFilter, FarmerStatsSize, FarmerStatsSummary,
+};
 use crate::legacy::PreloadConfig;
+use crate::models::pagination::{ListQuery, ListResponse};
 use crate::plugins::farmer::{
-    load_farmer_config, save_farmer_config, FarmerManager, FarmerStatus, HarvesterConfig,
+    load_farmer_config, load_stats_policy, save_farmer_config, ConnectionInfo, DirectoryConfig,
+    FarmerManager, FarmerStatus, HarvesterConfig, InstallProgress, StatsPolicy,
+    STATS_DAYS_SAVED_CONFIG_KEY, STATS_MAX_ROWS_CONFIG_KEY,
 };
 use crate::plugins::system_monitor::SystemMonitorPlugin;
+use crate::web::notifications::WebhookNotifier;
 use blst::min_pk::SecretKey;
 use dg_fast_farmer::cli::commands::{generate_config_from_mnemonic, GenerateConfig};
 use dg_fast_farmer::farmer::config::{Config, MetricsConfig};
@@ -14,17 +24,23 @@ use dg_fast_farmer::routes::FarmerPublicState;
 use dg_xch_clients::api::pool::create_pool_login_url;
 use dg_xch_core::blockchain::sized_bytes::Bytes32;
 use dg_xch_core::protocols::farmer::FarmerStats;
-use log::{info, warn, Level};
+use log::{debug, info, warn, Level};
+use portfu::prelude::http::{HeaderName, HeaderValue};
+use portfu::prelude::serde_json;
+use portfu::prelude::serde_json::json;
+use portfu::prelude::tokio_tungstenite::tungstenite::Message;
 use portfu::prelude::{Path, State, WebSocket};
-use portfu_core::Json;
-use portfu_macros::{get, post, websocket};
-use serde::Deserialize;
+use portfu_core::{Json, ServiceData};
+use portfu_macros::{delete, get, post, websocket};
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 use time::OffsetDateTime;
+use tokio::sync::RwLock;
 
 #[get("/farmer/config/ready", output = "json", eoutput = "bytes")]
 pub async fn is_config_ready(pool: State<SqlitePool>) -> Result<bool, Error> {
@@ -38,6 +54,51 @@ pub async fn get_config(pool: State<SqlitePool>) -> Result<Config<HarvesterConfi
     Ok(config)
 }
 
+#[get("/farmer/config/yaml", output = "bytes", eoutput = "bytes")]
+pub async fn get_config_yaml(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+) -> Result<String, Error> {
+    let config = load_farmer_config(pool.0.as_ref()).await?;
+    let yaml = serde_yaml::to_string(&config).map_err(Error::other)?;
+    let headers = data.response.headers_mut();
+    headers.insert(
+        HeaderName::from_static("content-type"),
+        HeaderValue::from_static("text/yaml"),
+    );
+    headers.insert(
+        HeaderName::from_static("content-disposition"),
+        HeaderValue::from_static("attachment; filename=\"fast_farmer_config.yaml\""),
+    );
+    Ok(yaml)
+}
+
+#[derive(Deserialize)]
+pub struct YamlConfigPayload {
+    pub yaml: String,
+}
+
+#[post("/farmer/config/yaml", output = "json", eoutput = "bytes")]
+pub async fn update_config_yaml(
+    pool: State<SqlitePool>,
+    payload: Json<Option<YamlConfigPayload>>,
+) -> Result<Config<HarvesterConfig>, Error> {
+    match payload.inner() {
+        Some(payload) => {
+            let config: Config<HarvesterConfig> =
+                serde_yaml::from_str(&payload.yaml).map_err(|e| {
+                    Error::new(ErrorKind::InvalidInput, format!("Invalid Config YAML: {e}"))
+                })?;
+            save_farmer_config(pool.0.as_ref(), &config).await?;
+            Ok(config)
+        }
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Config Payload",
+        )),
+    }
+}
+
 #[get("/farmer/pool/login", output = "json", eoutput = "bytes")]
 pub async fn get_pool_login(
     pool: State<SqlitePool>,
@@ -101,11 +162,93 @@ pub async fn get_farmer_metrics(farmer_manager: State<FarmerManager>) -> Result<
     farmer_manager.0.farmer_metrics().await
 }
 
+#[derive(Serialize)]
+pub struct FarmerStderrLog {
+    //False when stderr capture is disabled, lines are then empty rather than quiet
+    pub capturing: bool,
+    pub lines: Vec<String>,
+}
+
+#[get("/farmer/stderr_log", output = "json", eoutput = "bytes")]
+pub async fn get_farmer_stderr_log(
+    farmer_manager: State<FarmerManager>,
+) -> Result<FarmerStderrLog, Error> {
+    Ok(FarmerStderrLog {
+        capturing: farmer_manager.0.is_capturing_logs(),
+        lines: farmer_manager.0.stderr_log().await,
+    })
+}
+
 #[get("/farmer/stats", output = "json", eoutput = "bytes")]
 pub async fn get_farmer_stats(
+    data: &mut ServiceData,
+    database: State<SqlitePool>,
     farmer_manager: State<FarmerManager>,
-) -> Result<Vec<FarmerStats>, Error> {
-    farmer_manager.0.recent_farmer_stats().await
+) -> Result<ListResponse<FarmerStats>, Error> {
+    let query = ListQuery::parse(data.request.request.uri().query())?;
+    //Without page or per_page this stays the live in memory stats it always returned
+    if !query.paginated {
+        return farmer_manager
+            .0
+            .recent_farmer_stats()
+            .await
+            .map(ListResponse::All);
+    }
+    let filter = FarmerStatsFilter {
+        launcher_id: launcher_id_param(data)?,
+        start: timestamp_param(data, "start")?,
+        end: timestamp_param(data, "end")?,
+    };
+    get_farmer_stats_page(database.as_ref(), &filter, &query)
+        .await
+        .map(ListResponse::Page)
+}
+
+#[derive(Serialize)]
+pub struct FarmerConnections {
+    pub connections: Vec<ConnectionInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+async fn full_node_sync_warning(farmer_manager: &FarmerManager) -> Option<String> {
+    match farmer_manager.latest_stats().await {
+        Some(stats) if !stats.full_node_synced => {
+            Some("Full Node is not synced, proofs may not be submitted".to_string())
+        }
+        _ => None,
+    }
+}
+
+#[get("/farmer/connections", output = "json", eoutput = "bytes")]
+pub async fn get_farmer_connections(
+    farmer_manager: State<FarmerManager>,
+) -> Result<FarmerConnections, Error> {
+    if !farmer_manager.0.is_running().await {
+        return Ok(FarmerConnections {
+            connections: vec![],
+            warning: None,
+        });
+    }
+    Ok(FarmerConnections {
+        connections: farmer_manager.0.farmer_connections().await?,
+        warning: full_node_sync_warning(farmer_manager.0.as_ref()).await,
+    })
+}
+
+#[post("/farmer/connections/refresh", output = "json", eoutput = "bytes")]
+pub async fn refresh_farmer_connections(
+    farmer_manager: State<FarmerManager>,
+) -> Result<FarmerConnections, Error> {
+    if !farmer_manager.0.is_running().await {
+        return Err(Error::new(ErrorKind::InvalidInput, "Farmer is not running"));
+    }
+    farmer_manager.0.refresh_farmer_connections().await?;
+    info!("Requested Farmer Connection Refresh");
+    Ok(FarmerConnections {
+        connections: farmer_manager.0.farmer_connections().await?,
+        warning: full_node_sync_warning(farmer_manager.0.as_ref()).await,
+    })
 }
 
 #[get("/farmer/state", output = "json", eoutput = "bytes")]
@@ -125,7 +268,7 @@ pub struct RangePayload {
 pub async fn get_farmer_stats_range(
     farmer_manager: State<FarmerManager>,
     payload: Json<Option<RangePayload>>,
-) -> Result<HashMap<(Bytes32, Bytes32), FarmerStats>, Error> {
+) -> Result<HashMap<(Bytes32, Bytes32, Bytes32), FarmerStats>, Error> {
     match payload.inner() {
         Some(payload) => {
             let start = OffsetDateTime::from_unix_timestamp(payload.start).map_err(|e| {
@@ -143,6 +286,219 @@ pub async fn get_farmer_stats_range(
     }
 }
 
+fn query_param<'a>(data: &'a ServiceData, name: &str) -> Option<&'a str> {
+    data.request.request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, value)| *key == name && !value.is_empty())
+            .map(|(_, value)| value)
+    })
+}
+
+fn launcher_id_param(data: &ServiceData) -> Result<Option<Bytes32>, Error> {
+    query_param(data, "launcher_id")
+        .map(|launcher_id| {
+            Bytes32::from_str(launcher_id).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Failed to parse launcher_id: {e}"),
+                )
+            })
+        })
+        .transpose()
+}
+
+fn timestamp_param(data: &ServiceData, name: &str) -> Result<Option<OffsetDateTime>, Error> {
+    query_param(data, name)
+        .map(|value| {
+            i64::from_str(value)
+                .map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Failed to parse {name}: {e}"),
+                    )
+                })
+                .and_then(|timestamp| {
+                    OffsetDateTime::from_unix_timestamp(timestamp).map_err(|e| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("Failed to parse {name}: {e}"),
+                        )
+                    })
+                })
+        })
+        .transpose()
+}
+
+#[get("/farmer/rewards", output = "json", eoutput = "bytes")]
+pub async fn get_farmer_rewards(
+    data: &mut ServiceData,
+    database: State<SqlitePool>,
+) -> Result<Vec<FarmerReward>, Error> {
+    let launcher_id = launcher_id_param(data)?;
+    let start = timestamp_param(data, "start")?.unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    let end = timestamp_param(data, "end")?.unwrap_or_else(OffsetDateTime::now_utc);
+    load_farmer_rewards(database.as_ref(), launcher_id, start, end).await
+}
+
+#[derive(Serialize)]
+pub struct FarmerRewardsSummary {
+    pub launcher_id: Option<Bytes32>,
+    pub total: f64,
+    pub count: i64,
+    pub estimated_value: Option<f64>,
+}
+
+#[get("/farmer/rewards/total", output = "json", eoutput = "bytes")]
+pub async fn get_farmer_rewards_total(
+    data: &mut ServiceData,
+    database: State<SqlitePool>,
+    farmer_manager: State<FarmerManager>,
+) -> Result<FarmerRewardsSummary, Error> {
+    let launcher_id = launcher_id_param(data)?;
+    let totals = load_farmer_rewards_total(database.as_ref(), launcher_id).await?;
+    let estimated_value = farmer_manager
+        .0
+        .xch_price()
+        .await
+        .map(|price| price * totals.total);
+    Ok(FarmerRewardsSummary {
+        launcher_id,
+        total: totals.total,
+        count: totals.count,
+        estimated_value,
+    })
+}
+
+async fn farmer_stats_param_range(
+    data: &ServiceData,
+    farmer_manager: &FarmerManager,
+) -> Result<Vec<FarmerStats>, Error> {
+    let start = timestamp_param(data, "start")?.unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    let end = timestamp_param(data, "end")?.unwrap_or_else(OffsetDateTime::now_utc);
+    let mut stats: Vec<FarmerStats> = farmer_manager
+        .farmer_stats_range(start, end)
+        .await?
+        .into_values()
+        .collect();
+    stats.sort_by_key(|row| row.gathered);
+    Ok(stats)
+}
+
+#[get("/farmer/stats/export", output = "bytes", eoutput = "bytes")]
+pub async fn export_farmer_stats(
+    data: &mut ServiceData,
+    farmer_manager: State<FarmerManager>,
+) -> Result<String, Error> {
+    let stats = farmer_stats_param_range(data, farmer_manager.0.as_ref()).await?;
+    match query_param(data, "format").unwrap_or("json") {
+        "csv" => {
+            let today = OffsetDateTime::now_utc().date();
+            let disposition = format!(
+                "attachment; filename=\"farmer_stats_{:04}{:02}{:02}.csv\"",
+                today.year(),
+                today.month() as u8,
+                today.day()
+            );
+            let headers = data.response.headers_mut();
+            headers.insert(
+                HeaderName::from_static("content-type"),
+                HeaderValue::from_static("text/csv"),
+            );
+            headers.insert(
+                HeaderName::from_static("content-disposition"),
+                HeaderValue::from_str(&disposition).map_err(Error::other)?,
+            );
+            Ok(farmer_stats_to_csv(&stats))
+        }
+        "json" => {
+            data.response.headers_mut().insert(
+                HeaderName::from_static("content-type"),
+                HeaderValue::from_static("application/json"),
+            );
+            serde_json::to_string(&stats).map_err(Error::other)
+        }
+        format => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unsupported Export Format: {format}"),
+        )),
+    }
+}
+
+#[get("/farmer/stats/summary", output = "json", eoutput = "bytes")]
+pub async fn farmer_stats_summary(
+    data: &mut ServiceData,
+    farmer_manager: State<FarmerManager>,
+) -> Result<FarmerStatsSummary, Error> {
+    let stats = farmer_stats_param_range(data, farmer_manager.0.as_ref()).await?;
+    Ok(summarize_farmer_stats(&stats))
+}
+
+#[get("/farmer/stats/launchers", output = "json", eoutput = "bytes")]
+pub async fn farmer_stats_launchers(database: State<SqlitePool>) -> Result<Vec<Bytes32>, Error> {
+    get_farmer_stats_launchers(database.as_ref()).await
+}
+
+#[get("/api/stats/policy", output = "json", eoutput = "bytes")]
+pub async fn get_stats_policy(config: State<RwLock<ConfigManager>>) -> Result<StatsPolicy, Error> {
+    Ok(load_stats_policy(&*config.0.read().await).await)
+}
+
+#[post("/api/stats/policy", output = "json", eoutput = "bytes")]
+pub async fn set_stats_policy(
+    database: State<SqlitePool>,
+    config: State<RwLock<ConfigManager>>,
+    payload: Json<Option<StatsPolicy>>,
+) -> Result<StatsPolicy, Error> {
+    let Some(policy) = payload.inner() else {
+        return Err(Error::new(ErrorKind::InvalidInput, "Invalid Stats Policy"));
+    };
+    let mut config = config.0.write().await;
+    //Validate both before writing so a bad max_rows doesn't leave days_saved half applied
+    config.validate(STATS_DAYS_SAVED_CONFIG_KEY, &policy.days_saved.to_string())?;
+    config.validate(STATS_MAX_ROWS_CONFIG_KEY, &policy.max_rows.to_string())?;
+    config
+        .set_as(
+            STATS_DAYS_SAVED_CONFIG_KEY,
+            &policy.days_saved,
+            "farmer",
+            Some(database.as_ref()),
+        )
+        .await?;
+    config
+        .set_as(
+            STATS_MAX_ROWS_CONFIG_KEY,
+            &policy.max_rows,
+            "farmer",
+            Some(database.as_ref()),
+        )
+        .await?;
+    Ok(load_stats_policy(&config).await)
+}
+
+#[get("/api/stats/size", output = "json", eoutput = "bytes")]
+pub async fn farmer_stats_size(database: State<SqlitePool>) -> Result<FarmerStatsSize, Error> {
+    get_farmer_stats_size(database.as_ref()).await
+}
+
+#[get("/farmer/stats/{launcher_id}", output = "json", eoutput = "bytes")]
+pub async fn farmer_stats_by_launcher(
+    data: &mut ServiceData,
+    launcher_id: Path,
+    database: State<SqlitePool>,
+) -> Result<Vec<FarmerStats>, Error> {
+    let launcher_id = Bytes32::from_str(&launcher_id.inner()).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Failed to parse launcher_id: {e}"),
+        )
+    })?;
+    let start = timestamp_param(data, "start")?.unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    let end = timestamp_param(data, "end")?.unwrap_or_else(OffsetDateTime::now_utc);
+    get_farmer_stats_by_launcher(database.as_ref(), launcher_id, start, end).await
+}
+
 #[websocket("/farmer/log_stream/{level}")]
 pub async fn farmer_log_stream(
     socket: WebSocket,
@@ -159,6 +515,55 @@ pub async fn farmer_log_stream(
     farmer_manager.0.farmer_log_stream(level, socket).await
 }
 
+const FARMER_STATS_STREAM_SECS: u64 = 10;
+
+async fn farmer_stats_frame(farmer_manager: &FarmerManager) -> serde_json::Value {
+    if !farmer_manager.is_running().await {
+        return json!({ "running": false });
+    }
+    match farmer_manager.recent_farmer_stats().await {
+        Ok(stats) => json!({ "running": true, "stats": stats }),
+        Err(e) => json!({ "running": true, "error": e.to_string() }),
+    }
+}
+
+#[websocket("/farmer/stats/stream")]
+pub async fn farmer_stats_stream(
+    socket: WebSocket,
+    farmer_manager: State<FarmerManager>,
+) -> Result<(), Error> {
+    let mut interval = tokio::time::interval(Duration::from_secs(FARMER_STATS_STREAM_SECS));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let frame = farmer_stats_frame(farmer_manager.0.as_ref()).await;
+                if let Err(e) = socket.send(Message::Text(frame.to_string().into())).await {
+                    debug!("Failed to send farmer stats: {e:?}");
+                    break;
+                }
+            }
+            result = socket.next() => {
+                match result {
+                    Ok(Some(Message::Ping(ping_data))) => {
+                        if socket.send(Message::Pong(ping_data)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Some(Message::Close(_))) | Err(_) => break,
+                    Ok(Some(_)) => {
+                        //Ignore Client Messages
+                        continue;
+                    }
+                    Ok(None) => {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 #[post("/farmer/config", output = "json", eoutput = "bytes")]
 pub async fn update_config(
     pool: State<SqlitePool>,
@@ -176,6 +581,359 @@ pub async fn update_config(
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field_path: String,
+    pub old_value: serde_json::Value,
+    pub new_value: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigDiff {
+    pub added_launchers: Vec<Bytes32>,
+    pub removed_launchers: Vec<Bytes32>,
+    pub changed_fields: Vec<FieldChange>,
+}
+
+fn launcher_ids(config: &Config<HarvesterConfig>) -> Vec<Bytes32> {
+    config
+        .farmer_info
+        .iter()
+        .filter_map(|i| i.launcher_id)
+        .collect()
+}
+
+fn child_path(parent: &str, child: &str) -> String {
+    if parent.is_empty() {
+        child.to_string()
+    } else {
+        format!("{parent}.{child}")
+    }
+}
+
+fn diff_values(
+    path: &str,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    changes: &mut Vec<FieldChange>,
+) {
+    use serde_json::Value;
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            let mut keys: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                diff_values(
+                    &child_path(path, key),
+                    old_map.get(key).unwrap_or(&Value::Null),
+                    new_map.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (Value::Array(old_list), Value::Array(new_list)) => {
+            for index in 0..old_list.len().max(new_list.len()) {
+                diff_values(
+                    &format!("{path}[{index}]"),
+                    old_list.get(index).unwrap_or(&Value::Null),
+                    new_list.get(index).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        _ => {
+            if old != new {
+                changes.push(FieldChange {
+                    field_path: path.to_string(),
+                    old_value: old.clone(),
+                    new_value: new.clone(),
+                });
+            }
+        }
+    }
+}
+
+#[post("/farmer/config/diff", output = "json", eoutput = "bytes")]
+pub async fn diff_config(
+    pool: State<SqlitePool>,
+    payload: Json<Option<Config<HarvesterConfig>>>,
+) -> Result<ConfigDiff, Error> {
+    match payload.inner() {
+        Some(new_config) => {
+            let current_config = load_farmer_config(pool.0.as_ref()).await?;
+            let current_launchers = launcher_ids(&current_config);
+            let new_launchers = launcher_ids(&new_config);
+            let mut changed_fields = vec![];
+            diff_values(
+                "",
+                &serde_json::to_value(&current_config)?,
+                &serde_json::to_value(&new_config)?,
+                &mut changed_fields,
+            );
+            Ok(ConfigDiff {
+                added_launchers: new_launchers
+                    .iter()
+                    .filter(|l| !current_launchers.contains(l))
+                    .copied()
+                    .collect(),
+                removed_launchers: current_launchers
+                    .iter()
+                    .filter(|l| !new_launchers.contains(l))
+                    .copied()
+                    .collect(),
+                changed_fields,
+            })
+        }
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Config Payload",
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PlotDirectoryPayload {
+    pub path: String,
+    #[serde(default)]
+    pub max_cpu_cores: Option<i32>,
+    #[serde(default)]
+    pub max_cuda_devices: Option<i32>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct PlotDirectoryStats {
+    pub path: String,
+    pub mount_path: Option<String>,
+    pub total_gb: f64,
+    pub free_gb: f64,
+    pub plot_count: usize,
+}
+
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+fn plot_directories(config: &Config<HarvesterConfig>) -> Vec<DirectoryConfig> {
+    config
+        .harvester_configs
+        .custom_config
+        .as_ref()
+        .map(|c| c.plot_directories.clone())
+        .unwrap_or_default()
+}
+
+fn plot_directory_payload(payload: Option<PlotDirectoryPayload>) -> Result<DirectoryConfig, Error> {
+    match payload {
+        Some(payload) if !payload.path.trim().is_empty() => Ok(DirectoryConfig {
+            path: payload.path.trim().trim_end_matches('/').to_string(),
+            max_cpu_cores: payload.max_cpu_cores,
+            max_cuda_devices: payload.max_cuda_devices,
+            enabled: payload.enabled.unwrap_or(true),
+        }),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Plot Directory Payload",
+        )),
+    }
+}
+
+#[get("/farmer/config/plots", output = "json", eoutput = "bytes")]
+pub async fn get_plot_directories(pool: State<SqlitePool>) -> Result<Vec<DirectoryConfig>, Error> {
+    Ok(plot_directories(
+        &load_farmer_config(pool.0.as_ref()).await?,
+    ))
+}
+
+#[post("/farmer/config/plots", output = "json", eoutput = "bytes")]
+pub async fn add_plot_directory(
+    pool: State<SqlitePool>,
+    payload: Json<Option<PlotDirectoryPayload>>,
+) -> Result<Vec<DirectoryConfig>, Error> {
+    let directory = plot_directory_payload(payload.inner())?;
+    let path = directory.path.clone();
+    let metadata = tokio::fs::metadata(&path).await.map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Plot Directory {path} does not exist: {e}"),
+        )
+    })?;
+    if !metadata.is_dir() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Plot Directory {path} is not a directory"),
+        ));
+    }
+    let mut config = load_farmer_config(pool.0.as_ref()).await?;
+    let harvester_config = config
+        .harvester_configs
+        .custom_config
+        .get_or_insert_with(HarvesterConfig::default);
+    if harvester_config
+        .plot_directories
+        .iter()
+        .any(|directory| directory.path == path)
+    {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("Plot Directory {path} is already configured"),
+        ));
+    }
+    harvester_config.plot_directories.push(directory);
+    save_farmer_config(pool.0.as_ref(), &config).await?;
+    Ok(plot_directories(&config))
+}
+
+#[delete("/farmer/config/plots", output = "json", eoutput = "bytes")]
+pub async fn remove_plot_directory(
+    pool: State<SqlitePool>,
+    payload: Json<Option<PlotDirectoryPayload>>,
+) -> Result<Vec<DirectoryConfig>, Error> {
+    let path = plot_directory_payload(payload.inner())?.path;
+    let mut config = load_farmer_config(pool.0.as_ref()).await?;
+    let Some(harvester_config) = config.harvester_configs.custom_config.as_mut() else {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("Plot Directory {path} is not configured"),
+        ));
+    };
+    let before = harvester_config.plot_directories.len();
+    harvester_config
+        .plot_directories
+        .retain(|directory| directory.path.trim_end_matches('/') != path);
+    if harvester_config.plot_directories.len() == before {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("Plot Directory {path} is not configured"),
+        ));
+    }
+    save_farmer_config(pool.0.as_ref(), &config).await?;
+    Ok(plot_directories(&config))
+}
+
+fn filesystem_space(path: &str) -> Option<(u64, u64)> {
+    let c_path = std::ffi::CString::new(path).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block_size = stat.f_frsize as u64;
+    Some((
+        stat.f_blocks as u64 * block_size,
+        stat.f_bavail as u64 * block_size,
+    ))
+}
+
+async fn count_plots(directory: PathBuf, max_depth: i64) -> usize {
+    let mut count = 0;
+    let mut to_visit = vec![(directory, 0)];
+    while let Some((dir, depth)) = to_visit.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            match entry.file_type().await {
+                Ok(file_type) if file_type.is_dir() => {
+                    if depth < max_depth {
+                        to_visit.push((path, depth + 1));
+                    }
+                }
+                Ok(file_type) if file_type.is_file() => {
+                    if path.extension().map(|e| e == "plot").unwrap_or_default() {
+                        count += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    count
+}
+
+#[get("/farmer/config/plots/stats", output = "json", eoutput = "bytes")]
+pub async fn get_plot_directory_stats(
+    pool: State<SqlitePool>,
+    system_monitor: State<SystemMonitorPlugin>,
+) -> Result<Vec<PlotDirectoryStats>, Error> {
+    let config = load_farmer_config(pool.0.as_ref()).await?;
+    let search_depth = config
+        .harvester_configs
+        .custom_config
+        .as_ref()
+        .map(|c| c.plot_search_depth)
+        .unwrap_or_default();
+    //Mount path with the disk level totals when the whole disk is mounted
+    let mut mounts: Vec<(String, Option<(u64, u64)>)> = vec![];
+    for disk in system_monitor.0.get_disk_info().await? {
+        if let Some(mount_path) = &disk.mount_path {
+            mounts.push((
+                mount_path.clone(),
+                Some((disk.total, disk.total.saturating_sub(disk.used))),
+            ));
+        }
+        for partition in &disk.partitions {
+            if let Some(mount_path) = &partition.mount_path {
+                mounts.push((mount_path.display().to_string(), None));
+            }
+        }
+    }
+    let mut stats = vec![];
+    for directory in plot_directories(&config).into_iter().map(|d| d.path) {
+        let mount = mounts
+            .iter()
+            .filter(|(mount_path, _)| {
+                std::path::Path::new(&directory).starts_with(std::path::Path::new(mount_path))
+            })
+            .max_by_key(|(mount_path, _)| mount_path.len());
+        let (total, free) = mount
+            .and_then(|(_, space)| *space)
+            .or_else(|| filesystem_space(&directory))
+            .unwrap_or_default();
+        stats.push(PlotDirectoryStats {
+            mount_path: mount.map(|(mount_path, _)| mount_path.clone()),
+            total_gb: total as f64 / BYTES_PER_GB,
+            free_gb: free as f64 / BYTES_PER_GB,
+            plot_count: count_plots(PathBuf::from(&directory), search_depth).await,
+            path: directory,
+        });
+    }
+    Ok(stats)
+}
+
+fn has_launcher_id(config: &Config<HarvesterConfig>, launcher_id: &Bytes32) -> bool {
+    config
+        .farmer_info
+        .iter()
+        .any(|i| i.launcher_id.as_ref() == Some(launcher_id))
+}
+
+async fn generate_from_preload(
+    current_config: &Config<HarvesterConfig>,
+    pre_config: PreloadConfig,
+    launcher_id: Bytes32,
+) -> Result<Config<HarvesterConfig>, Error> {
+    generate_config_from_mnemonic::<HarvesterConfig>(
+        GenerateConfig {
+            output_path: None,
+            mnemonic_file: None,
+            mnemonic_string: Some(pre_config.mnemonic),
+            fullnode_ws_host: Some(current_config.fullnode_ws_host.clone()),
+            fullnode_ws_port: Some(current_config.fullnode_ws_port),
+            fullnode_rpc_host: Some(current_config.fullnode_rpc_host.clone()),
+            fullnode_rpc_port: Some(current_config.fullnode_rpc_port),
+            fullnode_ssl: current_config.ssl_root_path.clone(),
+            network: Some(current_config.selected_network.clone()),
+            launcher_id: Some(launcher_id),
+            payout_address: Some(current_config.payout_address.clone()),
+            plot_directories: Some(vec![]),
+            additional_headers: None,
+        },
+        false,
+    )
+    .await
+}
+
 #[post("/farmer/config/scan", output = "json", eoutput = "bytes")]
 pub async fn scan_for_legacy_configs(
     pool: State<SqlitePool>,
@@ -230,36 +988,13 @@ pub async fn scan_for_legacy_configs(
     } else {
         for pre_config in pconfs {
             let pre_launcher_id = Bytes32::from_str(&pre_config.launcher_id)?;
-            if current_config.farmer_info.iter().any(|i| {
-                if let Some(launcher_id) = &i.launcher_id {
-                    *launcher_id == pre_launcher_id
-                } else {
-                    false
-                }
-            }) {
+            if has_launcher_id(&current_config, &pre_launcher_id) {
                 info!("Skipping existing launcher ID");
                 continue;
             } else {
                 info!("Found new PreConfig for launcher ID {pre_launcher_id}");
-                let generated = generate_config_from_mnemonic::<HarvesterConfig>(
-                    GenerateConfig {
-                        output_path: None,
-                        mnemonic_file: None,
-                        mnemonic_string: Some(pre_config.mnemonic),
-                        fullnode_ws_host: Some(current_config.fullnode_ws_host.clone()),
-                        fullnode_ws_port: Some(current_config.fullnode_ws_port),
-                        fullnode_rpc_host: Some(current_config.fullnode_rpc_host.clone()),
-                        fullnode_rpc_port: Some(current_config.fullnode_rpc_port),
-                        fullnode_ssl: current_config.ssl_root_path.clone(),
-                        network: Some(current_config.selected_network.clone()),
-                        launcher_id: Some(pre_launcher_id),
-                        payout_address: Some(current_config.payout_address.clone()),
-                        plot_directories: Some(vec![]),
-                        additional_headers: None,
-                    },
-                    false,
-                )
-                .await?;
+                let generated =
+                    generate_from_preload(&current_config, pre_config, pre_launcher_id).await?;
                 current_config.merge(generated);
             }
         }
@@ -271,6 +1006,64 @@ pub async fn scan_for_legacy_configs(
     }
 }
 
+#[derive(Debug, Default, Serialize)]
+pub struct BatchImportResult {
+    pub imported: Vec<Bytes32>,
+    pub skipped: Vec<Bytes32>,
+    pub errors: Vec<String>,
+}
+
+#[post("/farmer/config/import/batch", output = "json", eoutput = "bytes")]
+pub async fn import_legacy_configs(
+    pool: State<SqlitePool>,
+    payload: Json<Option<Vec<PreloadConfig>>>,
+) -> Result<BatchImportResult, Error> {
+    let Some(pconfs) = payload.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Config Payload",
+        ));
+    };
+    let mut current_config = load_farmer_config(pool.0.as_ref()).await?;
+    let mut result = BatchImportResult::default();
+    for pre_config in pconfs {
+        let pre_launcher_id = match Bytes32::from_str(&pre_config.launcher_id) {
+            Ok(launcher_id) => launcher_id,
+            Err(e) => {
+                result.errors.push(format!(
+                    "Invalid launcher ID {}: {e}",
+                    pre_config.launcher_id
+                ));
+                continue;
+            }
+        };
+        //Duplicates inside the batch are skipped the same as ones already configured
+        if has_launcher_id(&current_config, &pre_launcher_id)
+            || result.imported.contains(&pre_launcher_id)
+        {
+            result.skipped.push(pre_launcher_id);
+            continue;
+        }
+        match generate_from_preload(&current_config, pre_config, pre_launcher_id).await {
+            Ok(generated) => {
+                current_config.merge(generated);
+                info!("Imported launcher ID {pre_launcher_id}");
+                result.imported.push(pre_launcher_id);
+            }
+            Err(e) => result.errors.push(format!(
+                "Failed to import launcher ID {pre_launcher_id}: {e}"
+            )),
+        }
+    }
+    if !result.imported.is_empty() {
+        if current_config.harvester_configs.custom_config.is_none() {
+            current_config.harvester_configs.custom_config = Some(HarvesterConfig::default());
+        }
+        save_farmer_config(pool.0.as_ref(), &current_config).await?;
+    }
+    Ok(result)
+}
+
 #[derive(Deserialize)]
 pub struct GenerateMnemonicRequest {
     mnemonic: String,
@@ -322,6 +1115,13 @@ pub async fn farmer_status(farmer_manager: State<FarmerManager>) -> Result<Farme
     Ok(farmer_manager.0.farmer_status().await)
 }
 
+#[get("/farmer/install/progress", output = "json", eoutput = "bytes")]
+pub async fn farmer_install_progress(
+    farmer_manager: State<FarmerManager>,
+) -> Result<Option<InstallProgress>, Error> {
+    Ok(farmer_manager.0.install_progress().await)
+}
+
 #[post("/farmer/start", output = "none", eoutput = "bytes")]
 pub async fn start_farmer(
     pool: State<SqlitePool>,
@@ -340,9 +1140,17 @@ pub async fn start_farmer(
 }
 
 #[post("/farmer/stop", output = "none", eoutput = "bytes")]
-pub async fn stop_farmer(farmer_manager: State<FarmerManager>) -> Result<(), Error> {
+pub async fn stop_farmer(
+    farmer_manager: State<FarmerManager>,
+    notifier: State<WebhookNotifier>,
+) -> Result<(), Error> {
     info!("Stopping Farmer");
-    farmer_manager.0.stop_farmer().await
+    farmer_manager.0.stop_farmer().await?;
+    notifier
+        .0
+        .send("farmer_stopped", json!({ "reason": "user" }))
+        .await;
+    Ok(())
 }
 
 #[post("/farmer/restart", output = "none", eoutput = "bytes")]
@@ -361,3 +1169,16 @@ pub async fn restart_farmer(
         ))?
     }
 }
+
+#[test]
+fn test_diff_values() {
+    let old = json!({"a": 1, "b": {"c": "x", "d": [1, 2]}, "e": true});
+    let new = json!({"a": 1, "b": {"c": "y", "d": [1]}, "f": false});
+    let mut changes = vec![];
+    diff_values("", &old, &new, &mut changes);
+    let paths: Vec<&str> = changes.iter().map(|c| c.field_path.as_str()).collect();
+    assert_eq!(paths, vec!["b.c", "b.d[1]", "e", "f"]);
+    assert_eq!(changes[0].old_value, json!("x"));
+    assert_eq!(changes[0].new_value, json!("y"));
+    assert_eq!(changes[1].new_value, serde_json::Value::Null);
+}
@@ -1,4 +1,6 @@
-use crate::plugins::led_manager::{LedColorMode, LedManager, PinColor};
+use crate::plugins::led_manager::{
+    validate_color_temperature, LedColorMode, LedManager, LedPreset, PinColor, SequenceFrame,
+};
 use portfu::prelude::{Path, State};
 use portfu_core::Json;
 use portfu_macros::{delete, get, post};
@@ -7,6 +9,11 @@ use std::io::{Error, ErrorKind};
 use std::str::FromStr;
 use tokio::sync::RwLock;
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StatusModePayload {
+    pub enabled: bool,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PinUpdatePayload {
     pub pin: u32,
@@ -34,6 +41,25 @@ pub async fn set_brightness(
     }
 }
 
+#[get("/led/pwm_period", output = "json", eoutput = "bytes")]
+pub async fn get_pwm_period(led_manager: State<RwLock<LedManager>>) -> Result<u64, Error> {
+    Ok(led_manager.0.read().await.get_pwm_period())
+}
+
+#[post("/led/pwm_period/{period_us}", output = "json", eoutput = "bytes")]
+pub async fn set_pwm_period(
+    led_manager: State<RwLock<LedManager>>,
+    period_us: Path,
+) -> Result<u64, Error> {
+    match u64::from_str(&period_us.inner()) {
+        Ok(period_us) => Ok(led_manager.0.write().await.set_pwm_period(period_us).await),
+        Err(e) => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid PWM Period: {e:?}"),
+        )),
+    }
+}
+
 #[get("/led/pin/{pin}", output = "json", eoutput = "bytes")]
 pub async fn get_pin_value(led_manager: State<RwLock<LedManager>>, pin: Path) -> Result<u8, Error> {
     match u32::from_str(&pin.inner()) {
@@ -72,6 +98,11 @@ pub async fn clear_pin_modes(led_manager: State<RwLock<LedManager>>) -> Result<(
     led_manager.0.write().await.clear().await
 }
 
+#[post("/led/off", output = "json", eoutput = "bytes")]
+pub async fn leds_off(led_manager: State<RwLock<LedManager>>) -> Result<(), Error> {
+    led_manager.0.write().await.power_off().await
+}
+
 #[post("/led/color", output = "json", eoutput = "bytes")]
 pub async fn set_color_mode(
     led_manager: State<RwLock<LedManager>>,
@@ -88,3 +119,103 @@ pub async fn set_color_mode(
         )),
     }
 }
+
+#[post("/led/temperature/{kelvin}", output = "json", eoutput = "bytes")]
+pub async fn set_color_temperature(
+    led_manager: State<RwLock<LedManager>>,
+    kelvin: Path,
+) -> Result<(), Error> {
+    let kelvin = u16::from_str(&kelvin.inner()).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid Color Temperature: {e:?}"),
+        )
+    })?;
+    validate_color_temperature(kelvin)?;
+    led_manager
+        .0
+        .write()
+        .await
+        .set_color_mode(LedColorMode::ColorTemperature(kelvin))
+        .await;
+    Ok(())
+}
+
+#[get("/led/sequence", output = "json", eoutput = "bytes")]
+pub async fn get_sequence(
+    led_manager: State<RwLock<LedManager>>,
+) -> Result<Option<Vec<SequenceFrame>>, Error> {
+    led_manager.0.read().await.get_sequence().await
+}
+
+#[post("/led/sequence", output = "json", eoutput = "bytes")]
+pub async fn set_sequence(
+    led_manager: State<RwLock<LedManager>>,
+    payload: Json<Option<Vec<SequenceFrame>>>,
+) -> Result<(), Error> {
+    match payload.inner() {
+        Some(payload) => led_manager.0.write().await.set_sequence(payload).await,
+        None => Err(Error::new(ErrorKind::InvalidInput, "Invalid Led Sequence")),
+    }
+}
+
+#[get("/led/presets", output = "json", eoutput = "bytes")]
+pub async fn get_presets(led_manager: State<RwLock<LedManager>>) -> Result<Vec<LedPreset>, Error> {
+    led_manager.0.read().await.get_presets().await
+}
+
+#[post("/led/presets", output = "json", eoutput = "bytes")]
+pub async fn save_preset(
+    led_manager: State<RwLock<LedManager>>,
+    payload: Json<Option<LedPreset>>,
+) -> Result<(), Error> {
+    match payload.inner() {
+        Some(payload) => led_manager.0.read().await.save_preset(payload).await,
+        None => Err(Error::new(ErrorKind::InvalidInput, "Invalid Led Preset")),
+    }
+}
+
+#[delete("/led/presets/{name}", output = "json", eoutput = "bytes")]
+pub async fn delete_preset(
+    led_manager: State<RwLock<LedManager>>,
+    name: Path,
+) -> Result<bool, Error> {
+    led_manager
+        .0
+        .read()
+        .await
+        .delete_preset(&name.inner())
+        .await
+}
+
+#[post("/led/presets/{name}/apply", output = "json", eoutput = "bytes")]
+pub async fn apply_preset(led_manager: State<RwLock<LedManager>>, name: Path) -> Result<(), Error> {
+    led_manager
+        .0
+        .write()
+        .await
+        .apply_preset(&name.inner())
+        .await
+}
+
+#[post("/led/status_mode", output = "json", eoutput = "bytes")]
+pub async fn set_status_mode(
+    led_manager: State<RwLock<LedManager>>,
+    payload: Json<Option<StatusModePayload>>,
+) -> Result<(), Error> {
+    match payload.inner() {
+        Some(payload) => {
+            led_manager
+                .0
+                .write()
+                .await
+                .set_status_mode(payload.enabled)
+                .await;
+            Ok(())
+        }
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Status Mode Payload",
+        )),
+    }
+}
@@ -0,0 +1,224 @@
+use dg_logger::{DruidGardenLogger, LogEntry as LoggerEntry};
+use log::Level;
+use portfu::prelude::http::{HeaderName, HeaderValue};
+use portfu::prelude::{serde_json, State};
+use portfu_core::ServiceData;
+use portfu_macros::{get, post};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+
+pub const LOG_BUFFER_CAPACITY: usize = 5000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+impl From<&LoggerEntry> for LogEntry {
+    fn from(entry: &LoggerEntry) -> Self {
+        Self {
+            timestamp: entry.timestamp.to_string(),
+            level: entry.level.to_string(),
+            target: entry.target.to_string(),
+            message: entry.message.to_string(),
+        }
+    }
+}
+impl LogEntry {
+    fn matches(&self, level: Level) -> bool {
+        Level::from_str(&self.level)
+            .map(|l| l <= level)
+            .unwrap_or(true)
+    }
+    pub fn to_line(&self) -> String {
+        format!(
+            "[{}][{}][{}] {}",
+            self.timestamp, self.level, self.target, self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogBufferSize {
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+/// Keeps the most recent log entries in memory so they can be exported
+pub struct LogBuffer {
+    entries: RwLock<VecDeque<LogEntry>>,
+    capacity: usize,
+}
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: RwLock::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+    pub async fn capture(self: Arc<Self>, logger: Arc<DruidGardenLogger>) {
+        let mut receiver = logger.subscribe();
+        loop {
+            match receiver.recv().await {
+                Ok(entry) => self.push(LogEntry::from(&entry)).await,
+                //Logging here would feed back into the buffer, dropped entries are just skipped
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+    pub async fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.write().await;
+        while entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+    /// Returns up to `limit` of the newest entries at or above `level`, oldest first
+    pub async fn entries(&self, level: Level, limit: usize) -> Vec<LogEntry> {
+        let entries = self.entries.read().await;
+        let mut matching: Vec<LogEntry> = entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.matches(level))
+            .take(limit)
+            .cloned()
+            .collect();
+        matching.reverse();
+        matching
+    }
+    pub async fn size(&self) -> LogBufferSize {
+        LogBufferSize {
+            entries: self.entries.read().await.len(),
+            capacity: self.capacity,
+        }
+    }
+    pub async fn clear(&self) -> usize {
+        let mut entries = self.entries.write().await;
+        let cleared = entries.len();
+        entries.clear();
+        cleared
+    }
+}
+
+fn query_param<'a>(data: &'a ServiceData, name: &str) -> Option<&'a str> {
+    data.request.request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, value)| *key == name && !value.is_empty())
+            .map(|(_, value)| value)
+    })
+}
+
+#[get("/api/system/logs/export", output = "bytes", eoutput = "bytes")]
+pub async fn export_logs(
+    data: &mut ServiceData,
+    buffer: State<LogBuffer>,
+) -> Result<String, Error> {
+    let level = query_param(data, "level")
+        .map(|level| {
+            Level::from_str(level).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{level} is not a valid Log Level: {e:?}"),
+                )
+            })
+        })
+        .transpose()?
+        .unwrap_or(Level::Trace);
+    let limit = query_param(data, "limit")
+        .map(|limit| {
+            limit
+                .parse::<usize>()
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid limit: {e}")))
+        })
+        .transpose()?
+        .unwrap_or(buffer.0.capacity);
+    let as_text = match query_param(data, "format") {
+        None | Some("json") => false,
+        Some("text") => true,
+        Some(format) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid format: {format}, expected json or text"),
+            ))
+        }
+    };
+    let entries = buffer.0.entries(level, limit).await;
+    let (content_type, body) = if as_text {
+        (
+            "text/plain",
+            entries
+                .iter()
+                .map(|entry| entry.to_line() + "\n")
+                .collect::<String>(),
+        )
+    } else {
+        ("application/json", serde_json::to_string(&entries)?)
+    };
+    data.response.headers_mut().insert(
+        HeaderName::from_static("content-type"),
+        HeaderValue::from_static(content_type),
+    );
+    Ok(body)
+}
+
+#[get("/api/system/logs/size", output = "json", eoutput = "bytes")]
+pub async fn log_buffer_size(buffer: State<LogBuffer>) -> Result<LogBufferSize, Error> {
+    Ok(buffer.0.size().await)
+}
+
+#[post("/api/system/logs/clear", output = "json", eoutput = "bytes")]
+pub async fn clear_log_buffer(buffer: State<LogBuffer>) -> Result<usize, Error> {
+    Ok(buffer.0.clear().await)
+}
+
+#[tokio::test]
+async fn test_log_buffer() {
+    let buffer = LogBuffer::new(3);
+    for (i, level) in [Level::Info, Level::Debug, Level::Warn, Level::Error]
+        .into_iter()
+        .enumerate()
+    {
+        buffer
+            .push(LogEntry {
+                timestamp: i.to_string(),
+                level: level.to_string(),
+                target: "test".to_string(),
+                message: format!("entry {i}"),
+            })
+            .await;
+    }
+    assert_eq!(buffer.size().await.entries, 3);
+    let warnings = buffer.entries(Level::Warn, 10).await;
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].to_line(), "[2][WARN][test] entry 2");
+    assert_eq!(buffer.entries(Level::Trace, 1).await[0].message, "entry 3");
+    assert_eq!(buffer.clear().await, 3);
+    assert_eq!(buffer.size().await.entries, 0);
+}
+
+#[test]
+fn test_log_entry_from_logger_entry() {
+    let entry = LogEntry::from(&LoggerEntry {
+        timestamp: "2026-10-16T12:00:00Z".to_string(),
+        level: Level::Warn,
+        target: "druid_garden::plugins".to_string(),
+        message: "Plugin Exited".to_string(),
+    });
+    assert_eq!(entry.level, "WARN");
+    assert!(entry.matches(Level::Info));
+    assert!(!entry.matches(Level::Error));
+    assert_eq!(
+        entry.to_line(),
+        "[2026-10-16T12:00:00Z][WARN][druid_garden::plugins] Plugin Exited"
+    );
+}
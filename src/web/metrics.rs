@@ -0,0 +1,114 @@
+use crate::plugins::farmer::{FarmerManager, FarmerStatus};
+use crate::plugins::system_monitor::SystemMonitorPlugin;
+use portfu::prelude::State;
+use portfu_macros::get;
+use std::fmt::Write;
+use std::io::Error;
+
+struct MetricsWriter {
+    output: String,
+}
+impl MetricsWriter {
+    fn new() -> Self {
+        Self {
+            output: String::new(),
+        }
+    }
+    fn gauge(&mut self, name: &str, help: &str) {
+        let _ = writeln!(self.output, "# HELP {name} {help}");
+        let _ = writeln!(self.output, "# TYPE {name} gauge");
+    }
+    fn value<V: std::fmt::Display>(&mut self, name: &str, labels: &[(&str, &str)], value: V) {
+        if labels.is_empty() {
+            let _ = writeln!(self.output, "{name} {value}");
+        } else {
+            let labels = labels
+                .iter()
+                .map(|(k, v)| format!("{k}=\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")))
+                .collect::<Vec<String>>()
+                .join(",");
+            let _ = writeln!(self.output, "{name}{{{labels}}} {value}");
+        }
+    }
+}
+
+#[get("/metrics", output = "bytes", eoutput = "bytes")]
+pub async fn prometheus_metrics(
+    system_monitor: State<SystemMonitorPlugin>,
+    farmer_manager: State<FarmerManager>,
+) -> Result<String, Error> {
+    let mut metrics = MetricsWriter::new();
+    let cpu = system_monitor.0.get_cpu_info().await?;
+    metrics.gauge("dg_cpu_usage_percent", "Global CPU usage");
+    metrics.value("dg_cpu_usage_percent", &[], cpu.global_usage);
+    let memory = system_monitor.0.get_memory_info().await?;
+    metrics.gauge("dg_memory_used_bytes", "Used memory in bytes");
+    metrics.value("dg_memory_used_bytes", &[], memory.used);
+    metrics.gauge("dg_memory_total_bytes", "Total memory in bytes");
+    metrics.value("dg_memory_total_bytes", &[], memory.total);
+    let disks = system_monitor.0.get_disk_info().await?;
+    metrics.gauge("dg_disk_used_bytes", "Used disk space in bytes");
+    for disk in &disks {
+        metrics.value(
+            "dg_disk_used_bytes",
+            &[("device", &disk.dev_path)],
+            disk.used,
+        );
+    }
+    metrics.gauge("dg_disk_total_bytes", "Total disk space in bytes");
+    for disk in &disks {
+        metrics.value(
+            "dg_disk_total_bytes",
+            &[("device", &disk.dev_path)],
+            disk.total,
+        );
+    }
+    metrics.gauge("dg_gpu_usage_percent", "GPU usage");
+    for gpu in system_monitor.0.get_gpu_info().await? {
+        let index = gpu.index.to_string();
+        let brand = format!("{:?}", gpu.brand).to_lowercase();
+        metrics.value(
+            "dg_gpu_usage_percent",
+            &[("index", &index), ("brand", &brand)],
+            gpu.gpu_usage,
+        );
+    }
+    let running = matches!(
+        farmer_manager.0.farmer_status().await,
+        FarmerStatus::Running
+    );
+    metrics.gauge("dg_farmer_running", "1 if the farmer is running");
+    metrics.value("dg_farmer_running", &[], u8::from(running));
+    if let Some(stats) = farmer_manager.0.latest_stats().await {
+        metrics.gauge("dg_farmer_plot_count", "Plot count by type");
+        metrics.value(
+            "dg_farmer_plot_count",
+            &[("type", "og")],
+            stats.og_plot_count,
+        );
+        metrics.value(
+            "dg_farmer_plot_count",
+            &[("type", "nft")],
+            stats.nft_plot_count,
+        );
+        metrics.value(
+            "dg_farmer_plot_count",
+            &[("type", "compressed")],
+            stats.compressed_plot_count,
+        );
+        metrics.value(
+            "dg_farmer_plot_count",
+            &[("type", "invalid")],
+            stats.invalid_plot_count,
+        );
+        metrics.gauge("dg_farmer_full_node_height", "Full node peak height");
+        metrics.value("dg_farmer_full_node_height", &[], stats.full_node_height);
+        metrics.gauge("dg_farmer_full_node_synced", "1 if the full node is synced");
+        metrics.value(
+            "dg_farmer_full_node_synced",
+            &[],
+            u8::from(stats.full_node_synced),
+        );
+    }
+    Ok(metrics.output)
+}
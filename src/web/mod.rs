@@ -1,6 +1,14 @@
+pub mod audit;
 pub mod auth;
 pub mod config;
+pub mod database;
+pub mod events;
 pub mod farmer;
 pub mod leds;
+pub mod logs;
+pub mod metrics;
+pub mod notifications;
 pub mod plugins;
+pub mod sse;
 pub mod system;
+pub mod wizard;
@@ -0,0 +1,361 @@
+use crate::config::ConfigManager;
+use crate::models::config::AddConfigEntry;
+use hmac::{Hmac, Mac};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::{debug, error, warn};
+use portfu::prelude::serde_json::{json, Value};
+use portfu::prelude::{serde_json, State};
+use portfu_core::Json;
+use portfu_macros::{get, post};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+use std::time::Duration;
+use sysinfo::System;
+use time::OffsetDateTime;
+use tokio::sync::RwLock;
+
+pub const WEBHOOKS_CONFIG_KEY: &str = "webhooks";
+pub const WEBHOOK_MAX_ATTEMPTS: u32 = 3;
+pub const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(5);
+pub const WEBHOOK_EVENTS: [&str; 6] = [
+    "farmer_stopped",
+    "disk_full",
+    "high_temp",
+    "plugin_crashed",
+    "memory_pressure",
+    "disk_temperature_alert",
+];
+pub const EMAIL_NOTIFIER_CONFIG_KEY: &str = "email_notifier";
+//Events important enough to also be sent by email when an EmailNotifier is configured
+pub const EMAIL_EVENTS: [&str; 4] = [
+    "farmer_stopped",
+    "disk_full",
+    "high_temp",
+    "disk_temperature_alert",
+];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EmailNotifier {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub username: String,
+    pub password: String,
+    pub from_address: String,
+    pub to_addresses: Vec<String>,
+}
+impl EmailNotifier {
+    fn validate(&self) -> Result<(Mailbox, Vec<Mailbox>), Error> {
+        let parse = |address: &str| {
+            address.parse::<Mailbox>().map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid Email Address {address}: {e}"),
+                )
+            })
+        };
+        if self.smtp_host.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "SMTP host is required"));
+        }
+        if self.to_addresses.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "At least one recipient is required",
+            ));
+        }
+        Ok((
+            parse(&self.from_address)?,
+            self.to_addresses
+                .iter()
+                .map(|address| parse(address))
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+    pub async fn send_email(&self, subject: &str, body: &str) -> Result<(), Error> {
+        let (from, to) = self.validate()?;
+        let hostname = System::host_name().unwrap_or("Unknown".to_string());
+        let mut builder = Message::builder()
+            .from(from)
+            .subject(format!("[{hostname}] {subject}"));
+        for to in to {
+            builder = builder.to(to);
+        }
+        let message = builder
+            .body(body.to_string())
+            .map_err(|e| Error::other(format!("Failed to build Email: {e}")))?;
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.smtp_host)
+            .map_err(|e| Error::other(format!("Failed to connect to {}: {e}", self.smtp_host)))?
+            .port(self.smtp_port)
+            .credentials(Credentials::new(
+                self.username.clone(),
+                self.password.clone(),
+            ))
+            .build();
+        transport
+            .send(message)
+            .await
+            .map_err(|e| Error::other(format!("Failed to send Email: {e}")))?;
+        Ok(())
+    }
+}
+
+pub struct WebhookNotifier {
+    endpoints: RwLock<Vec<WebhookEndpoint>>,
+    email: RwLock<Option<EmailNotifier>>,
+    client: Client,
+}
+impl WebhookNotifier {
+    pub async fn new(config_manager: Arc<RwLock<ConfigManager>>) -> Self {
+        let config_manager = config_manager.read().await;
+        let endpoints = match config_manager.get(WEBHOOKS_CONFIG_KEY).await {
+            Some(entry) => serde_json::from_str(&entry.value).unwrap_or_else(|e| {
+                error!("Failed to Parse Webhooks: {e:?}");
+                vec![]
+            }),
+            None => vec![],
+        };
+        let email = match config_manager.get(EMAIL_NOTIFIER_CONFIG_KEY).await {
+            Some(entry) => serde_json::from_str(&entry.value).unwrap_or_else(|e| {
+                error!("Failed to Parse Email Notifier: {e:?}");
+                None
+            }),
+            None => None,
+        };
+        Self {
+            endpoints: RwLock::new(endpoints),
+            email: RwLock::new(email),
+            client: Client::new(),
+        }
+    }
+    pub async fn email(&self) -> Option<EmailNotifier> {
+        self.email.read().await.clone()
+    }
+    pub async fn set_email(
+        &self,
+        mut email: Option<EmailNotifier>,
+        config_manager: &RwLock<ConfigManager>,
+        db: &SqlitePool,
+    ) -> Result<(), Error> {
+        if let Some(email) = email.as_mut() {
+            email.validate()?;
+            //GET never returns the password, so an empty one keeps the saved password
+            if email.password.is_empty() {
+                if let Some(current) = self.email.read().await.as_ref() {
+                    email.password = current.password.clone();
+                }
+            }
+        }
+        config_manager
+            .write()
+            .await
+            .set_as(EMAIL_NOTIFIER_CONFIG_KEY, &email, "notifications", Some(db))
+            .await?;
+        *self.email.write().await = email;
+        Ok(())
+    }
+    pub async fn endpoints(&self) -> Vec<WebhookEndpoint> {
+        self.endpoints.read().await.clone()
+    }
+    pub async fn set_endpoints(
+        &self,
+        endpoints: Vec<WebhookEndpoint>,
+        config_manager: &RwLock<ConfigManager>,
+        db: &SqlitePool,
+    ) -> Result<(), Error> {
+        for endpoint in &endpoints {
+            if let Some(event) = endpoint
+                .events
+                .iter()
+                .find(|e| !WEBHOOK_EVENTS.contains(&e.as_str()))
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Unknown Webhook Event: {event}"),
+                ));
+            }
+            reqwest::Url::parse(&endpoint.url).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid Webhook URL {}: {e}", endpoint.url),
+                )
+            })?;
+        }
+        let value = serde_json::to_string(&endpoints)?;
+        let mut config_manager = config_manager.write().await;
+        let last_value = config_manager
+            .get(WEBHOOKS_CONFIG_KEY)
+            .await
+            .map(|e| e.value)
+            .unwrap_or_default();
+        config_manager
+            .set(
+                WEBHOOKS_CONFIG_KEY,
+                AddConfigEntry {
+                    key: WEBHOOKS_CONFIG_KEY.to_string(),
+                    value,
+                    last_value,
+                    category: "notifications".to_string(),
+                    system: 0,
+                },
+                Some(db),
+            )
+            .await?;
+        *self.endpoints.write().await = endpoints;
+        Ok(())
+    }
+    pub async fn send(&self, event_type: &str, payload: Value) {
+        let body = json!({
+            "event": event_type,
+            "timestamp": OffsetDateTime::now_utc().unix_timestamp(),
+            "payload": payload,
+        })
+        .to_string();
+        if EMAIL_EVENTS.contains(&event_type) {
+            if let Some(email) = self.email().await {
+                let subject = format!("Druid Garden Alert: {event_type}");
+                let body = serde_json::to_string_pretty(&payload).unwrap_or_default();
+                //Send in the background so a slow SMTP server doesn't block the caller
+                tokio::spawn(async move {
+                    if let Err(e) = email.send_email(&subject, &body).await {
+                        error!("Failed to send {subject} Email: {e}");
+                    }
+                });
+            }
+        }
+        for endpoint in self.endpoints.read().await.iter() {
+            if !endpoint.events.iter().any(|e| e == event_type) {
+                continue;
+            }
+            let signature = match sign(&endpoint.secret, &body) {
+                Ok(signature) => signature,
+                Err(e) => {
+                    error!("Failed to Sign Webhook for {}: {e:?}", endpoint.url);
+                    continue;
+                }
+            };
+            let client = self.client.clone();
+            let url = endpoint.url.clone();
+            let body = body.clone();
+            let event_type = event_type.to_string();
+            //Deliver in the background so slow endpoints don't block the caller
+            tokio::spawn(async move {
+                for attempt in 1..=WEBHOOK_MAX_ATTEMPTS {
+                    let result = client
+                        .post(&url)
+                        .header("content-type", "application/json")
+                        .header("X-DG-Signature", format!("sha256={signature}"))
+                        .body(body.clone())
+                        .send()
+                        .await
+                        .and_then(|r| r.error_for_status());
+                    match result {
+                        Ok(_) => {
+                            debug!("Delivered {event_type} Webhook to {url}");
+                            return;
+                        }
+                        Err(e) => {
+                            warn!("Webhook {event_type} to {url} failed (attempt {attempt}): {e}");
+                            if attempt < WEBHOOK_MAX_ATTEMPTS {
+                                tokio::time::sleep(WEBHOOK_RETRY_DELAY).await;
+                            }
+                        }
+                    }
+                }
+                error!("Giving up on {event_type} Webhook to {url}");
+            });
+        }
+    }
+}
+
+fn sign(secret: &str, body: &str) -> Result<String, Error> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{e:?}")))?;
+    mac.update(body.as_bytes());
+    Ok(mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+#[get("/api/webhooks", output = "json", eoutput = "bytes")]
+pub async fn get_webhooks(notifier: State<WebhookNotifier>) -> Result<Vec<WebhookEndpoint>, Error> {
+    Ok(notifier.0.endpoints().await)
+}
+
+#[post("/api/webhooks", output = "json", eoutput = "bytes")]
+pub async fn set_webhooks(
+    db: State<SqlitePool>,
+    notifier: State<WebhookNotifier>,
+    config_manager: State<RwLock<ConfigManager>>,
+    payload: Json<Option<Vec<WebhookEndpoint>>>,
+) -> Result<Vec<WebhookEndpoint>, Error> {
+    match payload.inner() {
+        Some(endpoints) => {
+            notifier
+                .0
+                .set_endpoints(endpoints, config_manager.0.as_ref(), db.as_ref())
+                .await?;
+            Ok(notifier.0.endpoints().await)
+        }
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Webhook Payload",
+        )),
+    }
+}
+
+#[get("/api/notifications/email", output = "json", eoutput = "bytes")]
+pub async fn get_email_notifier(
+    notifier: State<WebhookNotifier>,
+) -> Result<Option<EmailNotifier>, Error> {
+    //Never hand the SMTP password back out
+    Ok(notifier.0.email().await.map(|mut email| {
+        email.password.clear();
+        email
+    }))
+}
+
+#[post("/api/notifications/email", output = "json", eoutput = "bytes")]
+pub async fn set_email_notifier(
+    db: State<SqlitePool>,
+    notifier: State<WebhookNotifier>,
+    config_manager: State<RwLock<ConfigManager>>,
+    payload: Json<Option<EmailNotifier>>,
+) -> Result<(), Error> {
+    notifier
+        .0
+        .set_email(payload.inner(), config_manager.0.as_ref(), db.as_ref())
+        .await
+}
+
+#[post("/api/notifications/email/test", output = "json", eoutput = "bytes")]
+pub async fn test_email_notifier(notifier: State<WebhookNotifier>) -> Result<(), Error> {
+    match notifier.0.email().await {
+        Some(email) => {
+            email
+                .send_email(
+                    "Druid Garden Test Email",
+                    "Email notifications are configured correctly.",
+                )
+                .await
+        }
+        None => Err(Error::new(
+            ErrorKind::NotFound,
+            "No Email Notifier is configured",
+        )),
+    }
+}
@@ -1,14 +1,32 @@
+use crate::config::ConfigManager;
+use crate::database::events::{log_event, EventSeverity};
 use crate::database::plugins::{
-    create_plugin_environment_entry, delete_plugin_environment_entry, get_all_plugins, get_plugin,
+    create_plugin_environment_entry, delete_plugin_environment_entry, get_plugin,
     get_plugin_environment_entries, get_plugin_environment_entry,
 };
+use crate::models::config::AddConfigEntry;
+use crate::models::pagination::{ListQuery, ListResponse, SortOrder};
 use crate::models::plugins::{AddPlugin, Plugin, PluginEnvironmentEntry};
-use crate::plugins::{PluginManager, PluginStatus, PluginUpdates, StorePlugin};
+use crate::plugins::resources::PluginResourceUsage;
+use crate::plugins::{
+    validate_plugin_store_url, PluginManager, PluginStatus, PluginStoreInfo,
+    PluginUpdateAvailability, PluginUpdates, StorePlugin, MAX_PLUGIN_RESTART_DELAY,
+    PLUGIN_STORE_URL_CONFIG_KEY,
+};
+use crate::utils::connect_to_docker;
+use bollard::image::{ListImagesOptions, PruneImagesOptions};
+use log::{info, warn};
+use portfu::prelude::serde_json::json;
 use portfu::prelude::*;
-use portfu_core::Json;
-use portfu_macros::{delete, get, post, put};
+use portfu_core::{Json, ServiceData};
+use portfu_macros::{delete, get, patch, post, put};
+use semver::Version;
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind};
+use time::OffsetDateTime;
 use tokio::sync::RwLock;
 
 #[post("/api/plugins/available", output = "json", eoutput = "bytes")]
@@ -33,9 +51,223 @@ pub async fn refresh_plugins(
     Ok(state.0.read().await.available_plugins().await)
 }
 
+pub const PLUGIN_SORT_FIELDS: [&str; 3] = ["name", "version", "added"];
+
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+#[get("/api/plugins/store/cache_age", output = "json", eoutput = "bytes")]
+pub async fn plugin_store_cache_age(
+    state: State<RwLock<PluginManager>>,
+) -> Result<Option<u64>, Error> {
+    Ok(state.0.read().await.store_cache_age())
+}
+
+#[get("/api/plugins/store/url", output = "json", eoutput = "bytes")]
+pub async fn plugin_store_url(
+    state: State<RwLock<PluginManager>>,
+) -> Result<PluginStoreInfo, Error> {
+    Ok(state.0.read().await.store_info())
+}
+
+#[derive(Deserialize)]
+pub struct PluginStoreUrlPayload {
+    pub url: String,
+}
+
+#[post("/api/plugins/store/url", output = "json", eoutput = "bytes")]
+pub async fn set_plugin_store_url(
+    state: State<RwLock<PluginManager>>,
+    config_manager: State<RwLock<ConfigManager>>,
+    db: State<SqlitePool>,
+    payload: Json<Option<PluginStoreUrlPayload>>,
+) -> Result<PluginStoreInfo, Error> {
+    let Some(payload) = payload.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Invalid Plugin Store Url Payload",
+        ));
+    };
+    let url = payload.url.trim().to_string();
+    validate_plugin_store_url(&url)?;
+    let last_value = state.0.read().await.store_info().url;
+    config_manager
+        .0
+        .write()
+        .await
+        .set(
+            PLUGIN_STORE_URL_CONFIG_KEY,
+            AddConfigEntry {
+                key: PLUGIN_STORE_URL_CONFIG_KEY.to_string(),
+                value: url.clone(),
+                last_value,
+                category: "plugins".to_string(),
+                system: 0,
+            },
+            Some(db.as_ref()),
+        )
+        .await?;
+    let mut plugin_manager = state.0.write().await;
+    plugin_manager.set_plugin_store_url(url);
+    if let Err(e) = plugin_manager.update_plugin_store().await {
+        warn!("Failed to refresh Plugin Store from new Url: {e:?}");
+    }
+    Ok(plugin_manager.store_info())
+}
+
+#[derive(Serialize)]
+pub struct InstalledPlugin {
+    #[serde(flatten)]
+    pub plugin: Plugin,
+    pub running: bool,
+}
+
 #[get("/api/plugins", output = "json", eoutput = "bytes")]
-pub async fn all_plugins(db: State<SqlitePool>) -> Result<Vec<Plugin>, Error> {
-    get_all_plugins(db.as_ref()).await
+pub async fn all_plugins(
+    data: &mut ServiceData,
+    state: State<RwLock<PluginManager>>,
+) -> Result<ListResponse<InstalledPlugin>, Error> {
+    let query = ListQuery::parse(data.request.request.uri().query())?;
+    let sort = query.sort_field(&PLUGIN_SORT_FIELDS, "name")?;
+    let plugin_type = query.filter("type").map(str::to_ascii_lowercase);
+    let enabled = query.bool_filter("enabled")?;
+    let plugin_manager = state.0.read().await;
+    let running: HashSet<String> = plugin_manager
+        .list_running()
+        .await
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    let mut plugins: Vec<Plugin> = plugin_manager
+        .installed_plugins()
+        .await
+        .into_iter()
+        .filter(|plugin| {
+            plugin_type
+                .as_ref()
+                .map(|t| format!("{:?}", plugin.plugin_type).to_ascii_lowercase() == *t)
+                .unwrap_or(true)
+        })
+        .filter(|plugin| enabled.map(|e| (plugin.enabled != 0) == e).unwrap_or(true))
+        .collect();
+    plugins.sort_by(|a, b| match sort {
+        "version" => compare_versions(&a.version, &b.version),
+        "added" => a.added.cmp(&b.added),
+        _ => a.name.cmp(&b.name),
+    });
+    if query.order_or(SortOrder::Asc) == SortOrder::Desc {
+        plugins.reverse();
+    }
+    let plugins = plugins
+        .into_iter()
+        .map(|plugin| InstalledPlugin {
+            running: running.contains(&plugin.name),
+            plugin,
+        })
+        .collect();
+    Ok(ListResponse::from_vec(plugins, &query))
+}
+
+#[derive(Serialize)]
+pub struct PluginStatusSummary {
+    pub plugin: Plugin,
+    pub status: PluginStatus,
+    pub uptime_seconds: Option<i64>,
+}
+
+async fn plugin_status_summaries(
+    plugin_manager: &PluginManager,
+    statuses: Vec<(String, PluginStatus)>,
+) -> Vec<PluginStatusSummary> {
+    let now = OffsetDateTime::now_utc();
+    let mut summaries = vec![];
+    for (name, status) in statuses {
+        if let Some(plugin) = plugin_manager.installed_plugin(&name).await {
+            summaries.push(PluginStatusSummary {
+                plugin,
+                uptime_seconds: status
+                    .started
+                    .filter(|_| status.running)
+                    .map(|started| (now - started).whole_seconds()),
+                status,
+            });
+        }
+    }
+    summaries.sort_by(|a, b| a.plugin.name.cmp(&b.plugin.name));
+    summaries
+}
+
+#[get("/api/plugins/running", output = "json", eoutput = "bytes")]
+pub async fn running_plugins(
+    state: State<RwLock<PluginManager>>,
+) -> Result<Vec<PluginStatusSummary>, Error> {
+    let plugin_manager = state.0.read().await;
+    let running = plugin_manager.list_running().await;
+    Ok(plugin_status_summaries(&plugin_manager, running).await)
+}
+
+#[get("/api/plugins/stopped", output = "json", eoutput = "bytes")]
+pub async fn stopped_plugins(
+    state: State<RwLock<PluginManager>>,
+) -> Result<Vec<PluginStatusSummary>, Error> {
+    let plugin_manager = state.0.read().await;
+    let stopped = plugin_manager.list_stopped().await;
+    Ok(plugin_status_summaries(&plugin_manager, stopped).await)
+}
+
+#[derive(Debug, Serialize)]
+pub struct PopularPlugin {
+    pub name: String,
+    pub label: Option<String>,
+    pub installed: bool,
+    pub download_count: i64,
+    pub store_download_count: Option<u64>,
+}
+
+#[get("/api/plugins/popular", output = "json", eoutput = "bytes")]
+pub async fn popular_plugins(
+    state: State<RwLock<PluginManager>>,
+) -> Result<Vec<PopularPlugin>, Error> {
+    let plugin_manager = state.0.read().await;
+    let mut store: HashMap<String, StorePlugin> = plugin_manager
+        .available_plugins()
+        .await
+        .into_iter()
+        .map(|plugin| (plugin.name.clone(), plugin))
+        .collect();
+    let mut popular: Vec<PopularPlugin> = plugin_manager
+        .installed_plugins()
+        .await
+        .into_iter()
+        .map(|plugin| PopularPlugin {
+            store_download_count: store
+                .remove(&plugin.name)
+                .and_then(|store_plugin| store_plugin.download_count),
+            name: plugin.name,
+            label: Some(plugin.label),
+            installed: true,
+            download_count: plugin.download_count,
+        })
+        .collect();
+    //Store only plugins are included so users can discover ones they have not installed
+    popular.extend(store.into_values().map(|store_plugin| PopularPlugin {
+        name: store_plugin.name,
+        label: None,
+        installed: false,
+        download_count: 0,
+        store_download_count: store_plugin.download_count,
+    }));
+    popular.sort_by(|a, b| {
+        b.download_count
+            .cmp(&a.download_count)
+            .then(b.store_download_count.cmp(&a.store_download_count))
+            .then(a.name.cmp(&b.name))
+    });
+    Ok(popular)
 }
 
 #[get("/api/plugins/{name}", output = "json", eoutput = "bytes")]
@@ -83,7 +315,19 @@ pub async fn start_plugin(
         Some(p) => {
             let rw_lock = state.0.clone();
             let mut plugin_manager = rw_lock.write().await;
+            let name = p.name.clone();
             let started = plugin_manager.start(p).await?;
+            if let Err(e) = log_event(
+                db.as_ref(),
+                EventSeverity::Info,
+                "plugin",
+                &format!("Plugin {name} Started"),
+                Some(json!({ "plugin": name })),
+            )
+            .await
+            {
+                warn!("Failed to record Plugin Started event: {e:?}");
+            }
             Ok(started)
         }
         None => Err(Error::new(
@@ -103,7 +347,19 @@ pub async fn stop_plugin(
         Some(p) => {
             let rw_lock = state.0.clone();
             let mut plugin_manager = rw_lock.write().await;
+            let name = p.name.clone();
             let stopped = plugin_manager.stop(p).await?;
+            if let Err(e) = log_event(
+                db.as_ref(),
+                EventSeverity::Info,
+                "plugin",
+                &format!("Plugin {name} Stopped"),
+                Some(json!({ "plugin": name })),
+            )
+            .await
+            {
+                warn!("Failed to record Plugin Stopped event: {e:?}");
+            }
             Ok(stopped)
         }
         None => Err(Error::new(
@@ -113,6 +369,64 @@ pub async fn stop_plugin(
     }
 }
 
+const DEFAULT_PLUGIN_RESTART_DELAY_MS: u64 = 500;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PluginRestartPayload {
+    pub delay_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PluginRestart {
+    pub restarted: bool,
+    pub was_running: bool,
+}
+
+#[post("/api/plugins/{name}/restart", output = "json", eoutput = "bytes")]
+pub async fn restart_plugin(
+    db: State<SqlitePool>,
+    state: State<RwLock<PluginManager>>,
+    name: Path,
+    body: Json<Option<PluginRestartPayload>>,
+) -> Result<PluginRestart, Error> {
+    let delay = std::time::Duration::from_millis(
+        body.inner()
+            .unwrap_or_default()
+            .delay_ms
+            .unwrap_or(DEFAULT_PLUGIN_RESTART_DELAY_MS),
+    )
+    .min(MAX_PLUGIN_RESTART_DELAY);
+    match get_plugin(db.as_ref(), &name.inner()).await? {
+        Some(p) => {
+            let rw_lock = state.0.clone();
+            let name = p.name.clone();
+            let (stage, was_running) = rw_lock.write().await.begin_restart(&p).await?;
+            //Sleep without the lock so other plugin requests are not blocked by the delay
+            tokio::time::sleep(delay).await;
+            rw_lock.write().await.finish_restart(p, stage).await?;
+            if let Err(e) = log_event(
+                db.as_ref(),
+                EventSeverity::Info,
+                "plugin",
+                &format!("Plugin {name} Restarted"),
+                Some(json!({ "plugin": name, "was_running": was_running })),
+            )
+            .await
+            {
+                warn!("Failed to record Plugin Restarted event: {e:?}");
+            }
+            Ok(PluginRestart {
+                restarted: true,
+                was_running,
+            })
+        }
+        None => Err(Error::new(
+            ErrorKind::NotFound,
+            "The provided plugin is Invalid",
+        )),
+    }
+}
+
 #[get("/api/plugins/{name}/status", output = "json", eoutput = "bytes")]
 pub async fn plugin_status(
     db: State<SqlitePool>,
@@ -133,6 +447,18 @@ pub async fn plugin_status(
     }
 }
 
+#[get(
+    "/api/plugins/{name}/resources/usage",
+    output = "json",
+    eoutput = "bytes"
+)]
+pub async fn plugin_resource_usage(
+    state: State<RwLock<PluginManager>>,
+    name: Path,
+) -> Result<PluginResourceUsage, Error> {
+    state.0.read().await.resource_usage(&name.inner()).await
+}
+
 #[delete("/api/plugins/{name}", output = "json", eoutput = "bytes")]
 pub async fn del_plugin(
     db: State<SqlitePool>,
@@ -140,7 +466,7 @@ pub async fn del_plugin(
     name: Path,
 ) -> Result<bool, Error> {
     match get_plugin(db.as_ref(), &name.inner()).await? {
-        Some(p) => state.0.write().await.uninstall(p, db.as_ref()).await,
+        Some(p) => state.0.write().await.uninstall(p, db.as_ref(), false).await,
         None => Err(Error::new(
             ErrorKind::NotFound,
             "The provided plugin is Invalid",
@@ -148,6 +474,144 @@ pub async fn del_plugin(
     }
 }
 
+#[derive(Deserialize)]
+pub struct PluginEnabledPayload {
+    pub enabled: bool,
+}
+
+#[patch("/api/plugins/{name}", output = "json", eoutput = "bytes")]
+pub async fn set_plugin_enabled(
+    db: State<SqlitePool>,
+    state: State<RwLock<PluginManager>>,
+    name: Path,
+    body: Json<Option<PluginEnabledPayload>>,
+) -> Result<Plugin, Error> {
+    let Some(payload) = body.inner() else {
+        return Err(Error::new(ErrorKind::InvalidInput, "Missing Enabled Flag"));
+    };
+    let mut plugin_manager = state.0.write().await;
+    match plugin_manager.installed_plugin(&name.inner()).await {
+        Some(p) => {
+            if payload.enabled {
+                plugin_manager.enable(p, db.as_ref()).await
+            } else {
+                plugin_manager.disable(p, db.as_ref()).await
+            }
+        }
+        None => Err(Error::new(
+            ErrorKind::NotFound,
+            "The provided plugin is Invalid",
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UninstallPayload {
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[post("/api/plugins/{name}/uninstall", output = "json", eoutput = "bytes")]
+pub async fn uninstall_plugin(
+    db: State<SqlitePool>,
+    state: State<RwLock<PluginManager>>,
+    name: Path,
+    body: Json<Option<UninstallPayload>>,
+) -> Result<bool, Error> {
+    let force = body.inner().map(|b| b.force).unwrap_or_default();
+    match get_plugin(db.as_ref(), &name.inner()).await? {
+        Some(p) => state.0.write().await.uninstall(p, db.as_ref(), force).await,
+        None => Err(Error::new(
+            ErrorKind::NotFound,
+            "The provided plugin is Invalid",
+        )),
+    }
+}
+
+#[get(
+    "/api/plugins/{name}/update/available",
+    output = "json",
+    eoutput = "bytes"
+)]
+pub async fn plugin_update_available(
+    state: State<RwLock<PluginManager>>,
+    name: Path,
+) -> Result<PluginUpdateAvailability, Error> {
+    state.0.read().await.update_available(&name.inner()).await
+}
+
+#[post("/api/plugins/{name}/update", output = "json", eoutput = "bytes")]
+pub async fn update_plugin_binary(
+    db: State<SqlitePool>,
+    state: State<RwLock<PluginManager>>,
+    name: Path,
+) -> Result<Plugin, Error> {
+    state
+        .0
+        .write()
+        .await
+        .update_file_plugin(&name.inner(), db.as_ref())
+        .await
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BatchUpdateResult {
+    pub updated: Vec<String>,
+    pub failed: Vec<(String, String)>,
+    pub skipped: Vec<String>,
+}
+
+#[post("/api/plugins/updates/apply", output = "json", eoutput = "bytes")]
+pub async fn apply_plugin_updates(
+    db: State<SqlitePool>,
+    state: State<RwLock<PluginManager>>,
+    body: Json<Option<Vec<String>>>,
+) -> Result<BatchUpdateResult, Error> {
+    let names = match body.inner() {
+        Some(names) if !names.is_empty() => names,
+        _ => {
+            let mut names: Vec<String> = state
+                .0
+                .read()
+                .await
+                .installed_plugins()
+                .await
+                .into_iter()
+                .map(|plugin| plugin.name)
+                .collect();
+            names.sort();
+            names
+        }
+    };
+    let mut result = BatchUpdateResult::default();
+    //Updates run one at a time so only a single download and binary swap is in flight
+    for name in names {
+        let mut plugin_manager = state.0.write().await;
+        match plugin_manager.update_available(&name).await {
+            Ok(availability) if !availability.has_update => {
+                result.skipped.push(name);
+                continue;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                result.failed.push((name, e.to_string()));
+                continue;
+            }
+        }
+        match plugin_manager.update_file_plugin(&name, db.as_ref()).await {
+            Ok(plugin) => {
+                info!("Updated Plugin {name} to {}", plugin.version);
+                result.updated.push(name);
+            }
+            Err(e) => {
+                warn!("Failed to update Plugin {name}: {e:?}");
+                result.failed.push((name, e.to_string()));
+            }
+        }
+    }
+    Ok(result)
+}
+
 #[get("/api/plugins/{name}/env", output = "json", eoutput = "bytes")]
 pub async fn get_plugin_environment(
     db: State<SqlitePool>,
@@ -189,3 +653,95 @@ pub async fn del_plugin_environment_value(
         .await
         .map(|v| v > 0)
 }
+
+#[derive(Serialize)]
+pub struct DockerImage {
+    pub id: String,
+    pub repo_tags: Vec<String>,
+    pub size: i64,
+    pub created: i64,
+}
+
+#[derive(Serialize)]
+pub struct DockerPruneResult {
+    pub images_deleted: usize,
+    pub space_reclaimed: i64,
+}
+
+#[derive(Serialize)]
+pub struct DockerDiskUsage {
+    pub layers_size: i64,
+    pub images: usize,
+    pub images_size: i64,
+    pub containers: usize,
+    pub containers_size: i64,
+    pub volumes: usize,
+}
+
+#[get("/api/docker/images", output = "json", eoutput = "bytes")]
+pub async fn docker_images() -> Result<Vec<DockerImage>, Error> {
+    let images = connect_to_docker()?
+        .list_images(None::<ListImagesOptions<String>>)
+        .await
+        .map_err(|e| Error::other(format!("Failed to list docker images: {e}")))?;
+    Ok(images
+        .into_iter()
+        .map(|image| DockerImage {
+            id: image.id,
+            repo_tags: image.repo_tags,
+            size: image.size,
+            created: image.created,
+        })
+        .collect())
+}
+
+#[delete("/api/docker/images/{id}", output = "json", eoutput = "bytes")]
+pub async fn remove_docker_image(id: Path) -> Result<bool, Error> {
+    let id = id.inner();
+    let deleted = connect_to_docker()?
+        .remove_image(&id, None, None)
+        .await
+        .map_err(|e| Error::other(format!("Failed to remove docker image {id}: {e}")))?;
+    info!("Removed docker image {id}");
+    Ok(!deleted.is_empty())
+}
+
+#[post("/api/docker/images/prune", output = "json", eoutput = "bytes")]
+pub async fn prune_docker_images() -> Result<DockerPruneResult, Error> {
+    let pruned = connect_to_docker()?
+        .prune_images(Some(PruneImagesOptions {
+            filters: HashMap::from([("dangling", vec!["true"])]),
+        }))
+        .await
+        .map_err(|e| Error::other(format!("Failed to prune docker images: {e}")))?;
+    let result = DockerPruneResult {
+        images_deleted: pruned.images_deleted.map(|v| v.len()).unwrap_or_default(),
+        space_reclaimed: pruned.space_reclaimed.unwrap_or_default(),
+    };
+    info!(
+        "Pruned {} dangling docker images, reclaimed {} bytes",
+        result.images_deleted, result.space_reclaimed
+    );
+    Ok(result)
+}
+
+#[get("/api/docker/disk", output = "json", eoutput = "bytes")]
+pub async fn docker_disk_usage() -> Result<DockerDiskUsage, Error> {
+    let usage = connect_to_docker()?
+        .df()
+        .await
+        .map_err(|e| Error::other(format!("Failed to load docker disk usage: {e}")))?;
+    let images = usage.images.unwrap_or_default();
+    let containers = usage.containers.unwrap_or_default();
+    Ok(DockerDiskUsage {
+        layers_size: usage.layers_size.unwrap_or_default(),
+        images: images.len(),
+        images_size: images.iter().map(|image| image.size).sum(),
+        containers: containers.len(),
+        containers_size: containers
+            .iter()
+            .filter_map(|container| container.size_rw)
+            .sum(),
+        volumes: usage.volumes.map(|v| v.len()).unwrap_or_default(),
+    })
+}
@@ -0,0 +1,85 @@
+use bytes::Bytes;
+use http_body::{Body, Frame};
+use http_body_util::combinators::BoxBody;
+use log::debug;
+use portfu::prelude::http::{HeaderName, HeaderValue};
+use portfu::prelude::serde_json;
+use portfu_core::ServiceData;
+use serde::Serialize;
+use std::io::Error;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+pub const SSE_RETRY_MS: u64 = 3000;
+pub const SSE_PING_INTERVAL: Duration = Duration::from_secs(30);
+const SSE_CHANNEL_SIZE: usize = 64;
+
+//Response body that yields each event as its own frame, ends when the EventStream is dropped
+struct EventStreamBody {
+    receiver: Receiver<Bytes>,
+}
+impl Body for EventStreamBody {
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.receiver
+            .poll_recv(cx)
+            .map(|event| event.map(|bytes| Ok(Frame::data(bytes))))
+    }
+}
+
+pub struct EventStream {
+    sender: Sender<Bytes>,
+}
+impl EventStream {
+    //Replaces the response body with a text/event-stream that stays open until the client leaves
+    pub fn start(data: &mut ServiceData) -> Self {
+        let headers = data.response.headers_mut();
+        headers.insert(
+            HeaderName::from_static("content-type"),
+            HeaderValue::from_static("text/event-stream"),
+        );
+        headers.insert(
+            HeaderName::from_static("cache-control"),
+            HeaderValue::from_static("no-cache"),
+        );
+        let (sender, receiver) = channel(SSE_CHANNEL_SIZE);
+        *data.response.body_mut() = BoxBody::new(EventStreamBody { receiver });
+        Self { sender }
+    }
+    pub async fn send_json<T: Serialize>(&self, event: &T) -> bool {
+        match serde_json::to_string(event) {
+            Ok(as_json) => self.send(format!("data: {as_json}\n\n")).await,
+            Err(e) => {
+                debug!("Failed to serialize event: {e:?}");
+                true
+            }
+        }
+    }
+    //Sets the EventSource reconnect delay, repeated as the keep alive ping
+    pub async fn ping(&self) -> bool {
+        self.send(format!("retry: {SSE_RETRY_MS}\n\n")).await
+    }
+    //False once the client has disconnected and the body was dropped
+    async fn send(&self, frame: String) -> bool {
+        self.sender.send(Bytes::from(frame)).await.is_ok()
+    }
+}
+
+#[tokio::test]
+async fn test_event_stream_body() {
+    use http_body_util::BodyExt;
+    let (sender, receiver) = channel(1);
+    let mut body = EventStreamBody { receiver };
+    sender.send(Bytes::from("data: {}\n\n")).await.unwrap();
+    drop(sender);
+    let frame = body.frame().await.unwrap().unwrap();
+    assert_eq!(frame.into_data().unwrap(), Bytes::from("data: {}\n\n"));
+    assert!(body.frame().await.is_none());
+}
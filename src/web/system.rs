@@ -1,6 +1,23 @@
+use crate::config::ConfigManager;
 use crate::database::config::get_config_key;
-use crate::utils::has_internet_connection;
+use crate::database::users::has_no_users;
+use crate::models::config::AddConfigEntry;
+use crate::models::hotspot::{
+    validate_hotspot_password, validate_hotspot_ssid, HotspotConfig, HOTSPOT_BAND_CONFIG_KEY,
+    HOTSPOT_CHANNEL_CONFIG_KEY, HOTSPOT_PASSWORD_CONFIG_KEY, HOTSPOT_SSID_CONFIG_KEY,
+};
+use crate::plugins::farmer::{load_farmer_config, FarmerManager};
+use crate::plugins::system_monitor::{GpuInfo, SystemMonitorPlugin};
+use crate::tls::{
+    issue_acme_certificate, load_certified_key, TlsCertificates, TlsPaths,
+    TLS_CERT_PATH_CONFIG_KEY, TLS_HOSTNAME_CONFIG_KEY, TLS_KEY_PATH_CONFIG_KEY,
+};
+use crate::utils::{
+    check_internet_connection, has_internet_connection, load_hotspot_config,
+    validate_check_endpoint, InternetCheckResult, DEFAULT_INTERNET_CHECK_ENDPOINTS,
+};
 use crate::version;
+use crate::web::sse::{EventStream, SSE_PING_INTERVAL};
 use dg_edge_updater::{fetch_manifest, UPDATER_SERVICE_NAME};
 use dg_logger::DruidGardenLogger;
 use dg_network_manager::dbus_api::devices::Device;
@@ -9,25 +26,109 @@ use dg_network_manager::{
     find_active_hotspots, find_all_hotspots, reset_active_connection, scan_all_ssids,
     wireless_device, wireless_devices,
 };
-use log::{debug, error, info, Level};
+use log::{debug, error, info, warn, Level};
+use portfu::prelude::http::StatusCode;
 use portfu::prelude::tokio_tungstenite::tungstenite::Message;
 use portfu::prelude::{serde_json, Path, State, WebSocket};
-use portfu_core::Json;
-use portfu_macros::{get, post, websocket};
+use portfu::wrappers::sessions::Session;
+use portfu_admin::auth::Claims;
+use portfu_core::{Json, ServiceData};
+use portfu_macros::{delete, get, patch, post, websocket};
 use reqwest::Client;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::io::{Error, ErrorKind};
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use sysinfo::System;
+use time::OffsetDateTime;
 use tokio::process::Command;
+use tokio::sync::RwLock;
+use x509_cert::Certificate;
 
 #[get("/system/device")]
 pub async fn find_device() -> Result<String, Error> {
     Ok("GardenOS".to_string())
 }
 
+pub static SERVER_STARTED: OnceLock<Instant> = OnceLock::new();
+
+#[derive(Serialize)]
+pub struct HealthStatus {
+    pub status: String,
+    pub version: String,
+    pub uptime: u64,
+    pub farmer: String,
+    pub db: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config_ready: Option<bool>,
+}
+
+async fn health_status(
+    data: &mut ServiceData,
+    pool: &SqlitePool,
+    farmer_manager: &FarmerManager,
+) -> HealthStatus {
+    let db_ok = matches!(
+        tokio::time::timeout(Duration::from_secs(1), has_no_users(pool)).await,
+        Ok(Ok(_))
+    );
+    if !db_ok {
+        *data.response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    HealthStatus {
+        status: if db_ok { "ok" } else { "degraded" }.to_string(),
+        version: version().to_string(),
+        uptime: SERVER_STARTED
+            .get()
+            .map(|started| started.elapsed().as_secs())
+            .unwrap_or_default(),
+        farmer: if farmer_manager.is_running().await {
+            "running"
+        } else {
+            "stopped"
+        }
+        .to_string(),
+        db: if db_ok { "ok" } else { "degraded" }.to_string(),
+        config_ready: None,
+    }
+}
+
+#[get("/health", output = "json", eoutput = "bytes")]
+pub async fn health(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    farmer_manager: State<FarmerManager>,
+) -> Result<HealthStatus, Error> {
+    Ok(health_status(data, pool.as_ref(), farmer_manager.0.as_ref()).await)
+}
+
+#[get("/health/ready", output = "json", eoutput = "bytes")]
+pub async fn health_ready(
+    data: &mut ServiceData,
+    pool: State<SqlitePool>,
+    farmer_manager: State<FarmerManager>,
+) -> Result<HealthStatus, Error> {
+    let mut status = health_status(data, pool.as_ref(), farmer_manager.0.as_ref()).await;
+    let config_ready = match load_farmer_config(pool.as_ref()).await {
+        Ok(config) => config.is_ready(),
+        Err(e) => {
+            debug!("Failed to load Farmer Config for Readiness Check: {e:?}");
+            false
+        }
+    };
+    if !config_ready {
+        status.status = "not_ready".to_string();
+        *data.response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    status.config_ready = Some(config_ready);
+    Ok(status)
+}
+
 #[derive(Serialize)]
 pub struct UpdateInfo {
     pub remote_version: Version,
@@ -76,11 +177,661 @@ async fn run_update_service() -> Result<(), Error> {
     Ok(())
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PowerActionPayload {
+    pub confirm: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UptimeInfo {
+    pub uptime_seconds: u64,
+    pub boot_time: i64,
+}
+
+async fn schedule_power_action(
+    action: &'static str,
+    session: &RwLock<Session>,
+    payload: Option<PowerActionPayload>,
+) -> Result<(), Error> {
+    let payload = match payload {
+        Some(payload) if payload.confirm => payload,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("System {action} must be confirmed"),
+            ))
+        }
+    };
+    let username = session
+        .read()
+        .await
+        .data
+        .get::<Claims>()
+        .map(|claims| claims.eml.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+    warn!(
+        "System {action} requested by {username}, Reason: {}",
+        payload.reason.as_deref().unwrap_or("None Given")
+    );
+    //Delay so the HTTP response is sent before the system goes down
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        match Command::new("systemctl").arg(action).status().await {
+            Ok(status) if status.success() => {}
+            Ok(status) => error!("Failed to run systemctl {action}: {status:?}"),
+            Err(e) => error!("Failed to run systemctl {action}: {e:?}"),
+        }
+    });
+    Ok(())
+}
+
+#[post("/system/reboot", output = "json", eoutput = "bytes")]
+pub async fn reboot_system(
+    session: State<RwLock<Session>>,
+    payload: Json<Option<PowerActionPayload>>,
+) -> Result<(), Error> {
+    schedule_power_action("reboot", session.0.as_ref(), payload.inner()).await
+}
+
+#[post("/system/shutdown", output = "json", eoutput = "bytes")]
+pub async fn shutdown_system(
+    session: State<RwLock<Session>>,
+    payload: Json<Option<PowerActionPayload>>,
+) -> Result<(), Error> {
+    schedule_power_action("poweroff", session.0.as_ref(), payload.inner()).await
+}
+
+#[get("/system/uptime", output = "json", eoutput = "bytes")]
+pub async fn get_uptime() -> Result<UptimeInfo, Error> {
+    Ok(UptimeInfo {
+        uptime_seconds: System::uptime(),
+        boot_time: System::boot_time() as i64,
+    })
+}
+
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+const TIMEZONE_FILE: &str = "/etc/timezone";
+pub const SYSTEM_TIMEZONE_CONFIG_KEY: &str = "system_timezone";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TimezonePayload {
+    pub timezone: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimezoneChange {
+    pub old_timezone: String,
+    pub new_timezone: String,
+}
+
+async fn current_timezone() -> Result<String, Error> {
+    if let Ok(timezone) = tokio::fs::read_to_string(TIMEZONE_FILE).await {
+        let timezone = timezone.trim();
+        if !timezone.is_empty() {
+            return Ok(timezone.to_string());
+        }
+    }
+    let output = Command::new("timedatectl")
+        .args(["show", "--property=Timezone", "--value"])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "Failed to read Timezone: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+async fn available_timezones() -> Result<Vec<String>, Error> {
+    let root = std::path::Path::new(ZONEINFO_DIR);
+    let mut timezones = vec![];
+    let mut to_visit = vec![root.to_path_buf()];
+    while let Some(dir) = to_visit.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            //Zone names start with an uppercase letter, lowercase entries are tables/metadata
+            if !name.starts_with(|c: char| c.is_ascii_uppercase()) || name.contains('.') {
+                continue;
+            }
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                to_visit.push(entry.path());
+            } else if let Ok(relative) = entry.path().strip_prefix(root) {
+                timezones.push(relative.to_string_lossy().to_string());
+            }
+        }
+    }
+    timezones.sort();
+    Ok(timezones)
+}
+
+#[get("/system/timezone", output = "json", eoutput = "bytes")]
+pub async fn get_timezone() -> Result<String, Error> {
+    current_timezone().await
+}
+
+#[get("/system/timezone/available", output = "json", eoutput = "bytes")]
+pub async fn get_available_timezones() -> Result<Vec<String>, Error> {
+    available_timezones().await
+}
+
+#[post("/system/timezone", output = "json", eoutput = "bytes")]
+pub async fn set_timezone(
+    pool: State<SqlitePool>,
+    config_manager: State<RwLock<ConfigManager>>,
+    data: Json<Option<TimezonePayload>>,
+) -> Result<TimezoneChange, Error> {
+    let Some(payload) = data.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Invalid Timezone Payload",
+        ));
+    };
+    let new_timezone = payload.timezone.trim().to_string();
+    if !available_timezones().await?.contains(&new_timezone) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unknown Timezone: {new_timezone}"),
+        ));
+    }
+    let old_timezone = current_timezone().await.unwrap_or_default();
+    let output = Command::new("timedatectl")
+        .arg("set-timezone")
+        .arg(&new_timezone)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "Failed to set Timezone: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    info!("System Timezone changed from {old_timezone} to {new_timezone}");
+    config_manager
+        .0
+        .write()
+        .await
+        .set(
+            SYSTEM_TIMEZONE_CONFIG_KEY,
+            AddConfigEntry {
+                key: SYSTEM_TIMEZONE_CONFIG_KEY.to_string(),
+                value: new_timezone.clone(),
+                last_value: old_timezone.clone(),
+                category: "system".to_string(),
+                system: 0,
+            },
+            Some(pool.as_ref()),
+        )
+        .await?;
+    Ok(TimezoneChange {
+        old_timezone,
+        new_timezone,
+    })
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TimeSyncStatus {
+    pub synchronized: bool,
+    pub source: String,
+    pub offset_ms: f64,
+    pub stratum: u8,
+    pub current_utc: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SetTimePayload {
+    pub time: String,
+}
+
+async fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    match Command::new(program).args(args).output().await {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+        Ok(output) => {
+            debug!(
+                "{program} exited with {:?}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            None
+        }
+        Err(e) => {
+            debug!("Failed to run {program}: {e:?}");
+            None
+        }
+    }
+}
+
+fn parse_chrony_tracking(output: &str, status: &mut TimeSyncStatus) -> bool {
+    let mut found = false;
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Reference ID" => {
+                found = true;
+                //Hardware clocks (PHC) are disciplined by PTP rather than NTP
+                status.source = if value.contains("PHC") || value.contains("PTP") {
+                    "ptp"
+                } else if value.starts_with("00000000") {
+                    "none"
+                } else {
+                    "ntp"
+                }
+                .to_string();
+            }
+            "Stratum" => status.stratum = value.parse().unwrap_or_default(),
+            "Last offset" => {
+                status.offset_ms = value
+                    .split_whitespace()
+                    .next()
+                    .and_then(|v| f64::from_str(v).ok())
+                    .map(|seconds| seconds * 1000.0)
+                    .unwrap_or_default();
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+fn parse_ntpq_peers(output: &str, status: &mut TimeSyncStatus) -> bool {
+    //The selected system peer is prefixed with '*', columns are:
+    //remote refid st t when poll reach delay offset jitter
+    let Some(peer) = output.lines().find(|line| line.starts_with('*')) else {
+        return false;
+    };
+    let columns: Vec<&str> = peer.split_whitespace().collect();
+    if columns.len() < 10 {
+        return false;
+    }
+    status.source = if columns[1].contains("PTP") {
+        "ptp"
+    } else {
+        "ntp"
+    }
+    .to_string();
+    status.stratum = columns[2].parse().unwrap_or_default();
+    status.offset_ms = columns[8].parse().unwrap_or_default();
+    true
+}
+
+async fn time_sync_status() -> TimeSyncStatus {
+    let mut status = TimeSyncStatus {
+        source: "none".to_string(),
+        current_utc: time::OffsetDateTime::now_utc().unix_timestamp(),
+        ..Default::default()
+    };
+    if let Some(output) = command_output("timedatectl", &["show"]).await {
+        status.synchronized = output
+            .lines()
+            .any(|line| line.trim() == "NTPSynchronized=yes");
+    }
+    let found = match command_output("chronyc", &["tracking"]).await {
+        Some(output) => parse_chrony_tracking(&output, &mut status),
+        None => false,
+    };
+    if !found {
+        if let Some(output) = command_output("ntpq", &["-p"]).await {
+            parse_ntpq_peers(&output, &mut status);
+        }
+    }
+    if !status.synchronized {
+        status.source = "none".to_string();
+    }
+    status
+}
+
+#[get("/system/time", output = "json", eoutput = "bytes")]
+pub async fn get_time_status() -> Result<TimeSyncStatus, Error> {
+    Ok(time_sync_status().await)
+}
+
+#[post("/system/time/sync", output = "json", eoutput = "bytes")]
+pub async fn sync_time() -> Result<TimeSyncStatus, Error> {
+    if command_output("chronyc", &["makestep"]).await.is_none() {
+        let status = Command::new("systemctl")
+            .arg("restart")
+            .arg("systemd-timesyncd")
+            .status()
+            .await?;
+        if !status.success() {
+            return Err(Error::other(format!(
+                "Failed to restart systemd-timesyncd: {status:?}"
+            )));
+        }
+    }
+    info!("Forced Time Synchronization");
+    Ok(time_sync_status().await)
+}
+
+#[post("/system/time/set", output = "json", eoutput = "bytes")]
+pub async fn set_time(data: Json<Option<SetTimePayload>>) -> Result<TimeSyncStatus, Error> {
+    let Some(payload) = data.inner() else {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid Time Payload"));
+    };
+    let format = time::format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second]")
+        .map_err(Error::other)?;
+    let new_time = payload.time.trim();
+    time::PrimitiveDateTime::parse(new_time, &format).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Time must be formatted as YYYY-MM-DD HH:MM:SS: {e}"),
+        )
+    })?;
+    if time_sync_status().await.synchronized || has_internet_connection().await {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Time can only be set manually when NTP is unavailable",
+        ));
+    }
+    //timedatectl refuses set-time while automatic synchronization is enabled
+    let _ = Command::new("timedatectl")
+        .args(["set-ntp", "false"])
+        .status()
+        .await;
+    let output = Command::new("timedatectl")
+        .arg("set-time")
+        .arg(new_time)
+        .output()
+        .await?;
+    //Re-enable synchronization so NTP takes over again once the network returns
+    let _ = Command::new("timedatectl")
+        .args(["set-ntp", "true"])
+        .status()
+        .await;
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "Failed to set Time: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    warn!("System Time manually set to {new_time}");
+    Ok(time_sync_status().await)
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TlsStatus {
+    pub cert_path: String,
+    pub key_path: String,
+    pub expires_at: Option<OffsetDateTime>,
+    pub issuer: Option<String>,
+    pub valid: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TlsUploadPayload {
+    pub certificate: String,
+    pub private_key: String,
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct AcmeRequestPayload {
+    pub contact_email: Option<String>,
+    #[serde(default)]
+    pub staging: bool,
+}
+
+fn tls_certificate_info(cert_pem: &[u8]) -> Result<(OffsetDateTime, String), Error> {
+    //The first certificate is the leaf, the rest of the chain is only served to clients
+    let certificate = Certificate::load_pem_chain(cert_pem)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Invalid Certificate: {e}")))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "No Certificate found in PEM"))?;
+    let not_after = certificate
+        .tbs_certificate
+        .validity
+        .not_after
+        .to_unix_duration();
+    let expires_at = OffsetDateTime::from_unix_timestamp(not_after.as_secs() as i64)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Invalid Expiry: {e}")))?;
+    Ok((expires_at, certificate.tbs_certificate.issuer.to_string()))
+}
+
+async fn tls_status(cert_path: &std::path::Path, key_path: &std::path::Path) -> TlsStatus {
+    let mut status = TlsStatus {
+        cert_path: cert_path.display().to_string(),
+        key_path: key_path.display().to_string(),
+        ..Default::default()
+    };
+    let Ok(cert_pem) = tokio::fs::read(cert_path).await else {
+        return status;
+    };
+    match tls_certificate_info(&cert_pem) {
+        Ok((expires_at, issuer)) => {
+            status.expires_at = Some(expires_at);
+            status.issuer = Some(issuer);
+        }
+        Err(e) => {
+            warn!(
+                "Failed to read TLS Certificate {}: {e}",
+                cert_path.display()
+            );
+            return status;
+        }
+    }
+    let key_valid = match tokio::fs::read(key_path).await {
+        Ok(key_pem) => load_certified_key(&cert_pem, &key_pem).is_ok(),
+        Err(_) => false,
+    };
+    status.valid = key_valid
+        && status
+            .expires_at
+            .is_some_and(|expires_at| expires_at > OffsetDateTime::now_utc());
+    status
+}
+
+async fn install_tls_certificate(
+    certificates: &TlsCertificates,
+    config_manager: &RwLock<ConfigManager>,
+    db: &SqlitePool,
+    cert_pem: &[u8],
+    key_pem: &[u8],
+    paths: TlsPaths,
+) -> Result<TlsStatus, Error> {
+    let (expires_at, issuer) = tls_certificate_info(cert_pem)?;
+    if expires_at <= OffsetDateTime::now_utc() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Certificate expired at {expires_at}"),
+        ));
+    }
+    let current = certificates.paths()?;
+    certificates
+        .install(cert_pem, key_pem, paths.clone())
+        .await?;
+    info!("Installed TLS Certificate issued by {issuer}, expires {expires_at}");
+    let mut config_manager = config_manager.write().await;
+    for (key, path, last_path) in [
+        (
+            TLS_CERT_PATH_CONFIG_KEY,
+            &paths.cert_path,
+            &current.cert_path,
+        ),
+        (TLS_KEY_PATH_CONFIG_KEY, &paths.key_path, &current.key_path),
+    ] {
+        let value = path.display().to_string();
+        if config_manager.get(key).await.map(|entry| entry.value) != Some(value.clone()) {
+            config_manager
+                .set(
+                    key,
+                    AddConfigEntry {
+                        key: key.to_string(),
+                        value,
+                        last_value: last_path.display().to_string(),
+                        category: "tls".to_string(),
+                        system: 0,
+                    },
+                    Some(db),
+                )
+                .await?;
+        }
+    }
+    Ok(tls_status(&paths.cert_path, &paths.key_path).await)
+}
+
+#[get("/system/tls", output = "json", eoutput = "bytes")]
+pub async fn get_tls_status(certificates: State<TlsCertificates>) -> Result<TlsStatus, Error> {
+    let paths = certificates.0.paths()?;
+    Ok(tls_status(&paths.cert_path, &paths.key_path).await)
+}
+
+#[post("/system/tls", output = "json", eoutput = "bytes")]
+pub async fn upload_tls_certificate(
+    certificates: State<TlsCertificates>,
+    config_manager: State<RwLock<ConfigManager>>,
+    db: State<SqlitePool>,
+    data: Json<Option<TlsUploadPayload>>,
+) -> Result<TlsStatus, Error> {
+    let Some(payload) = data.inner() else {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid TLS Payload"));
+    };
+    let mut paths = certificates.0.paths()?;
+    if let Some(cert_path) = payload.cert_path.filter(|path| !path.is_empty()) {
+        paths.cert_path = cert_path.into();
+    }
+    if let Some(key_path) = payload.key_path.filter(|path| !path.is_empty()) {
+        paths.key_path = key_path.into();
+    }
+    install_tls_certificate(
+        certificates.0.as_ref(),
+        config_manager.0.as_ref(),
+        db.as_ref(),
+        payload.certificate.as_bytes(),
+        payload.private_key.as_bytes(),
+        paths,
+    )
+    .await
+}
+
+#[post("/system/tls/acme", output = "json", eoutput = "bytes")]
+pub async fn request_acme_certificate(
+    certificates: State<TlsCertificates>,
+    config_manager: State<RwLock<ConfigManager>>,
+    db: State<SqlitePool>,
+    data: Json<Option<AcmeRequestPayload>>,
+) -> Result<TlsStatus, Error> {
+    let payload = data.inner().unwrap_or_default();
+    let hostname = config_manager
+        .0
+        .read()
+        .await
+        .get(TLS_HOSTNAME_CONFIG_KEY)
+        .await
+        .map(|entry| entry.value)
+        .filter(|hostname| !hostname.is_empty())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Set {TLS_HOSTNAME_CONFIG_KEY} before requesting a certificate"),
+            )
+        })?;
+    info!("Requesting TLS Certificate for {hostname} from Let's Encrypt");
+    let (cert_pem, key_pem) =
+        issue_acme_certificate(&hostname, payload.contact_email.as_deref(), payload.staging)
+            .await?;
+    install_tls_certificate(
+        certificates.0.as_ref(),
+        config_manager.0.as_ref(),
+        db.as_ref(),
+        cert_pem.as_bytes(),
+        key_pem.as_bytes(),
+        certificates.0.paths()?,
+    )
+    .await
+}
+
 #[post("/system/is_online", output = "json", eoutput = "bytes")]
 pub async fn is_online() -> Result<bool, Error> {
     Ok(has_internet_connection().await)
 }
 
+pub const INTERNET_CHECK_ENDPOINTS_CONFIG_KEY: &str = "internet_check_endpoints";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InternetCheckConfig {
+    pub default_endpoints: Vec<String>,
+    pub custom_endpoints: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InternetCheckConfigPayload {
+    pub custom_endpoints: Vec<String>,
+}
+
+async fn internet_check_config(config_manager: &ConfigManager) -> InternetCheckConfig {
+    InternetCheckConfig {
+        default_endpoints: DEFAULT_INTERNET_CHECK_ENDPOINTS
+            .iter()
+            .map(|address| address.to_string())
+            .collect(),
+        custom_endpoints: config_manager
+            .get_or_default(INTERNET_CHECK_ENDPOINTS_CONFIG_KEY)
+            .await,
+    }
+}
+
+#[post("/api/system/internet/check", output = "json", eoutput = "bytes")]
+pub async fn internet_check(
+    config_manager: State<RwLock<ConfigManager>>,
+) -> Result<InternetCheckResult, Error> {
+    let custom_endpoints = internet_check_config(&*config_manager.0.read().await)
+        .await
+        .custom_endpoints;
+    Ok(check_internet_connection(&custom_endpoints).await)
+}
+
+#[get("/api/system/internet/config", output = "json", eoutput = "bytes")]
+pub async fn get_internet_check_config(
+    config_manager: State<RwLock<ConfigManager>>,
+) -> Result<InternetCheckConfig, Error> {
+    Ok(internet_check_config(&*config_manager.0.read().await).await)
+}
+
+#[post("/api/system/internet/config", output = "json", eoutput = "bytes")]
+pub async fn set_internet_check_config(
+    pool: State<SqlitePool>,
+    config_manager: State<RwLock<ConfigManager>>,
+    data: Json<Option<InternetCheckConfigPayload>>,
+) -> Result<InternetCheckConfig, Error> {
+    let Some(payload) = data.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Invalid Internet Check Payload",
+        ));
+    };
+    let mut custom_endpoints: Vec<String> = vec![];
+    for address in payload.custom_endpoints {
+        let address = address.trim().to_string();
+        validate_check_endpoint(&address)?;
+        if !custom_endpoints.contains(&address)
+            && !DEFAULT_INTERNET_CHECK_ENDPOINTS.contains(&address.as_str())
+        {
+            custom_endpoints.push(address);
+        }
+    }
+    let mut config_manager = config_manager.0.write().await;
+    config_manager
+        .set_as(
+            INTERNET_CHECK_ENDPOINTS_CONFIG_KEY,
+            &custom_endpoints,
+            "system",
+            Some(pool.as_ref()),
+        )
+        .await?;
+    Ok(internet_check_config(&config_manager).await)
+}
+
 #[derive(Serialize)]
 pub struct AccessPoint {
     pub ssid: String,
@@ -176,6 +927,137 @@ pub async fn wifi_connect(
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct SavedWifiConnection {
+    pub ssid: String,
+    pub auto_connect: bool,
+    pub last_connected: Option<OffsetDateTime>,
+    #[serde(skip)]
+    uuid: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SavedWifiPayload {
+    pub auto_connect: bool,
+}
+
+async fn nmcli(args: &[&str]) -> Result<String, Error> {
+    let output = Command::new("nmcli").args(args).output().await?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(Error::other(format!(
+            "nmcli {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+fn parse_saved_wifi_connection(uuid: &str, output: &str) -> Option<SavedWifiConnection> {
+    //Fields are printed one per line in the order they were requested
+    let mut lines = output.lines();
+    let ssid = lines.next()?.to_string();
+    let auto_connect = lines.next()? == "yes";
+    let timestamp = lines.next()?.parse::<i64>().unwrap_or_default();
+    let mode = lines.next().unwrap_or_default();
+    //Access point profiles are the hotspots this device creates, not saved networks
+    if ssid.is_empty() || mode == "ap" {
+        return None;
+    }
+    Some(SavedWifiConnection {
+        ssid,
+        auto_connect,
+        last_connected: (timestamp > 0)
+            .then(|| OffsetDateTime::from_unix_timestamp(timestamp).ok())
+            .flatten(),
+        uuid: uuid.to_string(),
+    })
+}
+
+async fn saved_wifi_connections() -> Result<Vec<SavedWifiConnection>, Error> {
+    let mut saved = vec![];
+    for line in nmcli(&["-t", "-f", "UUID,TYPE", "connection", "show"])
+        .await?
+        .lines()
+    {
+        let Some((uuid, "802-11-wireless")) = line.split_once(':') else {
+            continue;
+        };
+        let output = nmcli(&[
+            "-g",
+            "802-11-wireless.ssid,connection.autoconnect,connection.timestamp,802-11-wireless.mode",
+            "connection",
+            "show",
+            "uuid",
+            uuid,
+        ])
+        .await?;
+        saved.extend(parse_saved_wifi_connection(uuid, &output));
+    }
+    Ok(saved)
+}
+
+async fn find_saved_wifi(ssid: &str) -> Result<Vec<SavedWifiConnection>, Error> {
+    let matching: Vec<SavedWifiConnection> = saved_wifi_connections()
+        .await?
+        .into_iter()
+        .filter(|connection| connection.ssid == ssid)
+        .collect();
+    if matching.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("No saved WiFi connection for {ssid}"),
+        ));
+    }
+    Ok(matching)
+}
+
+#[get("/system/wifi/saved", output = "json", eoutput = "bytes")]
+pub async fn get_saved_wifi() -> Result<Vec<SavedWifiConnection>, Error> {
+    saved_wifi_connections().await
+}
+
+#[delete("/system/wifi/saved/{ssid}", output = "json", eoutput = "bytes")]
+pub async fn delete_saved_wifi(ssid: Path) -> Result<usize, Error> {
+    let ssid = ssid.inner();
+    let matching = find_saved_wifi(&ssid).await?;
+    for connection in &matching {
+        nmcli(&["connection", "delete", "uuid", &connection.uuid]).await?;
+    }
+    info!(
+        "Removed {} saved WiFi connection(s) for {ssid}",
+        matching.len()
+    );
+    Ok(matching.len())
+}
+
+#[patch("/system/wifi/saved/{ssid}", output = "json", eoutput = "bytes")]
+pub async fn update_saved_wifi(
+    ssid: Path,
+    data: Json<Option<SavedWifiPayload>>,
+) -> Result<Vec<SavedWifiConnection>, Error> {
+    let Some(payload) = data.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Invalid Saved WiFi Payload",
+        ));
+    };
+    let ssid = ssid.inner();
+    let auto_connect = if payload.auto_connect { "yes" } else { "no" };
+    for connection in find_saved_wifi(&ssid).await? {
+        nmcli(&[
+            "connection",
+            "modify",
+            "uuid",
+            &connection.uuid,
+            "connection.autoconnect",
+            auto_connect,
+        ])
+        .await?;
+    }
+    find_saved_wifi(&ssid).await
+}
 #[post("/system/hotspot/active", output = "json", eoutput = "bytes")]
 pub async fn hotspot_active() -> Result<bool, Error> {
     find_active_hotspots().await.map(|v| !v.is_empty())
@@ -197,10 +1079,10 @@ pub async fn hotspot_stop() -> Result<bool, Error> {
     Ok(true)
 }
 
-#[derive(Deserialize)]
+#[derive(Default, Deserialize)]
 pub struct HotspotPayload {
     pub device: Option<String>,
-    pub ssid: String,
+    pub ssid: Option<String>,
     pub password: Option<String>,
 }
 
@@ -209,37 +1091,92 @@ pub async fn hotspot_start(
     payload: Json<Option<HotspotPayload>>,
     pool: State<SqlitePool>,
 ) -> Result<bool, Error> {
-    match payload.inner() {
-        Some(payload) => {
-            let device = match payload.device {
-                Some(name) => wireless_device(&name).await?.ok_or(Error::new(
-                    ErrorKind::NotFound,
-                    format!("Device not found: {name}"),
-                ))?,
-                None => match get_config_key(pool.0.as_ref(), "wifi_device").await? {
-                    Some(config_device) => {
-                        wireless_device(&config_device.value)
-                            .await?
-                            .ok_or(Error::new(
-                                ErrorKind::NotFound,
-                                format!("Device not found: {}", config_device.value),
-                            ))?
-                    }
-                    None => wireless_devices()
-                        .await?
-                        .into_iter()
-                        .next()
-                        .ok_or(Error::new(ErrorKind::NotFound, "No Wireless Devices found"))?,
-                },
-            };
-            for con in find_active_hotspots().await? {
-                delete_active_connection(con).await?;
+    //Anything missing from the payload falls back to the saved hotspot config
+    let payload = payload.inner().unwrap_or_default();
+    let config = load_hotspot_config(pool.0.as_ref()).await?;
+    let ssid = payload.ssid.unwrap_or(config.ssid);
+    let password = payload.password.unwrap_or(config.password);
+    validate_hotspot_ssid(&ssid)?;
+    validate_hotspot_password(&password)?;
+    let device = match payload.device {
+        Some(name) => wireless_device(&name).await?.ok_or(Error::new(
+            ErrorKind::NotFound,
+            format!("Device not found: {name}"),
+        ))?,
+        None => match get_config_key(pool.0.as_ref(), "wifi_device").await? {
+            Some(config_device) => {
+                wireless_device(&config_device.value)
+                    .await?
+                    .ok_or(Error::new(
+                        ErrorKind::NotFound,
+                        format!("Device not found: {}", config_device.value),
+                    ))?
             }
-            create_hotspot(device, None, payload.ssid.clone(), payload.password.clone()).await?;
-            Ok(true)
-        }
-        None => Err(Error::new(ErrorKind::InvalidInput, "Invalid payload"))?,
+            None => wireless_devices()
+                .await?
+                .into_iter()
+                .next()
+                .ok_or(Error::new(ErrorKind::NotFound, "No Wireless Devices found"))?,
+        },
+    };
+    for con in find_active_hotspots().await? {
+        delete_active_connection(con).await?;
     }
+    create_hotspot(device, None, ssid, Some(password)).await?;
+    Ok(true)
+}
+
+#[get("/system/hotspot/config", output = "json", eoutput = "bytes")]
+pub async fn get_hotspot_config(pool: State<SqlitePool>) -> Result<HotspotConfig, Error> {
+    load_hotspot_config(pool.0.as_ref()).await
+}
+
+#[post("/system/hotspot/config", output = "json", eoutput = "bytes")]
+pub async fn set_hotspot_config(
+    pool: State<SqlitePool>,
+    config_manager: State<RwLock<ConfigManager>>,
+    data: Json<Option<HotspotConfig>>,
+) -> Result<HotspotConfig, Error> {
+    let Some(mut config) = data.inner() else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Invalid Hotspot Payload",
+        ));
+    };
+    config.channel = config.channel.filter(|channel| *channel != 0);
+    config.validate()?;
+    let values = [
+        (HOTSPOT_SSID_CONFIG_KEY, config.ssid.clone()),
+        (HOTSPOT_PASSWORD_CONFIG_KEY, config.password.clone()),
+        (
+            HOTSPOT_CHANNEL_CONFIG_KEY,
+            config.channel.map(|c| c.to_string()).unwrap_or_default(),
+        ),
+        (HOTSPOT_BAND_CONFIG_KEY, config.band.to_string()),
+    ];
+    let mut config_manager = config_manager.0.write().await;
+    for (key, value) in values {
+        let (last_value, system) = config_manager
+            .get(key)
+            .await
+            .map(|entry| (entry.value, entry.system))
+            .unwrap_or_default();
+        config_manager
+            .set(
+                key,
+                AddConfigEntry {
+                    key: key.to_string(),
+                    value,
+                    last_value,
+                    category: "system".to_string(),
+                    system,
+                },
+                Some(pool.as_ref()),
+            )
+            .await?;
+    }
+    info!("Hotspot Config updated, SSID: {}", config.ssid);
+    Ok(config)
 }
 
 #[post("/system/hotspot/restart", output = "json", eoutput = "bytes")]
@@ -255,6 +1192,50 @@ pub async fn hotspot_restart() -> Result<bool, Error> {
     Ok(true)
 }
 
+#[get("/api/system/log/{level}/stream", output = "none", eoutput = "bytes")]
+pub async fn log_event_stream(
+    data: &mut ServiceData,
+    level: Path,
+    logger: State<DruidGardenLogger>,
+) -> Result<(), Error> {
+    let level = level.inner();
+    let level = Level::from_str(level.as_str()).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{level} is not a valid Log Level: {e:?}"),
+        )
+    })?;
+    let mut receiver = logger.0.subscribe();
+    let stream = EventStream::start(data);
+    //The body streams from this task after the handler returns, it ends when the client leaves
+    tokio::spawn(async move {
+        let mut ping = tokio::time::interval(SSE_PING_INTERVAL);
+        loop {
+            tokio::select! {
+                result = receiver.recv() => {
+                    match result {
+                        Ok(log_entry) => {
+                            if log_entry.level <= level && !stream.send_json(&log_entry).await {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to read message from log channel: {e:?}");
+                            break;
+                        }
+                    }
+                }
+                _ = ping.tick() => {
+                    if !stream.ping().await {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
 #[websocket("/api/system/log/{level}")]
 pub async fn log_stream(
     socket: WebSocket,
@@ -323,3 +1304,152 @@ pub async fn log_stream(
         None => Ok(()),
     }
 }
+
+pub const SYSTEM_STREAM_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Serialize)]
+pub struct GpuSummary {
+    pub index: u32,
+    pub name: String,
+    pub gpu_usage: u32,
+    pub memory_usage: u32,
+    pub temperature: u32,
+    pub power_watts: Option<f32>,
+}
+impl From<&GpuInfo> for GpuSummary {
+    fn from(gpu: &GpuInfo) -> Self {
+        Self {
+            index: gpu.index,
+            name: gpu.name.clone(),
+            gpu_usage: gpu.gpu_usage,
+            memory_usage: gpu.memory_usage,
+            temperature: gpu.temperature,
+            power_watts: gpu.power_watts,
+        }
+    }
+}
+
+//Returns the value when its hash differs from `last_hash`, updating it
+fn changed_value(value: serde_json::Value, last_hash: &mut Option<u64>) -> serde_json::Value {
+    let mut hasher = DefaultHasher::new();
+    value.to_string().hash(&mut hasher);
+    let hash = hasher.finish();
+    if *last_hash == Some(hash) {
+        serde_json::Value::Null
+    } else {
+        *last_hash = Some(hash);
+        value
+    }
+}
+
+#[derive(Default)]
+struct SystemStreamState {
+    disks_hash: Option<u64>,
+    networks_hash: Option<u64>,
+    networks_refreshed: Option<u64>,
+}
+
+async fn system_stream_frame(
+    monitor: &SystemMonitorPlugin,
+    state: &mut SystemStreamState,
+) -> Result<serde_json::Value, Error> {
+    let gpus: Vec<GpuSummary> = monitor
+        .get_gpu_info()
+        .await?
+        .iter()
+        .map(GpuSummary::from)
+        .collect();
+    let disks = changed_value(
+        serde_json::to_value(monitor.get_disk_info().await?)?,
+        &mut state.disks_hash,
+    );
+    //Network info is read over D-Bus, so only reload it after the monitor refreshed its devices
+    let networks_refreshed = monitor.last_network_update();
+    let networks = if state.networks_refreshed != Some(networks_refreshed) {
+        state.networks_refreshed = Some(networks_refreshed);
+        changed_value(
+            serde_json::to_value(monitor.get_network_info().await?)?,
+            &mut state.networks_hash,
+        )
+    } else {
+        serde_json::Value::Null
+    };
+    Ok(serde_json::json!({
+        "cpu": monitor.get_cpu_info().await?,
+        "memory": monitor.get_memory_info().await?,
+        "gpus": gpus,
+        "disks": disks,
+        "networks": networks,
+    }))
+}
+
+#[websocket("/api/system/stream")]
+pub async fn system_stream(
+    socket: WebSocket,
+    monitor: State<SystemMonitorPlugin>,
+) -> Result<(), Error> {
+    let mut state = SystemStreamState::default();
+    let mut interval = tokio::time::interval(SYSTEM_STREAM_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let frame = match system_stream_frame(monitor.0.as_ref(), &mut state).await {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        warn!("Failed to build system stream frame: {e:?}");
+                        continue;
+                    }
+                };
+                if let Err(e) = socket.send(Message::Text(frame.to_string().into())).await {
+                    debug!("Failed to send system stream frame: {e:?}");
+                    break;
+                }
+            }
+            result = socket.next() => {
+                match result {
+                    Ok(Some(Message::Ping(ping_data))) => {
+                        if socket.send(Message::Pong(ping_data)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Some(Message::Close(_))) | Err(_) => break,
+                    Ok(Some(_)) => {
+                        //Ignore Client Messages
+                        continue;
+                    }
+                    Ok(None) => {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_changed_value() {
+    let mut last_hash = None;
+    let disks = serde_json::json!([{ "name": "sda", "used": 10 }]);
+    assert_eq!(changed_value(disks.clone(), &mut last_hash), disks);
+    assert!(changed_value(disks, &mut last_hash).is_null());
+    let updated = serde_json::json!([{ "name": "sda", "used": 11 }]);
+    assert_eq!(changed_value(updated.clone(), &mut last_hash), updated);
+}
+
+#[test]
+fn test_parse_saved_wifi_connection() {
+    let saved =
+        parse_saved_wifi_connection("uuid-1", "Garden Net\nyes\n1700000000\ninfrastructure\n")
+            .unwrap();
+    assert_eq!(saved.ssid, "Garden Net");
+    assert!(saved.auto_connect);
+    assert_eq!(
+        saved.last_connected.map(|t| t.unix_timestamp()),
+        Some(1700000000)
+    );
+    let never = parse_saved_wifi_connection("uuid-2", "Cafe\nno\n0\ninfrastructure\n").unwrap();
+    assert!(!never.auto_connect);
+    assert!(never.last_connected.is_none());
+    assert!(parse_saved_wifi_connection("uuid-3", "DG_OS_SETUP\nyes\n0\nap\n").is_none());
+}
@@ -0,0 +1,23 @@
+use crate::database::wizard::{get_wizard_state, mark_step_complete};
+use crate::first_run::WizardStep;
+use portfu::prelude::*;
+use portfu_macros::{get, post};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::io::Error;
+use std::str::FromStr;
+
+#[get("/api/wizard/state", output = "json", eoutput = "bytes")]
+pub async fn wizard_state(db: State<SqlitePool>) -> Result<HashMap<WizardStep, bool>, Error> {
+    get_wizard_state(db.as_ref()).await
+}
+
+#[post("/api/wizard/{step}/complete", output = "json", eoutput = "bytes")]
+pub async fn complete_wizard_step(
+    db: State<SqlitePool>,
+    step: Path,
+) -> Result<HashMap<WizardStep, bool>, Error> {
+    let step = WizardStep::from_str(&step.inner())?;
+    mark_step_complete(db.as_ref(), step).await?;
+    get_wizard_state(db.as_ref()).await
+}